@@ -0,0 +1,253 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, FnArg, Ident, ItemTrait, Pat, ReturnType, TraitItem, Type};
+
+/// Attribute to declare a service shared by a client application and a trusted
+/// application, see the `rpc` crate for the generated items.
+/// ``` rust,ignore
+/// #[rpc::service(codec = rpc::codec::Json)]
+/// pub trait Greeter {
+///     fn hello(&mut self, name: String) -> rpc::Result<String>;
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut codec: Option<syn::Path> = None;
+    let args_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("codec") {
+            codec = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported service argument, expected `codec = <path>`"))
+        }
+    });
+    parse_macro_input!(args with args_parser);
+    let item = parse_macro_input!(input as ItemTrait);
+
+    let codec = match codec {
+        Some(codec) => codec,
+        None => {
+            return Error::new(item.span(), "`#[service]` requires `codec = <path>`")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    match expand(codec, item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Method {
+    attrs: Vec<syn::Attribute>,
+    name: Ident,
+    args: Vec<Ident>,
+    arg_types: Vec<Type>,
+    output: Type,
+}
+
+fn parse_method(method: &syn::TraitItemFn) -> syn::Result<Method> {
+    let sig = &method.sig;
+    if !sig.generics.params.is_empty() || sig.asyncness.is_some() || sig.variadic.is_some() {
+        return Err(Error::new(
+            sig.span(),
+            "service methods must not be generic, async or variadic",
+        ));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() => {}
+        _ => {
+            return Err(Error::new(
+                sig.span(),
+                "service methods must take `&self` or `&mut self`",
+            ))
+        }
+    }
+    let mut args = Vec::new();
+    let mut arg_types = Vec::new();
+    for input in inputs {
+        match input {
+            FnArg::Typed(arg) => match arg.pat.as_ref() {
+                Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => {
+                    args.push(pat.ident.clone());
+                    arg_types.push(arg.ty.as_ref().clone());
+                }
+                _ => {
+                    return Err(Error::new(
+                        arg.pat.span(),
+                        "service method arguments must be plain identifiers",
+                    ))
+                }
+            },
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(receiver.span(), "unexpected receiver"))
+            }
+        }
+    }
+
+    let output = match &sig.output {
+        ReturnType::Type(_, ty) => result_ok_type(ty),
+        ReturnType::Default => None,
+    }
+    .ok_or_else(|| Error::new(sig.span(), "service methods must return `rpc::Result<T>`"))?;
+
+    let attrs = method
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .cloned()
+        .collect();
+
+    Ok(Method {
+        attrs,
+        name: sig.ident.clone(),
+        args,
+        arg_types,
+        output,
+    })
+}
+
+// Returns `T` of `Result<T>` or `Result<T, E>`.
+fn result_ok_type(ty: &Type) -> Option<Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(generics) = &segment.arguments else {
+        return None;
+    };
+    match generics.args.first()? {
+        syn::GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+fn expand(codec: syn::Path, item: ItemTrait) -> syn::Result<TokenStream2> {
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "service traits must not be generic",
+        ));
+    }
+
+    let mut methods = Vec::new();
+    for trait_item in &item.items {
+        if let TraitItem::Fn(method) = trait_item {
+            methods.push(parse_method(method)?);
+        }
+    }
+
+    let vis = &item.vis;
+    let trait_name = &item.ident;
+    let client = format_ident!("{}Client", trait_name);
+    let dispatcher = format_ident!("{}Dispatcher", trait_name);
+    let client_doc = format!("Client of the [`{}`] service.", trait_name);
+    let dispatcher_doc = format!(
+        "Dispatches the requests of the [`{}`] service to an implementation.",
+        trait_name
+    );
+
+    let client_methods = methods.iter().enumerate().map(|(id, method)| {
+        let id = id as u32;
+        let Method {
+            attrs,
+            name,
+            args,
+            arg_types,
+            output,
+        } = method;
+        quote! {
+            #(#attrs)*
+            #vis fn #name(&mut self, #(#args: #arg_types),*) -> ::rpc::Result<#output> {
+                let request = <#codec as ::rpc::Codec>::encode(&(#(#args,)*))?;
+                let response = ::rpc::Transport::call(&mut self.transport, #id, &request)?;
+                <#codec as ::rpc::Codec>::decode(&response)
+            }
+        }
+    });
+
+    let dispatch_arms = methods.iter().enumerate().map(|(id, method)| {
+        let id = id as u32;
+        let Method {
+            name,
+            args,
+            arg_types,
+            ..
+        } = method;
+        quote! {
+            #id => {
+                let (#(#args,)*): (#(#arg_types,)*) =
+                    <#codec as ::rpc::Codec>::decode(request)?;
+                let response = #trait_name::#name(&mut self.0, #(#args),*)?;
+                <#codec as ::rpc::Codec>::encode(&response)
+            }
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        #[doc = #client_doc]
+        #vis struct #client<T> {
+            transport: T,
+        }
+
+        impl<T: ::rpc::Transport> #client<T> {
+            /// Creates a client sending its requests through `transport`.
+            #vis fn new(transport: T) -> Self {
+                Self { transport }
+            }
+
+            /// Returns the transport of the client.
+            #vis fn into_inner(self) -> T {
+                self.transport
+            }
+
+            #(#client_methods)*
+        }
+
+        #[doc = #dispatcher_doc]
+        #[derive(Default)]
+        #vis struct #dispatcher<S>(pub S);
+
+        impl<S: #trait_name> ::rpc::Dispatch for #dispatcher<S> {
+            fn dispatch(
+                &mut self,
+                command_id: u32,
+                request: &[u8],
+            ) -> ::rpc::Result<::rpc::__private::Vec<u8>> {
+                match command_id {
+                    #(#dispatch_arms)*
+                    _ => Err(::rpc::Error::UnknownCommand(command_id)),
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,34 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::Result;
+use alloc::vec::Vec;
+
+/// The trusted application side of a service, which runs the command
+/// `command_id` with an encoded `request` and returns the encoded response.
+///
+/// It is implemented by the `<Service>Dispatcher` generated by
+/// [`service`](crate::service).
+pub trait Dispatch {
+    fn dispatch(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl<D: Dispatch + ?Sized> Dispatch for &mut D {
+    fn dispatch(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>> {
+        (**self).dispatch(command_id, request)
+    }
+}
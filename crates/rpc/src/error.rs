@@ -0,0 +1,87 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use core::fmt;
+
+/// A specialized `Result` type for RPC calls.
+pub type Result<T> = core::result::Result<T, Error>;
+
+const TEE_ERROR_BAD_FORMAT: u32 = 0xFFFF_0005;
+const TEE_ERROR_NOT_SUPPORTED: u32 = 0xFFFF_000A;
+
+/// The error type for RPC calls.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// A request or a response could not be encoded or decoded.
+    Codec,
+    /// The command id is not part of the service.
+    UnknownCommand(u32),
+    /// An error code of the TEE, or returned by the trusted application.
+    Tee(u32),
+}
+
+impl Error {
+    /// Returns the TEE error code reported to the client for this error.
+    pub fn raw_code(&self) -> u32 {
+        match self {
+            Error::Codec => TEE_ERROR_BAD_FORMAT,
+            Error::UnknownCommand(_) => TEE_ERROR_NOT_SUPPORTED,
+            Error::Tee(code) => *code,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Codec => write!(f, "failed to encode or decode an RPC message"),
+            Error::UnknownCommand(id) => write!(f, "unknown RPC command {}", id),
+            Error::Tee(code) => write!(f, "TEE error code 0x{:x}", code),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "host")]
+impl From<optee_teec::Error> for Error {
+    fn from(err: optee_teec::Error) -> Self {
+        Error::Tee(err.raw_code())
+    }
+}
+
+#[cfg(feature = "host")]
+impl From<Error> for optee_teec::Error {
+    fn from(err: Error) -> Self {
+        optee_teec::Error::from_raw_error(err.raw_code())
+    }
+}
+
+#[cfg(feature = "ta")]
+impl From<optee_utee::Error> for Error {
+    fn from(err: optee_utee::Error) -> Self {
+        Error::Tee(err.raw_code())
+    }
+}
+
+#[cfg(feature = "ta")]
+impl From<Error> for optee_utee::Error {
+    fn from(err: Error) -> Self {
+        optee_utee::Error::from_raw_error(err.raw_code())
+    }
+}
@@ -0,0 +1,209 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Typed RPC between client applications and trusted applications.
+//!
+//! A service is declared once as a trait in the proto crate shared by the host
+//! and the TA, and annotated with [`service`]:
+//!
+//! ``` rust,ignore
+//! #[rpc::service(codec = rpc::codec::Json)]
+//! pub trait Greeter {
+//!     fn hello(&mut self, name: String) -> rpc::Result<String>;
+//!     fn add(&mut self, a: u32, b: u32) -> rpc::Result<u32>;
+//! }
+//! ```
+//!
+//! Besides the trait itself, the attribute generates:
+//!
+//! * `GreeterClient<T: Transport>`, which has the same methods as the trait and
+//!   sends them through a [`Transport`], for example an `optee_teec::Session`
+//!   with the `host` feature enabled:
+//!
+//!   ``` rust,ignore
+//!   let mut client = GreeterClient::new(ctx.open_session(uuid)?);
+//!   let greeting = client.hello("world".to_string())?;
+//!   ```
+//!
+//! * `GreeterDispatcher<S: Greeter>`, a [`Dispatch`] implementation which
+//!   decodes the requests and calls the service implemented by the TA. With the
+//!   `ta` feature enabled it is served with a [`Server`] kept as the session
+//!   context:
+//!
+//!   ``` rust,ignore
+//!   #[ta_invoke_command]
+//!   fn invoke_command(
+//!       server: &mut rpc::Server<GreeterDispatcher<MyGreeter>>,
+//!       cmd_id: u32,
+//!       params: &mut Parameters,
+//!   ) -> Result<()> {
+//!       server.serve(cmd_id, params)
+//!   }
+//!   ```
+//!
+//! Commands are numbered in the order of the trait methods, starting from 0.
+//! The arguments of a method are encoded as a tuple with the codec of the
+//! service, and the encoded request and response are passed in a temporary
+//! memory reference each (parameter 0 and 1). When the output buffer of the
+//! client is too short, the TA keeps the response and reports the required
+//! size, the client then retries with a buffer large enough without running
+//! the command again.
+//!
+//! # Features
+//!
+//! * `host`: [`Transport`] implementation for `optee_teec::Session`.
+//! * `ta`: [`Server`] for `optee_utee::Parameters`.
+//! * `bincode`, `json`, `postcard`: the corresponding [`codec`]s.
+//! * `std`: implements `std::error::Error` for [`Error`], enabled by `host` and
+//!   `bincode`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// Allows the generated code of `service` to refer to `::rpc` in tests.
+#[cfg(test)]
+extern crate self as rpc;
+
+pub use self::codec::Codec;
+pub use self::dispatch::Dispatch;
+pub use self::error::{Error, Result};
+#[cfg(feature = "ta")]
+pub use self::server::Server;
+pub use self::transport::Transport;
+pub use rpc_macros::service;
+
+pub mod codec;
+mod dispatch;
+mod error;
+#[cfg(feature = "ta")]
+mod server;
+mod transport;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec::Vec;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Codec, Dispatch, Error, Result, Transport};
+    use alloc::{string::String, vec::Vec};
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    struct TestCodec;
+
+    impl Codec for TestCodec {
+        fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+            serde_json::to_vec(value).map_err(|_| Error::Codec)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+            serde_json::from_slice(bytes).map_err(|_| Error::Codec)
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub struct Record {
+        id: u32,
+        name: String,
+    }
+
+    #[crate::service(codec = TestCodec)]
+    pub trait Registry {
+        fn add(&mut self, record: Record) -> Result<u32>;
+        fn get(&self, id: u32) -> Result<Record>;
+        fn rename(&mut self, id: u32, name: String) -> Result<()>;
+        fn count(&self) -> Result<usize>;
+    }
+
+    #[derive(Default)]
+    struct MemoryRegistry {
+        records: Vec<Record>,
+    }
+
+    impl Registry for MemoryRegistry {
+        fn add(&mut self, record: Record) -> Result<u32> {
+            let id = record.id;
+            self.records.push(record);
+            Ok(id)
+        }
+
+        fn get(&self, id: u32) -> Result<Record> {
+            self.records
+                .iter()
+                .find(|record| record.id == id)
+                .map(|record| Record {
+                    id: record.id,
+                    name: record.name.clone(),
+                })
+                .ok_or(Error::Tee(0xFFFF_0008))
+        }
+
+        fn rename(&mut self, id: u32, name: String) -> Result<()> {
+            let record = self
+                .records
+                .iter_mut()
+                .find(|record| record.id == id)
+                .ok_or(Error::Tee(0xFFFF_0008))?;
+            record.name = name;
+            Ok(())
+        }
+
+        fn count(&self) -> Result<usize> {
+            Ok(self.records.len())
+        }
+    }
+
+    struct Loopback<D>(D);
+
+    impl<D: Dispatch> Transport for Loopback<D> {
+        fn call(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>> {
+            self.0.dispatch(command_id, request)
+        }
+    }
+
+    #[test]
+    fn test_client_calls_dispatcher() {
+        let dispatcher = RegistryDispatcher(MemoryRegistry::default());
+        let mut client = RegistryClient::new(Loopback(dispatcher));
+
+        let record = Record {
+            id: 7,
+            name: String::from("seven"),
+        };
+        assert_eq!(client.add(record).unwrap(), 7);
+        client.rename(7, String::from("sept")).unwrap();
+        assert_eq!(
+            client.get(7).unwrap(),
+            Record {
+                id: 7,
+                name: String::from("sept"),
+            }
+        );
+        assert_eq!(client.count().unwrap(), 1);
+        assert_eq!(client.get(8), Err(Error::Tee(0xFFFF_0008)));
+        assert_eq!(client.into_inner().0 .0.records.len(), 1);
+    }
+
+    #[test]
+    fn test_dispatcher_rejects_bad_requests() {
+        let mut dispatcher = RegistryDispatcher(MemoryRegistry::default());
+        assert_eq!(dispatcher.dispatch(4, b"[]"), Err(Error::UnknownCommand(4)));
+        assert_eq!(dispatcher.dispatch(1, b"not json"), Err(Error::Codec));
+        assert_eq!(dispatcher.dispatch(1, b"[\"text\"]"), Err(Error::Codec));
+    }
+}
@@ -0,0 +1,260 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::Dispatch;
+use alloc::vec::Vec;
+use optee_utee::{ErrorKind, Parameters};
+
+// A response which did not fit in the output buffer of the client, kept for
+// the next call only, if the client retries the same request with a buffer
+// large enough.
+struct PendingResponse {
+    command_id: u32,
+    request: Vec<u8>,
+    response: Vec<u8>,
+}
+
+/// Serves the requests of a service in a trusted application. It is meant to be
+/// kept as the session context, so a response which is too large for the
+/// output buffer of the client can be returned on retry without running the
+/// command twice.
+///
+/// A call is taken as the retry of the previous one if it has the same command
+/// and request, and an output buffer large enough for the kept response. The
+/// client is expected to retry right away, as the `Transport` of
+/// `optee_teec::Session` does: a client which gives up and later sends the same
+/// request with a large buffer as its next call gets the kept response.
+///
+/// # Example
+///
+/// ``` rust,ignore
+/// #[ta_invoke_command]
+/// fn invoke_command(
+///     server: &mut rpc::Server<GreeterDispatcher<MyGreeter>>,
+///     cmd_id: u32,
+///     params: &mut Parameters,
+/// ) -> Result<()> {
+///     server.serve(cmd_id, params)
+/// }
+/// ```
+#[derive(Default)]
+pub struct Server<D> {
+    dispatcher: D,
+    pending: Option<PendingResponse>,
+}
+
+impl<D: Dispatch> Server<D> {
+    /// Creates a server for `dispatcher`.
+    pub fn new(dispatcher: D) -> Self {
+        Self {
+            dispatcher,
+            pending: None,
+        }
+    }
+
+    /// Returns a reference to the dispatcher.
+    pub fn dispatcher(&self) -> &D {
+        &self.dispatcher
+    }
+
+    /// Returns a mutable reference to the dispatcher.
+    pub fn dispatcher_mut(&mut self) -> &mut D {
+        &mut self.dispatcher
+    }
+
+    /// Runs the command `command_id` with the request in the first parameter
+    /// and writes the response into the second one.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the first two parameters are not memory references.
+    /// 2) `ShortBuffer`: the response does not fit in the output buffer, the
+    ///    required size is set as the updated size of the second parameter.
+    /// 3) The error returned by the dispatcher, as described in
+    ///    [`Error::raw_code`](crate::Error::raw_code).
+    pub fn serve(&mut self, command_id: u32, params: &mut Parameters) -> optee_utee::Result<()> {
+        let mut p0 = unsafe { params.0.as_memref()? };
+        let mut p1 = unsafe { params.1.as_memref()? };
+        let request = p0.buffer();
+
+        let output = p1.buffer();
+        // Any other call discards the kept response, so the command is run
+        // again with the current state of the service.
+        let response = match self.pending.take() {
+            Some(pending)
+                if pending.command_id == command_id
+                    && pending.request == request
+                    && pending.response.len() <= output.len() =>
+            {
+                pending.response
+            }
+            _ => self.dispatcher.dispatch(command_id, request)?,
+        };
+
+        if output.len() < response.len() {
+            p1.set_updated_size(response.len());
+            self.pending = Some(PendingResponse {
+                command_id,
+                request: request.to_vec(),
+                response,
+            });
+            return Err(ErrorKind::ShortBuffer.into());
+        }
+        output[..response.len()].copy_from_slice(&response);
+        p1.set_updated_size(response.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Server;
+    use crate::{Dispatch, Error, Result};
+    use alloc::{vec, vec::Vec};
+    use optee_utee::{ErrorKind, Parameters};
+    use optee_utee_sys as raw;
+
+    // Echoes the request and counts the calls.
+    #[derive(Default)]
+    struct Echo {
+        calls: usize,
+    }
+
+    impl Dispatch for Echo {
+        fn dispatch(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>> {
+            self.calls += 1;
+            match command_id {
+                0 => Ok(request.to_vec()),
+                _ => Err(Error::UnknownCommand(command_id)),
+            }
+        }
+    }
+
+    // Returns a response of 16 bytes set to its value.
+    #[derive(Default)]
+    struct Counter {
+        value: u8,
+        calls: usize,
+    }
+
+    impl Dispatch for Counter {
+        fn dispatch(&mut self, _command_id: u32, _request: &[u8]) -> Result<Vec<u8>> {
+            self.calls += 1;
+            Ok(vec![self.value; 16])
+        }
+    }
+
+    fn serve<D: Dispatch>(
+        server: &mut Server<D>,
+        command_id: u32,
+        request: &[u8],
+        output: &mut [u8],
+    ) -> (optee_utee::Result<()>, usize) {
+        let mut raw_params = [
+            raw::TEE_Param {
+                memref: raw::Memref {
+                    buffer: request.as_ptr() as _,
+                    size: request.len(),
+                },
+            },
+            raw::TEE_Param {
+                memref: raw::Memref {
+                    buffer: output.as_mut_ptr() as _,
+                    size: output.len(),
+                },
+            },
+            raw::TEE_Param {
+                value: raw::Value { a: 0, b: 0 },
+            },
+            raw::TEE_Param {
+                value: raw::Value { a: 0, b: 0 },
+            },
+        ];
+        let param_types = raw::TEE_PARAM_TYPES(
+            raw::TEE_PARAM_TYPE_MEMREF_INPUT,
+            raw::TEE_PARAM_TYPE_MEMREF_OUTPUT,
+            raw::TEE_PARAM_TYPE_NONE,
+            raw::TEE_PARAM_TYPE_NONE,
+        );
+        let mut params = Parameters::from_raw(&mut raw_params, param_types);
+        let result = server.serve(command_id, &mut params);
+        (result, unsafe { raw_params[1].memref.size })
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut server = Server::new(Echo::default());
+        let mut output = [0u8; 16];
+        let (result, size) = serve(&mut server, 0, b"hello", &mut output);
+        assert!(result.is_ok());
+        assert_eq!(&output[..size], b"hello");
+
+        let (result, _) = serve(&mut server, 1, b"hello", &mut output);
+        assert_eq!(
+            result.unwrap_err().raw_code(),
+            Error::UnknownCommand(1).raw_code()
+        );
+    }
+
+    #[test]
+    fn test_serve_short_buffer_retry() {
+        let mut server = Server::new(Echo::default());
+        let request = b"a response larger than the output buffer";
+        let mut output = [0u8; 8];
+        let (result, size) = serve(&mut server, 0, request, &mut output);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ShortBuffer);
+        assert_eq!(size, request.len());
+
+        // The retry returns the kept response without running the command.
+        let mut output = vec![0u8; size];
+        let (result, size) = serve(&mut server, 0, request, &mut output);
+        assert!(result.is_ok());
+        assert_eq!(&output[..size], request);
+        assert_eq!(server.dispatcher().calls, 1);
+
+        // A different request runs the command again.
+        let (result, _) = serve(&mut server, 0, b"other", &mut output);
+        assert!(result.is_ok());
+        assert_eq!(server.dispatcher().calls, 2);
+    }
+
+    #[test]
+    fn test_serve_short_buffer_state_change() {
+        let mut server = Server::new(Counter::default());
+        let mut output = [0u8; 8];
+        let (result, size) = serve(&mut server, 0, b"get", &mut output);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ShortBuffer);
+        assert_eq!(size, 16);
+
+        // The client gives up, the state changes and the same request is sent
+        // again with the same buffer: it is not a retry, the command runs again.
+        server.dispatcher_mut().value = 1;
+        let (result, _) = serve(&mut server, 0, b"get", &mut output);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::ShortBuffer);
+        assert_eq!(server.dispatcher().calls, 2);
+
+        // A call in between discards the kept response.
+        let mut output = [0u8; 16];
+        let (result, _) = serve(&mut server, 1, b"get", &mut output);
+        assert!(result.is_ok());
+        server.dispatcher_mut().value = 2;
+        let (result, size) = serve(&mut server, 0, b"get", &mut output);
+        assert!(result.is_ok());
+        assert_eq!(&output[..size], &[2u8; 16]);
+        assert_eq!(server.dispatcher().calls, 4);
+    }
+}
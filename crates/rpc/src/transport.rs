@@ -0,0 +1,63 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::Result;
+use alloc::vec::Vec;
+
+/// The client application side of a service, which sends an encoded `request`
+/// for the command `command_id` and returns the encoded response.
+///
+/// With the `host` feature it is implemented for `optee_teec::Session`, other
+/// implementations can for example call a [`Dispatch`](crate::Dispatch)
+/// directly in tests.
+pub trait Transport {
+    fn call(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn call(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>> {
+        (**self).call(command_id, request)
+    }
+}
+
+#[cfg(feature = "host")]
+mod session {
+    use super::Transport;
+    use crate::{Error, Result};
+    use optee_teec::{GrowableParam, Session};
+
+    // The output buffer of the first attempt, a larger one is used when the
+    // trusted application reports a short buffer.
+    const INITIAL_OUTPUT_SIZE: usize = 256;
+
+    impl Transport for Session {
+        fn call(&mut self, command_id: u32, request: &[u8]) -> Result<Vec<u8>> {
+            let [_, output, _, _] = self
+                .invoke_command_growing(
+                    command_id,
+                    [
+                        GrowableParam::Input(request),
+                        GrowableParam::output(INITIAL_OUTPUT_SIZE),
+                        GrowableParam::None,
+                        GrowableParam::None,
+                    ],
+                )
+                .map_err(Error::from)?;
+            Ok(output.into_buffer().unwrap_or_default())
+        }
+    }
+}
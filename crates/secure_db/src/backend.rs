@@ -15,32 +15,142 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use anyhow::{anyhow, bail, Result};
+use crate::compat::{Mutex, MutexGuard};
+use crate::error::{bail, format_err};
+use crate::Result;
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
 use optee_utee::{DataFlag, GenericObject, ObjectStorageConstants, PersistentObject};
 
+// StorageBackend is where SecureStorageDb keeps its objects: the persistent
+// objects of a TEE storage by default, or memory and the host file system for
+// tests and tools running off-device.
+// `save` must replace the object atomically, the transactions rely on it.
+
+pub trait StorageBackend {
+    fn load(&self, obj_id: &[u8]) -> Result<Option<Vec<u8>>>;
+    fn save(&mut self, obj_id: &[u8], data: &[u8]) -> Result<()>;
+    // Returns false if the object does not exist.
+    fn delete(&mut self, obj_id: &[u8]) -> Result<bool>;
+    // Returns the ids of the objects starting with `prefix`, in no particular
+    // order.
+    fn list(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>>;
+}
+
+// The persistent objects of a TEE storage, creating an object with
+// TEE_DATA_FLAG_OVERWRITE is atomic.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PersistentObjectBackend {
+    storage_id: ObjectStorageConstants,
+}
+
+impl PersistentObjectBackend {
+    pub fn new(storage_id: ObjectStorageConstants) -> Self {
+        Self { storage_id }
+    }
+
+    pub fn storage_id(&self) -> ObjectStorageConstants {
+        self.storage_id
+    }
+}
+
+impl Default for PersistentObjectBackend {
+    fn default() -> Self {
+        Self::new(ObjectStorageConstants::Private)
+    }
+}
+
+impl StorageBackend for PersistentObjectBackend {
+    fn load(&self, obj_id: &[u8]) -> Result<Option<Vec<u8>>> {
+        load_from_secure_storage(self.storage_id, obj_id)
+    }
+
+    fn save(&mut self, obj_id: &[u8], data: &[u8]) -> Result<()> {
+        save_in_secure_storage(self.storage_id, obj_id, data)
+    }
+
+    fn delete(&mut self, obj_id: &[u8]) -> Result<bool> {
+        delete_from_secure_storage_if_exists(self.storage_id, obj_id)
+    }
+
+    fn list(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let mut obj_ids = Vec::new();
+        for item in PersistentObject::list_with_prefix(self.storage_id, prefix)? {
+            obj_ids.push(item?.0);
+        }
+        Ok(obj_ids)
+    }
+}
+
+// Objects kept in memory. Clones share the same objects, so that a db can be
+// opened again in tests.
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBackend {
+    objects: Arc<Mutex<BTreeMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn objects(&self) -> Result<MutexGuard<'_, BTreeMap<Vec<u8>, Vec<u8>>>> {
+        self.objects
+            .lock()
+            .map_err(|_| format_err!("Failed to acquire lock"))
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn load(&self, obj_id: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.objects()?.get(obj_id).cloned())
+    }
+
+    fn save(&mut self, obj_id: &[u8], data: &[u8]) -> Result<()> {
+        self.objects()?.insert(obj_id.to_vec(), data.to_vec());
+        Ok(())
+    }
+
+    fn delete(&mut self, obj_id: &[u8]) -> Result<bool> {
+        Ok(self.objects()?.remove(obj_id).is_some())
+    }
+
+    fn list(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+        Ok(self
+            .objects()?
+            .range(prefix.to_vec()..)
+            .map(|(obj_id, _)| obj_id)
+            .take_while(|obj_id| obj_id.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
 // Wrapper functions for OP-TEE raw API
 
-pub fn save_in_secure_storage(obj_id: &[u8], data: &[u8]) -> Result<()> {
+pub fn save_in_secure_storage(
+    storage_id: ObjectStorageConstants,
+    obj_id: &[u8],
+    data: &[u8],
+) -> Result<()> {
     let obj_data_flag = DataFlag::ACCESS_READ
         | DataFlag::ACCESS_WRITE
         | DataFlag::ACCESS_WRITE_META
         | DataFlag::OVERWRITE;
 
-    PersistentObject::create(
-        ObjectStorageConstants::Private,
-        obj_id,
-        obj_data_flag,
-        None,
-        data,
-    )
-    .map_err(|e| anyhow!("[-] {:?}: failed to create object: {:?}", &obj_id, e))?;
+    PersistentObject::create(storage_id, obj_id, obj_data_flag, None, data)
+        .map_err(|e| format_err!("[-] {:?}: failed to create object: {:?}", &obj_id, e))?;
 
     Ok(())
 }
 
-pub fn load_from_secure_storage(obj_id: &[u8]) -> Result<Option<Vec<u8>>> {
+pub fn load_from_secure_storage(
+    storage_id: ObjectStorageConstants,
+    obj_id: &[u8],
+) -> Result<Option<Vec<u8>>> {
     match PersistentObject::open(
-        ObjectStorageConstants::Private,
+        storage_id,
         obj_id,
         DataFlag::ACCESS_READ | DataFlag::SHARE_READ,
     ) {
@@ -65,9 +175,9 @@ pub fn load_from_secure_storage(obj_id: &[u8]) -> Result<Option<Vec<u8>>> {
     }
 }
 
-pub fn delete_from_secure_storage(obj_id: &[u8]) -> Result<()> {
+pub fn delete_from_secure_storage(storage_id: ObjectStorageConstants, obj_id: &[u8]) -> Result<()> {
     match PersistentObject::open(
-        ObjectStorageConstants::Private,
+        storage_id,
         obj_id,
         DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE_META,
     ) {
@@ -81,3 +191,194 @@ pub fn delete_from_secure_storage(obj_id: &[u8]) -> Result<()> {
         }
     }
 }
+
+// Returns false if the object does not exist.
+pub fn delete_from_secure_storage_if_exists(
+    storage_id: ObjectStorageConstants,
+    obj_id: &[u8],
+) -> Result<bool> {
+    match PersistentObject::open(storage_id, obj_id, DataFlag::ACCESS_WRITE_META) {
+        Err(e) => match e.kind() {
+            optee_utee::ErrorKind::ItemNotFound => Ok(false),
+            _ => {
+                bail!("[-] {:?}: failed to open object: {:?}", &obj_id, e);
+            }
+        },
+
+        Ok(object) => {
+            object.close_and_delete()?;
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(target_os = "optee")))]
+pub use self::host::HostFsBackend;
+
+// The file system is only available to tools and tests running on the host.
+#[cfg(all(feature = "std", not(target_os = "optee")))]
+mod host {
+    use super::StorageBackend;
+    use crate::error::format_err;
+    use crate::Result;
+    use std::fmt::Write;
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    // Objects kept in a directory of the host, one file per object named after
+    // the hex encoded object id. Files are written to a temporary file and
+    // renamed, which is atomic. Files whose name is not a hex encoded id are
+    // ignored.
+
+    // File names are limited to 255 bytes on most file systems, including the
+    // `.tmp` extension of the temporary files, so longer ids are rejected.
+    const MAX_OBJ_ID_LEN: usize = 125;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct HostFsBackend {
+        dir: PathBuf,
+    }
+
+    impl HostFsBackend {
+        pub fn new<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+            let dir = dir.into();
+            fs::create_dir_all(&dir)?;
+            Ok(Self { dir })
+        }
+
+        fn path(&self, obj_id: &[u8]) -> Result<PathBuf> {
+            if obj_id.len() > MAX_OBJ_ID_LEN {
+                return Err(format_err!(
+                    "[-] {:?}: object id longer than {} bytes",
+                    obj_id,
+                    MAX_OBJ_ID_LEN
+                ));
+            }
+            let name = obj_id.iter().fold(String::new(), |mut name, byte| {
+                let _ = write!(name, "{:02x}", byte);
+                name
+            });
+            Ok(self.dir.join(name))
+        }
+    }
+
+    // Returns the object id of the file `name`, None if it is not the name of
+    // an object.
+    fn parse_name(name: &str) -> Option<Vec<u8>> {
+        let is_hex = |c: char| c.is_ascii_digit() || ('a'..='f').contains(&c);
+        if name.is_empty() || name.len() % 2 != 0 || !name.chars().all(is_hex) {
+            return None;
+        }
+        (0..name.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&name[i..i + 2], 16).ok())
+            .collect()
+    }
+
+    impl StorageBackend for HostFsBackend {
+        fn load(&self, obj_id: &[u8]) -> Result<Option<Vec<u8>>> {
+            match fs::read(self.path(obj_id)?) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        fn save(&mut self, obj_id: &[u8], data: &[u8]) -> Result<()> {
+            let path = self.path(obj_id)?;
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, data)?;
+            fs::rename(tmp_path, path)?;
+            Ok(())
+        }
+
+        fn delete(&mut self, obj_id: &[u8]) -> Result<bool> {
+            match fs::remove_file(self.path(obj_id)?) {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        fn list(&self, prefix: &[u8]) -> Result<Vec<Vec<u8>>> {
+            let mut obj_ids = Vec::new();
+            for entry in fs::read_dir(&self.dir)? {
+                // Skips the temporary files, as well as any other file.
+                let obj_id = match entry?.file_name().to_str().and_then(parse_name) {
+                    Some(obj_id) => obj_id,
+                    None => continue,
+                };
+                if obj_id.starts_with(prefix) {
+                    obj_ids.push(obj_id);
+                }
+            }
+            Ok(obj_ids)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use optee_utee_emulator as emulator;
+
+    fn check_backend(mut backend: impl StorageBackend) {
+        assert_eq!(backend.load(b"a#1").unwrap(), None);
+        backend.save(b"a#1", b"1").unwrap();
+        backend.save(b"a#2", b"2").unwrap();
+        backend.save(b"b#1", b"3").unwrap();
+        backend.save(b"a#1", b"4").unwrap();
+        assert_eq!(backend.load(b"a#1").unwrap().unwrap(), b"4");
+
+        let mut listed = backend.list(b"a#").unwrap();
+        listed.sort();
+        assert_eq!(listed, [b"a#1", b"a#2"]);
+        assert_eq!(backend.list(b"").unwrap().len(), 3);
+
+        assert!(backend.delete(b"a#1").unwrap());
+        assert!(!backend.delete(b"a#1").unwrap());
+        assert_eq!(backend.load(b"a#1").unwrap(), None);
+        assert_eq!(backend.list(b"a#").unwrap(), [b"a#2"]);
+    }
+
+    #[test]
+    fn test_persistent_object_backend() {
+        emulator::reset();
+        check_backend(PersistentObjectBackend::default());
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        let backend = MemoryBackend::new();
+        check_backend(backend.clone());
+        assert_eq!(backend.list(b"").unwrap(), [b"a#2", b"b#1"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_host_fs_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = HostFsBackend::new(dir.path()).unwrap();
+        check_backend(backend.clone());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // Files which are not objects are ignored.
+        for name in ["abc", "zz", "A1", "612e746d70.tmp"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let name = std::ffi::OsStr::from_bytes(b"\xff\xfe");
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        assert_eq!(backend.list(b"").unwrap().len(), 2);
+
+        let long_id = [b'a'; 126];
+        assert!(backend.save(&long_id, b"1").is_err());
+        assert!(backend.load(&long_id).is_err());
+        backend.save(&long_id[..125], b"1").unwrap();
+        assert_eq!(backend.list(b"aaa").unwrap(), [&long_id[..125]]);
+    }
+}
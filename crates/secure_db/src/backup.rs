@@ -0,0 +1,372 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::codec::{deserialize, serialize};
+use crate::error::{bail, ensure, format_err};
+use crate::{Error, Result, SecureStorageDb, StorageBackend};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use optee_utee::{
+    AlgorithmId, AttributeId, AttributeMemref, DeriveKey, ErrorKind, GenericObject, Mac,
+    OperationMode, Random, TransientObject, TransientObjectType, AE,
+};
+use serde::{Deserialize, Serialize};
+
+// A backup archive is laid out as:
+//   ARCHIVE_MAGIC | key kind (1 byte) | salt (32 bytes) | nonce (12 bytes)
+//   | ciphertext | tag (16 bytes)
+// The ciphertext is the bincode encoding of Archive, encrypted with
+// AES-256-GCM and the header as additional data. The AES key is derived from
+// the wrapping key or the ECDH shared secret with HKDF-SHA256 and the salt.
+// The ECDH shared secret is computed from the static keys of both TAs, so that
+// the importing TA only accepts archives sealed by the TA whose public value
+// it pinned.
+
+const ARCHIVE_MAGIC: &[u8] = b"SDBBACKUP1";
+const KIND_WRAPPING: u8 = 0;
+const KIND_ECDH: u8 = 1;
+const SALT_LEN: usize = 32;
+const ECC_COORDINATE_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const MIN_WRAPPING_KEY_LEN: usize = 16;
+const HKDF_INFO: &[u8] = b"secure_db backup";
+
+// The key sealing a backup archive.
+pub enum BackupKey<'a> {
+    // A secret shared by the exporting and the importing TA, e.g. provisioned
+    // by the vendor, of at least 16 bytes.
+    Wrapping(&'a [u8]),
+    // The ECDH P-256 keypair of this TA, and the public value of the other TA,
+    // which this one pinned: the importing TA for export, the exporting TA for
+    // import.
+    Ecdh {
+        keypair: &'a TransientObject,
+        peer_x: &'a [u8],
+        peer_y: &'a [u8],
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    name: String,
+    // Keys without value, like the entries of secondary indexes, have None.
+    entries: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl<B: StorageBackend> SecureStorageDb<B> {
+    // Returns a sealed archive of the entries of the db, which can be kept out
+    // of the TEE (e.g. in the REE) and restored with import, on this device or
+    // on another one sharing `key`.
+    pub fn export(&self, key: BackupKey) -> Result<Vec<u8>> {
+        let mut entries = Vec::new();
+        for key in self.keys()? {
+            entries.push((key.clone(), self.backend().load(key.as_bytes())?));
+        }
+        let archive = Archive {
+            name: self.name().to_string(),
+            entries,
+        };
+        seal(&serialize(&archive)?, key)
+    }
+
+    // Verifies `archive`, exported from a db of the same name, and replaces the
+    // entries of the db with its entries in one transaction.
+    pub fn import(&mut self, archive: &[u8], key: BackupKey) -> Result<()> {
+        let archive: Archive = deserialize(&unseal(archive, key)?)?;
+        ensure!(
+            archive.name == self.name(),
+            "Archive of the db {}, not {}",
+            archive.name,
+            self.name()
+        );
+        let keys: Vec<String> = self.keys()?.cloned().collect();
+        let mut transaction = self.transaction();
+        for key in keys {
+            transaction.delete(&key)?;
+        }
+        for (key, value) in archive.entries {
+            match value {
+                Some(value) => transaction.put(key, value),
+                None => transaction.insert_key(key),
+            }
+        }
+        transaction.commit()
+    }
+}
+
+fn seal(plaintext: &[u8], key: BackupKey) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    Random::generate(&mut salt);
+    let mut header = ARCHIVE_MAGIC.to_vec();
+    let secret = match key {
+        BackupKey::Wrapping(wrapping_key) => {
+            ensure!(
+                wrapping_key.len() >= MIN_WRAPPING_KEY_LEN,
+                "Wrapping key too short"
+            );
+            header.push(KIND_WRAPPING);
+            header.extend_from_slice(&salt);
+            wrapping_key.to_vec()
+        }
+        BackupKey::Ecdh {
+            keypair,
+            peer_x,
+            peer_y,
+        } => {
+            header.push(KIND_ECDH);
+            header.extend_from_slice(&salt);
+            ecdh_shared_secret(keypair, peer_x, peer_y)?
+        }
+    };
+    let mut nonce = [0u8; NONCE_LEN];
+    Random::generate(&mut nonce);
+    header.extend_from_slice(&nonce);
+
+    let operation = aes_gcm(OperationMode::Encrypt, &derive_key(&secret, &salt)?)?;
+    operation.init(&nonce, TAG_LEN * 8, header.len(), plaintext.len())?;
+    operation.update_aad(&header);
+    let mut ciphertext = vec![0u8; plaintext.len()];
+    let mut tag = [0u8; TAG_LEN];
+    let (len, _) = operation.encrypt_final(plaintext, &mut ciphertext, &mut tag)?;
+    ciphertext.truncate(len);
+
+    let mut archive = header;
+    archive.extend_from_slice(&ciphertext);
+    archive.extend_from_slice(&tag);
+    Ok(archive)
+}
+
+fn unseal(archive: &[u8], key: BackupKey) -> Result<Vec<u8>> {
+    let invalid = || format_err!("Invalid backup archive");
+    let rest = archive.strip_prefix(ARCHIVE_MAGIC).ok_or_else(invalid)?;
+    let (&kind, rest) = rest.split_first().ok_or_else(invalid)?;
+    ensure!(rest.len() >= SALT_LEN, "Invalid backup archive");
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let secret = match (kind, key) {
+        (KIND_WRAPPING, BackupKey::Wrapping(wrapping_key)) => wrapping_key.to_vec(),
+        (
+            KIND_ECDH,
+            BackupKey::Ecdh {
+                keypair,
+                peer_x,
+                peer_y,
+            },
+        ) => ecdh_shared_secret(keypair, peer_x, peer_y)?,
+        (KIND_WRAPPING, _) => bail!("Archive sealed with a wrapping key"),
+        (KIND_ECDH, _) => bail!("Archive sealed with an ECDH key"),
+        _ => return Err(invalid()),
+    };
+    ensure!(rest.len() >= NONCE_LEN + TAG_LEN, "Invalid backup archive");
+    let (nonce, rest) = rest.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let header = &archive[..archive.len() - rest.len()];
+
+    let operation = aes_gcm(OperationMode::Decrypt, &derive_key(&secret, salt)?)?;
+    operation.init(nonce, TAG_LEN * 8, header.len(), ciphertext.len())?;
+    operation.update_aad(header);
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let len = operation
+        .decrypt_final(ciphertext, &mut plaintext, tag)
+        .map_err(|e| match e.kind() {
+            ErrorKind::MacInvalid => format_err!("Backup archive authentication failed"),
+            _ => Error::Tee(e),
+        })?;
+    plaintext.truncate(len);
+    Ok(plaintext)
+}
+
+fn aes_gcm(mode: OperationMode, key: &[u8]) -> Result<AE> {
+    let mut object = TransientObject::allocate(TransientObjectType::Aes, key.len() * 8)?;
+    object.populate(&[AttributeMemref::from_ref(AttributeId::SecretValue, key).into()])?;
+    let operation = AE::allocate(AlgorithmId::AesGcm, mode, key.len() * 8)?;
+    operation.set_key(&object)?;
+    Ok(operation)
+}
+
+fn ecdh_shared_secret(keypair: &TransientObject, x: &[u8], y: &[u8]) -> Result<Vec<u8>> {
+    let operation = DeriveKey::allocate(AlgorithmId::EcDhDeriveSharedSecret, 256)?;
+    operation.set_key(keypair)?;
+    let mut secret = TransientObject::allocate(TransientObjectType::GenericSecret, 256)?;
+    operation.derive(
+        &[
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+        ],
+        &mut secret,
+    );
+    let mut value = [0u8; ECC_COORDINATE_LEN];
+    let len = secret.ref_attribute(AttributeId::SecretValue, &mut value)?;
+    Ok(value[..len].to_vec())
+}
+
+// HKDF-SHA256 (RFC 5869) of `secret`, with a single output block.
+fn derive_key(secret: &[u8], salt: &[u8]) -> Result<[u8; KEY_LEN]> {
+    let prk = hmac_sha256(salt, &[secret])?;
+    hmac_sha256(&prk, &[HKDF_INFO, &[1]])
+}
+
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> Result<[u8; KEY_LEN]> {
+    let mut object = TransientObject::allocate(TransientObjectType::HmacSha256, key.len() * 8)?;
+    object.populate(&[AttributeMemref::from_ref(AttributeId::SecretValue, key).into()])?;
+    let operation = Mac::allocate(AlgorithmId::HmacSha256, key.len() * 8)?;
+    operation.set_key(&object)?;
+    operation.init(&[]);
+    for chunk in data {
+        operation.update(chunk);
+    }
+    let mut mac = [0u8; KEY_LEN];
+    operation.compute_final(&[], &mut mac)?;
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBackend;
+    use optee_utee::AttributeValue;
+    use optee_utee_sys as raw;
+
+    const WRAPPING_KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    fn open(backend: &MemoryBackend) -> SecureStorageDb<MemoryBackend> {
+        SecureStorageDb::open_with_backend("db".to_string(), backend.clone()).unwrap()
+    }
+
+    fn populated() -> SecureStorageDb<MemoryBackend> {
+        let mut db = open(&MemoryBackend::new());
+        let mut transaction = db.transaction();
+        transaction.put("wallet#1".to_string(), b"seed 1".to_vec());
+        transaction.put("wallet#2".to_string(), b"seed 2".to_vec());
+        transaction.insert_key("wallet@label#main\u{0}1".to_string());
+        transaction.commit().unwrap();
+        db
+    }
+
+    #[test]
+    fn test_export_import_with_wrapping_key() {
+        let db = populated();
+        let archive = db.export(BackupKey::Wrapping(WRAPPING_KEY)).unwrap();
+        assert!(!archive.windows(6).any(|window| window == b"seed 1"));
+
+        let backend = MemoryBackend::new();
+        let mut restored = open(&backend);
+        restored
+            .put("stale".to_string(), b"value".to_vec())
+            .unwrap();
+        restored
+            .import(&archive, BackupKey::Wrapping(WRAPPING_KEY))
+            .unwrap();
+        let restored = open(&backend);
+        let keys: Vec<_> = restored.keys().unwrap().cloned().collect();
+        assert_eq!(keys, ["wallet#1", "wallet#2", "wallet@label#main\u{0}1"]);
+        assert_eq!(restored.get("wallet#2").unwrap(), b"seed 2");
+
+        let mut other = SecureStorageDb::open_with_backend("other".to_string(), backend).unwrap();
+        assert!(other
+            .import(&archive, BackupKey::Wrapping(WRAPPING_KEY))
+            .is_err());
+    }
+
+    #[test]
+    fn test_import_rejects_tampered_archive() {
+        let db = populated();
+        let archive = db.export(BackupKey::Wrapping(WRAPPING_KEY)).unwrap();
+        let mut restored = open(&MemoryBackend::new());
+
+        let wrong_key = [0u8; 32];
+        assert!(restored
+            .import(&archive, BackupKey::Wrapping(&wrong_key))
+            .is_err());
+        for index in [
+            ARCHIVE_MAGIC.len() + 1,
+            archive.len() - 20,
+            archive.len() - 1,
+        ] {
+            let mut tampered = archive.clone();
+            tampered[index] ^= 1;
+            assert!(restored
+                .import(&tampered, BackupKey::Wrapping(WRAPPING_KEY))
+                .is_err());
+        }
+        assert!(restored
+            .import(&archive[..40], BackupKey::Wrapping(WRAPPING_KEY))
+            .is_err());
+        assert_eq!(restored.keys().unwrap().count(), 0);
+        assert!(db.export(BackupKey::Wrapping(b"short")).is_err());
+    }
+
+    fn ecdh_keypair() -> (TransientObject, [u8; 32], [u8; 32]) {
+        let keypair = TransientObject::allocate(TransientObjectType::EcdhKeypair, 256).unwrap();
+        let curve =
+            AttributeValue::from_value(AttributeId::EccCurve, raw::TEE_ECC_CURVE_NIST_P256, 0);
+        keypair.generate_key(256, &[curve.into()]).unwrap();
+        let mut x = [0u8; 32];
+        let mut y = [0u8; 32];
+        keypair
+            .ref_attribute(AttributeId::EccPublicValueX, &mut x)
+            .unwrap();
+        keypair
+            .ref_attribute(AttributeId::EccPublicValueY, &mut y)
+            .unwrap();
+        (keypair, x, y)
+    }
+
+    #[test]
+    fn test_export_import_with_ecdh() {
+        let (exporter, exporter_x, exporter_y) = ecdh_keypair();
+        let (importer, importer_x, importer_y) = ecdh_keypair();
+        let export_key = BackupKey::Ecdh {
+            keypair: &exporter,
+            peer_x: &importer_x,
+            peer_y: &importer_y,
+        };
+        let import_key = || BackupKey::Ecdh {
+            keypair: &importer,
+            peer_x: &exporter_x,
+            peer_y: &exporter_y,
+        };
+
+        let db = populated();
+        let archive = db.export(export_key).unwrap();
+        let mut restored = open(&MemoryBackend::new());
+        assert!(restored
+            .import(&archive, BackupKey::Wrapping(WRAPPING_KEY))
+            .is_err());
+        restored.import(&archive, import_key()).unwrap();
+        assert_eq!(restored.get("wallet#1").unwrap(), b"seed 1");
+
+        // An archive sealed to the public value of the importer by another TA
+        // than the pinned one is rejected.
+        let (forger, _, _) = ecdh_keypair();
+        let mut forged = open(&MemoryBackend::new());
+        forged.put("forged".to_string(), b"1".to_vec()).unwrap();
+        let archive = forged
+            .export(BackupKey::Ecdh {
+                keypair: &forger,
+                peer_x: &importer_x,
+                peer_y: &importer_y,
+            })
+            .unwrap();
+        assert!(restored.import(&archive, import_key()).is_err());
+        assert_eq!(restored.get("wallet#1").unwrap(), b"seed 1");
+        assert!(!restored.contains_key("forged").unwrap());
+    }
+}
@@ -15,31 +15,108 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::compat::{HashMap, RwLock};
+use crate::error::{ensure, format_err};
+use crate::expiry::{expiry_key, expiry_prefix, expiry_value, ta_time, Clock};
+use crate::schema::{convert_legacy_records, encode, record_expiry};
+use crate::storable::{index_keys, index_prefix, indexed_key};
+use crate::BackupKey;
+use crate::Migrations;
+use crate::PersistentObjectBackend;
 use crate::SecureStorageDb;
 use crate::Storable;
-use anyhow::{anyhow, Result};
-use std::{
-    string::ToString,
-    collections::HashMap,
+use crate::StorageBackend;
+use crate::Transaction;
+use crate::{Error, Result};
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
     convert::TryFrom,
     hash::Hash,
-    sync::{Arc, RwLock},
+    ops::{Bound, RangeBounds},
 };
+use optee_utee::ObjectStorageConstants;
+use serde::de::DeserializeOwned;
 
 // SecureStorageClient is a client to interact with SecureStorageDb.
 // Bound operations to Structure that implements Storable trait.
 
-pub struct SecureStorageClient {
-    db: Arc<RwLock<SecureStorageDb>>,
+pub struct SecureStorageClient<B: StorageBackend = PersistentObjectBackend> {
+    db: Arc<RwLock<SecureStorageDb<B>>>,
+    migrations: Migrations,
 }
 
 impl SecureStorageClient {
     pub fn open(db_name: &str) -> Result<Self> {
+        Self::open_with_storage(db_name, ObjectStorageConstants::Private)
+    }
+
+    pub fn open_with_storage(db_name: &str, storage_id: ObjectStorageConstants) -> Result<Self> {
+        Self::from_db(SecureStorageDb::open_with_storage(
+            db_name.to_string(),
+            storage_id,
+        )?)
+    }
+
+    // Moves the db from the storage `from` to the storage `to`, see
+    // SecureStorageDb::migrate, and returns a client of the migrated db.
+    pub fn migrate(
+        db_name: &str,
+        from: ObjectStorageConstants,
+        to: ObjectStorageConstants,
+    ) -> Result<Self> {
+        Self::from_db(SecureStorageDb::migrate(db_name.to_string(), from, to)?)
+    }
+}
+
+impl<B: StorageBackend> SecureStorageClient<B> {
+    pub fn open_with_backend(db_name: &str, backend: B) -> Result<Self> {
+        Self::from_db(SecureStorageDb::open_with_backend(
+            db_name.to_string(),
+            backend,
+        )?)
+    }
+
+    // Records of a db written before versioning are given a header first, see
+    // the schema module.
+    fn from_db(mut db: SecureStorageDb<B>) -> Result<Self> {
+        convert_legacy_records(&mut db)?;
         Ok(Self {
-            db: Arc::new(RwLock::new(SecureStorageDb::open(db_name.to_string())?)),
+            db: Arc::new(RwLock::new(db)),
+            migrations: Migrations::new(),
         })
     }
 
+    // Registers the upgrade of the records of V from the version
+    // `from_version` to the next one, see Migrations::register. Records of an
+    // older version are upgraded when they are read, and the upgraded record
+    // is stored in place.
+    //
+    // # Example
+    //
+    // client.register_migration::<Wallet, WalletV0, _, _>(0, |old| {
+    //     Ok(Wallet { id: old.id, entropy: old.entropy, label: String::new() })
+    // });
+    pub fn register_migration<V, Old, New, F>(
+        &mut self,
+        from_version: u32,
+        migration: F,
+    ) -> &mut Self
+    where
+        V: Storable,
+        Old: DeserializeOwned,
+        New: serde::Serialize,
+        F: Fn(Old) -> Result<New> + Send + Sync + 'static,
+    {
+        self.migrations
+            .register::<V, Old, New, F>(from_version, migration);
+        self
+    }
+
     pub fn get<V>(&self, key: &V::Key) -> Result<V>
     where
         V: Storable + serde::de::DeserializeOwned,
@@ -50,36 +127,114 @@ impl SecureStorageClient {
         let value = self
             .db
             .read()
-            .map_err(|_| anyhow!("Failed to acquire read lock"))?
+            .map_err(|_| format_err!("Failed to acquire read lock"))?
             .get(&storage_key)?;
-        Ok(bincode::deserialize(&value)?)
+        let clock = Clock::new();
+        let mut pending = PendingWrites::default();
+        let value = self.decode(&storage_key, value, &clock, &mut pending)?;
+        self.apply_pending::<V>(pending, &clock)?;
+        value.ok_or(format_err!("Key not found: the entry has expired"))
     }
 
+    // Stores `value` and updates its secondary indexes, see Storable::INDEXES.
     pub fn put<V>(&self, value: &V) -> Result<()>
     where
-        V: Storable + serde::Serialize,
+        V: Storable + serde::Serialize + serde::de::DeserializeOwned,
     {
-        let key = value.storage_key();
-        let value = bincode::serialize(value)?;
-        self.db
-            .write()
-            .map_err(|_| anyhow!("Failed to acquire write lock"))?
-            .put(key, value)?;
-        Ok(())
+        self.transaction(|transaction| transaction.put(value))
+    }
+
+    // Stores `value`, which expires after `ttl` seconds of TA persistent time
+    // (see Time::ta_time). Expired entries are no longer returned, they are
+    // deleted when read or by purge_expired. Fails if the TA time is not
+    // available, see the expiry module for the behaviour when it is lost.
+    pub fn put_with_ttl<V>(&self, value: &V, ttl: u32) -> Result<()>
+    where
+        V: Storable + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.transaction(|transaction| transaction.put_with_ttl(value, ttl))
     }
 
     pub fn delete_entry<V>(&self, key: &V::Key) -> Result<()>
     where
-        V: Storable,
+        V: Storable + serde::de::DeserializeOwned,
         V::Key: ToString,
     {
-        let key = key.to_string();
-        let storage_key = V::concat_key(&key);
+        self.transaction(|transaction| transaction.delete_entry::<V>(key))
+    }
+
+    // Deletes the expired entries of V, and returns their number. Only the
+    // expiry index of V is scanned.
+    pub fn purge_expired<V>(&self) -> Result<usize>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+    {
+        let clock = Clock::new();
+        let prefix = expiry_prefix::<V>();
+        // Expiry keys are "$PREFIX$EXPIRES_AT\0$ID", so the keys of the entries
+        // expired at `now` are before "$PREFIX$NOW\u{1}".
+        let end = match clock.now() {
+            Some(now) => format!("{}{}\u{1}", prefix, expiry_value(now)),
+            None => index_end(&prefix),
+        };
+        let expiry_keys: Vec<String> = self
+            .db
+            .read()
+            .map_err(|_| format_err!("Failed to acquire read lock"))?
+            .keys_in_range((Bound::Included(&prefix), Bound::Excluded(&end)))?
+            .cloned()
+            .collect();
+        self.transaction(|transaction| {
+            let mut purged = 0;
+            for expiry_key in expiry_keys {
+                let storage_key =
+                    indexed_key::<V>(&expiry_key).ok_or(format_err!("Invalid expiry key"))?;
+                if transaction.delete_if_expired::<V>(&storage_key, &clock)? {
+                    purged += 1;
+                } else if transaction.inner.contains_key(&expiry_key)? {
+                    // the entry was deleted without its expiry key
+                    transaction.inner.delete(&expiry_key)?;
+                }
+            }
+            Ok(purged)
+        })
+    }
+
+    // Returns a sealed archive of the db, see SecureStorageDb::export.
+    pub fn export(&self, key: BackupKey) -> Result<Vec<u8>> {
+        self.db
+            .read()
+            .map_err(|_| format_err!("Failed to acquire read lock"))?
+            .export(key)
+    }
+
+    // Restores an archive returned by export, see SecureStorageDb::import.
+    pub fn import(&self, archive: &[u8], key: BackupKey) -> Result<()> {
         self.db
             .write()
-            .map_err(|_| anyhow!("Failed to acquire write lock"))?
-            .delete(&storage_key)?;
-        Ok(())
+            .map_err(|_| format_err!("Failed to acquire write lock"))?
+            .import(archive, key)
+    }
+
+    // Runs `f` in a transaction, which is committed if `f` succeeds and rolled
+    // back otherwise. The db is locked for writing until then. `f` may return
+    // its own error type, e.g. anyhow::Error, if it converts from Error.
+    pub fn transaction<R, E, F>(&self, f: F) -> core::result::Result<R, E>
+    where
+        F: FnOnce(&mut ClientTransaction<B>) -> core::result::Result<R, E>,
+        E: From<Error>,
+    {
+        let mut db = self
+            .db
+            .write()
+            .map_err(|_| format_err!("Failed to acquire write lock"))?;
+        let mut transaction = ClientTransaction {
+            inner: db.transaction(),
+            migrations: &self.migrations,
+        };
+        let result = f(&mut transaction)?;
+        transaction.inner.commit()?;
+        Ok(result)
     }
 
     pub fn list_entries<V>(&self) -> Result<HashMap<V::Key, V>>
@@ -90,14 +245,631 @@ impl SecureStorageClient {
         let map = self
             .db
             .read()
-            .map_err(|_| anyhow!("Failed to acquire read lock"))?
-            .list_entries_with_prefix(V::table_name())?;
+            .map_err(|_| format_err!("Failed to acquire read lock"))?
+            .list_entries_with_prefix(&V::concat_key(""))?;
         let mut result = HashMap::new();
-        for (_k, v) in map {
-            let value: V = bincode::deserialize(&v)?;
-            let key = value.unique_id();
-            result.insert(key, value);
+        let clock = Clock::new();
+        let mut pending = PendingWrites::default();
+        for (k, v) in map {
+            if let Some(value) = self.decode::<V>(&k, v, &clock, &mut pending)? {
+                let key = value.unique_id();
+                result.insert(key, value);
+            }
         }
+        self.apply_pending::<V>(pending, &clock)?;
         Ok(result)
     }
+
+    // Returns at most `limit` records of the table, ordered by key, starting
+    // after `cursor`. Pass the `next` cursor of a page to get the next one.
+    pub fn list_entries_page<V>(&self, cursor: Option<&str>, limit: usize) -> Result<Page<V>>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+    {
+        let clock = Clock::new();
+        let mut pending = PendingWrites::default();
+        let page = {
+            let db = self
+                .db
+                .read()
+                .map_err(|_| format_err!("Failed to acquire read lock"))?;
+            let prefix = V::concat_key("");
+            let start = match cursor {
+                Some(cursor) => Bound::Excluded(cursor),
+                None => Bound::Included(prefix.as_str()),
+            };
+            let keys = db
+                .keys_in_range((start, Bound::Unbounded))?
+                .take_while(|key| key.starts_with(&prefix));
+            Page::collect(keys, limit, |key| {
+                self.decode(key, db.get(key)?, &clock, &mut pending)
+            })?
+        };
+        self.apply_pending::<V>(pending, &clock)?;
+        Ok(page)
+    }
+
+    // Returns the records whose value in the secondary index `index` is
+    // `value`.
+    pub fn find_by_index<V>(&self, index: &str, value: &str) -> Result<Vec<V>>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+    {
+        let db = self
+            .db
+            .read()
+            .map_err(|_| format_err!("Failed to acquire read lock"))?;
+        let clock = Clock::new();
+        let mut pending = PendingWrites::default();
+        let prefix = format!("{}{}\0", index_prefix::<V>(index), value);
+        let entries = db
+            .keys_with_prefix(&prefix)?
+            .filter_map(|index_key| {
+                self.load_indexed(&db, index_key, &clock, &mut pending)
+                    .transpose()
+            })
+            .collect::<Result<_>>()?;
+        drop(db);
+        self.apply_pending::<V>(pending, &clock)?;
+        Ok(entries)
+    }
+
+    // Returns at most `limit` records whose value in the secondary index
+    // `index` is in `range`, ordered by value, starting after `cursor`. Pass
+    // the `next` cursor of a page to get the next one.
+    //
+    // # Example
+    //
+    // let page = client.range_by_index::<Wallet, _>("created_at", "2024".."2025", None, 10)?;
+    pub fn range_by_index<V, R>(
+        &self,
+        index: &str,
+        range: R,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page<V>>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+        R: RangeBounds<str>,
+    {
+        let db = self
+            .db
+            .read()
+            .map_err(|_| format_err!("Failed to acquire read lock"))?;
+        let clock = Clock::new();
+        let mut pending = PendingWrites::default();
+        // Index keys are "$PREFIX$VALUE\0$ID", so the keys of the values
+        // greater than `value` start at "$PREFIX$VALUE\u{1}".
+        let prefix = index_prefix::<V>(index);
+        let start = match (cursor, range.start_bound()) {
+            (Some(cursor), _) => Bound::Excluded(cursor.to_string()),
+            (None, Bound::Included(value)) => Bound::Included(format!("{}{}", prefix, value)),
+            (None, Bound::Excluded(value)) => Bound::Included(format!("{}{}\u{1}", prefix, value)),
+            (None, Bound::Unbounded) => Bound::Included(prefix.clone()),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(value) => Bound::Excluded(format!("{}{}\u{1}", prefix, value)),
+            Bound::Excluded(value) => Bound::Excluded(format!("{}{}", prefix, value)),
+            Bound::Unbounded => Bound::Excluded(index_end(&prefix)),
+        };
+        let keys = db.keys_in_range((
+            start.as_ref().map(String::as_str),
+            end.as_ref().map(String::as_str),
+        ))?;
+        let page = Page::collect(keys, limit, |index_key| {
+            self.load_indexed(&db, index_key, &clock, &mut pending)
+        })?;
+        drop(db);
+        self.apply_pending::<V>(pending, &clock)?;
+        Ok(page)
+    }
+
+    // Decodes the record stored at `key`, see Migrations::decode. Returns None
+    // if it has expired, and adds the writes it needs, its upgrade or its
+    // eviction, to `pending`.
+    fn decode<V>(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        clock: &Clock,
+        pending: &mut PendingWrites,
+    ) -> Result<Option<V>>
+    where
+        V: Storable + DeserializeOwned,
+    {
+        if clock.is_expired(record_expiry(&data)) {
+            pending.evictions.push(key.to_string());
+            return Ok(None);
+        }
+        let (value, encoded) = self.migrations.decode::<V>(&data)?;
+        if let Some(new) = encoded {
+            pending.upgrades.push(Upgrade {
+                key: key.to_string(),
+                old: data,
+                new,
+                index_keys: index_keys(&value)?,
+            });
+        }
+        Ok(Some(value))
+    }
+
+    fn load_indexed<V>(
+        &self,
+        db: &SecureStorageDb<B>,
+        index_key: &str,
+        clock: &Clock,
+        pending: &mut PendingWrites,
+    ) -> Result<Option<V>>
+    where
+        V: Storable + DeserializeOwned,
+    {
+        let storage_key = indexed_key::<V>(index_key).ok_or(format_err!("Invalid index key"))?;
+        self.decode(&storage_key, db.get(&storage_key)?, clock, pending)
+    }
+
+    // Applies the writes needed by a read once the read lock is released:
+    // upgraded records are stored in place, unless they were written since,
+    // and expired records are deleted. Migrations are expected to keep the
+    // index values of a record, only the missing index entries are added.
+    fn apply_pending<V>(&self, pending: PendingWrites, clock: &Clock) -> Result<()>
+    where
+        V: Storable + DeserializeOwned,
+    {
+        if pending.upgrades.is_empty() && pending.evictions.is_empty() {
+            return Ok(());
+        }
+        self.transaction(|transaction| {
+            for upgrade in pending.upgrades {
+                if transaction.inner.get(&upgrade.key).ok() != Some(upgrade.old) {
+                    continue;
+                }
+                for index_key in upgrade.index_keys {
+                    transaction.inner.insert_key(index_key);
+                }
+                transaction.inner.put(upgrade.key, upgrade.new);
+            }
+            for key in pending.evictions {
+                transaction.delete_if_expired::<V>(&key, clock)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// The writes needed by a read, see SecureStorageClient::apply_pending.
+#[derive(Default)]
+struct PendingWrites {
+    upgrades: Vec<Upgrade>,
+    // The keys of the expired records.
+    evictions: Vec<String>,
+}
+
+// A record upgraded by a read.
+struct Upgrade {
+    key: String,
+    old: Vec<u8>,
+    new: Vec<u8>,
+    index_keys: Vec<String>,
+}
+
+// A page of records, see SecureStorageClient::list_entries_page.
+
+#[derive(Debug)]
+pub struct Page<V> {
+    pub entries: Vec<V>,
+    // The cursor of the next page, None if this is the last one. The next page
+    // may be empty if the remaining records have expired.
+    pub next: Option<String>,
+}
+
+impl<V> Page<V> {
+    fn collect<'k>(
+        keys: impl Iterator<Item = &'k String>,
+        limit: usize,
+        mut load: impl FnMut(&str) -> Result<Option<V>>,
+    ) -> Result<Self> {
+        ensure!(limit > 0, "Page limit must be positive");
+        let mut keys = keys.peekable();
+        let mut entries = Vec::new();
+        let mut last = None;
+        while entries.len() < limit {
+            let key = match keys.next() {
+                Some(key) => key,
+                None => break,
+            };
+            if let Some(entry) = load(key)? {
+                entries.push(entry);
+            }
+            last = Some(key);
+        }
+        let next = match keys.peek() {
+            Some(_) => last.cloned(),
+            None => None,
+        };
+        Ok(Self { entries, next })
+    }
+}
+
+// Returns the smallest key greater than the keys starting with `prefix`, which
+// ends with the '#' separator.
+fn index_end(prefix: &str) -> String {
+    format!("{}$", prefix.trim_end_matches('#'))
+}
+
+// ClientTransaction is the typed counterpart of Transaction, see
+// SecureStorageClient::transaction.
+
+pub struct ClientTransaction<'a, B: StorageBackend = PersistentObjectBackend> {
+    inner: Transaction<'a, B>,
+    migrations: &'a Migrations,
+}
+
+impl<'a, B: StorageBackend> ClientTransaction<'a, B> {
+    pub fn get<V>(&self, key: &V::Key) -> Result<V>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+        V::Key: ToString,
+    {
+        let storage_key = V::concat_key(&key.to_string());
+        let data = self.inner.get(&storage_key)?;
+        ensure!(
+            !Clock::new().is_expired(record_expiry(&data)),
+            "Key not found: the entry has expired"
+        );
+        let (value, _) = self.migrations.decode(&data)?;
+        Ok(value)
+    }
+
+    pub fn put<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: Storable + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.put_with_expiry(value, None)
+    }
+
+    // Stores `value`, which expires after `ttl` seconds, see
+    // SecureStorageClient::put_with_ttl.
+    pub fn put_with_ttl<V>(&mut self, value: &V, ttl: u32) -> Result<()>
+    where
+        V: Storable + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let expires_at = ta_time()? + ttl as u64;
+        self.put_with_expiry(value, Some(expires_at))
+    }
+
+    fn put_with_expiry<V>(&mut self, value: &V, expires_at: Option<u64>) -> Result<()>
+    where
+        V: Storable + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let storage_key = value.storage_key();
+        if self.inner.contains_key(&storage_key)? {
+            self.delete_index_keys::<V>(&storage_key)?;
+        }
+        for index_key in index_keys(value)? {
+            self.inner.insert_key(index_key);
+        }
+        if let Some(expires_at) = expires_at {
+            let id = value.unique_id().to_string();
+            self.inner.insert_key(expiry_key::<V>(&id, expires_at));
+        }
+        self.inner.put(storage_key, encode(value, expires_at)?);
+        Ok(())
+    }
+
+    pub fn delete_entry<V>(&mut self, key: &V::Key) -> Result<()>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+        V::Key: ToString,
+    {
+        let storage_key = V::concat_key(&key.to_string());
+        self.delete_storage_key::<V>(&storage_key)
+    }
+
+    fn delete_storage_key<V>(&mut self, storage_key: &str) -> Result<()>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+    {
+        if self.inner.contains_key(storage_key)? {
+            self.delete_index_keys::<V>(storage_key)?;
+        }
+        self.inner.delete(storage_key)
+    }
+
+    // Deletes the record stored at `storage_key` if it has expired, returns
+    // true if it was deleted.
+    fn delete_if_expired<V>(&mut self, storage_key: &str, clock: &Clock) -> Result<bool>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+    {
+        if !self.inner.contains_key(storage_key)? {
+            return Ok(false);
+        }
+        if !clock.is_expired(record_expiry(&self.inner.get(storage_key)?)) {
+            return Ok(false);
+        }
+        self.delete_storage_key::<V>(storage_key)?;
+        Ok(true)
+    }
+
+    // Deletes the index and expiry entries of the record stored at
+    // `storage_key`. The record is only decoded if V has indexes, so that a
+    // record which can not be decoded can still be overwritten or deleted.
+    fn delete_index_keys<V>(&mut self, storage_key: &str) -> Result<()>
+    where
+        V: Storable + serde::de::DeserializeOwned,
+    {
+        let data = self.inner.get(storage_key)?;
+        let mut keys = Vec::new();
+        if let Some(expires_at) = record_expiry(&data) {
+            let table_prefix = V::concat_key("");
+            let id = storage_key
+                .strip_prefix(&table_prefix)
+                .unwrap_or(storage_key);
+            keys.push(expiry_key::<V>(id, expires_at));
+        }
+        if !V::INDEXES.is_empty() {
+            let (old, _): (V, _) = self.migrations.decode(&data)?;
+            keys.extend(index_keys(&old)?);
+        }
+        for key in keys {
+            if self.inner.contains_key(&key)? {
+                self.inner.delete(&key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBackend;
+    use anyhow::bail;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Account {
+        id: String,
+        balance: u64,
+    }
+
+    impl Storable for Account {
+        type Key = String;
+
+        fn unique_id(&self) -> Self::Key {
+            self.id.clone()
+        }
+    }
+
+    #[test]
+    fn test_transaction() {
+        let client = SecureStorageClient::open_with_backend("db", MemoryBackend::new()).unwrap();
+        let (alice, bob) = ("alice".to_string(), "bob".to_string());
+        client
+            .put(&Account {
+                id: alice.clone(),
+                balance: 10,
+            })
+            .unwrap();
+
+        let transfer = |amount: u64| {
+            // transactions may fail with the error type of the caller
+            client.transaction(|transaction| -> anyhow::Result<()> {
+                let mut from = transaction.get::<Account>(&alice)?;
+                transaction.put(&Account {
+                    id: bob.clone(),
+                    balance: amount,
+                })?;
+                if from.balance < amount {
+                    bail!("Insufficient balance");
+                }
+                from.balance -= amount;
+                transaction.put(&from)?;
+                Ok(())
+            })
+        };
+        assert!(transfer(20).is_err());
+        assert_eq!(client.list_entries::<Account>().unwrap().len(), 1);
+
+        transfer(4).unwrap();
+        assert_eq!(client.get::<Account>(&alice).unwrap().balance, 6);
+        assert_eq!(client.get::<Account>(&bob).unwrap().balance, 4);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Wallet {
+        id: String,
+        label: String,
+        created_at: u64,
+    }
+
+    impl Wallet {
+        fn new(id: &str, label: &str, created_at: u64) -> Self {
+            Self {
+                id: id.to_string(),
+                label: label.to_string(),
+                created_at,
+            }
+        }
+    }
+
+    impl Storable for Wallet {
+        type Key = String;
+
+        const INDEXES: &'static [&'static str] = &["label", "created_at"];
+
+        fn unique_id(&self) -> Self::Key {
+            self.id.clone()
+        }
+
+        fn index_value(&self, index: &str) -> Option<String> {
+            match index {
+                "label" => Some(self.label.clone()),
+                "created_at" => Some(format!("{:020}", self.created_at)),
+                _ => None,
+            }
+        }
+    }
+
+    fn ids(wallets: &[Wallet]) -> Vec<&str> {
+        wallets.iter().map(|wallet| wallet.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_index_maintenance() {
+        let client = SecureStorageClient::open_with_backend("db", MemoryBackend::new()).unwrap();
+        client.put(&Wallet::new("1", "savings", 10)).unwrap();
+        client.put(&Wallet::new("2", "savings", 20)).unwrap();
+        client.put(&Wallet::new("3", "daily", 30)).unwrap();
+        let found = client.find_by_index::<Wallet>("label", "savings").unwrap();
+        assert_eq!(ids(&found), ["1", "2"]);
+
+        client.put(&Wallet::new("2", "daily", 20)).unwrap();
+        let found = client.find_by_index::<Wallet>("label", "savings").unwrap();
+        assert_eq!(ids(&found), ["1"]);
+        let found = client.find_by_index::<Wallet>("label", "daily").unwrap();
+        assert_eq!(ids(&found), ["2", "3"]);
+
+        client.delete_entry::<Wallet>(&"3".to_string()).unwrap();
+        let found = client.find_by_index::<Wallet>("label", "daily").unwrap();
+        assert_eq!(ids(&found), ["2"]);
+        assert_eq!(client.list_entries::<Wallet>().unwrap().len(), 2);
+        assert!(client.put(&Wallet::new("4", "bad\0label", 40)).is_err());
+    }
+
+    #[test]
+    fn test_range_and_pagination() {
+        let client = SecureStorageClient::open_with_backend("db", MemoryBackend::new()).unwrap();
+        for i in 0..10u64 {
+            client
+                .put(&Wallet::new(&i.to_string(), "label", 100 - i * 10))
+                .unwrap();
+        }
+
+        let page = client.list_entries_page::<Wallet>(None, 4).unwrap();
+        assert_eq!(ids(&page.entries), ["0", "1", "2", "3"]);
+        let page = client
+            .list_entries_page::<Wallet>(page.next.as_deref(), 4)
+            .unwrap();
+        assert_eq!(ids(&page.entries), ["4", "5", "6", "7"]);
+        let page = client
+            .list_entries_page::<Wallet>(page.next.as_deref(), 4)
+            .unwrap();
+        assert_eq!(ids(&page.entries), ["8", "9"]);
+        assert!(page.next.is_none());
+
+        // created_at of wallet i is 100 - 10 * i
+        let (from, to) = (format!("{:020}", 30), format!("{:020}", 70));
+        let range = (Bound::Excluded(from.as_str()), Bound::Included(to.as_str()));
+        let page = client
+            .range_by_index::<Wallet, _>("created_at", range, None, 2)
+            .unwrap();
+        assert_eq!(ids(&page.entries), ["6", "5"]);
+        let page = client
+            .range_by_index::<Wallet, _>("created_at", range, page.next.as_deref(), 2)
+            .unwrap();
+        assert_eq!(ids(&page.entries), ["4", "3"]);
+        assert!(page.next.is_none());
+
+        let page = client
+            .range_by_index::<Wallet, _>("created_at", .., None, 100)
+            .unwrap();
+        assert_eq!(page.entries.len(), 10);
+        assert_eq!(page.entries[0].id, "9");
+        assert!(client
+            .range_by_index::<Wallet, _>("created_at", .., None, 0)
+            .is_err());
+    }
+
+    fn set_ta_time(seconds: u32) {
+        optee_utee::Time { seconds, millis: 0 }
+            .set_ta_time()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_expiry() {
+        let client = SecureStorageClient::open_with_backend("db", MemoryBackend::new()).unwrap();
+        let account = |id: &str| Account {
+            id: id.to_string(),
+            balance: 1,
+        };
+        set_ta_time(1000);
+        client.put_with_ttl(&account("token"), 60).unwrap();
+        client
+            .put_with_ttl(&Wallet::new("1", "temp", 10), 60)
+            .unwrap();
+        client.put(&account("alice")).unwrap();
+        assert_eq!(
+            client.get::<Account>(&"token".to_string()).unwrap().balance,
+            1
+        );
+        assert_eq!(client.list_entries::<Account>().unwrap().len(), 2);
+
+        set_ta_time(1100);
+        assert!(client.get::<Account>(&"token".to_string()).is_err());
+        assert_eq!(client.list_entries::<Account>().unwrap().len(), 1);
+        assert!(client
+            .find_by_index::<Wallet>("label", "temp")
+            .unwrap()
+            .is_empty());
+        let db = client.db.read().unwrap();
+        assert!(!db.contains_key("Account#token").unwrap());
+        assert!(!db.contains_key("Wallet#1").unwrap());
+        assert_eq!(db.keys().unwrap().count(), 1);
+        drop(db);
+
+        // an entry overwritten without ttl no longer expires
+        client.put_with_ttl(&account("token"), 60).unwrap();
+        client.put(&account("token")).unwrap();
+        set_ta_time(1200);
+        assert!(client.get::<Account>(&"token".to_string()).is_ok());
+        assert_eq!(client.db.read().unwrap().keys().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let client = SecureStorageClient::open_with_backend("db", MemoryBackend::new()).unwrap();
+        set_ta_time(1000);
+        for i in 0..5 {
+            client
+                .put_with_ttl(&Wallet::new(&i.to_string(), "temp", i), 10 + i as u32 * 10)
+                .unwrap();
+        }
+        client.put(&Wallet::new("5", "kept", 5)).unwrap();
+
+        set_ta_time(1025);
+        assert_eq!(client.purge_expired::<Wallet>().unwrap(), 2);
+        assert_eq!(client.purge_expired::<Wallet>().unwrap(), 0);
+        let page = client.list_entries_page::<Wallet>(None, 10).unwrap();
+        assert_eq!(ids(&page.entries), ["2", "3", "4", "5"]);
+
+        set_ta_time(1100);
+        assert_eq!(client.purge_expired::<Wallet>().unwrap(), 3);
+        let found = client.find_by_index::<Wallet>("label", "kept").unwrap();
+        assert_eq!(ids(&found), ["5"]);
+        // only the record and its two index entries are left
+        assert_eq!(client.db.read().unwrap().keys().unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_expiry_without_ta_time() {
+        let client = SecureStorageClient::open_with_backend("db", MemoryBackend::new()).unwrap();
+        let account = |id: &str| Account {
+            id: id.to_string(),
+            balance: 1,
+        };
+        optee_utee_emulator::reset();
+        assert!(client.put_with_ttl(&account("token"), 60).is_err());
+
+        set_ta_time(1000);
+        client.put_with_ttl(&account("token"), 60).unwrap();
+        client.put(&account("alice")).unwrap();
+        optee_utee_emulator::roll_back_ta_time();
+        assert!(client.put_with_ttl(&account("other"), 60).is_err());
+        assert_eq!(client.purge_expired::<Account>().unwrap(), 1);
+        assert!(client.get::<Account>(&"token".to_string()).is_err());
+        assert!(client.get::<Account>(&"alice".to_string()).is_ok());
+        assert_eq!(client.list_entries::<Account>().unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,79 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::{Error, Result};
+use alloc::{string::ToString, vec::Vec};
+use serde::{de::DeserializeOwned, Serialize};
+
+// Values are encoded with bincode, in the format of bincode 1 (fixed size
+// little endian integers) which the objects written by earlier versions of
+// the crate use.
+
+pub(crate) fn serialize<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    bincode::serde::encode_to_vec(value, bincode::config::legacy())
+        .map_err(|err| Error::Encoding(err.to_string()))
+}
+
+pub(crate) fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T> {
+    bincode::serde::decode_from_slice(data, bincode::config::legacy())
+        .map(|(value, _)| value)
+        .map_err(|err| Error::Encoding(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Write {
+        Put(Vec<u8>),
+        Delete,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sample {
+        id: u32,
+        label: String,
+        expires_at: Option<u64>,
+        writes: Vec<Write>,
+    }
+
+    #[test]
+    fn test_bincode_1_format() {
+        let sample = Sample {
+            id: 7,
+            label: "ab".to_string(),
+            expires_at: Some(1000),
+            writes: vec![Write::Put(vec![9]), Write::Delete],
+        };
+        // as encoded by bincode::serialize of bincode 1.3
+        let encoded = [
+            7, 0, 0, 0, // id
+            2, 0, 0, 0, 0, 0, 0, 0, b'a', b'b', // label
+            1, 232, 3, 0, 0, 0, 0, 0, 0, // expires_at
+            2, 0, 0, 0, 0, 0, 0, 0, // writes
+            0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 9, // Put
+            1, 0, 0, 0, // Delete
+        ];
+        assert_eq!(serialize(&sample).unwrap(), encoded);
+        assert_eq!(deserialize::<Sample>(&encoded).unwrap(), sample);
+        assert!(matches!(
+            deserialize::<Sample>(&encoded[..10]),
+            Err(Error::Encoding(_))
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+// The std collections and locks used by the crate, and their counterparts in
+// no_std TAs with the same API: hashbrown's HashMap, and spin locks, which are
+// never poisoned.
+
+#[cfg(feature = "std")]
+pub(crate) use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, OnceLock, RwLock},
+};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use self::no_std::*;
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use core::{convert::Infallible, fmt, mem};
+
+    pub(crate) use hashbrown::HashMap;
+    pub(crate) use spin::MutexGuard;
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T>(spin::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) fn lock(&self) -> Result<MutexGuard<'_, T>, Infallible> {
+            Ok(self.0.lock())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct RwLock<T>(spin::RwLock<T>);
+
+    impl<T> RwLock<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Self(spin::RwLock::new(value))
+        }
+
+        pub(crate) fn read(&self) -> Result<spin::RwLockReadGuard<'_, T>, Infallible> {
+            Ok(self.0.read())
+        }
+
+        pub(crate) fn write(&self) -> Result<spin::RwLockWriteGuard<'_, T>, Infallible> {
+            Ok(self.0.write())
+        }
+    }
+
+    pub(crate) struct OnceLock<T>(spin::Once<T>);
+
+    impl<T> OnceLock<T> {
+        pub(crate) fn new() -> Self {
+            Self(spin::Once::new())
+        }
+
+        pub(crate) fn get(&self) -> Option<&T> {
+            self.0.get()
+        }
+
+        pub(crate) fn get_mut(&mut self) -> Option<&mut T> {
+            self.0.get_mut()
+        }
+
+        pub(crate) fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+            self.0.call_once(f)
+        }
+
+        pub(crate) fn take(&mut self) -> Option<T> {
+            mem::replace(&mut self.0, spin::Once::new()).try_into_inner()
+        }
+    }
+
+    impl<T> From<T> for OnceLock<T> {
+        fn from(value: T) -> Self {
+            Self(spin::Once::initialized(value))
+        }
+    }
+
+    impl<T: Clone> Clone for OnceLock<T> {
+        fn clone(&self) -> Self {
+            match self.get() {
+                Some(value) => Self::from(value.clone()),
+                None => Self::new(),
+            }
+        }
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for OnceLock<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_tuple("OnceLock").field(&self.get()).finish()
+        }
+    }
+}
@@ -15,60 +15,187 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::{delete_from_secure_storage, load_from_secure_storage, save_in_secure_storage};
-use anyhow::{bail, ensure, Result};
-use hashbrown::HashSet;
-use std::collections::HashMap;
+use crate::compat::HashMap;
+use crate::error::{bail, ensure, format_err};
+use crate::key_list::{self, KeyList};
+use crate::transaction::journal_id;
+use crate::{PersistentObjectBackend, Result, StorageBackend, Transaction};
+use alloc::{format, string::String, vec::Vec};
+use core::ops::Bound;
+use optee_utee::ObjectStorageConstants;
 
 // SecureStorageDb is a key-value storage for TA to easily store and retrieve data.
 // First we store the key list in the secure storage, named as db_name.
 // Then we store the each key-value pairs in the secure storage.
+// The objects are kept in a StorageBackend, by default the persistent objects
+// of a TEE storage (see ObjectStorageConstants), e.g. RPMB for records which
+// must be protected against rollback.
+// Writes go through a Transaction, so that the values and the key list are
+// updated atomically.
+// The key list is ordered, so that prefix and range scans only visit the
+// matching keys, and sharded by key range, so that only the shards of the
+// modified keys are stored on each write (see KeyList).
+// Keys may have no value, e.g. the entries of secondary indexes, they are
+// skipped when listing the entries.
+
+// Object written to the destination of a migration to check that it is not
+// the source, see migrate_from.
+const MIGRATION_SUFFIX: &str = ".migration";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SecureStorageDb {
+pub struct SecureStorageDb<B: StorageBackend = PersistentObjectBackend> {
     name: String,
-    key_list: HashSet<String>,
+    backend: B,
+    key_list: KeyList,
 }
 
 impl SecureStorageDb {
     pub fn open(name: String) -> Result<Self> {
-        match load_from_secure_storage(name.as_bytes())? {
-            Some(data) => {
-                let key_list = bincode::deserialize(&data)?;
-                Ok(Self { name, key_list })
-            }
-            None => {
-                // create new db
-                Ok(Self {
-                    name,
-                    // Note: `std::collections::HashSet` was replaced with
-                    // `hashbrown::HashSet`, due to a write permission fault
-                    // observed during testing. The exact cause of the issue is
-                    // unclear, but using `hashbrown::HashSet` resolves it.
-                    key_list: HashSet::new(),
-                })
+        Self::open_with_storage(name, ObjectStorageConstants::Private)
+    }
+
+    pub fn open_with_storage(name: String, storage_id: ObjectStorageConstants) -> Result<Self> {
+        Self::open_with_backend(name, PersistentObjectBackend::new(storage_id))
+    }
+
+    // Moves the db `name` from the storage `from` to the storage `to`, see
+    // migrate_from. Only the REE and RPMB storages can be migrated between:
+    // the private storage of OP-TEE is one of them, depending on CFG_REE_FS.
+    pub fn migrate(
+        name: String,
+        from: ObjectStorageConstants,
+        to: ObjectStorageConstants,
+    ) -> Result<Self> {
+        use ObjectStorageConstants::{PrivateRee, PrivateRpmb};
+        ensure!(
+            matches!(
+                (from, to),
+                (PrivateRee, PrivateRpmb) | (PrivateRpmb, PrivateRee)
+            ),
+            "Migrations are only supported between the REE and RPMB storages"
+        );
+        Self::migrate_from(
+            name,
+            PersistentObjectBackend::new(from),
+            PersistentObjectBackend::new(to),
+        )
+    }
+
+    pub fn storage_id(&self) -> ObjectStorageConstants {
+        self.backend.storage_id()
+    }
+}
+
+impl<B: StorageBackend> SecureStorageDb<B> {
+    pub fn open_with_backend(name: String, mut backend: B) -> Result<Self> {
+        let key_list = KeyList::open(&mut backend, &name)?;
+        let mut db = Self {
+            name,
+            backend,
+            key_list,
+        };
+        db.recover()?;
+        Ok(db)
+    }
+
+    // Moves the db `name` from the backend `from` to the backend `to`, and
+    // returns it opened in `to`. The values are copied before the key list,
+    // and deleted from `from` afterwards, so an interrupted migration can be
+    // run again.
+    // The backends must not refer to the same objects, or the db would be
+    // deleted once copied onto itself: a marker object is written to `to`,
+    // and the migration fails if it is seen in `from`.
+    pub fn migrate_from<S: StorageBackend>(name: String, mut from: S, mut to: B) -> Result<Self> {
+        let marker = migration_id(&name);
+        from.delete(marker.as_bytes())?;
+        to.save(marker.as_bytes(), b"")?;
+        let aliased = from.load(marker.as_bytes())?.is_some();
+        to.delete(marker.as_bytes())?;
+        ensure!(!aliased, "Source and destination storages are the same");
+
+        let mut source = SecureStorageDb::open_with_backend(name.clone(), from)?;
+        let mut migrated = Self::open_with_backend(name, to)?;
+        let keys: Vec<String> = source.keys()?.cloned().collect();
+        for key in &keys {
+            if let Some(value) = source.backend.load(key.as_bytes())? {
+                migrated.backend.save(key.as_bytes(), &value)?;
             }
+            migrated.insert_key(key.clone())?;
+        }
+        migrated.set_record_headers(source.record_headers());
+        migrated.store_key_list()?;
+
+        for key in &keys {
+            source.backend.delete(key.as_bytes())?;
         }
+        source.key_list.delete(&mut source.backend)?;
+        Ok(migrated)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub(crate) fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    pub fn contains_key(&self, key: &str) -> Result<bool> {
+        self.key_list.contains(&self.backend, key)
+    }
+
+    // Returns all the keys, in order.
+    pub fn keys(&self) -> Result<impl Iterator<Item = &String>> {
+        self.keys_in_range((Bound::Unbounded, Bound::Unbounded))
+    }
+
+    // Returns the keys starting with `prefix`, in order.
+    pub fn keys_with_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> Result<impl Iterator<Item = &'a String>> {
+        Ok(self
+            .keys_in_range((Bound::Included(prefix), Bound::Unbounded))?
+            .take_while(move |key| key.starts_with(prefix)))
+    }
+
+    // Returns the keys in `range`, in order. Only the shards of the key list
+    // overlapping `range` are loaded.
+    pub fn keys_in_range<'a>(
+        &'a self,
+        range: (Bound<&'a str>, Bound<&'a str>),
+    ) -> Result<impl Iterator<Item = &'a String>> {
+        self.key_list.range(&self.backend, range)
+    }
+
+    // Merges the shards of the key list left small by deletions, and deletes
+    // the objects left by interrupted writes of the key list.
+    pub fn compact(&mut self) -> Result<()> {
+        self.key_list.compact(&mut self.backend)
+    }
+
+    // Starts a transaction, whose writes are applied atomically on commit.
+    pub fn transaction(&mut self) -> Transaction<'_, B> {
+        Transaction::new(self)
     }
 
     pub fn put(&mut self, key: String, value: Vec<u8>) -> Result<()> {
-        match save_in_secure_storage(key.as_bytes(), &value) {
-            Ok(_) => {
-                self.key_list.insert(key);
-                self.store_key_list()?;
-            }
-            Err(e) => {
-                bail!("[+] SecureStorage::insert(): save error: {}", e);
-            }
-        };
-        Ok(())
+        let mut transaction = self.transaction();
+        transaction.put(key, value);
+        transaction
+            .commit()
+            .map_err(|e| format_err!("[+] SecureStorage::insert(): save error: {}", e))
     }
 
     pub fn get(&self, key: &str) -> Result<Vec<u8>> {
-        ensure!(self.key_list.contains(key), "Key not found in key list");
-        match load_from_secure_storage(key.as_bytes()) {
+        ensure!(self.contains_key(key)?, "Key not found in key list");
+        match self.backend.load(key.as_bytes()) {
             Ok(Some(data)) => Ok(data),
-            Ok(None) => bail!("[+] SecureStorage::get(): object not found in db"),
+            Ok(None) => bail!("[+] SecureStorage::get(): key has no value in db"),
             Err(e) => {
                 bail!("[+] SecureStorage::get(): load error: {}", e);
             }
@@ -76,41 +203,170 @@ impl SecureStorageDb {
     }
 
     pub fn delete(&mut self, key: &str) -> Result<()> {
+        let mut transaction = self.transaction();
         // ensure key must exist
-        ensure!(self.key_list.contains(key), "Key not found in key list");
-        match delete_from_secure_storage(key.as_bytes()) {
-            Ok(_) => {
-                self.key_list.remove(key);
-                self.store_key_list()?;
-            }
-            Err(e) => {
-                bail!("[+] SecureStorage::delete(): delete error: {}", e);
-            }
-        };
-        Ok(())
+        transaction.delete(key)?;
+        transaction
+            .commit()
+            .map_err(|e| format_err!("[+] SecureStorage::delete(): delete error: {}", e))
     }
 
     pub fn clear(&mut self) -> Result<()> {
-        for key in self.key_list.clone() {
-            self.delete(&key)?;
+        let keys: Vec<String> = self.keys()?.cloned().collect();
+        let mut transaction = self.transaction();
+        for key in keys {
+            transaction.delete(&key)?;
         }
-        Ok(())
+        transaction.commit()
     }
 
     pub fn list_entries_with_prefix(&self, prefix: &str) -> Result<HashMap<String, Vec<u8>>> {
         let mut result = HashMap::new();
-        for key in &self.key_list {
-            if key.starts_with(prefix) {
-                let value = self.get(key)?;
+        for key in self.keys_with_prefix(prefix)? {
+            if let Some(value) = self.backend.load(key.as_bytes())? {
                 result.insert(key.clone(), value);
             }
         }
         Ok(result)
     }
 
-    fn store_key_list(&self) -> Result<()> {
-        let key_list = bincode::serialize(&self.key_list)?;
-        save_in_secure_storage(self.name.as_bytes(), &key_list)?;
-        Ok(())
+    // Returns true if `key` names an object of the db itself: the manifest and
+    // the shards of the key list, or the journal. Storing a value under such a
+    // key would overwrite it.
+    pub(crate) fn is_reserved_key(&self, key: &str) -> bool {
+        key == self.name
+            || key == journal_id(&self.name)
+            || key == migration_id(&self.name)
+            || key_list::is_shard_namespace(&self.name, key)
+    }
+
+    pub(crate) fn insert_key(&mut self, key: String) -> Result<()> {
+        self.key_list.insert(&self.backend, key)
+    }
+
+    pub(crate) fn remove_key(&mut self, key: &str) -> Result<()> {
+        self.key_list.remove(&self.backend, key)
+    }
+
+    pub(crate) fn store_key_list(&mut self) -> Result<()> {
+        self.key_list.store(&mut self.backend)
+    }
+
+    // Returns true if the records of the db start with a header, see
+    // schema.rs.
+    pub(crate) fn record_headers(&self) -> bool {
+        self.key_list.record_headers()
+    }
+
+    // Sets the format of the records, stored with the key list.
+    pub(crate) fn set_record_headers(&mut self, record_headers: bool) {
+        self.key_list.set_record_headers(record_headers)
+    }
+}
+
+fn migration_id(db_name: &str) -> String {
+    format!("{}{}", db_name, MIGRATION_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_from_secure_storage, MemoryBackend};
+    use optee_utee_emulator as emulator;
+
+    #[test]
+    fn test_open_with_storage() {
+        emulator::reset();
+        let mut db = SecureStorageDb::open_with_storage(
+            "db".to_string(),
+            ObjectStorageConstants::PrivateRpmb,
+        )
+        .unwrap();
+        db.put("key".to_string(), b"value".to_vec()).unwrap();
+
+        let db = SecureStorageDb::open_with_storage(
+            "db".to_string(),
+            ObjectStorageConstants::PrivateRpmb,
+        )
+        .unwrap();
+        assert_eq!(db.get("key").unwrap(), b"value");
+        let other = SecureStorageDb::open_with_storage(
+            "db".to_string(),
+            ObjectStorageConstants::PrivateRee,
+        )
+        .unwrap();
+        assert!(other.get("key").is_err());
+    }
+
+    #[test]
+    fn test_migrate() {
+        emulator::reset();
+        let (ree, rpmb) = (
+            ObjectStorageConstants::PrivateRee,
+            ObjectStorageConstants::PrivateRpmb,
+        );
+        let mut db = SecureStorageDb::open_with_storage("db".to_string(), ree).unwrap();
+        db.put("a".to_string(), b"1".to_vec()).unwrap();
+        db.put("b".to_string(), b"2".to_vec()).unwrap();
+
+        let migrated = SecureStorageDb::migrate("db".to_string(), ree, rpmb).unwrap();
+        assert_eq!(migrated.storage_id(), rpmb);
+        assert_eq!(migrated.list_entries_with_prefix("").unwrap().len(), 2);
+        assert_eq!(migrated.get("b").unwrap(), b"2");
+        for key in ["db", "a", "b"] {
+            assert!(load_from_secure_storage(ree, key.as_bytes())
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(
+            SecureStorageDb::open_with_storage("db".to_string(), rpmb).unwrap(),
+            migrated
+        );
+        assert!(SecureStorageDb::migrate("db".to_string(), rpmb, rpmb).is_err());
+    }
+
+    #[test]
+    fn test_migrate_aliased_storages() {
+        emulator::reset();
+        let (private, ree) = (
+            ObjectStorageConstants::Private,
+            ObjectStorageConstants::PrivateRee,
+        );
+        let mut db = SecureStorageDb::open_with_storage("db".to_string(), private).unwrap();
+        db.put("a".to_string(), b"1".to_vec()).unwrap();
+
+        // The private storage is the REE storage.
+        assert!(SecureStorageDb::migrate("db".to_string(), private, ree).is_err());
+        let err = SecureStorageDb::migrate_from(
+            "db".to_string(),
+            PersistentObjectBackend::new(private),
+            PersistentObjectBackend::new(ree),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("storages are the same"));
+        let db = SecureStorageDb::open_with_storage("db".to_string(), private).unwrap();
+        assert_eq!(db.get("a").unwrap(), b"1");
+        assert!(load_from_secure_storage(ree, b"db.migration")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_reserved_and_value_less_keys() {
+        let backend = MemoryBackend::new();
+        let mut db = SecureStorageDb::open_with_backend("db".to_string(), backend).unwrap();
+        for key in ["db", "db.journal", "db.migration", "db.keys.0", "db.keys.x"] {
+            assert!(db.put(key.to_string(), b"value".to_vec()).is_err());
+        }
+        db.put("db.journal.old".to_string(), b"1".to_vec()).unwrap();
+        db.put("other.journal".to_string(), b"2".to_vec()).unwrap();
+
+        let mut transaction = db.transaction();
+        transaction.insert_key("index\0db.journal.old".to_string());
+        transaction.commit().unwrap();
+        assert!(db.get("index\0db.journal.old").is_err());
+        let entries = db.list_entries_with_prefix("").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["other.journal"], b"2");
     }
 }
@@ -0,0 +1,112 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::SchemaError;
+use alloc::string::String;
+use core::fmt;
+
+// The error type of secure_db, available without std. With std it implements
+// std::error::Error, so that it converts to anyhow::Error and the like.
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    // A failure of the TEE Internal Core API, e.g. of the secure storage.
+    Tee(optee_utee::Error),
+    // A value which can not be encoded, or stored bytes which can not be
+    // decoded.
+    Encoding(String),
+    // A stored record which can not be read as its type, see Migrations.
+    Schema(SchemaError),
+    // A failure of the host file system, see HostFsBackend.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    // Any other failure, e.g. a missing key.
+    Other(String),
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Tee(err) => write!(f, "TEE error: {}", err),
+            Error::Encoding(reason) => write!(f, "Encoding error: {}", reason),
+            Error::Schema(err) => err.fmt(f),
+            #[cfg(feature = "std")]
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+// The tests always run with std.
+#[cfg(any(feature = "std", test))]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Tee(err) => Some(err),
+            Error::Schema(err) => Some(err),
+            #[cfg(feature = "std")]
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<optee_utee::Error> for Error {
+    fn from(err: optee_utee::Error) -> Self {
+        Error::Tee(err)
+    }
+}
+
+impl From<SchemaError> for Error {
+    fn from(err: SchemaError) -> Self {
+        Error::Schema(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+// format_err!, bail! and ensure! build Error::Other like their anyhow
+// counterparts.
+
+macro_rules! format_err {
+    ($($arg:tt)*) => {
+        $crate::Error::Other(::alloc::format!($($arg)*))
+    };
+}
+
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err($crate::error::format_err!($($arg)*).into())
+    };
+}
+
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)*) => {
+        if !$cond {
+            $crate::error::bail!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {bail, ensure, format_err};
@@ -0,0 +1,86 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::error::format_err;
+use crate::storable::index_prefix;
+use crate::{Result, Storable};
+use alloc::{format, string::String};
+use core::cell::OnceCell;
+use optee_utee::Time;
+
+// Records may expire at a given TA persistent time (see Time::ta_time), in
+// seconds. Expired records are evicted when they are read, and by
+// SecureStorageClient::purge_expired, which scans the expiry index of a type:
+// the keys "$TABLE@~expires#$EXPIRES_AT\0$KEY", with the expiry zero-padded so
+// that the keys are ordered by expiry.
+//
+// If the TA time is not available, because it was never set or because the
+// TEE detected that it was rolled back (TimeNeedsReset), expiring records are
+// treated as expired: the age of a record can no longer be told, and keeping
+// it could extend its lifetime, e.g. revive a one-time token. Records without
+// expiry are not affected.
+
+const EXPIRY_INDEX: &str = "~expires";
+
+// Returns the current TA time in seconds.
+pub(crate) fn ta_time() -> Result<u64> {
+    let mut time = Time::new();
+    time.ta_time()
+        .map_err(|e| format_err!("TA time not available: {:?}", e))?;
+    Ok(time.seconds as u64)
+}
+
+pub(crate) fn expiry_prefix<V: Storable>() -> String {
+    index_prefix::<V>(EXPIRY_INDEX)
+}
+
+pub(crate) fn expiry_key<V: Storable>(id: &str, expires_at: u64) -> String {
+    format!(
+        "{}{}\0{}",
+        expiry_prefix::<V>(),
+        expiry_value(expires_at),
+        id
+    )
+}
+
+pub(crate) fn expiry_value(expires_at: u64) -> String {
+    format!("{:020}", expires_at)
+}
+
+// The TA time, read when first needed by an operation.
+pub(crate) struct Clock {
+    now: OnceCell<Option<u64>>,
+}
+
+impl Clock {
+    pub(crate) fn new() -> Self {
+        Self {
+            now: OnceCell::new(),
+        }
+    }
+
+    // Returns the TA time, None if it is not available.
+    pub(crate) fn now(&self) -> Option<u64> {
+        *self.now.get_or_init(|| ta_time().ok())
+    }
+
+    pub(crate) fn is_expired(&self, expires_at: Option<u64>) -> bool {
+        match expires_at {
+            Some(expires_at) => self.now().map_or(true, |now| now >= expires_at),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,547 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::codec::{deserialize, serialize};
+use crate::compat::OnceLock;
+use crate::error::format_err;
+use crate::{Result, StorageBackend};
+use alloc::{
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{mem, ops::Bound};
+use serde::{Deserialize, Serialize};
+
+// The key list of a db is sharded by key range: each shard holds the keys from
+// its start key up to the start key of the next shard, and is stored as the
+// object `$DB_NAME.keys.$ID`. The manifest, stored as the object `$DB_NAME`,
+// holds the start keys and ids of the shards.
+//
+// Shards are loaded when a key of their range is first looked up, and only the
+// modified shards are stored. They are written under new ids, and the old
+// objects are deleted once the manifest is saved, so that the manifest always
+// refers to a consistent key list.
+//
+// Db written before sharding store the whole key list as `$DB_NAME`, they are
+// converted when opened.
+//
+// The manifest also holds the format of the records of the db: whether they
+// start with a header (see schema.rs). The manifests written before it have
+// LEGACY_MANIFEST_MAGIC, the records of their db have no header.
+
+const MANIFEST_MAGIC: &[u8] = b"\xffSDM";
+const LEGACY_MANIFEST_MAGIC: &[u8] = b"\xffSDK";
+const SHARD_SUFFIX: &str = ".keys.";
+// Shards larger than this are split in halves when stored.
+const MAX_SHARD_KEYS: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    shards: Vec<(String, u64)>,
+    next_id: u64,
+    record_headers: bool,
+}
+
+#[derive(Deserialize)]
+struct LegacyManifest {
+    shards: Vec<(String, u64)>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Shard {
+    start: String,
+    // None until the shard is first stored.
+    id: Option<u64>,
+    keys: OnceLock<BTreeSet<String>>,
+    dirty: bool,
+}
+
+impl Shard {
+    fn new(start: String, keys: BTreeSet<String>) -> Self {
+        Self {
+            start,
+            id: None,
+            keys: OnceLock::from(keys),
+            dirty: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KeyList {
+    name: String,
+    // Never empty, the start of the first shard is "".
+    shards: Vec<Shard>,
+    next_id: u64,
+    // Objects of the shards replaced since the last store.
+    obsolete: Vec<u64>,
+    record_headers: bool,
+    // The format of the records changed since the last store.
+    format_changed: bool,
+}
+
+// Two key lists are equal if they refer to the same stored shards.
+impl PartialEq for KeyList {
+    fn eq(&self, other: &Self) -> bool {
+        let ids = |list: &Self| {
+            list.shards
+                .iter()
+                .map(|shard| (shard.start.clone(), shard.id, shard.dirty))
+                .collect::<Vec<_>>()
+        };
+        self.name == other.name
+            && ids(self) == ids(other)
+            && self.record_headers == other.record_headers
+    }
+}
+
+impl Eq for KeyList {}
+
+impl KeyList {
+    pub(crate) fn open<B: StorageBackend>(backend: &mut B, name: &str) -> Result<Self> {
+        let data = match backend.load(name.as_bytes())? {
+            Some(data) => data,
+            None => return Ok(Self::with_keys(name, BTreeSet::new(), true)),
+        };
+        let manifest = if let Some(manifest) = data.strip_prefix(MANIFEST_MAGIC) {
+            deserialize(manifest)?
+        } else if let Some(manifest) = data.strip_prefix(LEGACY_MANIFEST_MAGIC) {
+            let manifest: LegacyManifest = deserialize(manifest)?;
+            Manifest {
+                shards: manifest.shards,
+                next_id: manifest.next_id,
+                record_headers: false,
+            }
+        } else {
+            let mut key_list = Self::with_keys(name, deserialize(&data)?, false);
+            key_list.store(backend)?;
+            return Ok(key_list);
+        };
+        let shards = manifest
+            .shards
+            .into_iter()
+            .map(|(start, id)| Shard {
+                start,
+                id: Some(id),
+                keys: OnceLock::new(),
+                dirty: false,
+            })
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            shards,
+            next_id: manifest.next_id,
+            obsolete: Vec::new(),
+            record_headers: manifest.record_headers,
+            format_changed: false,
+        })
+    }
+
+    fn with_keys(name: &str, keys: BTreeSet<String>, record_headers: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            shards: vec![Shard::new(String::new(), keys)],
+            next_id: 0,
+            obsolete: Vec::new(),
+            record_headers,
+            format_changed: false,
+        }
+    }
+
+    // Returns true if the records of the db start with a header.
+    pub(crate) fn record_headers(&self) -> bool {
+        self.record_headers
+    }
+
+    // Sets the format of the records, stored with the manifest.
+    pub(crate) fn set_record_headers(&mut self, record_headers: bool) {
+        if self.record_headers != record_headers {
+            self.record_headers = record_headers;
+            self.format_changed = true;
+        }
+    }
+
+    pub(crate) fn contains<B: StorageBackend>(&self, backend: &B, key: &str) -> Result<bool> {
+        let shard = &self.shards[self.shard_index(key)];
+        Ok(self.load(backend, shard)?.contains(key))
+    }
+
+    // Returns the keys in `range`, in order, loading the shards of the range.
+    pub(crate) fn range<'a, B: StorageBackend>(
+        &'a self,
+        backend: &B,
+        range: (Bound<&'a str>, Bound<&'a str>),
+    ) -> Result<impl Iterator<Item = &'a String>> {
+        let first = match range.0 {
+            Bound::Included(start) | Bound::Excluded(start) => self.shard_index(start),
+            Bound::Unbounded => 0,
+        };
+        let last = match range.1 {
+            Bound::Included(end) | Bound::Excluded(end) => self.shard_index(end),
+            Bound::Unbounded => self.shards.len() - 1,
+        };
+        let mut keys = Vec::new();
+        for shard in &self.shards[first..=last.max(first)] {
+            keys.push(self.load(backend, shard)?);
+        }
+        Ok(keys
+            .into_iter()
+            .flat_map(move |keys| keys.range::<str, _>(range)))
+    }
+
+    pub(crate) fn insert<B: StorageBackend>(&mut self, backend: &B, key: String) -> Result<()> {
+        let index = self.shard_index(&key);
+        if self.keys_mut(backend, index)?.insert(key) {
+            self.shards[index].dirty = true;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove<B: StorageBackend>(&mut self, backend: &B, key: &str) -> Result<()> {
+        let index = self.shard_index(key);
+        if self.keys_mut(backend, index)?.remove(key) {
+            self.shards[index].dirty = true;
+        }
+        Ok(())
+    }
+
+    // Stores the modified shards and the manifest.
+    pub(crate) fn store<B: StorageBackend>(&mut self, backend: &mut B) -> Result<()> {
+        if !self.format_changed && !self.shards.iter().any(|shard| shard.dirty) {
+            return Ok(());
+        }
+        let mut shards = Vec::with_capacity(self.shards.len());
+        for mut shard in self.shards.drain(..) {
+            if !shard.dirty {
+                shards.push(shard);
+                continue;
+            }
+            self.obsolete.extend(shard.id);
+            let keys = shard.keys.take().unwrap_or_default();
+            // the range of an empty shard is covered by the previous one
+            if !keys.is_empty() {
+                split(shard.start, keys, &mut shards);
+            }
+        }
+        if shards.is_empty() {
+            shards.push(Shard::new(String::new(), BTreeSet::new()));
+        }
+        shards[0].start = String::new();
+        self.shards = shards;
+
+        for shard in self.shards.iter_mut().filter(|shard| shard.dirty) {
+            let id = self.next_id;
+            self.next_id += 1;
+            let keys = shard
+                .keys
+                .get()
+                .ok_or(format_err!("Key list shard not loaded"))?;
+            backend.save(
+                shard_object_id(&self.name, id).as_bytes(),
+                &serialize(keys)?,
+            )?;
+            shard.id = Some(id);
+            shard.dirty = false;
+        }
+
+        let manifest = Manifest {
+            shards: self
+                .shards
+                .iter()
+                .map(|shard| (shard.start.clone(), shard.id.unwrap_or_default()))
+                .collect(),
+            next_id: self.next_id,
+            record_headers: self.record_headers,
+        };
+        let mut data = MANIFEST_MAGIC.to_vec();
+        data.extend(serialize(&manifest)?);
+        backend.save(self.name.as_bytes(), &data)?;
+        self.format_changed = false;
+
+        for id in self.obsolete.drain(..) {
+            backend.delete(shard_object_id(&self.name, id).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Merges the adjacent shards which fit in one, e.g. after many deletions,
+    // and deletes the shard objects left by interrupted stores.
+    pub(crate) fn compact<B: StorageBackend>(&mut self, backend: &mut B) -> Result<()> {
+        for shard in &self.shards {
+            self.load(backend, shard)?;
+        }
+        let len = |shard: &Shard| shard.keys.get().map_or(0, BTreeSet::len);
+        let mut merged: Vec<Shard> = Vec::with_capacity(self.shards.len());
+        for mut shard in mem::take(&mut self.shards) {
+            match merged.last_mut() {
+                Some(last) if len(last) + len(&shard) <= MAX_SHARD_KEYS => {
+                    if let (Some(keys), Some(last_keys)) = (shard.keys.take(), last.keys.get_mut())
+                    {
+                        last_keys.extend(keys);
+                    }
+                    last.dirty = true;
+                    self.obsolete.extend(shard.id);
+                }
+                _ => merged.push(shard),
+            }
+        }
+        self.shards = merged;
+        self.store(backend)?;
+
+        let used: BTreeSet<_> = self
+            .shards
+            .iter()
+            .filter_map(|shard| shard.id)
+            .map(|id| shard_object_id(&self.name, id).into_bytes())
+            .collect();
+        let prefix = format!("{}{}", self.name, SHARD_SUFFIX);
+        for object_id in backend.list(prefix.as_bytes())? {
+            if !used.contains(&object_id) && self.is_stale_shard(backend, &object_id)? {
+                backend.delete(&object_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns true if `object_id`, which is not a shard of the manifest, is a
+    // shard object left by an interrupted store. The shard namespace may also
+    // hold keys of db written before it was reserved, and the manifest of a db
+    // named in it, which are kept.
+    fn is_stale_shard<B: StorageBackend>(&self, backend: &B, object_id: &[u8]) -> Result<bool> {
+        let object_id = match core::str::from_utf8(object_id) {
+            Ok(object_id) => object_id,
+            Err(_) => return Ok(false),
+        };
+        let id = match object_id
+            .strip_prefix(self.name.as_str())
+            .and_then(|suffix| suffix.strip_prefix(SHARD_SUFFIX))
+        {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+        if id.parse::<u64>().map_or(true, |n| n.to_string() != id)
+            || self.contains(backend, object_id)?
+        {
+            return Ok(false);
+        }
+        let data = backend.load(object_id.as_bytes())?;
+        Ok(!data.map_or(false, |data| {
+            data.starts_with(MANIFEST_MAGIC) || data.starts_with(LEGACY_MANIFEST_MAGIC)
+        }))
+    }
+
+    // Deletes the manifest and the shards of the key list.
+    pub(crate) fn delete<B: StorageBackend>(self, backend: &mut B) -> Result<()> {
+        for id in self.shards.iter().filter_map(|shard| shard.id) {
+            backend.delete(shard_object_id(&self.name, id).as_bytes())?;
+        }
+        backend.delete(self.name.as_bytes())?;
+        Ok(())
+    }
+
+    // Returns the index of the shard whose range holds `key`.
+    fn shard_index(&self, key: &str) -> usize {
+        self.shards
+            .partition_point(|shard| shard.start.as_str() <= key)
+            .saturating_sub(1)
+    }
+
+    fn load<'a, B: StorageBackend>(
+        &self,
+        backend: &B,
+        shard: &'a Shard,
+    ) -> Result<&'a BTreeSet<String>> {
+        if let Some(keys) = shard.keys.get() {
+            return Ok(keys);
+        }
+        let keys = match shard.id {
+            Some(id) => {
+                let object_id = shard_object_id(&self.name, id);
+                let data = backend
+                    .load(object_id.as_bytes())?
+                    .ok_or(format_err!("Key list shard {} not found", object_id))?;
+                deserialize(&data)?
+            }
+            None => BTreeSet::new(),
+        };
+        Ok(shard.keys.get_or_init(|| keys))
+    }
+
+    fn keys_mut<B: StorageBackend>(
+        &mut self,
+        backend: &B,
+        index: usize,
+    ) -> Result<&mut BTreeSet<String>> {
+        self.load(backend, &self.shards[index])?;
+        self.shards[index]
+            .keys
+            .get_mut()
+            .ok_or(format_err!("Key list shard not loaded"))
+    }
+}
+
+// Pushes the shards holding `keys`, split in halves until they are small
+// enough, to `shards`.
+fn split(start: String, mut keys: BTreeSet<String>, shards: &mut Vec<Shard>) {
+    if keys.len() <= MAX_SHARD_KEYS {
+        shards.push(Shard::new(start, keys));
+        return;
+    }
+    let middle = keys.iter().nth(keys.len() / 2).cloned().unwrap_or_default();
+    let upper = keys.split_off(&middle);
+    split(start, keys, shards);
+    split(middle, upper, shards);
+}
+
+// Returns true if `object_id` is in the namespace of the shards of the db
+// `db_name`, which is reserved for them.
+pub(crate) fn is_shard_namespace(db_name: &str, object_id: &str) -> bool {
+    object_id
+        .strip_prefix(db_name)
+        .map_or(false, |suffix| suffix.starts_with(SHARD_SUFFIX))
+}
+
+fn shard_object_id(db_name: &str, id: u64) -> String {
+    format!("{}{}{}", db_name, SHARD_SUFFIX, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBackend;
+
+    fn key(i: usize) -> String {
+        format!("key#{:04}", i)
+    }
+
+    fn loaded(key_list: &KeyList) -> usize {
+        key_list
+            .shards
+            .iter()
+            .filter(|shard| shard.keys.get().is_some())
+            .count()
+    }
+
+    #[test]
+    fn test_sharding_and_lazy_loading() {
+        let mut backend = MemoryBackend::new();
+        let mut key_list = KeyList::open(&mut backend, "db").unwrap();
+        for i in 0..1000 {
+            key_list.insert(&backend, key(i)).unwrap();
+        }
+        key_list.store(&mut backend).unwrap();
+        assert!(key_list.shards.len() >= 1000 / MAX_SHARD_KEYS);
+
+        let key_list = KeyList::open(&mut backend, "db").unwrap();
+        assert_eq!(loaded(&key_list), 0);
+        assert!(key_list.contains(&backend, &key(500)).unwrap());
+        assert_eq!(loaded(&key_list), 1);
+        assert!(!key_list.contains(&backend, "key#5000").unwrap());
+
+        let range = (Bound::Included("key#0100"), Bound::Excluded("key#0200"));
+        let keys: Vec<_> = key_list.range(&backend, range).unwrap().cloned().collect();
+        assert_eq!(keys, (100..200).map(key).collect::<Vec<_>>());
+        assert!(loaded(&key_list) < key_list.shards.len());
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut backend = MemoryBackend::new();
+        let mut key_list = KeyList::open(&mut backend, "db").unwrap();
+        for i in 0..1000 {
+            key_list.insert(&backend, key(i)).unwrap();
+        }
+        key_list.store(&mut backend).unwrap();
+        for i in (0..1000).filter(|i| i % 10 != 0) {
+            key_list.remove(&backend, &key(i)).unwrap();
+        }
+        key_list.store(&mut backend).unwrap();
+        // left by an interrupted store
+        backend.save(b"db.keys.1000", b"").unwrap();
+
+        let mut key_list = KeyList::open(&mut backend, "db").unwrap();
+        key_list.compact(&mut backend).unwrap();
+        assert_eq!(key_list.shards.len(), 1);
+        assert_eq!(backend.list(b"db.keys.").unwrap().len(), 1);
+
+        let key_list = KeyList::open(&mut backend, "db").unwrap();
+        let keys = key_list.range(&backend, (Bound::Unbounded, Bound::Unbounded));
+        assert_eq!(keys.unwrap().count(), 100);
+    }
+
+    #[test]
+    fn test_compact_keeps_other_objects() {
+        let mut backend = MemoryBackend::new();
+        let mut key_list = KeyList::open(&mut backend, "db").unwrap();
+        // a key of a db written before the shard namespace was reserved
+        key_list.insert(&backend, "db.keys.7".to_string()).unwrap();
+        backend.save(b"db.keys.7", b"value").unwrap();
+        key_list.store(&mut backend).unwrap();
+        for object_id in [&b"db.keys.x"[..], b"db.keys.05", b"db.keys.1.tmp"] {
+            backend.save(object_id, b"").unwrap();
+        }
+        let mut other = KeyList::open(&mut backend, "db.keys.5").unwrap();
+        other.insert(&backend, "key".to_string()).unwrap();
+        other.store(&mut backend).unwrap();
+        backend.save(b"db.keys.1000", b"").unwrap();
+
+        let before = backend.list(b"db.keys.").unwrap().len();
+        key_list.compact(&mut backend).unwrap();
+        assert_eq!(backend.list(b"db.keys.").unwrap().len(), before - 1);
+        assert_eq!(backend.load(b"db.keys.1000").unwrap(), None);
+        let other = KeyList::open(&mut backend, "db.keys.5").unwrap();
+        assert!(other.contains(&backend, "key").unwrap());
+    }
+
+    #[test]
+    fn test_convert_legacy_key_list() {
+        let mut backend = MemoryBackend::new();
+        let legacy = BTreeSet::from([key(1), key(2)]);
+        backend.save(b"db", &serialize(&legacy).unwrap()).unwrap();
+
+        KeyList::open(&mut backend, "db").unwrap();
+        let key_list = KeyList::open(&mut backend, "db").unwrap();
+        assert_eq!(key_list.shards[0].id, Some(0));
+        assert!(key_list.contains(&backend, &key(2)).unwrap());
+        assert!(!key_list.record_headers());
+    }
+
+    #[test]
+    fn test_record_format() {
+        let mut backend = MemoryBackend::new();
+        let mut key_list = KeyList::open(&mut backend, "db").unwrap();
+        assert!(key_list.record_headers());
+        key_list.insert(&backend, key(1)).unwrap();
+        key_list.store(&mut backend).unwrap();
+
+        // a manifest written before the record format
+        let legacy = (vec![(String::new(), 0u64)], 1u64);
+        let mut data = LEGACY_MANIFEST_MAGIC.to_vec();
+        data.extend(serialize(&legacy).unwrap());
+        backend.save(b"db", &data).unwrap();
+        let mut key_list = KeyList::open(&mut backend, "db").unwrap();
+        assert!(!key_list.record_headers());
+        assert!(key_list.contains(&backend, &key(1)).unwrap());
+
+        key_list.set_record_headers(true);
+        key_list.store(&mut backend).unwrap();
+        assert!(KeyList::open(&mut backend, "db").unwrap().record_headers());
+    }
+}
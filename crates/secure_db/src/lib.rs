@@ -15,11 +15,29 @@
 // specific language governing permissions and limitations
 // under the License.
 
+// Only `alloc` is needed without std, see compat. The tests always run with
+// std.
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
+
 mod backend;
 pub use backend::*;
+mod backup;
+pub use backup::*;
 mod client;
 pub use client::*;
+mod codec;
+mod compat;
 mod db;
 pub use db::*;
+mod error;
+pub use error::{Error, Result};
+mod expiry;
+mod key_list;
+mod schema;
+pub use schema::*;
 mod storable;
 pub use storable::*;
+mod transaction;
+pub use transaction::*;
@@ -0,0 +1,436 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::codec::{deserialize, serialize};
+use crate::compat::HashMap;
+use crate::{Error, Result, SecureStorageDb, Storable, StorageBackend};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{convert::TryInto, fmt};
+use serde::{de::DeserializeOwned, Serialize};
+
+// Records are stored as RECORD_MAGIC, followed by the version of the type as a
+// little endian u32 and by the bincode encoding of the record. Records with an
+// expiry start with EXPIRING_RECORD_MAGIC instead, and the expiry, in seconds
+// of TA time as a little endian u64, follows the version.
+// Records written before versioning have no header, and may start with one of
+// the magics, e.g. a first field of type u32 with the value 0x424453ff. The
+// manifest of the db tells whether its records have a header (see
+// KeyList::record_headers): the records of a db written before are given the
+// header of version 0 when a client opens it, in the transaction which marks
+// the db, so that every record read by a client has a header.
+
+const RECORD_MAGIC: &[u8] = b"\xffSDB";
+const EXPIRING_RECORD_MAGIC: &[u8] = b"\xffSDE";
+const MAX_HEADER_LEN: usize = EXPIRING_RECORD_MAGIC.len() + 4 + 8;
+
+#[derive(Clone, Copy)]
+pub(crate) struct Header {
+    pub(crate) version: u32,
+    pub(crate) expires_at: Option<u64>,
+}
+
+pub(crate) fn encode<V: Storable + Serialize>(
+    value: &V,
+    expires_at: Option<u64>,
+) -> Result<Vec<u8>> {
+    let header = Header {
+        version: V::VERSION,
+        expires_at,
+    };
+    Ok(with_header(header, &serialize(value)?))
+}
+
+fn with_header(header: Header, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(MAX_HEADER_LEN + payload.len());
+    match header.expires_at {
+        Some(expires_at) => {
+            data.extend_from_slice(EXPIRING_RECORD_MAGIC);
+            data.extend_from_slice(&header.version.to_le_bytes());
+            data.extend_from_slice(&expires_at.to_le_bytes());
+        }
+        None => {
+            data.extend_from_slice(RECORD_MAGIC);
+            data.extend_from_slice(&header.version.to_le_bytes());
+        }
+    }
+    data.extend_from_slice(payload);
+    data
+}
+
+// Gives the header of version 0 to the records of a db written before
+// versioning, see above.
+pub(crate) fn convert_legacy_records<B: StorageBackend>(db: &mut SecureStorageDb<B>) -> Result<()> {
+    if db.record_headers() {
+        return Ok(());
+    }
+    let mut records = Vec::new();
+    for key in db.keys()? {
+        if let Some(data) = db.backend().load(key.as_bytes())? {
+            records.push((key.clone(), data));
+        }
+    }
+    let mut transaction = db.transaction();
+    for (key, data) in records {
+        let header = Header {
+            version: 0,
+            expires_at: None,
+        };
+        transaction.put(key, with_header(header, &data));
+    }
+    transaction.set_record_headers();
+    transaction.commit()
+}
+
+// Splits a stored record into its header and its bincode encoding, returns
+// None if it has no header.
+pub(crate) fn split_header(data: &[u8]) -> Option<(Header, &[u8])> {
+    if let Some(rest) = data.strip_prefix(RECORD_MAGIC) {
+        if rest.len() >= 4 {
+            let (version, payload) = rest.split_at(4);
+            let header = Header {
+                version: u32::from_le_bytes(version.try_into().unwrap()),
+                expires_at: None,
+            };
+            return Some((header, payload));
+        }
+    } else if let Some(rest) = data.strip_prefix(EXPIRING_RECORD_MAGIC) {
+        if rest.len() >= 12 {
+            let (version, rest) = rest.split_at(4);
+            let (expires_at, payload) = rest.split_at(8);
+            let header = Header {
+                version: u32::from_le_bytes(version.try_into().unwrap()),
+                expires_at: Some(u64::from_le_bytes(expires_at.try_into().unwrap())),
+            };
+            return Some((header, payload));
+        }
+    }
+    None
+}
+
+// Returns the expiry of a stored record, None if it has none.
+pub(crate) fn record_expiry(data: &[u8]) -> Option<u64> {
+    split_header(data).and_then(|(header, _)| header.expires_at)
+}
+
+// The error returned, as Error::Schema, when a stored record can not be read
+// as its type.
+#[derive(Debug)]
+pub enum SchemaError {
+    // The record has a version newer than the type, or no migration is
+    // registered to upgrade it.
+    UnknownVersion {
+        table: &'static str,
+        version: u32,
+        expected: u32,
+    },
+    // The record does not deserialize as the type of its version.
+    Corrupted {
+        table: &'static str,
+        version: u32,
+        reason: String,
+    },
+    // The record has no header, though its db was converted.
+    MissingHeader {
+        table: &'static str,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::UnknownVersion {
+                table,
+                version,
+                expected,
+            } => write!(
+                f,
+                "Unknown version {} of {} records, expected {} or a migration from it",
+                version, table, expected
+            ),
+            SchemaError::Corrupted {
+                table,
+                version,
+                reason,
+            } => write!(
+                f,
+                "Corrupted version {} record of {}: {}",
+                version, table, reason
+            ),
+            SchemaError::MissingHeader { table } => {
+                write!(f, "Record of {} without header", table)
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "std", test))]
+impl std::error::Error for SchemaError {}
+
+type Migration = Box<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync>;
+
+// Migrations is a registry of the upgrades of Storable types from a version to
+// the next one. Records of an older version are upgraded step by step when
+// they are read, see SecureStorageClient::register_migration.
+
+#[derive(Default)]
+pub struct Migrations {
+    steps: HashMap<(&'static str, u32), Migration>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers `migration`, which upgrades the records of V from the version
+    // `from_version`, deserialized as Old, to the version `from_version + 1`.
+    pub fn register<V, Old, New, F>(&mut self, from_version: u32, migration: F) -> &mut Self
+    where
+        V: Storable,
+        Old: DeserializeOwned,
+        New: Serialize,
+        F: Fn(Old) -> Result<New> + Send + Sync + 'static,
+    {
+        let step = move |data: &[u8]| {
+            let old: Old = deserialize(data).map_err(|err| corrupted::<V>(from_version, err))?;
+            serialize(&migration(old)?)
+        };
+        self.steps
+            .insert((V::table_name(), from_version), Box::new(step));
+        self
+    }
+
+    // Deserializes a stored record of V, upgrading it to V::VERSION if needed.
+    // The new encoding of the record is returned along with it if it was
+    // upgraded, so that it can be stored in place.
+    pub(crate) fn decode<V>(&self, data: &[u8]) -> Result<(V, Option<Vec<u8>>)>
+    where
+        V: Storable + DeserializeOwned,
+    {
+        let (header, payload) = split_header(data).ok_or(SchemaError::MissingHeader {
+            table: V::table_name(),
+        })?;
+        let mut version = header.version;
+        let unknown_version = |version| SchemaError::UnknownVersion {
+            table: V::table_name(),
+            version,
+            expected: V::VERSION,
+        };
+        if version > V::VERSION {
+            return Err(unknown_version(version).into());
+        }
+        let mut upgraded = None;
+        while version < V::VERSION {
+            let step = self
+                .steps
+                .get(&(V::table_name(), version))
+                .ok_or_else(|| unknown_version(version))?;
+            let payload = upgraded.as_deref().unwrap_or(payload);
+            upgraded = Some(step(payload)?);
+            version += 1;
+        }
+        let payload = upgraded.as_deref().unwrap_or(payload);
+        let value: V = deserialize(payload).map_err(|err| corrupted::<V>(version, err))?;
+        let encoded = upgraded.map(|payload| with_header(Header { version, ..header }, &payload));
+        Ok((value, encoded))
+    }
+}
+
+fn corrupted<V: Storable>(version: u32, err: Error) -> SchemaError {
+    let reason = match err {
+        Error::Encoding(reason) => reason,
+        err => err.to_string(),
+    };
+    SchemaError::Corrupted {
+        table: V::table_name(),
+        version,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryBackend, SecureStorageClient};
+    use alloc::collections::BTreeSet;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    struct WalletV0 {
+        id: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct WalletV1 {
+        id: u32,
+        label: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Wallet {
+        id: String,
+        label: String,
+    }
+
+    impl Storable for Wallet {
+        type Key = String;
+
+        const VERSION: u32 = 2;
+
+        fn unique_id(&self) -> Self::Key {
+            self.id.clone()
+        }
+    }
+
+    fn open(backend: &MemoryBackend) -> SecureStorageClient<MemoryBackend> {
+        let mut client = SecureStorageClient::open_with_backend("db", backend.clone()).unwrap();
+        client
+            .register_migration::<Wallet, WalletV0, _, _>(0, |old| {
+                Ok(WalletV1 {
+                    id: old.id,
+                    label: String::new(),
+                })
+            })
+            .register_migration::<Wallet, WalletV1, _, _>(1, |old| {
+                Ok(Wallet {
+                    id: old.id.to_string(),
+                    label: old.label,
+                })
+            });
+        client
+    }
+
+    fn header(version: u32) -> Header {
+        Header {
+            version,
+            expires_at: None,
+        }
+    }
+
+    fn put_raw(backend: &MemoryBackend, key: &str, data: Vec<u8>) {
+        let mut db = SecureStorageDb::open_with_backend("db".to_string(), backend.clone()).unwrap();
+        db.put(key.to_string(), data).unwrap();
+    }
+
+    // Stores a db written before versioning, with the key list of a db written
+    // before the record format was kept in its manifest.
+    fn put_legacy(backend: &MemoryBackend, records: &[(&str, Vec<u8>)]) {
+        let mut backend = backend.clone();
+        let keys: BTreeSet<&str> = records.iter().map(|(key, _)| *key).collect();
+        backend.save(b"db", &serialize(&keys).unwrap()).unwrap();
+        for (key, data) in records {
+            backend.save(key.as_bytes(), data).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_upgrade_in_place() {
+        let backend = MemoryBackend::new();
+        // written before versioning, without header
+        let legacy = serialize(&WalletV0 { id: 1 }).unwrap();
+        put_legacy(&backend, &[("Wallet#1", legacy)]);
+        // converted by the client
+        open(&backend);
+        let v1 = WalletV1 {
+            id: 2,
+            label: "savings".to_string(),
+        };
+        put_raw(
+            &backend,
+            "Wallet#2",
+            with_header(header(1), &serialize(&v1).unwrap()),
+        );
+
+        let client = open(&backend);
+        let wallet = client.get::<Wallet>(&"1".to_string()).unwrap();
+        assert_eq!(wallet.label, "");
+        assert_eq!(client.list_entries::<Wallet>().unwrap().len(), 2);
+        for key in [&b"Wallet#1"[..], b"Wallet#2"] {
+            let data = backend.load(key).unwrap().unwrap();
+            assert_eq!(split_header(&data).unwrap().0.version, Wallet::VERSION);
+        }
+
+        // the upgraded records no longer need the migrations
+        let client = SecureStorageClient::open_with_backend("db", backend.clone()).unwrap();
+        let wallet = client.get::<Wallet>(&"2".to_string()).unwrap();
+        assert_eq!(wallet.label, "savings");
+    }
+
+    #[test]
+    fn test_unknown_version_and_corruption() {
+        let backend = MemoryBackend::new();
+        put_raw(&backend, "Wallet#1", with_header(header(3), b""));
+        put_raw(&backend, "Wallet#2", with_header(header(2), b"\x01"));
+        let client = open(&backend);
+
+        let err = client.get::<Wallet>(&"1".to_string()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Schema(SchemaError::UnknownVersion {
+                version: 3,
+                expected: 2,
+                ..
+            })
+        ));
+        let err = client.get::<Wallet>(&"2".to_string()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Schema(SchemaError::Corrupted { version: 2, .. })
+        ));
+
+        // no migration registered from version 0
+        put_raw(&backend, "Wallet#3", with_header(header(0), b""));
+        // no header in a db with record headers
+        put_raw(
+            &backend,
+            "Wallet#4",
+            serialize(&WalletV0 { id: 4 }).unwrap(),
+        );
+        let client = SecureStorageClient::open_with_backend("db", backend.clone()).unwrap();
+        let err = client.get::<Wallet>(&"3".to_string()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Schema(SchemaError::UnknownVersion { version: 0, .. })
+        ));
+        let err = client.get::<Wallet>(&"4".to_string()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Schema(SchemaError::MissingHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn test_legacy_record_starting_with_magic() {
+        let backend = MemoryBackend::new();
+        let legacy = serialize(&WalletV0 { id: 0x424453ff }).unwrap();
+        assert!(legacy.starts_with(RECORD_MAGIC));
+        put_legacy(&backend, &[("Wallet#1111774207", legacy)]);
+
+        let client = open(&backend);
+        let wallet = client.get::<Wallet>(&"1111774207".to_string()).unwrap();
+        assert_eq!(wallet.label, "");
+        // the conversion is done once
+        let client = open(&backend);
+        let wallet = client.get::<Wallet>(&"1111774207".to_string()).unwrap();
+        assert_eq!(wallet.id, "1111774207");
+        let data = backend.load(b"Wallet#1111774207").unwrap().unwrap();
+        assert_eq!(split_header(&data).unwrap().0.version, Wallet::VERSION);
+    }
+}
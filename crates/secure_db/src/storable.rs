@@ -15,14 +15,29 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::hash::Hash;
+use crate::error::ensure;
+use crate::Result;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::hash::Hash;
 
 // For each key-value data, the storage key is "$TABLE_NAME#$KEY"
 // For example, if we store the Data whose type is Structure named
 // ExampleData, with id "123",
 // the storage key will be "ExampleData#123"
+//
+// Each entry of a secondary index is a key without value, named
+// "$TABLE_NAME@$INDEX#$VALUE\0$KEY", so that the records of an index are
+// ordered by value. For example, if ExampleData is indexed by "label", the
+// record above with the label "abc" has the index entry
+// "ExampleData@label#abc\0123".
 
 const CONCAT: &str = "#";
+const INDEX_CONCAT: &str = "@";
+const INDEX_VALUE_END: char = '\0';
 
 pub trait Storable {
     type Key: ToString + Eq + Hash; // Associated type `Key`
@@ -31,7 +46,7 @@ pub trait Storable {
 
     fn table_name() -> &'static str {
         // keeps the last part of the path
-        std::any::type_name::<Self>()
+        core::any::type_name::<Self>()
             .split("::")
             .last()
             .unwrap_or("WRONG_TABLE_NAME")
@@ -49,4 +64,52 @@ pub trait Storable {
     fn concat_key(key: &str) -> String {
         format!("{}{}{}", Self::table_name(), CONCAT, key)
     }
+
+    // Version of the schema of the type, stored with each record. Bump it when
+    // the type changes and register a migration from the previous version,
+    // see Migrations.
+    const VERSION: u32 = 0;
+
+    // Names of the secondary indexes of the type, see index_value.
+    const INDEXES: &'static [&'static str] = &[];
+
+    // Returns the value of the record in the secondary index `index`, or None
+    // if the record is not indexed. Values are ordered as strings, zero-pad
+    // numbers (e.g. `format!("{:020}", created_at)`) to order them
+    // numerically.
+    fn index_value(&self, _index: &str) -> Option<String> {
+        None
+    }
+}
+
+pub(crate) fn index_prefix<V: Storable>(index: &str) -> String {
+    format!("{}{}{}{}", V::table_name(), INDEX_CONCAT, index, CONCAT)
+}
+
+// Returns the index entries of `value`, see Storable::INDEXES.
+pub(crate) fn index_keys<V: Storable>(value: &V) -> Result<Vec<String>> {
+    let id = value.unique_id().to_string();
+    let mut keys = Vec::new();
+    for index in V::INDEXES {
+        if let Some(index_value) = value.index_value(index) {
+            ensure!(
+                !index_value.contains(INDEX_VALUE_END),
+                "Index value contains a NUL character"
+            );
+            keys.push(format!(
+                "{}{}{}{}",
+                index_prefix::<V>(index),
+                index_value,
+                INDEX_VALUE_END,
+                id
+            ));
+        }
+    }
+    Ok(keys)
+}
+
+// Returns the storage key of the record of an index entry.
+pub(crate) fn indexed_key<V: Storable>(index_key: &str) -> Option<String> {
+    let (_, id) = index_key.split_once(INDEX_VALUE_END)?;
+    Some(V::concat_key(id))
 }
@@ -0,0 +1,244 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::codec::{deserialize, serialize};
+use crate::error::{ensure, format_err};
+use crate::{PersistentObjectBackend, Result, SecureStorageDb, StorageBackend};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use serde::{Deserialize, Serialize};
+
+// A transaction is committed in three steps:
+// 1. The journal, holding the values to write and the keys to delete, is saved
+//    as `$DB_NAME.journal`. Backends save objects atomically (e.g. with
+//    TEE_DATA_FLAG_OVERWRITE), saving the journal is the commit point.
+// 2. The journal is applied: values are saved, deleted keys are removed and the
+//    modified shards of the key list are stored.
+// 3. The journal is deleted.
+// Applying a journal again has the same result, so if the TA crashes before the
+// journal is deleted, it is applied again when the db is opened.
+//
+// The objects of the db share the storage with the values, so the keys naming
+// them are rejected on commit (see SecureStorageDb::is_reserved_key).
+
+const JOURNAL_SUFFIX: &str = ".journal";
+
+#[derive(Serialize, Deserialize)]
+enum Write {
+    Put(Vec<u8>),
+    // A key without a value, e.g. an entry of a secondary index.
+    Key,
+    Delete,
+    // Marks the records of the db as starting with a header, written under the
+    // name of the db, see SecureStorageDb::set_record_headers.
+    RecordHeaders,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Journal {
+    writes: Vec<(String, Write)>,
+}
+
+// Transaction is a set of writes to SecureStorageDb which are applied
+// atomically on commit. Writes are kept in memory until then, and dropping the
+// transaction without committing it rolls it back.
+
+pub struct Transaction<'a, B: StorageBackend = PersistentObjectBackend> {
+    db: &'a mut SecureStorageDb<B>,
+    writes: BTreeMap<String, Write>,
+}
+
+impl<'a, B: StorageBackend> Transaction<'a, B> {
+    pub(crate) fn new(db: &'a mut SecureStorageDb<B>) -> Self {
+        Self {
+            db,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    pub fn put(&mut self, key: String, value: Vec<u8>) {
+        self.writes.insert(key, Write::Put(value));
+    }
+
+    // Adds `key` to the key list without storing a value, for keys which only
+    // matter for their order, like the entries of a secondary index.
+    pub fn insert_key(&mut self, key: String) {
+        self.writes.insert(key, Write::Key);
+    }
+
+    // Returns the value of `key`, including the writes of this transaction.
+    pub fn get(&self, key: &str) -> Result<Vec<u8>> {
+        match self.writes.get(key) {
+            Some(Write::Put(value)) => Ok(value.clone()),
+            Some(Write::Key) => Err(format_err!("Key has no value")),
+            Some(Write::Delete) => Err(format_err!("Key not found in key list")),
+            Some(Write::RecordHeaders) | None => self.db.get(key),
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> Result<bool> {
+        match self.writes.get(key) {
+            Some(Write::RecordHeaders) | None => self.db.contains_key(key),
+            Some(write) => Ok(!matches!(write, Write::Delete)),
+        }
+    }
+
+    pub fn delete(&mut self, key: &str) -> Result<()> {
+        ensure!(self.contains_key(key)?, "Key not found in key list");
+        self.writes.insert(key.to_string(), Write::Delete);
+        Ok(())
+    }
+
+    pub fn commit(self) -> Result<()> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+        for (key, write) in &self.writes {
+            ensure!(
+                matches!(write, Write::RecordHeaders) || !self.db.is_reserved_key(key),
+                "Key is reserved for the objects of the db: {}",
+                key
+            );
+        }
+        // A journal left by a previous commit which failed to be applied must
+        // be applied first, it is older than this one.
+        self.db.recover()?;
+
+        let journal = Journal {
+            writes: self.writes.into_iter().collect(),
+        };
+        let journal_id = journal_id(self.db.name());
+        self.db
+            .backend_mut()
+            .save(journal_id.as_bytes(), &serialize(&journal)?)?;
+        self.db.apply(journal)
+    }
+
+    // Marks the records of the db as starting with a header, along with the
+    // writes of the transaction, see schema::convert_legacy_records.
+    pub(crate) fn set_record_headers(&mut self) {
+        let name = self.db.name().to_string();
+        self.writes.insert(name, Write::RecordHeaders);
+    }
+
+    // Discards the writes of the transaction, which is the same as dropping it.
+    pub fn rollback(self) {}
+}
+
+impl<B: StorageBackend> SecureStorageDb<B> {
+    // Applies the journal of a commit which was interrupted, if any.
+    pub(crate) fn recover(&mut self) -> Result<()> {
+        let journal_id = journal_id(self.name());
+        if let Some(data) = self.backend().load(journal_id.as_bytes())? {
+            self.apply(deserialize(&data)?)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, journal: Journal) -> Result<()> {
+        for (key, write) in journal.writes {
+            match write {
+                Write::Put(value) => {
+                    self.backend_mut().save(key.as_bytes(), &value)?;
+                    self.insert_key(key)?;
+                }
+                Write::Key => self.insert_key(key)?,
+                Write::Delete => {
+                    self.backend_mut().delete(key.as_bytes())?;
+                    self.remove_key(&key)?;
+                }
+                Write::RecordHeaders => self.set_record_headers(true),
+            }
+        }
+        self.store_key_list()?;
+        let journal_id = journal_id(self.name());
+        self.backend_mut().delete(journal_id.as_bytes())?;
+        Ok(())
+    }
+}
+
+pub(crate) fn journal_id(db_name: &str) -> String {
+    format!("{}{}", db_name, JOURNAL_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryBackend;
+
+    fn open(backend: &MemoryBackend) -> SecureStorageDb<MemoryBackend> {
+        SecureStorageDb::open_with_backend("db".to_string(), backend.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_commit_and_rollback() {
+        let backend = MemoryBackend::new();
+        let mut db = open(&backend);
+        db.put("wallet#1".to_string(), b"old".to_vec()).unwrap();
+
+        let mut transaction = db.transaction();
+        transaction.put("wallet#1".to_string(), b"new".to_vec());
+        transaction.put("address#1".to_string(), b"wallet#1".to_vec());
+        assert_eq!(transaction.get("wallet#1").unwrap(), b"new");
+        transaction.rollback();
+        assert_eq!(db.get("wallet#1").unwrap(), b"old");
+        assert!(db.get("address#1").is_err());
+
+        let mut transaction = db.transaction();
+        transaction.put("address#1".to_string(), b"wallet#1".to_vec());
+        transaction.delete("wallet#1").unwrap();
+        assert!(transaction.get("wallet#1").is_err());
+        assert!(transaction.delete("wallet#2").is_err());
+        transaction.commit().unwrap();
+
+        let db = open(&backend);
+        assert_eq!(db.keys().unwrap().count(), 1);
+        assert_eq!(db.get("address#1").unwrap(), b"wallet#1");
+        assert_eq!(
+            backend.list(b"").unwrap(),
+            [&b"address#1"[..], b"db", b"db.keys.1"]
+        );
+    }
+
+    #[test]
+    fn test_recover_interrupted_commit() {
+        let mut backend = MemoryBackend::new();
+        let mut db = open(&backend);
+        db.put("a".to_string(), b"1".to_vec()).unwrap();
+
+        // The TA crashed after the commit point, with only the first write
+        // applied.
+        let journal = Journal {
+            writes: vec![
+                ("b".to_string(), Write::Put(b"2".to_vec())),
+                ("a".to_string(), Write::Delete),
+            ],
+        };
+        let data = serialize(&journal).unwrap();
+        backend.save(journal_id("db").as_bytes(), &data).unwrap();
+        backend.save(b"b", b"2").unwrap();
+
+        let db = open(&backend);
+        assert_eq!(db.keys().unwrap().count(), 1);
+        assert_eq!(db.get("b").unwrap(), b"2");
+        assert_eq!(backend.list(b"").unwrap(), [&b"b"[..], b"db", b"db.keys.1"]);
+    }
+}
@@ -0,0 +1,359 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Runs a trusted application natively on the host, so that it can be tested
+//! with `cargo test`.
+//!
+//! The `#[ta_create]`, `#[ta_open_session]`, `#[ta_invoke_command]`,
+//! `#[ta_close_session]` and `#[ta_destroy]` functions of a TA expand to the
+//! `TA_*EntryPoint` functions called by OP-TEE. A [`TrustedApp`] drives the
+//! same functions through the lifecycle of a TA instance:
+//!
+//! * [`TrustedApp::create`] calls `TA_CreateEntryPoint`, and dropping the
+//!   `TrustedApp` calls `TA_DestroyEntryPoint`;
+//! * [`TrustedApp::open_session`] calls `TA_OpenSessionEntryPoint`, the
+//!   session context boxed by the TA is kept by the [`Session`] and passed to
+//!   its commands;
+//! * [`Session::invoke_command`] calls `TA_InvokeCommandEntryPoint`, and
+//!   dropping the `Session` calls `TA_CloseSessionEntryPoint`, which frees the
+//!   session context.
+//!
+//! The TA runs on top of `optee-utee-emulator` (see [`emulator`]), its secure
+//! storage, crypto operations, time and properties work as on OP-TEE. Trace output is printed to the standard output.
+//!
+//! # Testing a TA
+//!
+//! The tests of a TA are unit tests of its binary crate, built for the host:
+//!
+//! ``` toml
+//! [dev-dependencies]
+//! ta_harness = { path = "../../../crates/ta_harness" }
+//! ```
+//!
+//! The TA is only `no_std` and `no_main` when it is not tested, and
+//! `optee_utee_build` still generates the TA header but does not link the TA
+//! against libutee, whose functions the emulator provides, when the
+//! `OPTEE_UTEE_BUILD_NO_LINK` environment variable is set, so the same
+//! `src/main.rs` and build script serve both. The commands of
+//! `tests/test_*.sh` then become plain tests, run with
+//! `OPTEE_UTEE_BUILD_NO_LINK=1 cargo test`:
+//!
+//! ``` rust,ignore
+//! #![cfg_attr(not(test), no_std)]
+//! #![cfg_attr(not(test), no_main)]
+//!
+//! // ... the TA entry points ...
+//!
+//! #[cfg(test)]
+//! mod tests {
+//!     use ta_harness::{entry_points, Operation, Param, TrustedApp};
+//!
+//!     #[test]
+//!     fn test_inc_value() {
+//!         let ta = TrustedApp::create(entry_points!(crate)).unwrap();
+//!         let mut session = ta.open_session().unwrap();
+//!         let mut operation = Operation::new(
+//!             Param::value_inout(29, 0),
+//!             Param::none(),
+//!             Param::none(),
+//!             Param::none(),
+//!         );
+//!         session.invoke_command(0, &mut operation).unwrap();
+//!         assert_eq!(operation.0.a(), 129);
+//!     }
+//! }
+//! ```
+//!
+//! The entry points are global symbols, so a test binary holds a single TA.
+//! A panic of the TA (including `TEE_Panic`) is not turned into
+//! `TEE_ERROR_TARGET_DEAD`, it fails the test.
+
+pub use self::param::{Operation, Param};
+pub use optee_utee::ParamType;
+pub use optee_utee_emulator as emulator;
+
+use optee_utee::{Error, Result};
+use optee_utee_sys as raw;
+use std::ffi::c_void;
+use std::ptr;
+
+mod param;
+
+/// The entry points of a TA, usually taken with [`entry_points!`].
+pub struct EntryPoints {
+    pub create: extern "C" fn() -> raw::TEE_Result,
+    pub destroy: extern "C" fn(),
+    pub open_session:
+        unsafe extern "C" fn(u32, &mut [raw::TEE_Param; 4], *mut *mut c_void) -> raw::TEE_Result,
+    pub close_session: unsafe extern "C" fn(*mut c_void),
+    pub invoke_command:
+        unsafe extern "C" fn(*mut c_void, u32, u32, &mut [raw::TEE_Param; 4]) -> raw::TEE_Result,
+}
+
+/// Returns the [`EntryPoints`] defined by the TA entry point macros in the
+/// given module, usually the root of the TA crate.
+///
+/// # Examples
+///
+/// ``` rust,ignore
+/// let ta = TrustedApp::create(ta_harness::entry_points!(crate))?;
+/// ```
+#[macro_export]
+macro_rules! entry_points {
+    ($($module:ident)::+) => {
+        $crate::EntryPoints {
+            create: $($module::)+TA_CreateEntryPoint,
+            destroy: $($module::)+TA_DestroyEntryPoint,
+            open_session: $($module::)+TA_OpenSessionEntryPoint,
+            close_session: $($module::)+TA_CloseSessionEntryPoint,
+            invoke_command: $($module::)+TA_InvokeCommandEntryPoint,
+        }
+    };
+}
+
+fn check(code: raw::TEE_Result) -> Result<()> {
+    match code {
+        raw::TEE_SUCCESS => Ok(()),
+        code => Err(Error::from_raw_error(code)),
+    }
+}
+
+/// An instance of a TA running on the host.
+pub struct TrustedApp {
+    entry_points: EntryPoints,
+}
+
+impl TrustedApp {
+    /// Creates an instance of the TA by calling its `#[ta_create]` function.
+    ///
+    /// # Errors
+    ///
+    /// 1) The error returned by the `#[ta_create]` function, the
+    ///    `#[ta_destroy]` function is not called then.
+    pub fn create(entry_points: EntryPoints) -> Result<Self> {
+        check((entry_points.create)())?;
+        Ok(Self { entry_points })
+    }
+
+    /// Opens a session without parameters.
+    pub fn open_session(&self) -> Result<Session<'_>> {
+        self.open_session_with_operation(&mut Operation::default())
+    }
+
+    /// Opens a session by calling the `#[ta_open_session]` function of the TA
+    /// with the parameters of `operation`, which are updated with its output.
+    ///
+    /// # Errors
+    ///
+    /// 1) The error returned by the `#[ta_open_session]` function, the
+    ///    `#[ta_close_session]` function is not called then.
+    pub fn open_session_with_operation(&self, operation: &mut Operation) -> Result<Session<'_>> {
+        let mut context = ptr::null_mut();
+        let (param_types, mut params) = operation.as_raw();
+        let code =
+            unsafe { (self.entry_points.open_session)(param_types, &mut params, &mut context) };
+        operation.update_from_raw(&params);
+        check(code)?;
+        Ok(Session { ta: self, context })
+    }
+}
+
+impl Drop for TrustedApp {
+    fn drop(&mut self) {
+        (self.entry_points.destroy)();
+    }
+}
+
+/// A session opened with a [`TrustedApp`], closed when dropped.
+pub struct Session<'ta> {
+    ta: &'ta TrustedApp,
+    // The session context boxed by the TA, null if it has none.
+    context: *mut c_void,
+}
+
+impl<'ta> Session<'ta> {
+    /// Invokes the command `cmd_id` by calling the `#[ta_invoke_command]`
+    /// function of the TA with the parameters of `operation`, which are
+    /// updated with its output, also when the command fails (e.g. with the
+    /// size required by a `ShortBuffer` error).
+    ///
+    /// # Errors
+    ///
+    /// 1) The error returned by the `#[ta_invoke_command]` function.
+    pub fn invoke_command(&mut self, cmd_id: u32, operation: &mut Operation) -> Result<()> {
+        let (param_types, mut params) = operation.as_raw();
+        let code = unsafe {
+            (self.ta.entry_points.invoke_command)(self.context, cmd_id, param_types, &mut params)
+        };
+        operation.update_from_raw(&params);
+        check(code)
+    }
+}
+
+impl<'ta> Drop for Session<'ta> {
+    fn drop(&mut self) {
+        unsafe { (self.ta.entry_points.close_session)(self.context) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use optee_utee::{
+        ta_close_session, ta_create, ta_destroy, ta_invoke_command, ta_open_session, trace_println,
+        ErrorKind, Parameters,
+    };
+    use std::cell::RefCell;
+
+    const CMD_COUNT: u32 = 0;
+    const CMD_ECHO: u32 = 1;
+
+    thread_local! {
+        static EVENTS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(event: &'static str) {
+        EVENTS.with(|events| events.borrow_mut().push(event));
+    }
+
+    fn events() -> Vec<&'static str> {
+        EVENTS.with(|events| events.borrow_mut().split_off(0))
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        count: u32,
+    }
+
+    #[ta_create]
+    fn create() -> Result<()> {
+        trace_println!("[+] TA create");
+        record("create");
+        Ok(())
+    }
+
+    #[ta_open_session]
+    fn open_session(params: &mut Parameters, counter: &mut Counter) -> Result<()> {
+        record("open");
+        if let Ok(value) = unsafe { params.0.as_value() } {
+            counter.count = value.a();
+        }
+        Ok(())
+    }
+
+    #[ta_close_session]
+    fn close_session(_counter: &mut Counter) {
+        record("close");
+    }
+
+    #[ta_destroy]
+    fn destroy() {
+        record("destroy");
+    }
+
+    #[ta_invoke_command]
+    fn invoke_command(counter: &mut Counter, cmd_id: u32, params: &mut Parameters) -> Result<()> {
+        match cmd_id {
+            CMD_COUNT => {
+                counter.count += 1;
+                let mut value = unsafe { params.0.as_value()? };
+                value.set_a(counter.count);
+                Ok(())
+            }
+            CMD_ECHO => {
+                let input = unsafe { params.0.as_memref()? }.buffer().to_vec();
+                let mut output = unsafe { params.1.as_memref()? };
+                if output.buffer().len() < input.len() {
+                    output.set_updated_size(input.len());
+                    return Err(ErrorKind::ShortBuffer.into());
+                }
+                output.buffer()[..input.len()].copy_from_slice(&input);
+                output.set_updated_size(input.len());
+                Ok(())
+            }
+            _ => Err(ErrorKind::BadParameters.into()),
+        }
+    }
+
+    fn count(session: &mut Session) -> u32 {
+        let mut operation = Operation::new(
+            Param::value_output(),
+            Param::none(),
+            Param::none(),
+            Param::none(),
+        );
+        session.invoke_command(CMD_COUNT, &mut operation).unwrap();
+        operation.0.a()
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let ta = TrustedApp::create(entry_points!(self)).unwrap();
+        let mut first = ta.open_session().unwrap();
+        let mut operation = Operation::new(
+            Param::value_input(10, 0),
+            Param::none(),
+            Param::none(),
+            Param::none(),
+        );
+        let mut second = ta.open_session_with_operation(&mut operation).unwrap();
+
+        // Each session has its own context.
+        assert_eq!(count(&mut first), 1);
+        assert_eq!(count(&mut first), 2);
+        assert_eq!(count(&mut second), 11);
+        drop(first);
+        drop(second);
+        drop(ta);
+        assert_eq!(
+            events(),
+            ["create", "open", "open", "close", "close", "destroy"]
+        );
+    }
+
+    #[test]
+    fn test_memref_and_errors() {
+        let ta = TrustedApp::create(entry_points!(crate::test)).unwrap();
+        let mut session = ta.open_session().unwrap();
+
+        let mut operation = Operation::new(
+            Param::memref_input(b"hello"),
+            Param::memref_output(2),
+            Param::none(),
+            Param::none(),
+        );
+        let err = session
+            .invoke_command(CMD_ECHO, &mut operation)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(operation.1.updated_size(), 5);
+
+        let mut operation = Operation::new(
+            Param::memref_input(b"hello"),
+            Param::memref_output(8),
+            Param::none(),
+            Param::none(),
+        );
+        session.invoke_command(CMD_ECHO, &mut operation).unwrap();
+        assert_eq!(operation.1.buffer(), b"hello");
+
+        let err = session
+            .invoke_command(42, &mut Operation::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+}
@@ -0,0 +1,173 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::ParamType;
+use optee_utee_sys as raw;
+use std::ptr;
+
+/// A parameter passed to the TA, as a client application passes it.
+pub struct Param {
+    param_type: ParamType,
+    a: u32,
+    b: u32,
+    buffer: Vec<u8>,
+    // The size of a memory reference, as updated by the TA.
+    size: usize,
+}
+
+impl Param {
+    fn new(param_type: ParamType, a: u32, b: u32, buffer: Vec<u8>) -> Self {
+        let size = buffer.len();
+        Self {
+            param_type,
+            a,
+            b,
+            buffer,
+            size,
+        }
+    }
+
+    /// Creates an unused parameter.
+    pub fn none() -> Self {
+        Self::new(ParamType::None, 0, 0, Vec::new())
+    }
+
+    pub fn value_input(a: u32, b: u32) -> Self {
+        Self::new(ParamType::ValueInput, a, b, Vec::new())
+    }
+
+    pub fn value_output() -> Self {
+        Self::new(ParamType::ValueOutput, 0, 0, Vec::new())
+    }
+
+    pub fn value_inout(a: u32, b: u32) -> Self {
+        Self::new(ParamType::ValueInout, a, b, Vec::new())
+    }
+
+    pub fn memref_input(data: &[u8]) -> Self {
+        Self::new(ParamType::MemrefInput, 0, 0, data.to_vec())
+    }
+
+    /// Creates an output memory reference of `size` zeroed bytes.
+    pub fn memref_output(size: usize) -> Self {
+        Self::new(ParamType::MemrefOutput, 0, 0, vec![0; size])
+    }
+
+    pub fn memref_inout(data: &[u8]) -> Self {
+        Self::new(ParamType::MemrefInout, 0, 0, data.to_vec())
+    }
+
+    pub fn param_type(&self) -> ParamType {
+        self.param_type
+    }
+
+    pub fn a(&self) -> u32 {
+        self.a
+    }
+
+    pub fn b(&self) -> u32 {
+        self.b
+    }
+
+    /// Returns the size of a memory reference as updated by the TA: the size
+    /// of its output, or the required size when it fails with `ShortBuffer`.
+    pub fn updated_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the content of a memory reference, up to its updated size.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[..self.size.min(self.buffer.len())]
+    }
+
+    fn as_raw(&mut self) -> raw::TEE_Param {
+        match self.param_type {
+            ParamType::MemrefInput | ParamType::MemrefOutput | ParamType::MemrefInout => {
+                raw::TEE_Param {
+                    memref: raw::Memref {
+                        buffer: if self.buffer.is_empty() {
+                            ptr::null_mut()
+                        } else {
+                            self.buffer.as_mut_ptr() as _
+                        },
+                        size: self.buffer.len(),
+                    },
+                }
+            }
+            _ => raw::TEE_Param {
+                value: raw::Value {
+                    a: self.a,
+                    b: self.b,
+                },
+            },
+        }
+    }
+
+    fn update_from_raw(&mut self, param: &raw::TEE_Param) {
+        match self.param_type {
+            ParamType::ValueOutput | ParamType::ValueInout => {
+                let value = unsafe { param.value };
+                self.a = value.a;
+                self.b = value.b;
+            }
+            ParamType::MemrefOutput | ParamType::MemrefInout => {
+                self.size = unsafe { param.memref.size };
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for Param {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// The four parameters of an operation, updated with the output of the TA
+/// once the operation returns.
+#[derive(Default)]
+pub struct Operation(pub Param, pub Param, pub Param, pub Param);
+
+impl Operation {
+    pub fn new(p0: Param, p1: Param, p2: Param, p3: Param) -> Self {
+        Self(p0, p1, p2, p3)
+    }
+
+    pub(crate) fn as_raw(&mut self) -> (u32, [raw::TEE_Param; 4]) {
+        let param_types = raw::TEE_PARAM_TYPES(
+            self.0.param_type as u32,
+            self.1.param_type as u32,
+            self.2.param_type as u32,
+            self.3.param_type as u32,
+        );
+        let params = [
+            self.0.as_raw(),
+            self.1.as_raw(),
+            self.2.as_raw(),
+            self.3.as_raw(),
+        ];
+        (param_types, params)
+    }
+
+    pub(crate) fn update_from_raw(&mut self, params: &[raw::TEE_Param; 4]) {
+        self.0.update_from_raw(&params[0]);
+        self.1.update_from_raw(&params[1]);
+        self.2.update_from_raw(&params[2]);
+        self.3.update_from_raw(&params[3]);
+    }
+}
@@ -15,8 +15,8 @@
 // specific language governing permissions and limitations
 // under the License.
 
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 
 use optee_utee::{
     ta_close_session, ta_create, ta_destroy, ta_invoke_command, ta_open_session, trace_println,
@@ -64,3 +64,36 @@ fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
 }
 
 include!(concat!(env!("OUT_DIR"), "/user_ta_header.rs"));
+
+#[cfg(test)]
+mod tests {
+    use proto::Command;
+    use ta_harness::{entry_points, Operation, Param, TrustedApp};
+
+    #[test]
+    fn test_inc_dec_value() {
+        let ta = TrustedApp::create(entry_points!(crate)).unwrap();
+        let mut session = ta.open_session().unwrap();
+        let mut operation = Operation::new(
+            Param::value_inout(29, 0),
+            Param::none(),
+            Param::none(),
+            Param::none(),
+        );
+        session
+            .invoke_command(Command::IncValue as u32, &mut operation)
+            .unwrap();
+        assert_eq!(operation.0.a(), 129);
+
+        let mut operation = Operation::new(
+            Param::value_inout(operation.0.a(), 0),
+            Param::none(),
+            Param::none(),
+            Param::none(),
+        );
+        session
+            .invoke_command(Command::DecValue as u32, &mut operation)
+            .unwrap();
+        assert_eq!(operation.0.a(), 29);
+    }
+}
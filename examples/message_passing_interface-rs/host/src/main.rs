@@ -15,16 +15,13 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use optee_teec::{
-    Context, ErrorKind, Operation, ParamNone, ParamTmpRef, ParamType, ParamValue, Uuid,
-};
+use optee_teec::{Context, ErrorKind, GrowableParam, Uuid};
 
 type Result<T> = optee_teec::Result<T>;
 
 pub struct EnclaveClient {
     uuid: String,
     context: optee_teec::Context,
-    buffer: Vec<u8>,
 }
 
 impl EnclaveClient {
@@ -47,33 +44,34 @@ impl EnclaveClient {
         Ok(Self {
             uuid: uuid.to_string(),
             context,
-            buffer: vec![0; 128],
         })
     }
 
     pub fn invoke(&mut self, input: &proto::EnclaveInput) -> Result<proto::EnclaveOutput> {
         let command_id = input.command as u32;
-        let mut serialized_input = proto::serde_json::to_vec(input).map_err(|e| {
+        let serialized_input = proto::serde_json::to_vec(input).map_err(|e| {
             eprintln!("Failed to serialize input: {}", e);
             ErrorKind::BadParameters
         })?;
 
-        let p0 = ParamTmpRef::new_input(serialized_input.as_mut_slice());
-        let p1 = ParamTmpRef::new_output(&mut self.buffer);
-        let p2 = ParamValue::new(0, 0, ParamType::ValueInout);
-
-        let mut operation = Operation::new(0, p0, p1, p2, ParamNone);
-
         let uuid = Uuid::parse_str(&self.uuid)?;
         let mut session = self.context.open_session(uuid)?;
-        session.invoke_command(command_id, &mut operation)?;
-        let len = operation.parameters().2.a() as usize;
+        // The output buffer is reallocated if the TA reports a larger size.
+        let [_, output, _, _] = session.invoke_command_growing(
+            command_id,
+            [
+                GrowableParam::Input(&serialized_input),
+                GrowableParam::output(128),
+                GrowableParam::None,
+                GrowableParam::None,
+            ],
+        )?;
+        let buffer = output.into_buffer().unwrap_or_default();
 
-        let output: proto::EnclaveOutput = proto::serde_json::from_slice(&self.buffer[0..len])
-            .map_err(|e| {
-                eprintln!("Failed to deserialize output: {}", e);
-                ErrorKind::BadParameters
-            })?;
+        let output: proto::EnclaveOutput = proto::serde_json::from_slice(&buffer).map_err(|e| {
+            eprintln!("Failed to deserialize output: {}", e);
+            ErrorKind::BadParameters
+        })?;
         Ok(output)
     }
 }
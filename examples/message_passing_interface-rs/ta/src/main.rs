@@ -22,7 +22,6 @@ use optee_utee::{
 };
 use optee_utee::{ErrorKind, Parameters, Result};
 use proto::{self, Command};
-use std::io::Write;
 
 fn handle_invoke(command: Command, input: proto::EnclaveInput) -> Result<proto::EnclaveOutput> {
     match command {
@@ -69,7 +68,6 @@ fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
     trace_println!("[+] TA invoke command");
     let mut p0 = unsafe { params.0.as_memref()? };
     let mut p1 = unsafe { params.1.as_memref()? };
-    let mut p2 = unsafe { params.2.as_value()? };
 
     let input: proto::EnclaveInput = proto::serde_json::from_slice(p0.buffer()).map_err(|e| {
         trace_println!("Failed to deserialize input: {}", e);
@@ -81,11 +79,13 @@ fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
         trace_println!("Failed to serialize output: {}", e);
         ErrorKind::BadFormat
     })?;
-    p1.buffer().write_all(&output_vec).map_err(|e| {
-        trace_println!("Failed to write output: {}", e);
-        ErrorKind::BadParameters
-    })?;
-    p2.set_a(output_vec.len() as u32);
+    // Report the required size, the client retries with a larger buffer.
+    if p1.buffer().len() < output_vec.len() {
+        p1.set_updated_size(output_vec.len());
+        return Err(ErrorKind::ShortBuffer.into());
+    }
+    p1.buffer()[..output_vec.len()].copy_from_slice(&output_vec);
+    p1.set_updated_size(output_vec.len());
 
     Ok(())
 }
@@ -15,7 +15,10 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::{raw, ConnectionMethods, Error, Operation, Param, ParamNone, Result, Session, Uuid};
+use crate::{
+    raw, ConnectionMethods, Error, Operation, Param, ParamNone, Result, Session, SharedMemory,
+    SharedMemoryFlags, Uuid,
+};
 use std::{cell::RefCell, ptr, rc::Rc};
 
 pub struct InnerContext(pub raw::TEEC_Context);
@@ -137,6 +140,52 @@ impl Context {
     ) -> Result<Session> {
         Session::new(self, uuid, ConnectionMethods::LoginPublic, Some(operation))
     }
+
+    /// Allocates a block of `size` bytes of memory shared with the TEE. The
+    /// memory is released when the returned `SharedMemory` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_teec::{Context, SharedMemoryFlags};
+    ///
+    /// fn main() -> optee_teec::Result<()> {
+    ///     let mut ctx = Context::new()?;
+    ///     let mut shm = ctx.allocate_shared_memory(1024, SharedMemoryFlags::Inout)?;
+    ///     shm.buffer_mut()[..5].copy_from_slice(b"hello");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn allocate_shared_memory(
+        &mut self,
+        size: usize,
+        flags: SharedMemoryFlags,
+    ) -> Result<SharedMemory<'static>> {
+        SharedMemory::allocate(self, size, flags)
+    }
+
+    /// Registers `buffer` as a block of memory shared with the TEE. The buffer
+    /// stays borrowed until the returned `SharedMemory` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_teec::{Context, SharedMemoryFlags};
+    ///
+    /// fn main() -> optee_teec::Result<()> {
+    ///     let mut ctx = Context::new()?;
+    ///     let mut buffer = vec![0u8; 1024];
+    ///     let shm = ctx.register_shared_memory(&mut buffer, SharedMemoryFlags::Output)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn register_shared_memory<'a>(
+        &mut self,
+        buffer: &'a mut [u8],
+        flags: SharedMemoryFlags,
+    ) -> Result<SharedMemory<'a>> {
+        SharedMemory::register(self, buffer, flags)
+    }
 }
 
 // Internal usage only
@@ -18,19 +18,39 @@
 pub use self::context::Context;
 pub use self::error::{Error, ErrorKind, ErrorOrigin, Result};
 pub use self::extension::*;
-pub use self::operation::Operation;
-pub use self::parameter::{Param, ParamNone, ParamTmpRef, ParamType, ParamTypes, ParamValue};
+pub use self::operation::{CancellationHandle, Operation};
+pub use self::parameter::{
+    GrowableParam, Param, ParamMemRefPartial, ParamMemRefWhole, ParamNone, ParamTmpRef, ParamType,
+    ParamTypes, ParamValue,
+};
 pub use self::session::{ConnectionMethods, Session};
+pub use self::shared_memory::{SharedMemory, SharedMemoryFlags};
 pub use self::uuid::Uuid;
 pub use optee_teec_macros::{plugin_init, plugin_invoke};
 // Re-export optee_teec_sys so developers don't have to add it to their cargo
 // dependencies.
+#[cfg(not(feature = "simulator"))]
 pub use optee_teec_sys as raw;
 
+/// The raw TEE client API, with the functions implemented by the
+/// [`simulator`].
+#[cfg(feature = "simulator")]
+pub mod raw {
+    pub use crate::simulator::ffi::{
+        TEEC_AllocateSharedMemory, TEEC_CloseSession, TEEC_FinalizeContext,
+        TEEC_InitializeContext, TEEC_InvokeCommand, TEEC_OpenSession, TEEC_RegisterSharedMemory,
+        TEEC_ReleaseSharedMemory, TEEC_RequestCancellation,
+    };
+    pub use optee_teec_sys::*;
+}
+
 mod context;
 mod error;
 mod extension;
 mod operation;
 mod parameter;
 mod session;
+mod shared_memory;
+#[cfg(feature = "simulator")]
+pub mod simulator;
 mod uuid;
@@ -16,13 +16,23 @@
 // under the License.
 
 use crate::{raw, Param, ParamTypes};
-use std::{marker::PhantomData, mem};
+use std::{
+    marker::PhantomData,
+    mem,
+    sync::{Arc, Mutex},
+};
 
 /// This type defines the payload of either an open session operation or an
 /// invoke command operation. It is also used for cancellation of operations,
 /// which may be desirable even if no payload is passed.
 pub struct Operation<A, B, C, D> {
-    raw: raw::TEEC_Operation,
+    // Declared before `raw` as fields are dropped in declaration order: the
+    // target of the cancellation handles must be cleared before the raw
+    // operation is freed.
+    cancel_target: Option<CancelGuard>,
+    // Boxed so that the address shared with cancellation handles stays stable
+    // when the operation is moved.
+    raw: Box<raw::TEEC_Operation>,
     phantom0: PhantomData<A>,
     phantom1: PhantomData<B>,
     phantom2: PhantomData<C>,
@@ -42,7 +52,8 @@ impl<A: Param, B: Param, C: Param, D: Param> Operation<A, B, C, D> {
         .into();
         raw_op.params = [p0.into_raw(), p1.into_raw(), p2.into_raw(), p3.into_raw()];
         Operation {
-            raw: raw_op,
+            cancel_target: None,
+            raw: Box::new(raw_op),
             phantom0: PhantomData,
             phantom1: PhantomData,
             phantom2: PhantomData,
@@ -51,7 +62,47 @@ impl<A: Param, B: Param, C: Param, D: Param> Operation<A, B, C, D> {
     }
 
     pub(crate) fn as_mut_raw_ptr(&mut self) -> *mut raw::TEEC_Operation {
-        &mut self.raw
+        self.raw.as_mut()
+    }
+
+    /// Returns a handle which can request the cancellation of this operation
+    /// from another thread while it is being used by `Session::invoke_command`
+    /// or `Context::open_session_with_operation`.
+    ///
+    /// Cancellation is only possible if the operation is created with
+    /// `started` set to 0. Requesting cancellation is a hint, the trusted
+    /// application needs to poll for it and the operation may still complete
+    /// successfully. Once the operation is dropped the handle does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_teec::{Context, Operation, ParamNone, ParamType, ParamValue, Uuid};
+    /// use std::{thread, time::Duration};
+    ///
+    /// fn main() -> optee_teec::Result<()> {
+    ///     let mut ctx = Context::new()?;
+    ///     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+    ///     let mut session = ctx.open_session(uuid)?;
+    ///     let p0 = ParamValue::new(0, 0, ParamType::ValueInout);
+    ///     let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+    ///     let handle = operation.cancellation_handle();
+    ///     thread::spawn(move || {
+    ///         thread::sleep(Duration::from_secs(10));
+    ///         handle.cancel();
+    ///     });
+    ///     session.invoke_command(0, &mut operation)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn cancellation_handle(&mut self) -> CancellationHandle {
+        let raw_op = self.as_mut_raw_ptr();
+        let guard = self
+            .cancel_target
+            .get_or_insert_with(|| CancelGuard(Arc::new(Mutex::new(CancelTarget(Some(raw_op))))));
+        CancellationHandle {
+            target: guard.0.clone(),
+        }
     }
 
     pub fn parameters(&self) -> (A, B, C, D) {
@@ -64,3 +115,79 @@ impl<A: Param, B: Param, C: Param, D: Param> Operation<A, B, C, D> {
         )
     }
 }
+
+struct CancelTarget(Option<*mut raw::TEEC_Operation>);
+
+// The pointer is only dereferenced by TEEC_RequestCancellation, which is
+// designed to be called from another thread than the one using the operation,
+// and it is cleared under the lock before the operation is freed.
+unsafe impl Send for CancelTarget {}
+
+// Clears the target when the operation is dropped. It is kept in a field
+// rather than implemented on Operation itself, so the borrows of the parameters
+// end at the last use of the operation as before.
+struct CancelGuard(Arc<Mutex<CancelTarget>>);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        // Waits for a cancellation request in progress, so the raw operation is
+        // never accessed after it is freed.
+        let mut target = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        target.0 = None;
+    }
+}
+
+/// A handle to request the cancellation of an [`Operation`], which can be sent
+/// to another thread. It is created by [`Operation::cancellation_handle`].
+#[derive(Clone)]
+pub struct CancellationHandle {
+    target: Arc<Mutex<CancelTarget>>,
+}
+
+impl CancellationHandle {
+    /// Requests the cancellation of the operation. It does nothing if the
+    /// operation is not started yet, has completed or has been dropped.
+    pub fn cancel(&self) {
+        let target = self.target.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(raw_op) = target.0 {
+            unsafe {
+                raw::TEEC_RequestCancellation(raw_op);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::{ParamNone, ParamType, ParamValue};
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+    };
+
+    #[test]
+    fn test_cancel_while_dropped() {
+        let stop = Arc::new(AtomicBool::new(false));
+        for _ in 0..100 {
+            let p0 = ParamValue::new(0, 0, ParamType::ValueInout);
+            let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+            let handle = operation.cancellation_handle();
+            stop.store(false, Ordering::SeqCst);
+            let canceller = {
+                let (handle, stop) = (handle.clone(), stop.clone());
+                thread::spawn(move || {
+                    while !stop.load(Ordering::SeqCst) {
+                        handle.cancel();
+                    }
+                })
+            };
+            drop(operation);
+            assert!(handle.target.lock().unwrap().0.is_none());
+            stop.store(true, Ordering::SeqCst);
+            canceller.join().unwrap();
+            // Does nothing once the operation is dropped.
+            handle.cancel();
+        }
+    }
+}
@@ -15,7 +15,7 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::raw;
+use crate::{raw, Error, ErrorKind, Result, SharedMemory};
 use std::{marker, mem};
 
 pub trait Param {
@@ -127,6 +127,21 @@ impl<'a> ParamTmpRef<'a> {
         }
     }
 
+    /// Creates a temporary input and output memory reference. `buffer` is a
+    /// region of memory which needs to be temporarily registered for the
+    /// duration of the `Operation`.
+    pub fn new_inout(buffer: &'a mut [u8]) -> Self {
+        let raw = raw::TEEC_TempMemoryReference {
+            buffer: buffer.as_ptr() as _,
+            size: buffer.len(),
+        };
+        Self {
+            raw,
+            param_type: ParamType::MemrefTempInout,
+            _marker: marker::PhantomData,
+        }
+    }
+
     pub fn updated_size(&self) -> usize {
         self.raw.size
     }
@@ -150,6 +165,295 @@ impl<'a> Param for ParamTmpRef<'a> {
     }
 }
 
+/// This type defines a registered memory reference to the entirety of a
+/// `SharedMemory` block. It is used as a `Operation` parameter with the
+/// parameter type `MemrefWhole`, the direction is derived from the flags of the
+/// shared memory.
+pub struct ParamMemRefWhole<'a> {
+    raw: raw::TEEC_RegisteredMemoryReference,
+    _marker: marker::PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> ParamMemRefWhole<'a> {
+    /// Creates a memory reference to the whole `shared_memory`, which is
+    /// borrowed for the duration of the `Operation`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_teec::{Context, Operation, ParamMemRefWhole, ParamNone, SharedMemoryFlags, Uuid};
+    ///
+    /// fn main() -> optee_teec::Result<()> {
+    ///     let mut ctx = Context::new()?;
+    ///     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+    ///     let mut session = ctx.open_session(uuid)?;
+    ///     let mut shm = ctx.allocate_shared_memory(4096, SharedMemoryFlags::Inout)?;
+    ///     {
+    ///         let p0 = ParamMemRefWhole::new(&mut shm);
+    ///         let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+    ///         session.invoke_command(0, &mut operation)?;
+    ///     }
+    ///     println!("{:?}", &shm.buffer()[..16]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn new(shared_memory: &'a mut SharedMemory) -> Self {
+        let raw = raw::TEEC_RegisteredMemoryReference {
+            parent: shared_memory.as_mut_raw_ptr(),
+            size: shared_memory.size(),
+            offset: 0,
+        };
+        Self {
+            raw,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Returns the size of the data written by the trusted application, or
+    /// the required size when the operation failed with `ShortBuffer`.
+    pub fn updated_size(&self) -> usize {
+        self.raw.size
+    }
+}
+
+impl<'a> Param for ParamMemRefWhole<'a> {
+    fn into_raw(&mut self) -> raw::TEEC_Parameter {
+        raw::TEEC_Parameter { memref: self.raw }
+    }
+
+    fn param_type(&self) -> ParamType {
+        ParamType::MemrefWhole
+    }
+
+    fn from_raw(raw: raw::TEEC_Parameter, _param_type: ParamType) -> Self {
+        Self {
+            raw: unsafe { raw.memref },
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+/// This type defines a registered memory reference to a region of a
+/// `SharedMemory` block. It is used as a `Operation` parameter when the
+/// corresponding parameter type is one of `MemrefPartialInput`,
+/// `MemrefPartialOutput`, or `MemrefPartialInout`.
+pub struct ParamMemRefPartial<'a> {
+    raw: raw::TEEC_RegisteredMemoryReference,
+    param_type: ParamType,
+    _marker: marker::PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> ParamMemRefPartial<'a> {
+    fn new(
+        shared_memory: &'a mut SharedMemory,
+        offset: usize,
+        size: usize,
+        param_type: ParamType,
+    ) -> Result<Self> {
+        let flags = shared_memory.flags();
+        let direction_ok = match param_type {
+            ParamType::MemrefPartialInput => flags.is_input(),
+            ParamType::MemrefPartialOutput => flags.is_output(),
+            _ => flags.is_input() && flags.is_output(),
+        };
+        let in_bounds = offset
+            .checked_add(size)
+            .map_or(false, |end| end <= shared_memory.size());
+        if !direction_ok || !in_bounds {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let raw = raw::TEEC_RegisteredMemoryReference {
+            parent: shared_memory.as_mut_raw_ptr(),
+            size,
+            offset,
+        };
+        Ok(Self {
+            raw,
+            param_type,
+            _marker: marker::PhantomData,
+        })
+    }
+
+    /// Creates an input only memory reference to `size` bytes at `offset` of
+    /// `shared_memory`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the region is out of the bounds of the shared
+    ///    memory, or the shared memory is not created with an input flag.
+    pub fn new_input(
+        shared_memory: &'a mut SharedMemory,
+        offset: usize,
+        size: usize,
+    ) -> Result<Self> {
+        Self::new(shared_memory, offset, size, ParamType::MemrefPartialInput)
+    }
+
+    /// Creates an output only memory reference to `size` bytes at `offset` of
+    /// `shared_memory`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the region is out of the bounds of the shared
+    ///    memory, or the shared memory is not created with an output flag.
+    pub fn new_output(
+        shared_memory: &'a mut SharedMemory,
+        offset: usize,
+        size: usize,
+    ) -> Result<Self> {
+        Self::new(shared_memory, offset, size, ParamType::MemrefPartialOutput)
+    }
+
+    /// Creates an input and output memory reference to `size` bytes at
+    /// `offset` of `shared_memory`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: the region is out of the bounds of the shared
+    ///    memory, or the shared memory is not created with `Inout` flags.
+    pub fn new_inout(
+        shared_memory: &'a mut SharedMemory,
+        offset: usize,
+        size: usize,
+    ) -> Result<Self> {
+        Self::new(shared_memory, offset, size, ParamType::MemrefPartialInout)
+    }
+
+    /// Returns the offset of the region in the shared memory.
+    pub fn offset(&self) -> usize {
+        self.raw.offset
+    }
+
+    /// Returns the size of the data written by the trusted application, or
+    /// the required size when the operation failed with `ShortBuffer`.
+    pub fn updated_size(&self) -> usize {
+        self.raw.size
+    }
+}
+
+impl<'a> Param for ParamMemRefPartial<'a> {
+    fn into_raw(&mut self) -> raw::TEEC_Parameter {
+        raw::TEEC_Parameter { memref: self.raw }
+    }
+
+    fn param_type(&self) -> ParamType {
+        self.param_type
+    }
+
+    fn from_raw(raw: raw::TEEC_Parameter, param_type: ParamType) -> Self {
+        Self {
+            raw: unsafe { raw.memref },
+            param_type,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+/// A parameter of [`Session::invoke_command_growing`](crate::Session::invoke_command_growing),
+/// whose output buffers are owned so they can be reallocated when the trusted
+/// application reports that they are too short.
+pub enum GrowableParam<'a> {
+    /// The parameter is not used.
+    None,
+    /// A value parameter, with its own input/output direction.
+    Value(ParamValue),
+    /// A temporary memory reference tagged as input.
+    Input(&'a [u8]),
+    /// A temporary memory reference tagged as output, the length of the vector
+    /// is the initial size of the buffer.
+    Output(Vec<u8>),
+    /// A temporary memory reference tagged as both input and output, the
+    /// vector holds the input data. Its size is the size of the input, so the
+    /// buffer does not grow: the operation fails with `ShortBuffer` if the
+    /// trusted application requires a larger one.
+    Inout(Vec<u8>),
+}
+
+impl<'a> GrowableParam<'a> {
+    /// Creates an output parameter with an initial buffer of `size` bytes.
+    pub fn output(size: usize) -> Self {
+        GrowableParam::Output(vec![0u8; size])
+    }
+
+    /// Returns the buffer of an output or inout parameter.
+    pub fn buffer(&self) -> Option<&[u8]> {
+        match self {
+            GrowableParam::Output(buffer) | GrowableParam::Inout(buffer) => Some(buffer),
+            _ => None,
+        }
+    }
+
+    /// Consumes the parameter and returns the buffer of an output or inout
+    /// parameter.
+    pub fn into_buffer(self) -> Option<Vec<u8>> {
+        match self {
+            GrowableParam::Output(buffer) | GrowableParam::Inout(buffer) => Some(buffer),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a value parameter.
+    pub fn value(&self) -> Option<&ParamValue> {
+        match self {
+            GrowableParam::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn param_type(&self) -> ParamType {
+        match self {
+            GrowableParam::None => ParamType::None,
+            GrowableParam::Value(value) => value.param_type(),
+            GrowableParam::Input(_) => ParamType::MemrefTempInput,
+            GrowableParam::Output(_) => ParamType::MemrefTempOutput,
+            GrowableParam::Inout(_) => ParamType::MemrefTempInout,
+        }
+    }
+
+    pub(crate) fn as_raw(&mut self) -> raw::TEEC_Parameter {
+        match self {
+            GrowableParam::None => ParamNone.into_raw(),
+            GrowableParam::Value(value) => value.into_raw(),
+            GrowableParam::Input(buffer) => ParamTmpRef::new_input(buffer).into_raw(),
+            GrowableParam::Output(buffer) => ParamTmpRef::new_output(buffer).into_raw(),
+            GrowableParam::Inout(buffer) => ParamTmpRef::new_inout(buffer).into_raw(),
+        }
+    }
+
+    // Updates the parameter with the raw parameter after the operation
+    // succeeded, output buffers are truncated to the size written by the
+    // trusted application.
+    pub(crate) fn update_from_raw(&mut self, raw: raw::TEEC_Parameter) {
+        match self {
+            GrowableParam::Value(value) => {
+                *value = ParamValue::from_raw(raw, value.param_type());
+            }
+            GrowableParam::Output(buffer) | GrowableParam::Inout(buffer) => {
+                let size = unsafe { raw.tmpref.size };
+                buffer.truncate(size);
+            }
+            _ => {}
+        }
+    }
+
+    // Grows an output buffer to the size required by the trusted application
+    // after the operation failed with `ShortBuffer`, returns true if it grew.
+    // Inout buffers never grow, padding them would change the input.
+    pub(crate) fn grow_from_raw(&mut self, raw: raw::TEEC_Parameter) -> bool {
+        match self {
+            GrowableParam::Output(buffer) => {
+                let size = unsafe { raw.tmpref.size };
+                if size > buffer.len() {
+                    buffer.resize(size, 0);
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
 /// These are used to indicate the type of Parameter encoded inside the
 /// operation structure.
 #[derive(Copy, Clone)]
@@ -16,8 +16,13 @@
 // under the License.
 
 use super::context::InnerContext;
-use crate::{raw, Context, Error, Operation, Param, Result, Uuid};
-use std::{cell::RefCell, ptr, rc::Rc};
+use crate::{
+    raw, Context, Error, ErrorKind, GrowableParam, Operation, Param, ParamTypes, Result, Uuid,
+};
+use std::{cell::RefCell, mem, ptr, rc::Rc};
+
+// The number of times `invoke_command_growing` retries with larger buffers.
+const GROWING_MAX_RETRIES: usize = 8;
 
 /// Session login methods.
 #[derive(Copy, Clone)]
@@ -114,6 +119,110 @@ impl Session {
     }
 }
 
+impl Session {
+    /// Invokes a command with parameters whose output buffers grow on demand.
+    ///
+    /// When the trusted application fails with `ShortBuffer` and reports the
+    /// required size of an output buffer, the buffer is reallocated to that
+    /// size and the command is invoked again, with the original input of inout
+    /// buffers. On success the parameters are returned with their buffers
+    /// truncated to the size written by the trusted application, and values
+    /// updated.
+    ///
+    /// The trusted application must not have side effects before it reports a
+    /// short buffer, as the command runs again on retry.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// use optee_teec::{Context, GrowableParam, Uuid};
+    ///
+    /// fn main() -> optee_teec::Result<()> {
+    ///     let mut ctx = Context::new()?;
+    ///     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b").unwrap();
+    ///     let mut session = ctx.open_session(uuid)?;
+    ///     let [_, output, _, _] = session.invoke_command_growing(
+    ///         0,
+    ///         [
+    ///             GrowableParam::Input(b"request"),
+    ///             GrowableParam::output(64),
+    ///             GrowableParam::None,
+    ///             GrowableParam::None,
+    ///         ],
+    ///     )?;
+    ///     let response = output.into_buffer().unwrap();
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 1) `ShortBuffer`: the trusted application reports a short buffer without
+    ///    requiring a larger size for any output buffer (inout buffers do not
+    ///    grow), or keeps requiring larger buffers.
+    /// 2) The error returned by the TEE or the trusted application.
+    pub fn invoke_command_growing<'a>(
+        &mut self,
+        command_id: u32,
+        mut params: [GrowableParam<'a>; 4],
+    ) -> Result<[GrowableParam<'a>; 4]> {
+        // The trusted application may write inout buffers before it reports a
+        // short buffer, their input is restored before each retry.
+        let inputs: Vec<Option<Vec<u8>>> = params
+            .iter()
+            .map(|param| match param {
+                GrowableParam::Inout(buffer) => Some(buffer.clone()),
+                _ => None,
+            })
+            .collect();
+        for _ in 0..=GROWING_MAX_RETRIES {
+            // SAFETY:
+            // raw_op is a C struct(TEEC_Operation), which zero value is valid.
+            let mut raw_op: raw::TEEC_Operation = unsafe { mem::zeroed() };
+            raw_op.paramTypes = ParamTypes::new(
+                params[0].param_type(),
+                params[1].param_type(),
+                params[2].param_type(),
+                params[3].param_type(),
+            )
+            .into();
+            for (raw_param, param) in raw_op.params.iter_mut().zip(params.iter_mut()) {
+                *raw_param = param.as_raw();
+            }
+
+            let mut err_origin: u32 = 0;
+            match unsafe {
+                raw::TEEC_InvokeCommand(&mut self.raw, command_id, &mut raw_op, &mut err_origin)
+            } {
+                raw::TEEC_SUCCESS => {
+                    for (raw_param, param) in raw_op.params.iter().zip(params.iter_mut()) {
+                        param.update_from_raw(*raw_param);
+                    }
+                    return Ok(params);
+                }
+                raw::TEEC_ERROR_SHORT_BUFFER => {
+                    let mut grown = false;
+                    for (raw_param, param) in raw_op.params.iter().zip(params.iter_mut()) {
+                        grown |= param.grow_from_raw(*raw_param);
+                    }
+                    if !grown {
+                        return Err(
+                            Error::new(ErrorKind::ShortBuffer).with_origin(err_origin.into())
+                        );
+                    }
+                    for (param, input) in params.iter_mut().zip(&inputs) {
+                        if let (GrowableParam::Inout(buffer), Some(input)) = (param, input) {
+                            buffer.copy_from_slice(input);
+                        }
+                    }
+                }
+                code => return Err(Error::from_raw_error(code).with_origin(err_origin.into())),
+            }
+        }
+        Err(Error::new(ErrorKind::ShortBuffer))
+    }
+}
+
 impl Drop for Session {
     fn drop(&mut self) {
         unsafe {
@@ -121,3 +230,173 @@ impl Drop for Session {
         }
     }
 }
+
+#[cfg(all(test, feature = "simulator"))]
+mod tests {
+    use super::*;
+    use crate::{simulator, ParamType, ParamValue};
+    use optee_utee::{ErrorKind as TaErrorKind, Parameters};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    // Fills the output buffer with the size in the value.
+    const CMD_FILL: u32 = 0;
+    // Reports a short buffer without a larger size.
+    const CMD_SHORT: u32 = 1;
+    // Always requires a buffer one byte larger.
+    const CMD_LARGER: u32 = 2;
+    // Reverses the inout buffer in place, then copies the input twice to the
+    // output buffer.
+    const CMD_REVERSE: u32 = 3;
+
+    fn handler(cmd_id: u32, params: &mut Parameters) -> optee_utee::Result<()> {
+        match cmd_id {
+            CMD_FILL => {
+                let size = unsafe { params.0.as_value()? }.a() as usize;
+                let mut output = unsafe { params.1.as_memref()? };
+                if output.buffer().len() < size {
+                    output.set_updated_size(size);
+                    return Err(TaErrorKind::ShortBuffer.into());
+                }
+                output.buffer()[..size].fill(0xaa);
+                output.set_updated_size(size);
+                Ok(())
+            }
+            CMD_SHORT => Err(TaErrorKind::ShortBuffer.into()),
+            CMD_LARGER => {
+                let mut output = unsafe { params.0.as_memref()? };
+                let size = output.buffer().len() + 1;
+                output.set_updated_size(size);
+                Err(TaErrorKind::ShortBuffer.into())
+            }
+            CMD_REVERSE => {
+                let mut inout = unsafe { params.0.as_memref()? };
+                let input = inout.buffer().to_vec();
+                inout.buffer().reverse();
+                let mut output = unsafe { params.1.as_memref()? };
+                let size = input.len() * 2;
+                if output.buffer().len() < size {
+                    output.set_updated_size(size);
+                    return Err(TaErrorKind::ShortBuffer.into());
+                }
+                output.buffer()[..input.len()].copy_from_slice(&input);
+                output.buffer()[input.len()..size].copy_from_slice(&input);
+                output.set_updated_size(size);
+                Ok(())
+            }
+            _ => Err(TaErrorKind::NotSupported.into()),
+        }
+    }
+
+    // Each test uses its own TA as tests run concurrently, the number of
+    // invocations is counted.
+    fn open_session(uuid: &str) -> (Session, Arc<AtomicUsize>) {
+        let uuid = Uuid::parse_str(uuid).unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        simulator::register_ta(&uuid, move |cmd_id, params| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            handler(cmd_id, params)
+        });
+        let mut ctx = Context::new().unwrap();
+        (ctx.open_session(uuid).unwrap(), calls)
+    }
+
+    #[test]
+    fn test_invoke_command_growing() {
+        let (mut session, calls) = open_session("f0e1d2c3-0000-4000-8000-000000000101");
+        let [_, output, _, _] = session
+            .invoke_command_growing(
+                CMD_FILL,
+                [
+                    GrowableParam::Value(ParamValue::new(100, 0, ParamType::ValueInput)),
+                    GrowableParam::output(4),
+                    GrowableParam::None,
+                    GrowableParam::None,
+                ],
+            )
+            .unwrap();
+        assert_eq!(output.into_buffer().unwrap(), vec![0xaa; 100]);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        // the buffer is truncated to the written size
+        let [_, output, _, _] = session
+            .invoke_command_growing(
+                CMD_FILL,
+                [
+                    GrowableParam::Value(ParamValue::new(3, 0, ParamType::ValueInput)),
+                    GrowableParam::output(16),
+                    GrowableParam::None,
+                    GrowableParam::None,
+                ],
+            )
+            .unwrap();
+        assert_eq!(output.into_buffer().unwrap(), vec![0xaa; 3]);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_invoke_command_growing_short_buffer() {
+        let (mut session, calls) = open_session("f0e1d2c3-0000-4000-8000-000000000102");
+        let params = [
+            GrowableParam::output(4),
+            GrowableParam::None,
+            GrowableParam::None,
+            GrowableParam::None,
+        ];
+        let err = session
+            .invoke_command_growing(CMD_SHORT, params)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let params = [
+            GrowableParam::output(4),
+            GrowableParam::None,
+            GrowableParam::None,
+            GrowableParam::None,
+        ];
+        let err = session
+            .invoke_command_growing(CMD_LARGER, params)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(calls.load(Ordering::Relaxed), 1 + GROWING_MAX_RETRIES + 1);
+    }
+
+    #[test]
+    fn test_invoke_command_growing_inout() {
+        let (mut session, calls) = open_session("f0e1d2c3-0000-4000-8000-000000000103");
+        let [inout, output, _, _] = session
+            .invoke_command_growing(
+                CMD_REVERSE,
+                [
+                    GrowableParam::Inout(b"abc".to_vec()),
+                    GrowableParam::output(1),
+                    GrowableParam::None,
+                    GrowableParam::None,
+                ],
+            )
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(inout.into_buffer().unwrap(), b"cba");
+        assert_eq!(output.into_buffer().unwrap(), b"abcabc");
+
+        // inout buffers do not grow
+        let params = [
+            GrowableParam::Inout(b"abc".to_vec()),
+            GrowableParam::None,
+            GrowableParam::None,
+            GrowableParam::None,
+        ];
+        let err = session
+            .invoke_command_growing(CMD_LARGER, params)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+}
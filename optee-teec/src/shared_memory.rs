@@ -0,0 +1,168 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::context::InnerContext;
+use crate::{raw, Context, Error, Result};
+use std::{cell::RefCell, marker, mem, rc::Rc, slice};
+
+/// The direction(s) in which a shared memory block is used between the client
+/// application and the trusted application.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum SharedMemoryFlags {
+    /// The memory can carry data from the client application to the trusted
+    /// application.
+    Input = raw::TEEC_MEM_INPUT,
+    /// The memory can carry data from the trusted application to the client
+    /// application.
+    Output = raw::TEEC_MEM_OUTPUT,
+    /// The memory can carry data in both directions.
+    Inout = raw::TEEC_MEM_INPUT | raw::TEEC_MEM_OUTPUT,
+}
+
+impl SharedMemoryFlags {
+    /// Returns true if the memory can carry data to the trusted application.
+    pub fn is_input(&self) -> bool {
+        (*self as u32) & raw::TEEC_MEM_INPUT != 0
+    }
+
+    /// Returns true if the memory can carry data from the trusted application.
+    pub fn is_output(&self) -> bool {
+        (*self as u32) & raw::TEEC_MEM_OUTPUT != 0
+    }
+}
+
+/// A block of memory shared between the client application and the TEE. It is
+/// either allocated by the TEE client library with
+/// [`Context::allocate_shared_memory`] or registered from a buffer of the
+/// client application with [`Context::register_shared_memory`].
+///
+/// Unlike a [`ParamTmpRef`](crate::ParamTmpRef), the memory stays registered
+/// until the `SharedMemory` is dropped, so it can be referenced by many
+/// operations (and by several sessions of the same context) without being
+/// copied or mapped again. Use [`ParamMemRefWhole`](crate::ParamMemRefWhole)
+/// or [`ParamMemRefPartial`](crate::ParamMemRefPartial) to pass it to the
+/// trusted application.
+///
+/// The lifetime `'a` is the lifetime of the registered buffer, allocated memory
+/// is owned by the TEE client library and is not bound to any borrow.
+pub struct SharedMemory<'a> {
+    // Boxed so that the address handed to the TEE client library and used as
+    // the parent of memory references stays stable when the value is moved.
+    raw: Box<raw::TEEC_SharedMemory>,
+
+    // Just a holder to ensure InnerContext is not finalized before the memory
+    // is released, never use it.
+    _ctx: Rc<RefCell<InnerContext>>,
+    _marker: marker::PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> SharedMemory<'a> {
+    fn new_raw(buffer: *mut u8, size: usize, flags: SharedMemoryFlags) -> raw::TEEC_SharedMemory {
+        // SAFETY:
+        // TEEC_SharedMemory is a C struct, which zero value is valid.
+        let mut raw_shm: raw::TEEC_SharedMemory = unsafe { mem::zeroed() };
+        raw_shm.buffer = buffer as _;
+        raw_shm.size = size;
+        raw_shm.flags = flags as u32;
+        raw_shm
+    }
+
+    pub(crate) fn allocate(
+        context: &mut Context,
+        size: usize,
+        flags: SharedMemoryFlags,
+    ) -> Result<SharedMemory<'static>> {
+        let mut raw_shm = Box::new(Self::new_raw(std::ptr::null_mut(), size, flags));
+        let inner_ctx = context.inner_context();
+        let raw_ctx = &mut inner_ctx.borrow_mut().0;
+        match unsafe { raw::TEEC_AllocateSharedMemory(raw_ctx, raw_shm.as_mut()) } {
+            raw::TEEC_SUCCESS => Ok(SharedMemory {
+                raw: raw_shm,
+                _ctx: context.inner_context(),
+                _marker: marker::PhantomData,
+            }),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+
+    pub(crate) fn register(
+        context: &mut Context,
+        buffer: &'a mut [u8],
+        flags: SharedMemoryFlags,
+    ) -> Result<Self> {
+        let mut raw_shm = Box::new(Self::new_raw(buffer.as_mut_ptr(), buffer.len(), flags));
+        let inner_ctx = context.inner_context();
+        let raw_ctx = &mut inner_ctx.borrow_mut().0;
+        match unsafe { raw::TEEC_RegisterSharedMemory(raw_ctx, raw_shm.as_mut()) } {
+            raw::TEEC_SUCCESS => Ok(SharedMemory {
+                raw: raw_shm,
+                _ctx: context.inner_context(),
+                _marker: marker::PhantomData,
+            }),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+
+    /// Returns the size of the shared memory in bytes.
+    pub fn size(&self) -> usize {
+        self.raw.size
+    }
+
+    /// Returns the direction(s) the shared memory was created for.
+    pub fn flags(&self) -> SharedMemoryFlags {
+        match self.raw.flags & (raw::TEEC_MEM_INPUT | raw::TEEC_MEM_OUTPUT) {
+            raw::TEEC_MEM_INPUT => SharedMemoryFlags::Input,
+            raw::TEEC_MEM_OUTPUT => SharedMemoryFlags::Output,
+            _ => SharedMemoryFlags::Inout,
+        }
+    }
+
+    /// Returns the content of the shared memory.
+    pub fn buffer(&self) -> &[u8] {
+        if self.raw.buffer.is_null() {
+            return &[];
+        }
+        // SAFETY:
+        // buffer points to `size` bytes which stay valid until the memory is
+        // released in drop.
+        unsafe { slice::from_raw_parts(self.raw.buffer as *const u8, self.raw.size) }
+    }
+
+    /// Returns the content of the shared memory as a mutable slice.
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        if self.raw.buffer.is_null() {
+            return &mut [];
+        }
+        // SAFETY:
+        // buffer points to `size` bytes which stay valid until the memory is
+        // released in drop, and `&mut self` guarantees exclusive access.
+        unsafe { slice::from_raw_parts_mut(self.raw.buffer as *mut u8, self.raw.size) }
+    }
+
+    pub(crate) fn as_mut_raw_ptr(&mut self) -> *mut raw::TEEC_SharedMemory {
+        self.raw.as_mut()
+    }
+}
+
+impl<'a> Drop for SharedMemory<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            raw::TEEC_ReleaseSharedMemory(self.raw.as_mut());
+        }
+    }
+}
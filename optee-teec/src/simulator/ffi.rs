@@ -0,0 +1,439 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Rust implementations of the functions of the TEE client library, used in
+// place of libteec when the `simulator` feature is enabled. They keep the
+// signatures of optee-teec-sys so the rest of the crate is unchanged.
+
+#![allow(non_snake_case, clippy::missing_safety_doc)]
+
+use super::{lookup_ta, TrustedApp};
+use optee_teec_sys::*;
+use optee_utee::Parameters;
+use optee_utee_sys::{
+    Memref, TEE_Param, Value, TEE_PARAM_TYPE_MEMREF_INOUT, TEE_PARAM_TYPE_MEMREF_INPUT,
+    TEE_PARAM_TYPE_MEMREF_OUTPUT, TEE_PARAM_TYPE_NONE, TEE_PARAM_TYPE_VALUE_INOUT,
+    TEE_PARAM_TYPE_VALUE_INPUT, TEE_PARAM_TYPE_VALUE_OUTPUT,
+};
+use std::{
+    collections::HashMap,
+    os::raw::{c_char, c_void},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::{
+        atomic::{AtomicI32, AtomicU32, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+// Marks the shared memory allocated by the simulator, which is freed on
+// release, while registered memory is owned by the client application.
+const SHM_ALLOCATED: i32 = 1;
+
+static NEXT_CONTEXT_FD: AtomicI32 = AtomicI32::new(1);
+static NEXT_SESSION_ID: AtomicU32 = AtomicU32::new(1);
+
+fn sessions() -> &'static Mutex<HashMap<u32, Arc<TrustedApp>>> {
+    static SESSIONS: OnceLock<Mutex<HashMap<u32, Arc<TrustedApp>>>> = OnceLock::new();
+    SESSIONS.get_or_init(Default::default)
+}
+
+// Where the outputs of the TA are copied once the operation is done.
+enum Output {
+    None,
+    Value,
+    TmpRef { buffer: *mut u8, size: usize },
+    MemRef { buffer: *mut u8, size: usize },
+}
+
+// The parameters of an operation as passed to the TA: the raw parameters point
+// to the copies of the memory references owned by `buffers`.
+struct TaParams {
+    param_types: u32,
+    raw: [TEE_Param; 4],
+    buffers: Vec<Vec<u8>>,
+}
+
+impl TaParams {
+    fn none() -> Self {
+        Self {
+            param_types: TEE_PARAM_TYPE_NONE,
+            raw: [TEE_Param {
+                value: Value { a: 0, b: 0 },
+            }; 4],
+            buffers: Vec::new(),
+        }
+    }
+}
+
+unsafe fn set_origin(return_origin: *mut u32, origin: u32) {
+    if !return_origin.is_null() {
+        *return_origin = origin;
+    }
+}
+
+fn is_valid_shm_flags(flags: u32) -> bool {
+    flags != 0 && flags & !(TEEC_MEM_INPUT | TEEC_MEM_OUTPUT) == 0
+}
+
+// Returns the copy of `size` bytes at `buffer` seen by the TA, which is zeroed
+// for output memory and for a null buffer (used to query the required size).
+unsafe fn copy_to_ta(buffer: *const u8, size: usize, input: bool) -> Vec<u8> {
+    if input && !buffer.is_null() {
+        slice::from_raw_parts(buffer, size).to_vec()
+    } else {
+        vec![0u8; size]
+    }
+}
+
+fn memref_type(input: bool, output: bool) -> u32 {
+    match (input, output) {
+        (true, false) => TEE_PARAM_TYPE_MEMREF_INPUT,
+        (false, true) => TEE_PARAM_TYPE_MEMREF_OUTPUT,
+        _ => TEE_PARAM_TYPE_MEMREF_INOUT,
+    }
+}
+
+// Prepares the parameter `index` of `params` from the parameter of the client,
+// returns where its outputs go.
+unsafe fn prepare_param(
+    params: &mut TaParams,
+    index: usize,
+    raw_type: u32,
+    raw_param: &TEEC_Parameter,
+) -> Option<Output> {
+    let (param_type, output) = match raw_type {
+        TEEC_NONE => (TEE_PARAM_TYPE_NONE, Output::None),
+        TEEC_VALUE_INPUT | TEEC_VALUE_OUTPUT | TEEC_VALUE_INOUT => {
+            let (a, b, param_type) = match raw_type {
+                TEEC_VALUE_INPUT => (
+                    raw_param.value.a,
+                    raw_param.value.b,
+                    TEE_PARAM_TYPE_VALUE_INPUT,
+                ),
+                TEEC_VALUE_OUTPUT => (0, 0, TEE_PARAM_TYPE_VALUE_OUTPUT),
+                _ => (
+                    raw_param.value.a,
+                    raw_param.value.b,
+                    TEE_PARAM_TYPE_VALUE_INOUT,
+                ),
+            };
+            params.raw[index].value = Value { a, b };
+            let output = match param_type {
+                TEE_PARAM_TYPE_VALUE_INPUT => Output::None,
+                _ => Output::Value,
+            };
+            (param_type, output)
+        }
+        TEEC_MEMREF_TEMP_INPUT | TEEC_MEMREF_TEMP_OUTPUT | TEEC_MEMREF_TEMP_INOUT => {
+            let buffer = raw_param.tmpref.buffer as *mut u8;
+            let size = raw_param.tmpref.size;
+            let input = raw_type != TEEC_MEMREF_TEMP_OUTPUT;
+            let output = raw_type != TEEC_MEMREF_TEMP_INPUT;
+            set_memref(params, index, copy_to_ta(buffer, size, input));
+            match output {
+                true => (memref_type(input, output), Output::TmpRef { buffer, size }),
+                false => (memref_type(input, output), Output::None),
+            }
+        }
+        TEEC_MEMREF_WHOLE
+        | TEEC_MEMREF_PARTIAL_INPUT
+        | TEEC_MEMREF_PARTIAL_OUTPUT
+        | TEEC_MEMREF_PARTIAL_INOUT => {
+            let memref = raw_param.memref;
+            if memref.parent.is_null() {
+                return None;
+            }
+            let parent = &*memref.parent;
+            let (offset, size, flags) = match raw_type {
+                TEEC_MEMREF_WHOLE => (0, parent.size, parent.flags),
+                TEEC_MEMREF_PARTIAL_INPUT => (memref.offset, memref.size, TEEC_MEM_INPUT),
+                TEEC_MEMREF_PARTIAL_OUTPUT => (memref.offset, memref.size, TEEC_MEM_OUTPUT),
+                _ => (memref.offset, memref.size, TEEC_MEM_INPUT | TEEC_MEM_OUTPUT),
+            };
+            let in_bounds = offset
+                .checked_add(size)
+                .map_or(false, |end| end <= parent.size);
+            if !in_bounds || parent.flags & flags != flags || parent.buffer.is_null() {
+                return None;
+            }
+            let buffer = (parent.buffer as *mut u8).add(offset);
+            let input = flags & TEEC_MEM_INPUT != 0;
+            let output = flags & TEEC_MEM_OUTPUT != 0;
+            set_memref(params, index, copy_to_ta(buffer, size, input));
+            match output {
+                true => (memref_type(input, output), Output::MemRef { buffer, size }),
+                false => (memref_type(input, output), Output::None),
+            }
+        }
+        _ => return None,
+    };
+    params.param_types |= param_type << (4 * index);
+    Some(output)
+}
+
+fn set_memref(params: &mut TaParams, index: usize, mut data: Vec<u8>) {
+    params.raw[index].memref = Memref {
+        buffer: data.as_mut_ptr() as *mut c_void,
+        size: data.len(),
+    };
+    params.buffers.push(data);
+}
+
+// Builds the parameters seen by the TA from the operation of the client.
+unsafe fn prepare(operation: &TEEC_Operation) -> Option<(TaParams, Vec<Output>)> {
+    let mut params = TaParams::none();
+    let mut outputs = Vec::with_capacity(4);
+    for (index, raw_param) in operation.params.iter().enumerate() {
+        let raw_type = (operation.paramTypes >> (4 * index)) & 0xf;
+        outputs.push(prepare_param(&mut params, index, raw_type, raw_param)?);
+    }
+    Some((params, outputs))
+}
+
+// Copies the outputs of the TA back to the operation of the client, whatever
+// the result of the operation, as libteec does. The reported size of memory
+// references is the one set by the TA, but at most the original size is
+// copied.
+unsafe fn finish(operation: &mut TEEC_Operation, params: &TaParams, outputs: Vec<Output>) {
+    for ((raw_param, param), output) in operation.params.iter_mut().zip(&params.raw).zip(outputs) {
+        match output {
+            Output::Value => {
+                raw_param.value = TEEC_Value {
+                    a: param.value.a,
+                    b: param.value.b,
+                }
+            }
+            Output::TmpRef { buffer, size } => {
+                copy_from_ta(buffer, size, &param.memref);
+                raw_param.tmpref.size = param.memref.size;
+            }
+            Output::MemRef { buffer, size } => {
+                copy_from_ta(buffer, size, &param.memref);
+                raw_param.memref.size = param.memref.size;
+            }
+            Output::None => {}
+        }
+    }
+}
+
+// Calls a handler of the TA with the parameters of `operation`, which may be
+// null, and copies its outputs back. The handler is locked for the call, a
+// poisoned lock means the TA panicked before and stays dead.
+unsafe fn call_ta<H: ?Sized>(
+    handler: &Mutex<Box<H>>,
+    operation: *mut TEEC_Operation,
+    return_origin: *mut u32,
+    call: impl FnOnce(&mut H, &mut Parameters) -> optee_utee::Result<()>,
+) -> TEEC_Result {
+    let (mut params, outputs) = if operation.is_null() {
+        (TaParams::none(), Vec::new())
+    } else {
+        match prepare(&*operation) {
+            Some(prepared) => prepared,
+            None => return TEEC_ERROR_BAD_PARAMETERS,
+        }
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut handler = handler.lock().map_err(|_| ())?;
+        let mut parameters = Parameters::from_raw(&mut params.raw, params.param_types);
+        Ok(call(&mut handler, &mut parameters))
+    }));
+
+    if !operation.is_null() {
+        finish(&mut *operation, &params, outputs);
+    }
+    match result {
+        Ok(Ok(Ok(()))) => {
+            set_origin(return_origin, TEEC_ORIGIN_TRUSTED_APP);
+            TEEC_SUCCESS
+        }
+        Ok(Ok(Err(err))) => {
+            set_origin(return_origin, TEEC_ORIGIN_TRUSTED_APP);
+            err.raw_code()
+        }
+        Ok(Err(())) | Err(_) => {
+            set_origin(return_origin, TEEC_ORIGIN_TEE);
+            TEEC_ERROR_TARGET_DEAD
+        }
+    }
+}
+
+// Copies the output of the TA, at most the copy it got, the size it set may be
+// larger.
+unsafe fn copy_from_ta(buffer: *mut u8, size: usize, memref: &Memref) {
+    if !buffer.is_null() {
+        let len = memref.size.min(size);
+        ptr::copy_nonoverlapping(memref.buffer as *const u8, buffer, len);
+    }
+}
+
+pub unsafe fn TEEC_InitializeContext(
+    _name: *const c_char,
+    context: *mut TEEC_Context,
+) -> TEEC_Result {
+    if context.is_null() {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    (*context).imp.fd = NEXT_CONTEXT_FD.fetch_add(1, Ordering::Relaxed);
+    (*context).imp.reg_mem = true;
+    (*context).imp.memref_null = true;
+    TEEC_SUCCESS
+}
+
+pub unsafe fn TEEC_FinalizeContext(_context: *mut TEEC_Context) {}
+
+pub unsafe fn TEEC_OpenSession(
+    context: *mut TEEC_Context,
+    session: *mut TEEC_Session,
+    destination: *const TEEC_UUID,
+    _connectionMethod: u32,
+    _connectionData: *const c_void,
+    operation: *mut TEEC_Operation,
+    returnOrigin: *mut u32,
+) -> TEEC_Result {
+    set_origin(returnOrigin, TEEC_ORIGIN_API);
+    if context.is_null() || session.is_null() || destination.is_null() {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    if !operation.is_null() && prepare(&*operation).is_none() {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    let ta = match lookup_ta(&*destination) {
+        Some(ta) => ta,
+        None => {
+            set_origin(returnOrigin, TEEC_ORIGIN_TEE);
+            return TEEC_ERROR_ITEM_NOT_FOUND;
+        }
+    };
+    if !operation.is_null() {
+        (*operation).imp.session = session;
+    }
+    let res = call_ta(
+        &ta.open_session,
+        operation,
+        returnOrigin,
+        |open_session, params| open_session(params),
+    );
+    if res != TEEC_SUCCESS {
+        return res;
+    }
+    let session_id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    sessions()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(session_id, ta);
+    (*session).imp.ctx = context;
+    (*session).imp.session_id = session_id;
+    set_origin(returnOrigin, TEEC_ORIGIN_TRUSTED_APP);
+    TEEC_SUCCESS
+}
+
+pub unsafe fn TEEC_CloseSession(session: *mut TEEC_Session) {
+    if session.is_null() {
+        return;
+    }
+    sessions()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .remove(&(*session).imp.session_id);
+}
+
+pub unsafe fn TEEC_InvokeCommand(
+    session: *mut TEEC_Session,
+    commandID: u32,
+    operation: *mut TEEC_Operation,
+    returnOrigin: *mut u32,
+) -> TEEC_Result {
+    set_origin(returnOrigin, TEEC_ORIGIN_API);
+    if session.is_null() {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    let ta = match sessions()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(&(*session).imp.session_id)
+    {
+        Some(ta) => ta.clone(),
+        None => return TEEC_ERROR_BAD_STATE,
+    };
+    if !operation.is_null() {
+        (*operation).imp.session = session;
+    }
+    call_ta(&ta.handler, operation, returnOrigin, |handler, params| {
+        handler(commandID, params)
+    })
+}
+
+pub unsafe fn TEEC_RegisterSharedMemory(
+    context: *mut TEEC_Context,
+    sharedMem: *mut TEEC_SharedMemory,
+) -> TEEC_Result {
+    if context.is_null() || sharedMem.is_null() {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    let shm = &mut *sharedMem;
+    if !is_valid_shm_flags(shm.flags) || (shm.buffer.is_null() && shm.size != 0) {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    shm.imp.id = 0;
+    shm.imp.alloced_size = 0;
+    shm.imp.flags = shm.flags;
+    TEEC_SUCCESS
+}
+
+pub unsafe fn TEEC_AllocateSharedMemory(
+    context: *mut TEEC_Context,
+    sharedMem: *mut TEEC_SharedMemory,
+) -> TEEC_Result {
+    if context.is_null() || sharedMem.is_null() {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    let shm = &mut *sharedMem;
+    if !is_valid_shm_flags(shm.flags) {
+        return TEEC_ERROR_BAD_PARAMETERS;
+    }
+    let buffer = vec![0u8; shm.size].into_boxed_slice();
+    shm.buffer = Box::into_raw(buffer) as *mut c_void;
+    shm.imp.id = SHM_ALLOCATED;
+    shm.imp.alloced_size = shm.size;
+    shm.imp.flags = shm.flags;
+    TEEC_SUCCESS
+}
+
+pub unsafe fn TEEC_ReleaseSharedMemory(sharedMemory: *mut TEEC_SharedMemory) {
+    if sharedMemory.is_null() {
+        return;
+    }
+    let shm = &mut *sharedMemory;
+    if shm.imp.id == SHM_ALLOCATED && !shm.buffer.is_null() {
+        let buffer = ptr::slice_from_raw_parts_mut(shm.buffer as *mut u8, shm.imp.alloced_size);
+        drop(Box::from_raw(buffer));
+        shm.buffer = ptr::null_mut();
+        shm.size = 0;
+    }
+    shm.imp.id = 0;
+}
+
+pub unsafe fn TEEC_RequestCancellation(operation: *mut TEEC_Operation) {
+    // Reads the session of the operation like libteec does, the request itself
+    // has no effect.
+    if !operation.is_null() {
+        let _session = ptr::read_volatile(ptr::addr_of!((*operation).imp.session));
+    }
+}
@@ -0,0 +1,333 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! An in-process simulator of the TEE, enabled by the `simulator` feature.
+//!
+//! With the feature enabled, the TEE client library is not linked and the
+//! functions of [`raw`](crate::raw) are implemented in Rust: [`Context`],
+//! [`Session`] and [`SharedMemory`] work as usual, but the operations are
+//! dispatched to trusted applications registered in the current process with
+//! [`register_ta`]. This allows host applications to be tested with plain
+//! `cargo test`.
+//!
+//! A handler has the signature of a `#[ta_invoke_command]` function: it takes
+//! the `optee_utee::Parameters` of the operation and returns an `optee_utee`
+//! result, so the command handler of a TA can be registered as is. The TA runs
+//! in the current process, if it calls the TEE Internal Core API the
+//! `optee-utee-emulator` crate must be linked in, see also the `ta_harness`
+//! crate to run all the entry points of a TA. An open session handler, with
+//! the signature of a `#[ta_open_session]` function, can be registered with
+//! [`register_ta_with_open_session`], it gets the parameters of the operation
+//! passed to `Context::open_session_with_operation`. The parameters follow the
+//! semantics of OP-TEE:
+//!
+//! * values are copied to the TA, and back for output values;
+//! * the TA gets a copy of memory references, the data is copied back to the
+//!   client for output memory references only, and the size set by the TA is
+//!   reported to the client (including the required size on `ShortBuffer`);
+//! * errors returned by the handler have the `TA` origin, a handler which
+//!   panics makes the operation fail with `TargetDead`, and errors detected
+//!   before calling the TA have the `API` or `TEE` origin.
+//!
+//! Cancellation requests are accepted but have no effect: handlers have no way
+//! to poll for them, and the operation runs to completion.
+//!
+//! # Examples
+//!
+//! ```
+//! use optee_teec::{simulator, Context, Operation, ParamNone, ParamType, ParamValue, Uuid};
+//! use optee_utee::{ta_invoke_command, Parameters, Result};
+//! # use std::ffi::c_void;
+//!
+//! #[ta_invoke_command]
+//! fn invoke_command(cmd_id: u32, params: &mut Parameters) -> Result<()> {
+//!     let mut value = unsafe { params.0.as_value()? };
+//!     value.set_a(value.a() + cmd_id);
+//!     Ok(())
+//! }
+//!
+//! fn main() -> optee_teec::Result<()> {
+//!     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51b")?;
+//!     simulator::register_ta(&uuid, invoke_command);
+//!
+//!     let mut ctx = Context::new()?;
+//!     let mut session = ctx.open_session(uuid)?;
+//!     let p0 = ParamValue::new(29, 0, ParamType::ValueInout);
+//!     let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+//!     session.invoke_command(13, &mut operation)?;
+//!     assert_eq!(operation.parameters().0.a(), 42);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! [`Context`]: crate::Context
+//! [`Session`]: crate::Session
+//! [`SharedMemory`]: crate::SharedMemory
+
+use crate::{raw, Uuid};
+use optee_utee::{Parameters, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+pub(crate) mod ffi;
+
+type Handler = dyn FnMut(u32, &mut Parameters) -> Result<()> + Send;
+type OpenSessionHandler = dyn FnMut(&mut Parameters) -> Result<()> + Send;
+type UuidKey = (u32, u16, u16, [u8; 8]);
+
+pub(crate) struct TrustedApp {
+    pub(crate) open_session: Mutex<Box<OpenSessionHandler>>,
+    pub(crate) handler: Mutex<Box<Handler>>,
+}
+
+fn registry() -> &'static Mutex<HashMap<UuidKey, Arc<TrustedApp>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<UuidKey, Arc<TrustedApp>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+fn key(raw_uuid: &raw::TEEC_UUID) -> UuidKey {
+    (
+        raw_uuid.timeLow,
+        raw_uuid.timeMid,
+        raw_uuid.timeHiAndVersion,
+        raw_uuid.clockSeqAndNode,
+    )
+}
+
+/// Registers `handler` as the command handler of the trusted application
+/// `uuid`, replacing any previous one. Sessions already opened keep using the
+/// previous handler. Opening a session always succeeds, see
+/// [`register_ta_with_open_session`].
+pub fn register_ta<F>(uuid: &Uuid, handler: F)
+where
+    F: FnMut(u32, &mut Parameters) -> Result<()> + Send + 'static,
+{
+    register_ta_with_open_session(uuid, |_| Ok(()), handler);
+}
+
+/// Registers the trusted application `uuid` like [`register_ta`], with
+/// `open_session` called when a session is opened. The outputs of the open
+/// session operation are copied back to the client, and an error returned by
+/// `open_session` makes opening the session fail with the `TA` origin.
+///
+/// # Examples
+///
+/// ```
+/// use optee_teec::{simulator, Context, Operation, ParamNone, ParamType, ParamValue, Uuid};
+/// use optee_utee::{ErrorKind, Parameters};
+///
+/// fn main() -> optee_teec::Result<()> {
+///     let uuid = Uuid::parse_str("8abcf200-2450-11e4-abe2-0002a5d5c51c")?;
+///     simulator::register_ta_with_open_session(
+///         &uuid,
+///         |params: &mut Parameters| {
+///             let mut value = unsafe { params.0.as_value()? };
+///             value.set_b(value.a() * 2);
+///             Ok(())
+///         },
+///         |_, _| Err(ErrorKind::NotSupported.into()),
+///     );
+///
+///     let mut ctx = Context::new()?;
+///     let p0 = ParamValue::new(21, 0, ParamType::ValueInout);
+///     let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+///     let _session = ctx.open_session_with_operation(uuid, &mut operation)?;
+///     assert_eq!(operation.parameters().0.b(), 42);
+///     Ok(())
+/// }
+/// ```
+pub fn register_ta_with_open_session<O, F>(uuid: &Uuid, open_session: O, handler: F)
+where
+    O: FnMut(&mut Parameters) -> Result<()> + Send + 'static,
+    F: FnMut(u32, &mut Parameters) -> Result<()> + Send + 'static,
+{
+    let ta = Arc::new(TrustedApp {
+        open_session: Mutex::new(Box::new(open_session)),
+        handler: Mutex::new(Box::new(handler)),
+    });
+    let key = key(unsafe { &*uuid.as_raw_ptr() });
+    registry()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(key, ta);
+}
+
+/// Unregisters the trusted application `uuid`, returns false if it was not
+/// registered.
+pub fn unregister_ta(uuid: &Uuid) -> bool {
+    let key = key(unsafe { &*uuid.as_raw_ptr() });
+    registry()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .remove(&key)
+        .is_some()
+}
+
+pub(crate) fn lookup_ta(raw_uuid: &raw::TEEC_UUID) -> Option<Arc<TrustedApp>> {
+    registry()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(&key(raw_uuid))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Context, ErrorKind, ErrorOrigin, Operation, ParamMemRefWhole, ParamNone, ParamTmpRef,
+        ParamType as HostParamType, ParamValue as HostParamValue, SharedMemoryFlags,
+    };
+    use optee_utee::ErrorKind as TaErrorKind;
+
+    const CMD_ECHO: u32 = 0;
+    const CMD_PANIC: u32 = 1;
+
+    // Each test uses its own TA as tests run concurrently.
+    fn echo_ta(uuid: &str) -> Uuid {
+        let uuid = Uuid::parse_str(uuid).unwrap();
+        register_ta(&uuid, |cmd_id, params| match cmd_id {
+            CMD_ECHO => {
+                let input = unsafe { params.0.as_memref()? }.buffer().to_vec();
+                let mut output = unsafe { params.1.as_memref()? };
+                if output.buffer().len() < input.len() {
+                    output.set_updated_size(input.len());
+                    return Err(TaErrorKind::ShortBuffer.into());
+                }
+                output.buffer()[..input.len()].copy_from_slice(&input);
+                output.set_updated_size(input.len());
+                Ok(())
+            }
+            CMD_PANIC => panic!("the TA panicked"),
+            _ => Err(TaErrorKind::NotSupported.into()),
+        });
+        uuid
+    }
+
+    #[test]
+    fn test_memref_output_and_short_buffer() {
+        let uuid = echo_ta("f0e1d2c3-0000-4000-8000-000000000001");
+        let mut ctx = Context::new().unwrap();
+        let mut session = ctx.open_session(uuid).unwrap();
+
+        let input = *b"hello";
+        let mut output = [0u8; 2];
+        let p0 = ParamTmpRef::new_input(&input);
+        let p1 = ParamTmpRef::new_output(&mut output);
+        let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+        let err = session
+            .invoke_command(CMD_ECHO, &mut operation)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ShortBuffer);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+        assert_eq!(operation.parameters().1.updated_size(), 5);
+
+        let mut output = [0u8; 8];
+        let p0 = ParamTmpRef::new_input(&input);
+        let p1 = ParamTmpRef::new_output(&mut output);
+        let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+        session.invoke_command(CMD_ECHO, &mut operation).unwrap();
+        assert_eq!(operation.parameters().1.updated_size(), 5);
+        assert_eq!(&output[..5], b"hello");
+    }
+
+    #[test]
+    fn test_shared_memory() {
+        let uuid = echo_ta("f0e1d2c3-0000-4000-8000-000000000002");
+        let mut ctx = Context::new().unwrap();
+        let mut session = ctx.open_session(uuid).unwrap();
+
+        let mut input = ctx
+            .allocate_shared_memory(3, SharedMemoryFlags::Input)
+            .unwrap();
+        input.buffer_mut().copy_from_slice(b"abc");
+        let mut buffer = [0u8; 4];
+        let mut output = ctx
+            .register_shared_memory(&mut buffer, SharedMemoryFlags::Output)
+            .unwrap();
+        let p0 = ParamMemRefWhole::new(&mut input);
+        let p1 = ParamMemRefWhole::new(&mut output);
+        let mut operation = Operation::new(0, p0, p1, ParamNone, ParamNone);
+        session.invoke_command(CMD_ECHO, &mut operation).unwrap();
+        assert_eq!(operation.parameters().1.updated_size(), 3);
+        drop(operation);
+        assert_eq!(&output.buffer()[..3], b"abc");
+    }
+
+    #[test]
+    fn test_errors_origin() {
+        let uuid = echo_ta("f0e1d2c3-0000-4000-8000-000000000003");
+        let mut ctx = Context::new().unwrap();
+
+        let unknown = Uuid::parse_str("f0e1d2c3-0000-4000-8000-0000000000ff").unwrap();
+        let err = ctx.open_session(unknown).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TEE));
+
+        let mut session = ctx.open_session(uuid.clone()).unwrap();
+        let p0 = HostParamValue::new(0, 0, HostParamType::ValueInput);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        let err = session
+            .invoke_command(CMD_ECHO, &mut operation)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+
+        let err = session
+            .invoke_command(CMD_PANIC, &mut operation)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TargetDead);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TEE));
+        assert!(unregister_ta(&uuid));
+    }
+
+    #[test]
+    fn test_open_session() {
+        let uuid = Uuid::parse_str("f0e1d2c3-0000-4000-8000-000000000004").unwrap();
+        register_ta_with_open_session(
+            &uuid,
+            |params| {
+                let mut value = unsafe { params.0.as_value()? };
+                value.set_b(value.a() + 1);
+                match value.a() {
+                    0 => Err(TaErrorKind::AccessDenied.into()),
+                    _ => Ok(()),
+                }
+            },
+            |_, _| Ok(()),
+        );
+        let mut ctx = Context::new().unwrap();
+
+        let p0 = HostParamValue::new(0, 0, HostParamType::ValueInout);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        let err = ctx
+            .open_session_with_operation(uuid.clone(), &mut operation)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::AccessDenied);
+        assert_eq!(err.origin(), Some(ErrorOrigin::TA));
+        assert_eq!(operation.parameters().0.b(), 1);
+
+        let p0 = HostParamValue::new(41, 0, HostParamType::ValueInout);
+        let mut operation = Operation::new(0, p0, ParamNone, ParamNone, ParamNone);
+        ctx.open_session_with_operation(uuid, &mut operation)
+            .unwrap();
+        assert_eq!(operation.parameters().0.b(), 42);
+    }
+}
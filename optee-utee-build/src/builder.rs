@@ -26,6 +26,7 @@ use crate::TaConfig;
 use crate::{Linker, LinkerType};
 
 const DEFAULT_HEADER_FILE_NAME: &str = "user_ta_header.rs";
+const ENV_NO_LINK: &str = "OPTEE_UTEE_BUILD_NO_LINK";
 
 /// The Builder of TA, use it to handle file generation and linking stuff
 ///
@@ -67,6 +68,10 @@ const DEFAULT_HEADER_FILE_NAME: &str = "user_ta_header.rs";
 /// # Ok(())
 /// # }
 /// ```
+///
+/// When the TA is built for its tests on the host (e.g. with ta_harness), set
+/// the `OPTEE_UTEE_BUILD_NO_LINK` environment variable: the header is still
+/// generated, but the TA is not linked against libutee.
 pub struct Builder {
     out_dir: Option<PathBuf>,
     edition: RustEdition,
@@ -103,8 +108,17 @@ impl Builder {
             None => PathBuf::from(std::env::var("OUT_DIR")?),
         };
         self.write_header_file(out_dir.clone())?;
-        self.link(out_dir)?;
-        Ok(())
+        // The target does not tell a TA from a host build of it, e.g. for the
+        // tests on an Arm host, so the link is skipped on request only.
+        println!("cargo:rerun-if-env-changed={}", ENV_NO_LINK);
+        if std::env::var_os(ENV_NO_LINK).is_some() {
+            println!(
+                "cargo:warning=not linking the TA against libutee, {} is set",
+                ENV_NO_LINK
+            );
+            return Ok(());
+        }
+        self.link(out_dir)
     }
 }
 
@@ -0,0 +1,1549 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Operation handles, and the digest, symmetric cipher, MAC and authenticated
+// encryption functions.
+
+use super::object::{self, KeyObject};
+use super::symmetric::{Ae, BlockCipher, ChainMode, Cipher, Mac};
+use super::{asymmetric, input, output, tee_panic};
+use crate::raw::{self, TEE_ObjectHandle, TEE_OperationHandle, TEE_Result};
+use std::ffi::c_void;
+use std::mem;
+
+pub(super) type Digest = Box<dyn digest::DynDigest>;
+
+// The ECC algorithms taking the curve from the key, as `AlgorithmId` exposes
+// them, they have no constant in optee-utee-sys.
+const TEE_ALG_ECDSA_SHA256: u32 = 0x70003042;
+const TEE_ALG_ECDSA_SHA384: u32 = 0x70004042;
+const TEE_ALG_ECDH_DERIVE_SHARED_SECRET: u32 = 0x80000042;
+
+// Returns the digest of a hash identifier, the lowest bits of the digest and
+// HMAC algorithm identifiers.
+pub(super) fn new_digest(hash_id: u32) -> Option<Digest> {
+    let digest: Digest = match hash_id {
+        1 => Box::new(md5::Md5::default()),
+        2 => Box::new(sha1::Sha1::default()),
+        3 => Box::new(sha2::Sha224::default()),
+        4 => Box::new(sha2::Sha256::default()),
+        5 => Box::new(sha2::Sha384::default()),
+        6 => Box::new(sha2::Sha512::default()),
+        7 => Box::new(sm3::Sm3::default()),
+        _ => return None,
+    };
+    Some(digest)
+}
+
+fn is_supported(algorithm: u32) -> bool {
+    matches!(
+        algorithm,
+        raw::TEE_ALG_AES_ECB_NOPAD
+            | raw::TEE_ALG_AES_CBC_NOPAD
+            | raw::TEE_ALG_AES_CTR
+            | raw::TEE_ALG_AES_CBC_MAC_NOPAD
+            | raw::TEE_ALG_AES_CBC_MAC_PKCS5
+            | raw::TEE_ALG_AES_CMAC
+            | raw::TEE_ALG_AES_CCM
+            | raw::TEE_ALG_AES_GCM
+            | raw::TEE_ALG_DES_ECB_NOPAD
+            | raw::TEE_ALG_DES_CBC_NOPAD
+            | raw::TEE_ALG_DES_CBC_MAC_NOPAD
+            | raw::TEE_ALG_DES_CBC_MAC_PKCS5
+            | raw::TEE_ALG_DES3_ECB_NOPAD
+            | raw::TEE_ALG_DES3_CBC_NOPAD
+            | raw::TEE_ALG_DES3_CBC_MAC_NOPAD
+            | raw::TEE_ALG_DES3_CBC_MAC_PKCS5
+            | raw::TEE_ALG_SM4_ECB_NOPAD
+            | raw::TEE_ALG_SM4_CBC_NOPAD
+            | raw::TEE_ALG_SM4_CTR
+            | raw::TEE_ALG_MD5
+            | raw::TEE_ALG_SHA1
+            | raw::TEE_ALG_SHA224
+            | raw::TEE_ALG_SHA256
+            | raw::TEE_ALG_SHA384
+            | raw::TEE_ALG_SHA512
+            | raw::TEE_ALG_SM3
+            | raw::TEE_ALG_HMAC_MD5
+            | raw::TEE_ALG_HMAC_SHA1
+            | raw::TEE_ALG_HMAC_SHA224
+            | raw::TEE_ALG_HMAC_SHA256
+            | raw::TEE_ALG_HMAC_SHA384
+            | raw::TEE_ALG_HMAC_SHA512
+            | raw::TEE_ALG_HMAC_SM3
+            | raw::TEE_ALG_RSASSA_PKCS1_V1_5_MD5
+            | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA1
+            | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA224
+            | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA256
+            | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA384
+            | raw::TEE_ALG_RSASSA_PKCS1_V1_5_SHA512
+            | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA1
+            | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA224
+            | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA256
+            | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA384
+            | raw::TEE_ALG_RSASSA_PKCS1_PSS_MGF1_SHA512
+            | raw::TEE_ALG_RSAES_PKCS1_V1_5
+            | raw::TEE_ALG_RSAES_PKCS1_OAEP_MGF1_SHA1
+            | raw::TEE_ALG_RSAES_PKCS1_OAEP_MGF1_SHA224
+            | raw::TEE_ALG_RSAES_PKCS1_OAEP_MGF1_SHA256
+            | raw::TEE_ALG_RSAES_PKCS1_OAEP_MGF1_SHA384
+            | raw::TEE_ALG_RSAES_PKCS1_OAEP_MGF1_SHA512
+            | raw::TEE_ALG_RSA_NOPAD
+            | raw::TEE_ALG_ECDSA_P256
+            | raw::TEE_ALG_ECDSA_P384
+            | raw::TEE_ALG_ECDH_P256
+            | raw::TEE_ALG_ECDH_P384
+            | TEE_ALG_ECDSA_SHA256
+            | TEE_ALG_ECDSA_SHA384
+            | TEE_ALG_ECDH_DERIVE_SHARED_SECRET
+            | asymmetric::TEE_ALG_ED25519
+            | raw::TEE_ALG_SM2_DSA_SM3
+            | raw::TEE_ALG_SM2_PKE
+            | raw::TEE_ALG_SM2_KEP
+            | raw::TEE_ALG_HKDF_MD5_DERIVE_KEY
+            | raw::TEE_ALG_HKDF_SHA1_DERIVE_KEY
+            | raw::TEE_ALG_HKDF_SHA224_DERIVE_KEY
+            | raw::TEE_ALG_HKDF_SHA256_DERIVE_KEY
+            | raw::TEE_ALG_HKDF_SHA384_DERIVE_KEY
+            | raw::TEE_ALG_HKDF_SHA512_DERIVE_KEY
+            | raw::TEE_ALG_CONCAT_KDF_SHA1_DERIVE_KEY
+            | raw::TEE_ALG_CONCAT_KDF_SHA224_DERIVE_KEY
+            | raw::TEE_ALG_CONCAT_KDF_SHA256_DERIVE_KEY
+            | raw::TEE_ALG_CONCAT_KDF_SHA384_DERIVE_KEY
+            | raw::TEE_ALG_CONCAT_KDF_SHA512_DERIVE_KEY
+            | raw::TEE_ALG_PBKDF2_HMAC_SHA1_DERIVE_KEY
+    )
+}
+
+// Returns the operation class of `algorithm`, its first four bits except for
+// the SM2 encryption and key exchange, as in OP-TEE.
+fn operation_class(algorithm: u32) -> u32 {
+    match algorithm {
+        raw::TEE_ALG_SM2_PKE => raw::TEE_OPERATION_ASYMMETRIC_CIPHER,
+        raw::TEE_ALG_SM2_KEP => raw::TEE_OPERATION_KEY_DERIVATION,
+        _ => algorithm >> 28,
+    }
+}
+
+fn is_hmac(algorithm: u32) -> bool {
+    (algorithm >> 28) == raw::TEE_OPERATION_MAC && (algorithm >> 8) & 0xF == 0
+}
+
+fn is_valid_mode(class: u32, mode: u32) -> bool {
+    match class {
+        raw::TEE_OPERATION_CIPHER
+        | raw::TEE_OPERATION_AE
+        | raw::TEE_OPERATION_ASYMMETRIC_CIPHER => {
+            matches!(mode, raw::TEE_MODE_ENCRYPT | raw::TEE_MODE_DECRYPT)
+        }
+        raw::TEE_OPERATION_MAC => mode == raw::TEE_MODE_MAC,
+        raw::TEE_OPERATION_DIGEST => mode == raw::TEE_MODE_DIGEST,
+        raw::TEE_OPERATION_ASYMMETRIC_SIGNATURE => {
+            matches!(mode, raw::TEE_MODE_SIGN | raw::TEE_MODE_VERIFY)
+        }
+        raw::TEE_OPERATION_KEY_DERIVATION => mode == raw::TEE_MODE_DERIVE,
+        _ => false,
+    }
+}
+
+// Returns the object types of the keys usable by an operation.
+fn key_types(algorithm: u32, mode: u32) -> Vec<u32> {
+    if operation_class(algorithm) == raw::TEE_OPERATION_DIGEST {
+        return Vec::new();
+    }
+    if is_hmac(algorithm) {
+        return vec![
+            raw::TEE_TYPE_HMAC_MD5 - 1 + (algorithm & 0xF),
+            raw::TEE_TYPE_GENERIC_SECRET,
+        ];
+    }
+    let public_usable = matches!(mode, raw::TEE_MODE_ENCRYPT | raw::TEE_MODE_VERIFY);
+    if asymmetric::is_ecdsa(algorithm) {
+        return if public_usable {
+            vec![raw::TEE_TYPE_ECDSA_PUBLIC_KEY, raw::TEE_TYPE_ECDSA_KEYPAIR]
+        } else {
+            vec![raw::TEE_TYPE_ECDSA_KEYPAIR]
+        };
+    }
+    // The SM2 algorithms share their last byte, each has its own key types.
+    let sm2_types = match algorithm {
+        raw::TEE_ALG_SM2_DSA_SM3 => Some((
+            raw::TEE_TYPE_SM2_DSA_PUBLIC_KEY,
+            raw::TEE_TYPE_SM2_DSA_KEYPAIR,
+        )),
+        raw::TEE_ALG_SM2_PKE => Some((
+            raw::TEE_TYPE_SM2_PKE_PUBLIC_KEY,
+            raw::TEE_TYPE_SM2_PKE_KEYPAIR,
+        )),
+        raw::TEE_ALG_SM2_KEP => Some((
+            raw::TEE_TYPE_SM2_KEP_PUBLIC_KEY,
+            raw::TEE_TYPE_SM2_KEP_KEYPAIR,
+        )),
+        _ => None,
+    };
+    if let Some((public, keypair)) = sm2_types {
+        return if public_usable {
+            vec![public, keypair]
+        } else {
+            vec![keypair]
+        };
+    }
+    match algorithm & 0xFF {
+        0x10 => vec![raw::TEE_TYPE_AES],
+        0x11 => vec![raw::TEE_TYPE_DES],
+        0x13 => vec![raw::TEE_TYPE_DES3],
+        0x14 => vec![raw::TEE_TYPE_SM4],
+        0x30 if public_usable => vec![raw::TEE_TYPE_RSA_PUBLIC_KEY, raw::TEE_TYPE_RSA_KEYPAIR],
+        0x30 => vec![raw::TEE_TYPE_RSA_KEYPAIR],
+        0x42 => vec![raw::TEE_TYPE_ECDH_KEYPAIR],
+        0x43 if public_usable => vec![
+            asymmetric::TEE_TYPE_ED25519_PUBLIC_KEY,
+            asymmetric::TEE_TYPE_ED25519_KEYPAIR,
+        ],
+        0x43 => vec![asymmetric::TEE_TYPE_ED25519_KEYPAIR],
+        0xC0 => vec![raw::TEE_TYPE_HKDF_IKM],
+        0xC1 => vec![raw::TEE_TYPE_CONCAT_KDF_Z],
+        0xC2 => vec![raw::TEE_TYPE_PBKDF2_PASSWORD],
+        _ => Vec::new(),
+    }
+}
+
+fn required_usage(mode: u32) -> u32 {
+    match mode {
+        raw::TEE_MODE_ENCRYPT => raw::TEE_USAGE_ENCRYPT,
+        raw::TEE_MODE_DECRYPT => raw::TEE_USAGE_DECRYPT,
+        raw::TEE_MODE_SIGN => raw::TEE_USAGE_SIGN,
+        raw::TEE_MODE_VERIFY => raw::TEE_USAGE_VERIFY,
+        raw::TEE_MODE_MAC => raw::TEE_USAGE_MAC,
+        raw::TEE_MODE_DERIVE => raw::TEE_USAGE_DERIVE,
+        _ => 0,
+    }
+}
+
+#[derive(Clone)]
+enum OpState {
+    None,
+    Digest(Digest),
+    Cipher(Cipher),
+    Mac(Mac),
+    Ae(Ae),
+}
+
+#[derive(Clone)]
+pub(super) struct Operation {
+    pub algorithm: u32,
+    pub mode: u32,
+    pub class: u32,
+    pub max_key_size: u32,
+    pub key: Option<KeyObject>,
+    // The second key of the operations with two keys, see `key_count`.
+    pub key2: Option<KeyObject>,
+    initialized: bool,
+    state: OpState,
+}
+
+impl Operation {
+    fn initial_state(&self) -> OpState {
+        match self.class {
+            raw::TEE_OPERATION_DIGEST => OpState::Digest(new_digest(self.algorithm & 0xF).unwrap()),
+            _ => OpState::None,
+        }
+    }
+
+    fn needs_key(&self) -> bool {
+        self.class != raw::TEE_OPERATION_DIGEST
+    }
+
+    // The SM2 key exchange takes the static and the ephemeral key-pairs of
+    // the TA, set with `TEE_SetOperationKey2`.
+    fn key_count(&self) -> usize {
+        match self.algorithm {
+            _ if !self.needs_key() => 0,
+            raw::TEE_ALG_SM2_KEP => 2,
+            _ => 1,
+        }
+    }
+
+    fn digest_length(&self) -> u32 {
+        match &self.state {
+            OpState::Digest(digest) => digest.output_size() as u32,
+            OpState::Mac(mac) => mac.output_len() as u32,
+            OpState::Ae(ae) => ae.tag_len() as u32,
+            _ if is_hmac(self.algorithm) => {
+                new_digest(self.algorithm & 0xF).map_or(0, |digest| digest.output_size() as u32)
+            }
+            _ if self.class == raw::TEE_OPERATION_MAC => match self.algorithm & 0xFF {
+                0x10 => 16,
+                _ => 8,
+            },
+            _ => 0,
+        }
+    }
+
+    fn handle_state(&self) -> u32 {
+        let mut state = 0;
+        if self.key.is_some() {
+            state |= raw::TEE_HANDLE_FLAG_KEY_SET;
+        }
+        if self.initialized || !self.needs_key() {
+            state |= raw::TEE_HANDLE_FLAG_INITIALIZED;
+        }
+        state
+    }
+
+    fn block_cipher(&self, function: &str) -> BlockCipher {
+        let key = match &self.key {
+            Some(key) => key,
+            None => tee_panic(function, "no key set"),
+        };
+        key.buffer(raw::TEE_ATTR_SECRET_VALUE)
+            .and_then(|secret| BlockCipher::new(key.object_type, secret))
+            .unwrap_or_else(|| tee_panic(function, "invalid key"))
+    }
+}
+
+pub(super) unsafe fn operation_mut<'a>(
+    handle: TEE_OperationHandle,
+    function: &str,
+) -> &'a mut Operation {
+    if handle.is_null() {
+        tee_panic(function, "invalid operation handle");
+    }
+    &mut *(handle as *mut Operation)
+}
+
+// Returns the operation of `handle`, checking that it is of `class`.
+unsafe fn operation_of<'a>(
+    handle: TEE_OperationHandle,
+    class: u32,
+    function: &str,
+) -> &'a mut Operation {
+    let operation = operation_mut(handle, function);
+    if operation.class != class {
+        tee_panic(function, "wrong operation class");
+    }
+    operation
+}
+
+// Checks that an output buffer of `*len` bytes can hold `required` bytes,
+// setting `*len` to the required size when it can not.
+unsafe fn check_output(required: usize, len: *mut usize) -> Result<(), TEE_Result> {
+    if *len < required {
+        *len = required;
+        return Err(raw::TEE_ERROR_SHORT_BUFFER);
+    }
+    Ok(())
+}
+
+fn to_code(result: Result<(), TEE_Result>) -> TEE_Result {
+    match result {
+        Ok(()) => raw::TEE_SUCCESS,
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AllocateOperation(
+    operation: *mut TEE_OperationHandle,
+    algorithm: u32,
+    mode: u32,
+    maxKeySize: u32,
+) -> TEE_Result {
+    let class = operation_class(algorithm);
+    if !is_supported(algorithm) || !is_valid_mode(class, mode) {
+        return raw::TEE_ERROR_NOT_SUPPORTED;
+    }
+    if let Some(key_type) = key_types(algorithm, mode).first() {
+        let valid = match algorithm & 0xFF {
+            // The maximum key size of an ECC operation is its curve size.
+            0x41 | 0x42 => match asymmetric::algorithm_curve(algorithm) {
+                Some(curve) => asymmetric::curve_size(curve) == Some(maxKeySize),
+                None => matches!(maxKeySize, 256 | 384),
+            },
+            _ => object::is_valid_size(*key_type, maxKeySize) == Some(true),
+        };
+        if !valid {
+            return raw::TEE_ERROR_NOT_SUPPORTED;
+        }
+    }
+    let mut new_operation = Operation {
+        algorithm,
+        mode,
+        class,
+        max_key_size: maxKeySize,
+        key: None,
+        key2: None,
+        initialized: false,
+        state: OpState::None,
+    };
+    new_operation.state = new_operation.initial_state();
+    unsafe { *operation = Box::into_raw(Box::new(new_operation)) as TEE_OperationHandle };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_FreeOperation(operation: TEE_OperationHandle) {
+    if !operation.is_null() {
+        drop(unsafe { Box::from_raw(operation as *mut Operation) });
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetOperationInfo(
+    operation: TEE_OperationHandle,
+    operationInfo: *mut raw::TEE_OperationInfo,
+) {
+    let operation = unsafe { operation_mut(operation, "TEE_GetOperationInfo") };
+    let info = raw::TEE_OperationInfo {
+        algorithm: operation.algorithm,
+        operationClass: operation.class,
+        mode: operation.mode,
+        digestLength: operation.digest_length(),
+        maxKeySize: operation.max_key_size,
+        keySize: operation.key.as_ref().map_or(0, |key| key.object_size),
+        requiredKeyUsage: required_usage(operation.mode),
+        handleState: operation.handle_state(),
+    };
+    unsafe { *operationInfo = info };
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetOperationInfoMultiple(
+    operation: TEE_OperationHandle,
+    operationInfoMultiple: *mut raw::TEE_OperationInfoMultiple,
+    operationSize: *mut usize,
+) -> TEE_Result {
+    let operation = unsafe { operation_mut(operation, "TEE_GetOperationInfoMultiple") };
+    let keys = match operation.key_count() {
+        2 => vec![&operation.key, &operation.key2],
+        1 => vec![&operation.key],
+        _ => Vec::new(),
+    };
+    // A header of eight words followed by the key information, as laid out
+    // by the C structure and its flexible array member.
+    let header = 8 * mem::size_of::<u32>();
+    let key_info = mem::size_of::<raw::TEE_OperationInfoKey>();
+    let required = header + keys.len() * key_info;
+    if let Err(code) = unsafe { check_output(required, operationSize) } {
+        return code;
+    }
+    let state = if operation.initialized {
+        raw::TEE_OPERATION_STATE_ACTIVE
+    } else {
+        raw::TEE_OPERATION_STATE_INITIAL
+    };
+    let words = [
+        operation.algorithm,
+        operation.class,
+        operation.mode,
+        operation.digest_length(),
+        operation.max_key_size,
+        operation.handle_state(),
+        state,
+        keys.len() as u32,
+    ];
+    unsafe {
+        let base = operationInfoMultiple as *mut u8;
+        std::ptr::copy_nonoverlapping(words.as_ptr() as *const u8, base, header);
+        for (index, key) in keys.iter().enumerate() {
+            let key = raw::TEE_OperationInfoKey {
+                keySize: key.as_ref().map_or(0, |key| key.object_size),
+                requiredKeyUsage: required_usage(operation.mode),
+            };
+            let offset = header + index * key_info;
+            std::ptr::write_unaligned(base.add(offset) as *mut raw::TEE_OperationInfoKey, key);
+        }
+        *operationSize = required;
+    }
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_ResetOperation(operation: TEE_OperationHandle) {
+    let operation = unsafe { operation_mut(operation, "TEE_ResetOperation") };
+    if operation.needs_key() && operation.key.is_none() {
+        tee_panic("TEE_ResetOperation", "no key set");
+    }
+    operation.state = operation.initial_state();
+    operation.initialized = false;
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_SetOperationKey(
+    operation: TEE_OperationHandle,
+    key: TEE_ObjectHandle,
+) -> TEE_Result {
+    let operation = unsafe { operation_mut(operation, "TEE_SetOperationKey") };
+    match operation.key_count() {
+        0 => tee_panic("TEE_SetOperationKey", "operation without key"),
+        2 => tee_panic("TEE_SetOperationKey", "operation with two keys"),
+        _ => {}
+    }
+    operation.state = operation.initial_state();
+    operation.initialized = false;
+    if key.is_null() {
+        operation.key = None;
+        return raw::TEE_SUCCESS;
+    }
+    let key = unsafe { object::object_mut(key, "TEE_SetOperationKey") }.key();
+    check_key(operation, key, "TEE_SetOperationKey");
+    operation.key = Some(key.clone());
+    raw::TEE_SUCCESS
+}
+
+// Checks that `key` can be set as a key of `operation`.
+fn check_key(operation: &Operation, key: &KeyObject, function: &str) {
+    if !key.initialized
+        || !key_types(operation.algorithm, operation.mode).contains(&key.object_type)
+    {
+        tee_panic(function, "incompatible key");
+    }
+    let usage = required_usage(operation.mode);
+    if key.usage & usage != usage {
+        tee_panic(function, "key usage not allowed");
+    }
+    if key.object_size > operation.max_key_size {
+        tee_panic(function, "key larger than the maximum key size");
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_SetOperationKey2(
+    operation: TEE_OperationHandle,
+    key1: TEE_ObjectHandle,
+    key2: TEE_ObjectHandle,
+) -> TEE_Result {
+    let operation = unsafe { operation_mut(operation, "TEE_SetOperationKey2") };
+    if operation.key_count() != 2 {
+        tee_panic("TEE_SetOperationKey2", "operation without two keys");
+    }
+    operation.state = operation.initial_state();
+    operation.initialized = false;
+    if key1.is_null() && key2.is_null() {
+        operation.key = None;
+        operation.key2 = None;
+        return raw::TEE_SUCCESS;
+    }
+    if key1.is_null() || key2.is_null() {
+        tee_panic("TEE_SetOperationKey2", "only one key");
+    }
+    let key1 = unsafe { object::object_mut(key1, "TEE_SetOperationKey2") }.key();
+    let key2 = unsafe { object::object_mut(key2, "TEE_SetOperationKey2") }.key();
+    check_key(operation, key1, "TEE_SetOperationKey2");
+    check_key(operation, key2, "TEE_SetOperationKey2");
+    operation.key = Some(key1.clone());
+    operation.key2 = Some(key2.clone());
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CopyOperation(
+    dstOperation: TEE_OperationHandle,
+    srcOperation: TEE_OperationHandle,
+) {
+    let src = unsafe { operation_mut(srcOperation, "TEE_CopyOperation") }.clone();
+    let dst = unsafe { operation_mut(dstOperation, "TEE_CopyOperation") };
+    if dst.algorithm != src.algorithm || dst.mode != src.mode {
+        tee_panic("TEE_CopyOperation", "incompatible operations");
+    }
+    if src.key.as_ref().map_or(0, |key| key.object_size) > dst.max_key_size {
+        tee_panic("TEE_CopyOperation", "key larger than the maximum key size");
+    }
+    *dst = Operation {
+        max_key_size: dst.max_key_size,
+        ..src
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_IsAlgorithmSupported(algId: u32, element: u32) -> TEE_Result {
+    let supported = is_supported(algId)
+        && (element == raw::TEE_CRYPTO_ELEMENT_NONE
+            || (matches!(algId & 0xFF, 0x41 | 0x42)
+                && asymmetric::algorithm_curve(algId)
+                    .map_or(asymmetric::curve_size(element).is_some(), |curve| {
+                        curve == element
+                    })));
+    if supported {
+        raw::TEE_SUCCESS
+    } else {
+        raw::TEE_ERROR_NOT_SUPPORTED
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_DigestUpdate(
+    operation: TEE_OperationHandle,
+    chunk: *const c_void,
+    chunkSize: usize,
+) {
+    let operation =
+        unsafe { operation_of(operation, raw::TEE_OPERATION_DIGEST, "TEE_DigestUpdate") };
+    if let OpState::Digest(digest) = &mut operation.state {
+        digest.update(unsafe { input(chunk, chunkSize) });
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_DigestDoFinal(
+    operation: TEE_OperationHandle,
+    chunk: *const c_void,
+    chunkLen: usize,
+    hash: *mut c_void,
+    hashLen: *mut usize,
+) -> TEE_Result {
+    let operation =
+        unsafe { operation_of(operation, raw::TEE_OPERATION_DIGEST, "TEE_DigestDoFinal") };
+    let digest = match &mut operation.state {
+        OpState::Digest(digest) => digest,
+        _ => unreachable!(),
+    };
+    if let Err(code) = unsafe { check_output(digest.output_size(), hashLen) } {
+        return code;
+    }
+    digest.update(unsafe { input(chunk, chunkLen) });
+    unsafe { output(&digest.finalize_reset(), hash, hashLen) }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CipherInit(operation: TEE_OperationHandle, IV: *const c_void, IVLen: usize) {
+    let operation = unsafe { operation_of(operation, raw::TEE_OPERATION_CIPHER, "TEE_CipherInit") };
+    let mode = match (operation.algorithm >> 8) & 0xF {
+        0 => ChainMode::Ecb,
+        1 => ChainMode::Cbc,
+        _ => ChainMode::Ctr,
+    };
+    let cipher = operation.block_cipher("TEE_CipherInit");
+    let encrypt = operation.mode == raw::TEE_MODE_ENCRYPT;
+    match Cipher::new(cipher, mode, encrypt, unsafe { input(IV, IVLen) }) {
+        Ok(cipher) => {
+            operation.state = OpState::Cipher(cipher);
+            operation.initialized = true;
+        }
+        Err(_) => tee_panic("TEE_CipherInit", "invalid IV"),
+    }
+}
+
+unsafe fn cipher_of<'a>(handle: TEE_OperationHandle, function: &str) -> &'a mut Operation {
+    let operation = operation_of(handle, raw::TEE_OPERATION_CIPHER, function);
+    if !operation.initialized {
+        tee_panic(function, "operation not initialized");
+    }
+    operation
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CipherUpdate(
+    operation: TEE_OperationHandle,
+    srcData: *const c_void,
+    srcLen: usize,
+    destData: *mut c_void,
+    destLen: *mut usize,
+) -> TEE_Result {
+    let operation = unsafe { cipher_of(operation, "TEE_CipherUpdate") };
+    let cipher = match &mut operation.state {
+        OpState::Cipher(cipher) => cipher,
+        _ => unreachable!(),
+    };
+    if let Err(code) = unsafe { check_output(cipher.update_len(srcLen), destLen) } {
+        return code;
+    }
+    let result = cipher.update(unsafe { input(srcData, srcLen) });
+    unsafe { output(&result, destData, destLen) }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CipherDoFinal(
+    operation: TEE_OperationHandle,
+    srcData: *const c_void,
+    srcLen: usize,
+    destData: *mut c_void,
+    destLen: *mut usize,
+) -> TEE_Result {
+    let operation = unsafe { cipher_of(operation, "TEE_CipherDoFinal") };
+    let cipher = match &mut operation.state {
+        OpState::Cipher(cipher) => cipher,
+        _ => unreachable!(),
+    };
+    if let Err(code) = unsafe { check_output(cipher.final_len(srcLen), destLen) } {
+        return code;
+    }
+    match cipher.finish(unsafe { input(srcData, srcLen) }) {
+        Ok(result) => {
+            operation.initialized = false;
+            unsafe { output(&result, destData, destLen) }
+        }
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_MACInit(operation: TEE_OperationHandle, IV: *const c_void, IVLen: usize) {
+    let operation = unsafe { operation_of(operation, raw::TEE_OPERATION_MAC, "TEE_MACInit") };
+    let mac = if is_hmac(operation.algorithm) {
+        let key = operation
+            .key
+            .as_ref()
+            .unwrap_or_else(|| tee_panic("TEE_MACInit", "no key set"));
+        let secret = key.buffer(raw::TEE_ATTR_SECRET_VALUE).unwrap_or_default();
+        Mac::hmac(operation.algorithm & 0xF, secret).unwrap()
+    } else {
+        let cipher = operation.block_cipher("TEE_MACInit");
+        let iv = unsafe { input(IV, IVLen) };
+        let mac = match (operation.algorithm >> 8) & 0xF {
+            1 => Mac::cbc_mac(cipher, iv, false),
+            5 => Mac::cbc_mac(cipher, iv, true),
+            _ => Ok(Mac::cmac(cipher)),
+        };
+        mac.unwrap_or_else(|_| tee_panic("TEE_MACInit", "invalid IV"))
+    };
+    operation.state = OpState::Mac(mac);
+    operation.initialized = true;
+}
+
+unsafe fn mac_of<'a>(handle: TEE_OperationHandle, function: &str) -> &'a mut Mac {
+    let operation = operation_of(handle, raw::TEE_OPERATION_MAC, function);
+    match &mut operation.state {
+        OpState::Mac(mac) if operation.initialized => mac,
+        _ => tee_panic(function, "operation not initialized"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_MACUpdate(
+    operation: TEE_OperationHandle,
+    chunk: *const c_void,
+    chunkSize: usize,
+) {
+    let mac = unsafe { mac_of(operation, "TEE_MACUpdate") };
+    mac.update(unsafe { input(chunk, chunkSize) });
+}
+
+// Computes the MAC of the remaining `message` and ends the operation.
+unsafe fn mac_final(
+    handle: TEE_OperationHandle,
+    message: *const c_void,
+    messageLen: usize,
+    function: &str,
+) -> Result<Vec<u8>, TEE_Result> {
+    let mac = mac_of(handle, function);
+    mac.update(input(message, messageLen));
+    let result = mac.finish();
+    operation_mut(handle, function).initialized = false;
+    result
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_MACComputeFinal(
+    operation: TEE_OperationHandle,
+    message: *const c_void,
+    messageLen: usize,
+    mac: *mut c_void,
+    macLen: *mut usize,
+) -> TEE_Result {
+    let required = unsafe { mac_of(operation, "TEE_MACComputeFinal") }.output_len();
+    if let Err(code) = unsafe { check_output(required, macLen) } {
+        return code;
+    }
+    match unsafe { mac_final(operation, message, messageLen, "TEE_MACComputeFinal") } {
+        Ok(result) => unsafe { output(&result, mac, macLen) },
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_MACCompareFinal(
+    operation: TEE_OperationHandle,
+    message: *const c_void,
+    messageLen: usize,
+    mac: *const c_void,
+    macLen: usize,
+) -> TEE_Result {
+    match unsafe { mac_final(operation, message, messageLen, "TEE_MACCompareFinal") } {
+        Ok(result) => {
+            let expected = unsafe { input(mac, macLen) };
+            let diff = result
+                .iter()
+                .zip(expected)
+                .fold(0, |diff, (a, b)| diff | (a ^ b));
+            if result.len() != expected.len() || diff != 0 {
+                return raw::TEE_ERROR_MAC_INVALID;
+            }
+            raw::TEE_SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AEInit(
+    operation: TEE_OperationHandle,
+    nonce: *const c_void,
+    nonceLen: usize,
+    tagLen: u32,
+    AADLen: usize,
+    payloadLen: usize,
+) -> TEE_Result {
+    let operation = unsafe { operation_of(operation, raw::TEE_OPERATION_AE, "TEE_AEInit") };
+    let cipher = operation.block_cipher("TEE_AEInit");
+    let encrypt = operation.mode == raw::TEE_MODE_ENCRYPT;
+    let nonce = unsafe { input(nonce, nonceLen) };
+    let ae = match operation.algorithm {
+        raw::TEE_ALG_AES_GCM => Ae::gcm(cipher, encrypt, nonce, tagLen),
+        _ => Ae::ccm(cipher, encrypt, nonce, tagLen, AADLen, payloadLen),
+    };
+    match ae {
+        Ok(ae) => {
+            operation.state = OpState::Ae(ae);
+            operation.initialized = true;
+            raw::TEE_SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+unsafe fn ae_of<'a>(handle: TEE_OperationHandle, function: &str) -> &'a mut Ae {
+    let operation = operation_of(handle, raw::TEE_OPERATION_AE, function);
+    match &mut operation.state {
+        OpState::Ae(ae) if operation.initialized => ae,
+        _ => tee_panic(function, "operation not initialized"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AEUpdateAAD(
+    operation: TEE_OperationHandle,
+    AADdata: *const c_void,
+    AADdataLen: usize,
+) {
+    let ae = unsafe { ae_of(operation, "TEE_AEUpdateAAD") };
+    if ae
+        .update_aad(unsafe { input(AADdata, AADdataLen) })
+        .is_err()
+    {
+        tee_panic("TEE_AEUpdateAAD", "unexpected additional data");
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AEUpdate(
+    operation: TEE_OperationHandle,
+    srcData: *const c_void,
+    srcLen: usize,
+    destData: *mut c_void,
+    destLen: *mut usize,
+) -> TEE_Result {
+    let ae = unsafe { ae_of(operation, "TEE_AEUpdate") };
+    if let Err(code) = unsafe { check_output(srcLen, destLen) } {
+        return code;
+    }
+    match ae.update(unsafe { input(srcData, srcLen) }) {
+        Ok(result) => unsafe { output(&result, destData, destLen) },
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AEEncryptFinal(
+    operation: TEE_OperationHandle,
+    srcData: *const c_void,
+    srcLen: usize,
+    destData: *mut c_void,
+    destLen: *mut usize,
+    tag: *mut c_void,
+    tagLen: *mut usize,
+) -> TEE_Result {
+    let ae = unsafe { ae_of(operation, "TEE_AEEncryptFinal") };
+    let short_dest = unsafe { check_output(srcLen, destLen) };
+    let short_tag = unsafe { check_output(ae.tag_len(), tagLen) };
+    if let Err(code) = short_dest.and(short_tag) {
+        return code;
+    }
+    let result = ae.encrypt_final(unsafe { input(srcData, srcLen) });
+    unsafe { operation_mut(operation, "TEE_AEEncryptFinal") }.initialized = false;
+    match result {
+        Ok((result, computed)) => unsafe {
+            output(&result, destData, destLen);
+            output(&computed, tag, tagLen)
+        },
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AEDecryptFinal(
+    operation: TEE_OperationHandle,
+    srcData: *const c_void,
+    srcLen: usize,
+    destData: *mut c_void,
+    destLen: *mut usize,
+    tag: *mut c_void,
+    tagLen: usize,
+) -> TEE_Result {
+    let ae = unsafe { ae_of(operation, "TEE_AEDecryptFinal") };
+    if let Err(code) = unsafe { check_output(srcLen, destLen) } {
+        return code;
+    }
+    let result = ae.decrypt_final(unsafe { input(srcData, srcLen) }, unsafe {
+        input(tag, tagLen)
+    });
+    unsafe { operation_mut(operation, "TEE_AEDecryptFinal") }.initialized = false;
+    to_code(result.map(|result| unsafe {
+        output(&result, destData, destLen);
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{hex, unhex};
+    use crate::raw;
+    use optee_utee::crypto::{easy, AeReader, AeWriter, CipherReader, CipherWriter, Padding};
+    use optee_utee::key::{
+        AesAeMode, AesCipherMode, AesKey, AesMacMode, HmacKey, Sha256, Sm2DsaKeypair,
+        Sm2KepKeypair, Sm2KepPeer, Sm2KepRole, Sm2PkeKeypair, Sm3, Sm4CipherMode, Sm4Key,
+    };
+    use optee_utee::{
+        AlgorithmId, AttributeId, AttributeMemref, AttributeValue, Cipher, Digest, ElementId,
+        ErrorKind, GenericObject, KeyWrapAlgorithm, Mac, OperationMode, StdCompatRead,
+        StdCompatWrite, TransientObject, TransientObjectType, AE,
+    };
+    use std::convert::TryFrom;
+
+    fn secret(object_type: TransientObjectType, key: &[u8]) -> TransientObject {
+        let mut object = TransientObject::allocate(object_type, key.len() * 8).unwrap();
+        let attribute = AttributeMemref::from_ref(AttributeId::SecretValue, key);
+        object.populate(&[attribute.into()]).unwrap();
+        object
+    }
+
+    #[test]
+    fn test_digest() {
+        let digest = Digest::allocate(AlgorithmId::Sha256).unwrap();
+        digest.update(b"a");
+        let mut hash = [0u8; 32];
+        assert_eq!(digest.do_final(b"bc", &mut hash).unwrap(), 32);
+        assert_eq!(
+            hex(&hash),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_aes_cbc() {
+        // NIST SP 800-38A F.2.1
+        let key = secret(
+            TransientObjectType::Aes,
+            &unhex("2b7e151628aed2a6abf7158809cf4f3c"),
+        );
+        let iv = unhex("000102030405060708090a0b0c0d0e0f");
+        let plaintext = unhex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+        let cipher =
+            Cipher::allocate(AlgorithmId::AesCbcNopad, OperationMode::Encrypt, 128).unwrap();
+        cipher.set_key(&key).unwrap();
+        cipher.init(&iv);
+        let mut ciphertext = [0u8; 32];
+        assert_eq!(
+            cipher.update(&plaintext[..20], &mut ciphertext).unwrap(),
+            16
+        );
+        assert_eq!(
+            cipher
+                .do_final(&plaintext[20..], &mut ciphertext[16..])
+                .unwrap(),
+            16
+        );
+        assert_eq!(
+            hex(&ciphertext),
+            "7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2"
+        );
+    }
+
+    #[test]
+    fn test_mac() {
+        // RFC 4231 test case 4
+        let key: Vec<u8> = (1..=25).collect();
+        let key = secret(TransientObjectType::HmacSha256, &key);
+        let mac = Mac::allocate(AlgorithmId::HmacSha256, 256).unwrap();
+        mac.set_key(&key).unwrap();
+        mac.init(&[]);
+        mac.update(&[0xcd; 20]);
+        let mut tag = [0u8; 32];
+        assert_eq!(mac.compute_final(&[0xcd; 30], &mut tag).unwrap(), 32);
+        assert_eq!(
+            hex(&tag),
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"
+        );
+
+        // RFC 4493 example 2
+        let key = secret(
+            TransientObjectType::Aes,
+            &unhex("2b7e151628aed2a6abf7158809cf4f3c"),
+        );
+        let mac = Mac::allocate(AlgorithmId::AesCmac, 128).unwrap();
+        mac.set_key(&key).unwrap();
+        mac.init(&[]);
+        let message = unhex("6bc1bee22e409f96e93d7e117393172a");
+        mac.compare_final(&message, &unhex("070a16b46b4d4144f79bdd9dd04a287c"))
+            .unwrap();
+        mac.init(&[]);
+        let err = mac.compare_final(&message, &[0u8; 16]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::MacInvalid);
+    }
+
+    #[test]
+    fn test_aes_gcm() {
+        // GCM specification test case 2
+        let key = secret(TransientObjectType::Aes, &[0u8; 16]);
+        let encrypt = AE::allocate(AlgorithmId::AesGcm, OperationMode::Encrypt, 128).unwrap();
+        encrypt.set_key(&key).unwrap();
+        encrypt.init(&[0u8; 12], 128, 0, 0).unwrap();
+        let mut ciphertext = [0u8; 16];
+        let mut tag = [0u8; 16];
+        assert_eq!(
+            encrypt
+                .encrypt_final(&[0u8; 16], &mut ciphertext, &mut tag)
+                .unwrap(),
+            (16, 16)
+        );
+        assert_eq!(hex(&ciphertext), "0388dace60b6a392f328c2b971b2fe78");
+        assert_eq!(hex(&tag), "ab6e47d42cec13bdf53a67b21257bddf");
+
+        let decrypt = AE::allocate(AlgorithmId::AesGcm, OperationMode::Decrypt, 128).unwrap();
+        decrypt.set_key(&key).unwrap();
+        decrypt.init(&[0u8; 12], 128, 0, 0).unwrap();
+        let mut plaintext = [0xFFu8; 16];
+        assert_eq!(
+            decrypt
+                .decrypt_final(&ciphertext, &mut plaintext, &tag)
+                .unwrap(),
+            16
+        );
+        assert_eq!(plaintext, [0u8; 16]);
+
+        tag[0] ^= 1;
+        decrypt.init(&[0u8; 12], 128, 0, 0).unwrap();
+        let err = decrypt
+            .decrypt_final(&ciphertext, &mut plaintext, &tag)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::MacInvalid);
+    }
+
+    #[test]
+    fn test_key_wrap() {
+        let wrapping_key = secret(TransientObjectType::Aes, &(0..16).collect::<Vec<u8>>());
+        let key = secret(TransientObjectType::GenericSecret, &[0x5A; 20]);
+        // Checked against the RFC 5649 implementation of pyca/cryptography.
+        let wrapped = key
+            .export_wrapped(&wrapping_key, KeyWrapAlgorithm::AesKw)
+            .unwrap();
+        assert_eq!(
+            hex(&wrapped),
+            "54414b5701010000e97500040111923188a9e26b155b9d4a2c3fc12665c623a5\
+             ffe9b07d18daae2587263224073e56cc67dea31c9e77324f"
+        );
+
+        let other_key = secret(TransientObjectType::Aes, &[0u8; 16]);
+        for algorithm in [KeyWrapAlgorithm::AesKw, KeyWrapAlgorithm::AesGcm] {
+            let wrapped = key.export_wrapped(&wrapping_key, algorithm).unwrap();
+            let restored = TransientObject::import_wrapped(&wrapping_key, &wrapped).unwrap();
+            let info = restored.info().unwrap();
+            assert_eq!(
+                info.object_type(),
+                TransientObjectType::GenericSecret as u32
+            );
+            assert_eq!(info.object_size(), 160);
+            let mut value = [0u8; 20];
+            assert_eq!(
+                restored
+                    .ref_attribute(AttributeId::SecretValue, &mut value)
+                    .unwrap(),
+                20
+            );
+            assert_eq!(value, [0x5A; 20]);
+
+            let err = TransientObject::import_wrapped(&other_key, &wrapped)
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), ErrorKind::MacInvalid);
+            let mut tampered = wrapped.clone();
+            *tampered.last_mut().unwrap() ^= 1;
+            let err = TransientObject::import_wrapped(&wrapping_key, &tampered)
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), ErrorKind::MacInvalid);
+        }
+
+        let err = TransientObject::import_wrapped(&wrapping_key, b"not a key")
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadFormat);
+    }
+
+    #[test]
+    fn test_typed_keys() {
+        // NIST SP 800-38A F.2.1
+        let key = AesKey::from_bytes(&unhex("2b7e151628aed2a6abf7158809cf4f3c")).unwrap();
+        let cipher = key
+            .cipher(AesCipherMode::CbcNopad, OperationMode::Encrypt)
+            .unwrap();
+        cipher.init(&unhex("000102030405060708090a0b0c0d0e0f"));
+        let mut ciphertext = [0u8; 16];
+        cipher
+            .do_final(&unhex("6bc1bee22e409f96e93d7e117393172a"), &mut ciphertext)
+            .unwrap();
+        assert_eq!(hex(&ciphertext), "7649abac8119b246cee98e9b12e9197d");
+
+        // RFC 4493 example 2
+        let mac = key.mac(AesMacMode::Cmac).unwrap();
+        mac.init(&[]);
+        mac.compare_final(
+            &unhex("6bc1bee22e409f96e93d7e117393172a"),
+            &unhex("070a16b46b4d4144f79bdd9dd04a287c"),
+        )
+        .unwrap();
+
+        let key = AesKey::generate(256).unwrap();
+        let mut tag = [0u8; 16];
+        let encrypt = key.ae(AesAeMode::Gcm, OperationMode::Encrypt).unwrap();
+        encrypt.init(&[0u8; 12], 128, 0, 0).unwrap();
+        encrypt
+            .encrypt_final(b"0123456789abcdef", &mut ciphertext, &mut tag)
+            .unwrap();
+        let decrypt = key.ae(AesAeMode::Gcm, OperationMode::Decrypt).unwrap();
+        decrypt.init(&[0u8; 12], 128, 0, 0).unwrap();
+        let mut plaintext = [0u8; 16];
+        decrypt
+            .decrypt_final(&ciphertext, &mut plaintext, &tag)
+            .unwrap();
+        assert_eq!(&plaintext, b"0123456789abcdef");
+
+        // RFC 4231 test case 4
+        let key = HmacKey::<Sha256>::from_bytes(&(1..=25).collect::<Vec<u8>>()).unwrap();
+        let mac = key.mac().unwrap();
+        mac.init(&[]);
+        let mut tag = [0u8; 32];
+        mac.compute_final(&[0xcd; 50], &mut tag).unwrap();
+        assert_eq!(
+            hex(&tag),
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"
+        );
+
+        let object = key.into_object();
+        let err = AesKey::try_from(object).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+        let object = secret(TransientObjectType::Aes, &[0u8; 16]);
+        let key = AesKey::try_from(object).unwrap();
+        assert_eq!(key.object().info().unwrap().object_size(), 128);
+    }
+
+    #[test]
+    fn test_easy() {
+        assert_eq!(
+            hex(&easy::sha256(b"abc").unwrap()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&easy::sha384(b"abc").unwrap()),
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded163\
+             1a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+        );
+        assert_eq!(
+            hex(&easy::sha512(b"abc").unwrap()),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+
+        // RFC 4231 test case 4
+        let key: Vec<u8> = (1..=25).collect();
+        assert_eq!(
+            hex(&easy::hmac::<Sha256>(&key, &[0xcd; 50]).unwrap()),
+            "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"
+        );
+
+        // GCM specification test case 2
+        let sealed = easy::aes_gcm_seal(&[0u8; 16], &[0u8; 12], &[], &[0u8; 16]).unwrap();
+        assert_eq!(
+            hex(&sealed),
+            "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf"
+        );
+        let key = [0x42u8; 32];
+        let sealed = easy::aes_gcm_seal(&key, &[7u8; 12], b"header", b"secret").unwrap();
+        assert_eq!(sealed.len(), 6 + easy::AES_GCM_TAG_LEN);
+        let opened = easy::aes_gcm_open(&key, &[7u8; 12], b"header", &sealed).unwrap();
+        assert_eq!(opened, b"secret");
+        for (aad, sealed) in [(&b"other"[..], &sealed[..]), (b"header", &sealed[..10])].iter() {
+            let err = easy::aes_gcm_open(&key, &[7u8; 12], aad, sealed)
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), ErrorKind::MacInvalid);
+        }
+
+        let key = unhex("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = unhex("000102030405060708090a0b0c0d0e0f");
+        let ciphertext = easy::aes_cbc_pkcs7_encrypt(&key, &iv, b"the quick brown fox").unwrap();
+        assert_eq!(
+            hex(&ciphertext),
+            "b91cd26487bcd9615e73c8209754023bab40adafca69b5d527e3cf146ced8513"
+        );
+        let plaintext = easy::aes_cbc_pkcs7_decrypt(&key, &iv, &ciphertext).unwrap();
+        assert_eq!(plaintext, b"the quick brown fox");
+        let ciphertext = easy::aes_cbc_pkcs7_encrypt(&key, &iv, b"0123456789abcdef").unwrap();
+        assert_eq!(ciphertext.len(), 32);
+        let plaintext = easy::aes_cbc_pkcs7_decrypt(&key, &iv, &ciphertext).unwrap();
+        assert_eq!(plaintext, b"0123456789abcdef");
+        // A zero and an inconsistent padding.
+        for ciphertext in [
+            "9c91ebee369deb1db2f0b96cb79f039d",
+            "7706e2340cd1a206cf12c5318bd24ba5",
+        ]
+        .iter()
+        {
+            let err = easy::aes_cbc_pkcs7_decrypt(&key, &iv, &unhex(ciphertext))
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), ErrorKind::BadFormat);
+        }
+        let err = easy::aes_cbc_pkcs7_decrypt(&key, &iv, &[0u8; 17])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+
+    // Writes `data` to `writer` in chunks of growing lengths, not aligned on
+    // blocks.
+    fn write_chunks<W: StdCompatWrite>(writer: &mut W, data: &[u8]) {
+        let mut rest = data;
+        let mut len = 1;
+        while !rest.is_empty() {
+            let (chunk, next) = rest.split_at(len.min(rest.len()));
+            writer.write_all(chunk).unwrap();
+            rest = next;
+            len += 7;
+        }
+    }
+
+    // Reads `reader` to its end with a small buffer.
+    fn read_all<R: StdCompatRead>(reader: &mut R) -> optee_utee::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buffer = [0u8; 13];
+        loop {
+            match reader.read(&mut buffer)? {
+                0 => return Ok(data),
+                len => data.extend_from_slice(&buffer[..len]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_streams() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i * 7) as u8).collect();
+
+        let mut digest = Digest::allocate(AlgorithmId::Sha256).unwrap();
+        write_chunks(&mut digest, &data);
+        let mut hash = [0u8; 32];
+        digest.do_final(&[], &mut hash).unwrap();
+        assert_eq!(hash.to_vec(), easy::sha256(&data).unwrap());
+
+        let key: Vec<u8> = (1..=25).collect();
+        let mut mac = HmacKey::<Sha256>::from_bytes(&key).unwrap().mac().unwrap();
+        mac.init(&[]);
+        write_chunks(&mut mac, &data);
+        let mut tag = [0u8; 32];
+        mac.compute_final(&[], &mut tag).unwrap();
+        assert_eq!(tag.to_vec(), easy::hmac::<Sha256>(&key, &data).unwrap());
+
+        let key = AesKey::from_bytes(&unhex("2b7e151628aed2a6abf7158809cf4f3c")).unwrap();
+        let iv = unhex("000102030405060708090a0b0c0d0e0f");
+        let encrypt = key
+            .cipher(AesCipherMode::CbcNopad, OperationMode::Encrypt)
+            .unwrap();
+        let decrypt = key
+            .cipher(AesCipherMode::CbcNopad, OperationMode::Decrypt)
+            .unwrap();
+        for len in [0, 19, 32, 3000].iter() {
+            let plaintext = &data[..*len];
+            encrypt.init(&iv);
+            let mut writer = CipherWriter::new(&encrypt, Padding::Pkcs7, Vec::new());
+            write_chunks(&mut writer, plaintext);
+            let ciphertext = writer.finish().unwrap();
+            assert_eq!(
+                ciphertext,
+                easy::aes_cbc_pkcs7_encrypt(
+                    &unhex("2b7e151628aed2a6abf7158809cf4f3c"),
+                    &iv,
+                    plaintext
+                )
+                .unwrap()
+            );
+            decrypt.init(&iv);
+            let mut reader = CipherReader::new(&decrypt, Padding::Pkcs7, &ciphertext[..]);
+            assert_eq!(read_all(&mut reader).unwrap(), plaintext);
+            decrypt.init(&iv);
+            let mut writer = CipherWriter::new(&decrypt, Padding::Pkcs7, Vec::new());
+            write_chunks(&mut writer, &ciphertext);
+            assert_eq!(writer.finish().unwrap(), plaintext);
+        }
+        // A zero padding.
+        decrypt.init(&iv);
+        let ciphertext = unhex("9c91ebee369deb1db2f0b96cb79f039d");
+        let mut reader = CipherReader::new(&decrypt, Padding::Pkcs7, &ciphertext[..]);
+        assert_eq!(
+            read_all(&mut reader).err().unwrap().kind(),
+            ErrorKind::BadFormat
+        );
+
+        let ctr = key
+            .cipher(AesCipherMode::Ctr, OperationMode::Encrypt)
+            .unwrap();
+        ctr.init(&iv);
+        let mut reader = CipherReader::new(&ctr, Padding::None, &data[..]);
+        let ciphertext = read_all(&mut reader).unwrap();
+        assert_eq!(ciphertext.len(), data.len());
+        ctr.init(&iv);
+        let mut writer = CipherWriter::new(&ctr, Padding::None, Vec::new());
+        write_chunks(&mut writer, &ciphertext);
+        assert_eq!(writer.finish().unwrap(), data);
+
+        let key = AesKey::from_bytes(&[0x42u8; 32]).unwrap();
+        let nonce = [7u8; 12];
+        let encrypt = key.ae(AesAeMode::Gcm, OperationMode::Encrypt).unwrap();
+        encrypt.init(&nonce, 128, 0, 0).unwrap();
+        let mut writer = AeWriter::new(&encrypt, Vec::new());
+        write_chunks(&mut writer, &data);
+        let mut tag = [0u8; 16];
+        let (mut sealed, tag_len) = writer.finish_encrypt(&mut tag).unwrap();
+        assert_eq!(tag_len, 16);
+        sealed.extend_from_slice(&tag);
+        assert_eq!(
+            sealed,
+            easy::aes_gcm_seal(&[0x42u8; 32], &nonce, &[], &data).unwrap()
+        );
+        let ciphertext = &sealed[..data.len()];
+
+        encrypt.init(&nonce, 128, 0, 0).unwrap();
+        let mut reader = AeReader::encrypt(&encrypt, 16, &data[..]);
+        assert!(reader.tag().is_none());
+        assert_eq!(read_all(&mut reader).unwrap(), ciphertext);
+        assert_eq!(reader.tag().unwrap(), tag);
+
+        let decrypt = key.ae(AesAeMode::Gcm, OperationMode::Decrypt).unwrap();
+        decrypt.init(&nonce, 128, 0, 0).unwrap();
+        let mut reader = AeReader::decrypt(&decrypt, &tag, ciphertext);
+        assert_eq!(read_all(&mut reader).unwrap(), data);
+        decrypt.init(&nonce, 128, 0, 0).unwrap();
+        let mut writer = AeWriter::new(&decrypt, Vec::new());
+        write_chunks(&mut writer, ciphertext);
+        assert_eq!(writer.finish_decrypt(&tag).unwrap(), data);
+
+        tag[0] ^= 1;
+        decrypt.init(&nonce, 128, 0, 0).unwrap();
+        let mut reader = AeReader::decrypt(&decrypt, &tag, ciphertext);
+        assert_eq!(
+            read_all(&mut reader).err().unwrap().kind(),
+            ErrorKind::MacInvalid
+        );
+    }
+
+    #[test]
+    fn test_sm() {
+        assert_eq!(AlgorithmId::Sm3 as u32, raw::TEE_ALG_SM3);
+        assert_eq!(AlgorithmId::Sm4CbcNopad as u32, raw::TEE_ALG_SM4_CBC_NOPAD);
+        assert_eq!(AlgorithmId::Sm2DsaSm3 as u32, raw::TEE_ALG_SM2_DSA_SM3);
+        assert_eq!(AlgorithmId::Sm2Pke as u32, raw::TEE_ALG_SM2_PKE);
+        assert_eq!(AlgorithmId::Sm2Kep as u32, raw::TEE_ALG_SM2_KEP);
+        assert_eq!(ElementId::EccCurveSm2 as u32, raw::TEE_ECC_CURVE_SM2);
+        assert_ne!(
+            ElementId::EccCurveSm2 as u32,
+            ElementId::EccCurve25519 as u32
+        );
+
+        // GB/T 32905-2016 example 1
+        assert_eq!(
+            hex(&easy::sm3(b"abc").unwrap()),
+            "66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0"
+        );
+        let key = HmacKey::<Sm3>::from_bytes(&b"key".repeat(8)).unwrap();
+        let mac = key.mac().unwrap();
+        mac.init(&[]);
+        let mut tag = [0u8; 32];
+        mac.compute_final(b"The quick brown fox", &mut tag).unwrap();
+        assert_eq!(
+            hex(&tag),
+            "8e1f6ace073f8a85afce8087bca6dbe3c6d310c825d8464e469b7eaa5f8014d8"
+        );
+
+        // GB/T 32907-2016 example 1
+        let bytes = unhex("0123456789abcdeffedcba9876543210");
+        let key = Sm4Key::from_bytes(&bytes).unwrap();
+        let encrypt = key
+            .cipher(Sm4CipherMode::EcbNopad, OperationMode::Encrypt)
+            .unwrap();
+        encrypt.init(&[]);
+        let mut ciphertext = [0u8; 16];
+        encrypt.do_final(&bytes, &mut ciphertext).unwrap();
+        assert_eq!(hex(&ciphertext), "681edf34d206965e86b3e94f536e4246");
+
+        let iv: Vec<u8> = (0..16).collect();
+        let plaintext: Vec<u8> = (0..32).collect();
+        for (mode, len, expected) in [
+            (
+                Sm4CipherMode::CbcNopad,
+                32,
+                "2677f46b09c122cc975533105bd4a22ad9ee98830e69745c9827f934a19621f8",
+            ),
+            (
+                Sm4CipherMode::Ctr,
+                30,
+                "06999e6239a36eaa2284fd89eda5f7657f161f5854b6ea16c28809fe9d1d",
+            ),
+        ] {
+            let encrypt = key.cipher(mode, OperationMode::Encrypt).unwrap();
+            encrypt.init(&iv);
+            let mut ciphertext = vec![0u8; len];
+            encrypt
+                .do_final(&plaintext[..len], &mut ciphertext)
+                .unwrap();
+            assert_eq!(hex(&ciphertext), expected);
+            let decrypt = key.cipher(mode, OperationMode::Decrypt).unwrap();
+            decrypt.init(&iv);
+            let mut decrypted = vec![0u8; len];
+            decrypt.do_final(&ciphertext, &mut decrypted).unwrap();
+            assert_eq!(decrypted, plaintext[..len]);
+        }
+        assert_eq!(
+            Sm4Key::from_bytes(&[0u8; 24])
+                .map(|_| ())
+                .map_err(|e| e.kind()),
+            Err(ErrorKind::NotSupported)
+        );
+
+        // The SM2 vectors of GB/T 32918 are on another curve, see the sm2
+        // module, these are round trips.
+        let key = Sm2DsaKeypair::generate().unwrap();
+        let public_key = key.public_key().unwrap();
+        let signature = easy::sm2_sign(&key, b"ALICE123@YAHOO.COM", b"message digest").unwrap();
+        assert_eq!(signature.len(), 64);
+        easy::sm2_verify(
+            &public_key,
+            b"ALICE123@YAHOO.COM",
+            b"message digest",
+            &signature,
+        )
+        .unwrap();
+        for (id, message) in [
+            (&b"ALICE123@YAHOO.COM"[..], &b"message digesT"[..]),
+            (b"BILL456@YAHOO.COM", b"message digest"),
+        ] {
+            assert_eq!(
+                easy::sm2_verify(&public_key, id, message, &signature).map_err(|e| e.kind()),
+                Err(ErrorKind::SignatureInvalid)
+            );
+        }
+
+        let key = Sm2PkeKeypair::generate().unwrap();
+        let ciphertext =
+            easy::sm2_encrypt(&key.public_key().unwrap(), b"encryption standard").unwrap();
+        assert_eq!(ciphertext.len(), 97 + 19);
+        assert_eq!(
+            easy::sm2_decrypt(&key, &ciphertext).unwrap(),
+            b"encryption standard"
+        );
+        let mut tampered = ciphertext;
+        tampered[70] ^= 1;
+        assert!(easy::sm2_decrypt(&key, &tampered).is_err());
+
+        let (alice, alice_ephemeral) = (
+            Sm2KepKeypair::generate().unwrap(),
+            Sm2KepKeypair::generate().unwrap(),
+        );
+        let (bill, bill_ephemeral) = (
+            Sm2KepKeypair::generate().unwrap(),
+            Sm2KepKeypair::generate().unwrap(),
+        );
+        let public_keys = |key: &Sm2KepKeypair, ephemeral: &Sm2KepKeypair| {
+            (key.public_key().unwrap(), ephemeral.public_key().unwrap())
+        };
+        let (alice_public, alice_ephemeral_public) = public_keys(&alice, &alice_ephemeral);
+        let (bill_public, bill_ephemeral_public) = public_keys(&bill, &bill_ephemeral);
+        let alice_peer = Sm2KepPeer {
+            public_key: &alice_public,
+            ephemeral_public_key: &alice_ephemeral_public,
+            id: b"ALICE123@YAHOO.COM",
+        };
+        let bill_peer = Sm2KepPeer {
+            public_key: &bill_public,
+            ephemeral_public_key: &bill_ephemeral_public,
+            id: b"BILL456@YAHOO.COM",
+        };
+        let secret_value = |secret: TransientObject| {
+            let mut value = [0u8; 16];
+            assert_eq!(
+                secret
+                    .ref_attribute(AttributeId::SecretValue, &mut value)
+                    .unwrap(),
+                16
+            );
+            value
+        };
+        let alice_secret = alice
+            .shared_secret(
+                &alice_ephemeral,
+                Sm2KepRole::Initiator,
+                b"ALICE123@YAHOO.COM",
+                &bill_peer,
+                16,
+            )
+            .unwrap();
+        let bill_secret = bill
+            .shared_secret(
+                &bill_ephemeral,
+                Sm2KepRole::Responder,
+                b"BILL456@YAHOO.COM",
+                &alice_peer,
+                16,
+            )
+            .unwrap();
+        assert_eq!(secret_value(alice_secret), secret_value(bill_secret));
+
+        // The type of the SM2 keys implies the curve, they have no curve
+        // attribute.
+        let mut object =
+            TransientObject::allocate(TransientObjectType::Sm2DsaPublicKey, 256).unwrap();
+        let (mut x, mut y) = ([0u8; 32], [0u8; 32]);
+        public_key
+            .object()
+            .ref_attribute(AttributeId::EccPublicValueX, &mut x)
+            .unwrap();
+        public_key
+            .object()
+            .ref_attribute(AttributeId::EccPublicValueY, &mut y)
+            .unwrap();
+        let curve =
+            AttributeValue::from_value(AttributeId::EccCurve, ElementId::EccCurveSm2 as u32, 0);
+        assert_eq!(
+            object
+                .populate(&[
+                    AttributeMemref::from_ref(AttributeId::EccPublicValueX, &x).into(),
+                    AttributeMemref::from_ref(AttributeId::EccPublicValueY, &y).into(),
+                    curve.into(),
+                ])
+                .map_err(|e| e.kind()),
+            Err(ErrorKind::BadParameters)
+        );
+    }
+}
@@ -0,0 +1,264 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// The HKDF, Concat KDF and PBKDF2 key derivation functions, extensions of
+// OP-TEE.
+
+use super::crypto::new_digest;
+use super::object::{find_buffer, find_value, Attribute, KeyObject};
+use super::symmetric::Mac;
+use crate::raw::{self, TEE_Result};
+
+pub(super) fn is_kdf(algorithm: u32) -> bool {
+    matches!(algorithm & 0xFF, 0xC0..=0xC2)
+}
+
+// Returns the derived key of a KDF algorithm, from the key object and the
+// parameters of `TEE_DeriveKey`.
+pub(super) fn derive(
+    algorithm: u32,
+    key: &KeyObject,
+    params: &[Attribute],
+) -> Result<Vec<u8>, TEE_Result> {
+    let hash_id = (algorithm >> 12) & 0xF;
+    match algorithm & 0xFF {
+        0xC0 => {
+            check_params(
+                params,
+                &[
+                    raw::TEE_ATTR_HKDF_SALT,
+                    raw::TEE_ATTR_HKDF_INFO,
+                    raw::TEE_ATTR_HKDF_OKM_LENGTH,
+                ],
+            )?;
+            hkdf(
+                hash_id,
+                key.buffer(raw::TEE_ATTR_HKDF_IKM).unwrap_or_default(),
+                find_buffer(params, raw::TEE_ATTR_HKDF_SALT).unwrap_or_default(),
+                find_buffer(params, raw::TEE_ATTR_HKDF_INFO).unwrap_or_default(),
+                length(params, raw::TEE_ATTR_HKDF_OKM_LENGTH)?,
+            )
+        }
+        0xC1 => {
+            check_params(
+                params,
+                &[
+                    raw::TEE_ATTR_CONCAT_KDF_OTHER_INFO,
+                    raw::TEE_ATTR_CONCAT_KDF_DKM_LENGTH,
+                ],
+            )?;
+            Ok(concat_kdf(
+                hash_id,
+                key.buffer(raw::TEE_ATTR_CONCAT_KDF_Z).unwrap_or_default(),
+                find_buffer(params, raw::TEE_ATTR_CONCAT_KDF_OTHER_INFO).unwrap_or_default(),
+                length(params, raw::TEE_ATTR_CONCAT_KDF_DKM_LENGTH)?,
+            ))
+        }
+        0xC2 => {
+            check_params(
+                params,
+                &[
+                    raw::TEE_ATTR_PBKDF2_SALT,
+                    raw::TEE_ATTR_PBKDF2_DKM_LENGTH,
+                    raw::TEE_ATTR_PBKDF2_ITERATION_COUNT,
+                ],
+            )?;
+            let (iterations, _) = find_value(params, raw::TEE_ATTR_PBKDF2_ITERATION_COUNT)
+                .ok_or(raw::TEE_ERROR_BAD_PARAMETERS)?;
+            if iterations == 0 {
+                return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+            }
+            Ok(pbkdf2(
+                hash_id,
+                key.buffer(raw::TEE_ATTR_PBKDF2_PASSWORD)
+                    .unwrap_or_default(),
+                find_buffer(params, raw::TEE_ATTR_PBKDF2_SALT).unwrap_or_default(),
+                iterations,
+                length(params, raw::TEE_ATTR_PBKDF2_DKM_LENGTH)?,
+            ))
+        }
+        _ => Err(raw::TEE_ERROR_NOT_SUPPORTED),
+    }
+}
+
+fn check_params(params: &[Attribute], known: &[u32]) -> Result<(), TEE_Result> {
+    if params.iter().all(|attr| known.contains(&attr.id)) {
+        Ok(())
+    } else {
+        Err(raw::TEE_ERROR_BAD_PARAMETERS)
+    }
+}
+
+// Returns the non-zero length in bytes of the derived key.
+fn length(params: &[Attribute], id: u32) -> Result<usize, TEE_Result> {
+    match find_value(params, id) {
+        Some((len, _)) if len > 0 => Ok(len as usize),
+        _ => Err(raw::TEE_ERROR_BAD_PARAMETERS),
+    }
+}
+
+fn hmac(hash_id: u32, key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let mut mac = Mac::hmac(hash_id, key).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finish().unwrap()
+}
+
+// HKDF of RFC 5869, an empty salt is the same as no salt.
+fn hkdf(
+    hash_id: u32,
+    ikm: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    len: usize,
+) -> Result<Vec<u8>, TEE_Result> {
+    let hash_len = new_digest(hash_id)
+        .ok_or(raw::TEE_ERROR_NOT_SUPPORTED)?
+        .output_size();
+    if len > 255 * hash_len {
+        return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+    }
+    let prk = hmac(hash_id, salt, &[ikm]);
+    let mut okm = Vec::with_capacity(len + hash_len);
+    let mut block = Vec::new();
+    for counter in 1..=255u8 {
+        if okm.len() >= len {
+            break;
+        }
+        block = hmac(hash_id, &prk, &[&block, info, &[counter]]);
+        okm.extend_from_slice(&block);
+    }
+    okm.truncate(len);
+    Ok(okm)
+}
+
+// The single-step KDF of NIST SP 800-56A with a hash function.
+fn concat_kdf(hash_id: u32, z: &[u8], other_info: &[u8], len: usize) -> Vec<u8> {
+    let mut dkm = Vec::new();
+    let mut counter = 1u32;
+    while dkm.len() < len {
+        let mut digest = new_digest(hash_id).unwrap();
+        digest.update(&counter.to_be_bytes());
+        digest.update(z);
+        digest.update(other_info);
+        dkm.extend_from_slice(&digest.finalize());
+        counter += 1;
+    }
+    dkm.truncate(len);
+    dkm
+}
+
+// PBKDF2 of RFC 8018 with HMAC.
+fn pbkdf2(hash_id: u32, password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let mut dkm = Vec::new();
+    let mut index = 1u32;
+    while dkm.len() < len {
+        let mut block = hmac(hash_id, password, &[salt, &index.to_be_bytes()]);
+        let mut result = block.clone();
+        for _ in 1..iterations {
+            block = hmac(hash_id, password, &[&block]);
+            result
+                .iter_mut()
+                .zip(&block)
+                .for_each(|(byte, other)| *byte ^= other);
+        }
+        dkm.extend_from_slice(&result);
+        index += 1;
+    }
+    dkm.truncate(len);
+    dkm
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{hex, unhex};
+    use optee_utee::crypto::kdf::{ConcatKdf, ConcatKdfHash, Hkdf, HkdfHash, Pbkdf2};
+    use optee_utee::{AttributeId, ErrorKind, GenericObject, TransientObject};
+
+    fn secret(object: TransientObject) -> String {
+        let mut buffer = [0u8; 64];
+        let len = object
+            .ref_attribute(AttributeId::SecretValue, &mut buffer)
+            .unwrap();
+        assert_eq!(object.info().unwrap().object_size(), len * 8);
+        hex(&buffer[..len])
+    }
+
+    #[test]
+    fn test_hkdf() {
+        // RFC 5869, test cases 1 and 3.
+        let ikm = [0x0b; 22];
+        let okm = Hkdf::new(HkdfHash::Sha256)
+            .with_salt(&unhex("000102030405060708090a0b0c"))
+            .with_info(&unhex("f0f1f2f3f4f5f6f7f8f9"))
+            .derive(&ikm, 42)
+            .unwrap();
+        assert_eq!(
+            secret(okm),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        );
+        let okm = Hkdf::new(HkdfHash::Sha256).derive(&ikm, 42).unwrap();
+        assert_eq!(
+            secret(okm),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"
+        );
+
+        let hkdf = Hkdf::new(HkdfHash::Sha1);
+        for (ikm, len) in [(&[][..], 16), (&ikm[..], 0), (&ikm[..], 513)].iter() {
+            let err = hkdf.derive(ikm, *len).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::BadParameters);
+        }
+    }
+
+    #[test]
+    fn test_concat_kdf() {
+        let okm = ConcatKdf::new(ConcatKdfHash::Sha256)
+            .with_other_info(b"concat kdf")
+            .derive(&(0..32).collect::<Vec<u8>>(), 40)
+            .unwrap();
+        assert_eq!(
+            secret(okm),
+            "b150be2e7240f1b9da84777331119c4a5aa1e6e2436c7a0469b6277b208608ff8b70a37af948b876"
+        );
+        let okm = ConcatKdf::new(ConcatKdfHash::Sha1)
+            .derive(&[0x0b; 20], 16)
+            .unwrap();
+        assert_eq!(secret(okm), "8e0b653c5b51648b76778964e957d6b1");
+    }
+
+    #[test]
+    fn test_pbkdf2() {
+        // RFC 6070.
+        let okm = Pbkdf2::new(4096)
+            .with_salt(b"salt")
+            .derive(b"password", 20)
+            .unwrap();
+        assert_eq!(secret(okm), "4b007901b765489abead49d926f721d065a429c1");
+        let okm = Pbkdf2::new(4096)
+            .with_salt(b"saltSALTsaltSALTsaltSALTsaltSALTsalt")
+            .derive(b"passwordPASSWORDpassword", 25)
+            .unwrap();
+        assert_eq!(
+            secret(okm),
+            "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038"
+        );
+
+        let err = Pbkdf2::new(0).derive(b"password", 20).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadParameters);
+    }
+}
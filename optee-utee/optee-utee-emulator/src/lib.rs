@@ -0,0 +1,167 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A functional emulation of the TEE Internal Core API on the host.
+//!
+//! Unlike the mocked object functions of `optee-utee-mock`, which return what
+//! the test expects, the emulator implements the functions of `optee-utee-sys`
+//! so that code built on `optee-utee` behaves as in a TA:
+//!
+//! * persistent objects, in memory or in a directory (see
+//!   [`use_storage_dir`]), with the data stream and enumerator functions, the
+//!   private storage being the REE storage (see
+//!   [`use_rpmb_as_private_storage`]);
+//! * transient objects, attributes and key generation;
+//! * digest, MAC, symmetric cipher, authenticated encryption, asymmetric
+//!   cipher, signature and ECDH key derivation operations, backed by the
+//!   RustCrypto crates, the SM2 signature, encryption and key exchange, and
+//!   the HKDF, Concat KDF and PBKDF2 key derivations of OP-TEE;
+//! * random numbers, system, REE and TA persistent time (see
+//!   [`roll_back_ta_time`]), cancellation, properties (see [`set_property`])
+//!   and trace output, which is printed to the standard output.
+//!
+//! The state of the emulator (storage, properties, time, cancellation) is kept
+//! per thread, so tests running in parallel do not see each other's objects.
+//! Call [`reset`] to start again from an empty storage within a test.
+//!
+//! The emulator and `optee-utee-mock` define the same functions, a test
+//! binary depends on one of them. The functions are only linked in if the
+//! crate is used, so make sure the tests refer to it, e.g. by calling
+//! [`reset`].
+//!
+//! # Examples
+//!
+//! ``` rust,ignore
+//! use optee_utee::{DataFlag, ObjectStorageConstants, PersistentObject};
+//! use optee_utee_emulator as emulator;
+//!
+//! #[test]
+//! fn test_store_secret() {
+//!     emulator::reset();
+//!     let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE;
+//!     let mut object = PersistentObject::create(
+//!         ObjectStorageConstants::Private,
+//!         b"secret",
+//!         flags,
+//!         None,
+//!         b"42",
+//!     )
+//!     .unwrap();
+//!     let mut buffer = [0u8; 2];
+//!     assert_eq!(object.read(&mut buffer).unwrap(), 2);
+//!     assert_eq!(&buffer, b"42");
+//! }
+//! ```
+//!
+//! Algorithms which are not supported (e.g. DSA, DH, AES-XTS)
+//! make `TEE_AllocateOperation` fail with `TEE_ERROR_NOT_SUPPORTED`, and the
+//! arithmetical, internal client and socket APIs are not emulated.
+
+#![allow(non_snake_case)]
+
+pub use self::property::{set_property, PropertySet, PropertyValue};
+pub use self::storage::{use_rpmb_as_private_storage, use_storage_dir};
+pub use self::system::{request_cancellation, roll_back_ta_time};
+
+use optee_utee_sys as raw;
+use std::cell::RefCell;
+
+mod asymmetric;
+mod crypto;
+mod kdf;
+mod object;
+mod property;
+mod sm2;
+mod storage;
+mod symmetric;
+mod system;
+
+// The state of the emulator for the current thread.
+#[derive(Default)]
+struct State {
+    storage: storage::Storage,
+    properties: property::Properties,
+    time: system::TimeState,
+    cancellation: system::CancellationState,
+    trace_level: i32,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+/// Resets the state of the emulator for the current thread: the storage is
+/// emptied (or switched back to memory), and the properties, the TA
+/// persistent time and the cancellation flag are restored to their defaults.
+///
+/// Handles opened before the reset must not be used afterwards.
+pub fn reset() {
+    with_state(|state| *state = State::default());
+}
+
+// Panics like `TEE_Panic`, for the misuses for which the specification
+// requires the TA to be panicked.
+fn tee_panic(function: &str, reason: &str) -> ! {
+    panic!("TEE_Panic in {}: {}", function, reason)
+}
+
+// Returns the slice of `len` bytes at `ptr`, accepting a null pointer for an
+// empty slice.
+unsafe fn input<'a>(ptr: *const core::ffi::c_void, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr as *const u8, len)
+    }
+}
+
+// Copies `data` to the output buffer `ptr` of `*len` bytes, or fails with
+// `TEE_ERROR_SHORT_BUFFER`, `*len` is set to the size of `data` in both
+// cases.
+unsafe fn output(data: &[u8], ptr: *mut core::ffi::c_void, len: *mut usize) -> u32 {
+    let available = *len;
+    *len = data.len();
+    if available < data.len() {
+        return crate::raw::TEE_ERROR_SHORT_BUFFER;
+    }
+    if !data.is_empty() {
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len());
+    }
+    crate::raw::TEE_SUCCESS
+}
+
+// Formats `data` as lowercase hexadecimal.
+fn hex(data: &[u8]) -> String {
+    use std::fmt::Write;
+    data.iter().fold(String::new(), |mut string, byte| {
+        let _ = write!(string, "{:02x}", byte);
+        string
+    })
+}
+
+// Parses lowercase or uppercase hexadecimal.
+#[cfg(test)]
+fn unhex(string: &str) -> Vec<u8> {
+    (0..string.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&string[index..index + 2], 16).unwrap())
+        .collect()
+}
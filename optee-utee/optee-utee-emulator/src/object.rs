@@ -0,0 +1,694 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Transient objects, attributes and the generic object functions.
+
+use super::{asymmetric, input, output, sm2, storage, tee_panic};
+use crate::raw::{self, TEE_Attribute, TEE_ObjectHandle, TEE_ObjectInfo, TEE_Result};
+use rand::RngCore;
+use std::ffi::c_void;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum AttributeValue {
+    Ref(Vec<u8>),
+    Value(u32, u32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Attribute {
+    pub id: u32,
+    pub value: AttributeValue,
+}
+
+impl Attribute {
+    pub fn buffer(id: u32, buffer: &[u8]) -> Self {
+        Self {
+            id,
+            value: AttributeValue::Ref(buffer.to_vec()),
+        }
+    }
+
+    pub fn value(id: u32, a: u32, b: u32) -> Self {
+        Self {
+            id,
+            value: AttributeValue::Value(a, b),
+        }
+    }
+
+    pub unsafe fn from_raw(raw_attr: &TEE_Attribute) -> Self {
+        if raw_attr.attributeID & raw::TEE_ATTR_FLAG_VALUE != 0 {
+            let value = raw_attr.content.value;
+            Self::value(raw_attr.attributeID, value.a, value.b)
+        } else {
+            let memref = raw_attr.content.memref;
+            Self::buffer(raw_attr.attributeID, input(memref.buffer, memref.size))
+        }
+    }
+}
+
+// Converts the attributes passed to a function of the API.
+pub(super) unsafe fn attributes_from_raw(
+    attrs: *const TEE_Attribute,
+    count: u32,
+) -> Vec<Attribute> {
+    if count == 0 {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(attrs, count as usize)
+        .iter()
+        .map(|attr| Attribute::from_raw(attr))
+        .collect()
+}
+
+pub(super) fn find_buffer(attributes: &[Attribute], id: u32) -> Option<&[u8]> {
+    attributes.iter().find_map(|attr| match &attr.value {
+        AttributeValue::Ref(buffer) if attr.id == id => Some(buffer.as_slice()),
+        _ => None,
+    })
+}
+
+pub(super) fn find_value(attributes: &[Attribute], id: u32) -> Option<(u32, u32)> {
+    attributes.iter().find_map(|attr| match attr.value {
+        AttributeValue::Value(a, b) if attr.id == id => Some((a, b)),
+        _ => None,
+    })
+}
+
+// The type, usage and attributes of an object, which make the key of an
+// operation.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct KeyObject {
+    pub object_type: u32,
+    pub object_size: u32,
+    pub max_object_size: u32,
+    pub usage: u32,
+    pub initialized: bool,
+    pub attributes: Vec<Attribute>,
+}
+
+impl KeyObject {
+    pub fn new(object_type: u32, max_object_size: u32) -> Self {
+        Self {
+            object_type,
+            object_size: 0,
+            max_object_size,
+            usage: 0xFFFF_FFFF,
+            initialized: false,
+            attributes: Vec::new(),
+        }
+    }
+
+    // The attributes of a pure data persistent object.
+    pub fn data() -> Self {
+        Self {
+            initialized: true,
+            ..Self::new(raw::TEE_TYPE_DATA, 0)
+        }
+    }
+
+    pub fn buffer(&self, id: u32) -> Option<&[u8]> {
+        find_buffer(&self.attributes, id)
+    }
+
+    pub fn value(&self, id: u32) -> Option<(u32, u32)> {
+        find_value(&self.attributes, id)
+    }
+}
+
+pub(super) enum Object {
+    Transient(KeyObject),
+    Persistent(storage::PersistentHandle),
+}
+
+impl Object {
+    pub fn key(&self) -> &KeyObject {
+        match self {
+            Object::Transient(key) => key,
+            Object::Persistent(handle) => &handle.key,
+        }
+    }
+}
+
+pub(super) fn into_handle(object: Object) -> TEE_ObjectHandle {
+    Box::into_raw(Box::new(object)) as TEE_ObjectHandle
+}
+
+pub(super) unsafe fn object_mut<'a>(handle: TEE_ObjectHandle, function: &str) -> &'a mut Object {
+    if handle.is_null() {
+        tee_panic(function, "invalid object handle");
+    }
+    &mut *(handle as *mut Object)
+}
+
+pub(super) unsafe fn transient_mut<'a>(
+    handle: TEE_ObjectHandle,
+    function: &str,
+) -> &'a mut KeyObject {
+    match object_mut(handle, function) {
+        Object::Transient(key) => key,
+        Object::Persistent(_) => tee_panic(function, "not a transient object"),
+    }
+}
+
+fn is_secret_type(object_type: u32) -> bool {
+    matches!(
+        object_type,
+        raw::TEE_TYPE_AES
+            | raw::TEE_TYPE_DES
+            | raw::TEE_TYPE_DES3
+            | raw::TEE_TYPE_SM4
+            | raw::TEE_TYPE_HMAC_MD5
+            | raw::TEE_TYPE_HMAC_SHA1
+            | raw::TEE_TYPE_HMAC_SHA224
+            | raw::TEE_TYPE_HMAC_SHA256
+            | raw::TEE_TYPE_HMAC_SHA384
+            | raw::TEE_TYPE_HMAC_SHA512
+            | raw::TEE_TYPE_HMAC_SM3
+            | raw::TEE_TYPE_GENERIC_SECRET
+    )
+}
+
+fn is_ecc_type(object_type: u32) -> bool {
+    matches!(
+        object_type,
+        raw::TEE_TYPE_ECDSA_PUBLIC_KEY
+            | raw::TEE_TYPE_ECDSA_KEYPAIR
+            | raw::TEE_TYPE_ECDH_PUBLIC_KEY
+            | raw::TEE_TYPE_ECDH_KEYPAIR
+    )
+}
+
+fn is_sm2_type(object_type: u32) -> bool {
+    matches!(
+        object_type,
+        raw::TEE_TYPE_SM2_DSA_PUBLIC_KEY
+            | raw::TEE_TYPE_SM2_DSA_KEYPAIR
+            | raw::TEE_TYPE_SM2_PKE_PUBLIC_KEY
+            | raw::TEE_TYPE_SM2_PKE_KEYPAIR
+            | raw::TEE_TYPE_SM2_KEP_PUBLIC_KEY
+            | raw::TEE_TYPE_SM2_KEP_KEYPAIR
+    )
+}
+
+// Returns whether `size` is a valid key size in bits for `object_type`, or
+// None if the object type is not supported.
+pub(super) fn is_valid_size(object_type: u32, size: u32) -> Option<bool> {
+    let in_range = |min: u32, max: u32| (min..=max).contains(&size) && size % 8 == 0;
+    let valid = match object_type {
+        raw::TEE_TYPE_AES => matches!(size, 128 | 192 | 256),
+        raw::TEE_TYPE_DES => matches!(size, 56 | 64),
+        raw::TEE_TYPE_DES3 => matches!(size, 112 | 128 | 168 | 192),
+        raw::TEE_TYPE_SM4 => size == 128,
+        raw::TEE_TYPE_HMAC_MD5 => in_range(64, 512),
+        raw::TEE_TYPE_HMAC_SHA1 => in_range(80, 512),
+        raw::TEE_TYPE_HMAC_SHA224 => in_range(112, 512),
+        raw::TEE_TYPE_HMAC_SHA256 => in_range(192, 1024),
+        raw::TEE_TYPE_HMAC_SHA384 | raw::TEE_TYPE_HMAC_SHA512 => in_range(256, 1024),
+        raw::TEE_TYPE_HMAC_SM3 => in_range(80, 1024),
+        raw::TEE_TYPE_GENERIC_SECRET
+        | raw::TEE_TYPE_HKDF_IKM
+        | raw::TEE_TYPE_CONCAT_KDF_Z
+        | raw::TEE_TYPE_PBKDF2_PASSWORD => in_range(8, 4096),
+        raw::TEE_TYPE_RSA_PUBLIC_KEY | raw::TEE_TYPE_RSA_KEYPAIR => (256..=4096).contains(&size),
+        object_type if is_ecc_type(object_type) => matches!(size, 256 | 384),
+        object_type if is_sm2_type(object_type) => size == 256,
+        asymmetric::TEE_TYPE_ED25519_PUBLIC_KEY | asymmetric::TEE_TYPE_ED25519_KEYPAIR => {
+            size == 256
+        }
+        _ => return None,
+    };
+    Some(valid)
+}
+
+// Returns the required and optional attributes of an object type.
+fn attribute_ids(object_type: u32) -> (&'static [u32], &'static [u32]) {
+    match object_type {
+        object_type if is_secret_type(object_type) => (&[raw::TEE_ATTR_SECRET_VALUE], &[]),
+        raw::TEE_TYPE_RSA_PUBLIC_KEY => (
+            &[raw::TEE_ATTR_RSA_MODULUS, raw::TEE_ATTR_RSA_PUBLIC_EXPONENT],
+            &[],
+        ),
+        raw::TEE_TYPE_RSA_KEYPAIR => (
+            &[
+                raw::TEE_ATTR_RSA_MODULUS,
+                raw::TEE_ATTR_RSA_PUBLIC_EXPONENT,
+                raw::TEE_ATTR_RSA_PRIVATE_EXPONENT,
+            ],
+            &[
+                raw::TEE_ATTR_RSA_PRIME1,
+                raw::TEE_ATTR_RSA_PRIME2,
+                raw::TEE_ATTR_RSA_EXPONENT1,
+                raw::TEE_ATTR_RSA_EXPONENT2,
+                raw::TEE_ATTR_RSA_COEFFICIENT,
+            ],
+        ),
+        raw::TEE_TYPE_ECDSA_PUBLIC_KEY | raw::TEE_TYPE_ECDH_PUBLIC_KEY => (
+            &[
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_X,
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y,
+                raw::TEE_ATTR_ECC_CURVE,
+            ],
+            &[],
+        ),
+        raw::TEE_TYPE_ECDSA_KEYPAIR | raw::TEE_TYPE_ECDH_KEYPAIR => (
+            &[
+                raw::TEE_ATTR_ECC_PRIVATE_VALUE,
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_X,
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y,
+                raw::TEE_ATTR_ECC_CURVE,
+            ],
+            &[],
+        ),
+        // The SM2 keys have no curve attribute, their type implies it.
+        raw::TEE_TYPE_SM2_DSA_PUBLIC_KEY
+        | raw::TEE_TYPE_SM2_PKE_PUBLIC_KEY
+        | raw::TEE_TYPE_SM2_KEP_PUBLIC_KEY => (
+            &[
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_X,
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y,
+            ],
+            &[],
+        ),
+        raw::TEE_TYPE_SM2_DSA_KEYPAIR
+        | raw::TEE_TYPE_SM2_PKE_KEYPAIR
+        | raw::TEE_TYPE_SM2_KEP_KEYPAIR => (
+            &[
+                raw::TEE_ATTR_ECC_PRIVATE_VALUE,
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_X,
+                raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y,
+            ],
+            &[],
+        ),
+        asymmetric::TEE_TYPE_ED25519_PUBLIC_KEY => {
+            (&[asymmetric::TEE_ATTR_ED25519_PUBLIC_VALUE], &[])
+        }
+        asymmetric::TEE_TYPE_ED25519_KEYPAIR => (
+            &[
+                asymmetric::TEE_ATTR_ED25519_PRIVATE_VALUE,
+                asymmetric::TEE_ATTR_ED25519_PUBLIC_VALUE,
+            ],
+            &[],
+        ),
+        raw::TEE_TYPE_HKDF_IKM => (&[raw::TEE_ATTR_HKDF_IKM], &[]),
+        raw::TEE_TYPE_CONCAT_KDF_Z => (&[raw::TEE_ATTR_CONCAT_KDF_Z], &[]),
+        raw::TEE_TYPE_PBKDF2_PASSWORD => (&[raw::TEE_ATTR_PBKDF2_PASSWORD], &[]),
+        _ => (&[], &[]),
+    }
+}
+
+fn bit_length(buffer: &[u8]) -> u32 {
+    match buffer.iter().position(|byte| *byte != 0) {
+        Some(first) => (buffer.len() - first) as u32 * 8 - buffer[first].leading_zeros(),
+        None => 0,
+    }
+}
+
+// Returns the size in bits of the key made of `attributes`.
+fn key_size(object_type: u32, attributes: &[Attribute]) -> Option<u32> {
+    match object_type {
+        object_type if is_secret_type(object_type) => {
+            find_buffer(attributes, raw::TEE_ATTR_SECRET_VALUE).map(|key| key.len() as u32 * 8)
+        }
+        raw::TEE_TYPE_RSA_PUBLIC_KEY | raw::TEE_TYPE_RSA_KEYPAIR => {
+            find_buffer(attributes, raw::TEE_ATTR_RSA_MODULUS).map(bit_length)
+        }
+        object_type if is_ecc_type(object_type) => {
+            let (curve, _) = find_value(attributes, raw::TEE_ATTR_ECC_CURVE)?;
+            asymmetric::curve_size(curve)
+        }
+        object_type if is_sm2_type(object_type) => Some(256),
+        asymmetric::TEE_TYPE_ED25519_PUBLIC_KEY | asymmetric::TEE_TYPE_ED25519_KEYPAIR => Some(256),
+        raw::TEE_TYPE_HKDF_IKM | raw::TEE_TYPE_CONCAT_KDF_Z | raw::TEE_TYPE_PBKDF2_PASSWORD => {
+            let (required, _) = attribute_ids(object_type);
+            find_buffer(attributes, required[0]).map(|key| key.len() as u32 * 8)
+        }
+        _ => None,
+    }
+}
+
+// Checks `attributes` against the ones of `key` and returns the key size.
+fn check_attributes(key: &KeyObject, attributes: &[Attribute]) -> Result<u32, TEE_Result> {
+    let (required, optional) = attribute_ids(key.object_type);
+    for (index, attr) in attributes.iter().enumerate() {
+        let known = required.contains(&attr.id) || optional.contains(&attr.id);
+        let duplicated = attributes[..index].iter().any(|other| other.id == attr.id);
+        if !known || duplicated {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+    }
+    if !required
+        .iter()
+        .all(|id| attributes.iter().any(|attr| attr.id == *id))
+    {
+        return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+    }
+    let size = key_size(key.object_type, attributes).ok_or(raw::TEE_ERROR_BAD_PARAMETERS)?;
+    let exact = matches!(
+        key.object_type,
+        raw::TEE_TYPE_AES | raw::TEE_TYPE_DES | raw::TEE_TYPE_DES3 | raw::TEE_TYPE_SM4
+    );
+    if size > key.max_object_size || (exact && is_valid_size(key.object_type, size) != Some(true)) {
+        return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+    }
+    Ok(size)
+}
+
+fn generate_attributes(
+    object_type: u32,
+    key_size: u32,
+    params: &[Attribute],
+) -> Result<Vec<Attribute>, TEE_Result> {
+    match object_type {
+        object_type if is_secret_type(object_type) => {
+            let mut secret = vec![0u8; key_size as usize / 8];
+            rand::rngs::OsRng.fill_bytes(&mut secret);
+            Ok(vec![Attribute::buffer(raw::TEE_ATTR_SECRET_VALUE, &secret)])
+        }
+        raw::TEE_TYPE_RSA_KEYPAIR => {
+            let exponent = find_buffer(params, raw::TEE_ATTR_RSA_PUBLIC_EXPONENT);
+            asymmetric::generate_rsa(key_size, exponent)
+        }
+        raw::TEE_TYPE_ECDSA_KEYPAIR | raw::TEE_TYPE_ECDH_KEYPAIR => {
+            let (curve, _) =
+                find_value(params, raw::TEE_ATTR_ECC_CURVE).ok_or(raw::TEE_ERROR_BAD_PARAMETERS)?;
+            if asymmetric::curve_size(curve) != Some(key_size) {
+                return Err(raw::TEE_ERROR_NOT_SUPPORTED);
+            }
+            asymmetric::generate_ecc(curve)
+        }
+        asymmetric::TEE_TYPE_ED25519_KEYPAIR => Ok(asymmetric::generate_ed25519()),
+        // The curve is implied by the type, OP-TEE rejects a curve parameter.
+        raw::TEE_TYPE_SM2_DSA_KEYPAIR
+        | raw::TEE_TYPE_SM2_PKE_KEYPAIR
+        | raw::TEE_TYPE_SM2_KEP_KEYPAIR => match params.is_empty() {
+            true => Ok(sm2::generate()),
+            false => Err(raw::TEE_ERROR_BAD_PARAMETERS),
+        },
+        _ => Err(raw::TEE_ERROR_NOT_SUPPORTED),
+    }
+}
+
+// Returns the information of an object, for transient and persistent ones.
+pub(super) fn object_info(object: &Object) -> TEE_ObjectInfo {
+    let key = object.key();
+    let mut info = TEE_ObjectInfo {
+        objectType: key.object_type,
+        objectSize: key.object_size,
+        maxObjectSize: key.max_object_size,
+        objectUsage: key.usage,
+        dataSize: 0,
+        dataPosition: 0,
+        handleFlags: 0,
+    };
+    if key.initialized {
+        info.handleFlags |= raw::TEE_HANDLE_FLAG_INITIALIZED;
+    }
+    if let Object::Persistent(handle) = object {
+        storage::fill_info(handle, &mut info);
+    }
+    info
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AllocateTransientObject(
+    objectType: u32,
+    maxObjectSize: u32,
+    object: *mut TEE_ObjectHandle,
+) -> TEE_Result {
+    if objectType == raw::TEE_TYPE_DATA {
+        return raw::TEE_ERROR_NOT_SUPPORTED;
+    }
+    match is_valid_size(objectType, maxObjectSize) {
+        Some(true) => {}
+        _ => return raw::TEE_ERROR_NOT_SUPPORTED,
+    }
+    let key = KeyObject::new(objectType, maxObjectSize);
+    unsafe { *object = into_handle(Object::Transient(key)) };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_FreeTransientObject(object: TEE_ObjectHandle) {
+    if object.is_null() {
+        return;
+    }
+    unsafe {
+        transient_mut(object, "TEE_FreeTransientObject");
+        drop(Box::from_raw(object as *mut Object));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_ResetTransientObject(object: TEE_ObjectHandle) {
+    if object.is_null() {
+        return;
+    }
+    let key = unsafe { transient_mut(object, "TEE_ResetTransientObject") };
+    *key = KeyObject::new(key.object_type, key.max_object_size);
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_PopulateTransientObject(
+    object: TEE_ObjectHandle,
+    attrs: *const TEE_Attribute,
+    attrCount: u32,
+) -> TEE_Result {
+    let key = unsafe { transient_mut(object, "TEE_PopulateTransientObject") };
+    if key.initialized {
+        tee_panic("TEE_PopulateTransientObject", "object already initialized");
+    }
+    let attributes = unsafe { attributes_from_raw(attrs, attrCount) };
+    match check_attributes(key, &attributes) {
+        Ok(size) => {
+            key.object_size = size;
+            key.attributes = attributes;
+            key.initialized = true;
+            raw::TEE_SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_InitRefAttribute(
+    attr: *mut TEE_Attribute,
+    attributeID: u32,
+    buffer: *const c_void,
+    length: usize,
+) {
+    if attributeID & raw::TEE_ATTR_FLAG_VALUE != 0 {
+        tee_panic("TEE_InitRefAttribute", "not a buffer attribute");
+    }
+    unsafe {
+        (*attr).attributeID = attributeID;
+        (*attr).content.memref = raw::Memref {
+            buffer: buffer as *mut c_void,
+            size: length,
+        };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_InitValueAttribute(
+    attr: *mut TEE_Attribute,
+    attributeID: u32,
+    a: u32,
+    b: u32,
+) {
+    if attributeID & raw::TEE_ATTR_FLAG_VALUE == 0 {
+        tee_panic("TEE_InitValueAttribute", "not a value attribute");
+    }
+    unsafe {
+        (*attr).attributeID = attributeID;
+        (*attr).content.value = raw::Value { a, b };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CopyObjectAttributes1(
+    destObject: TEE_ObjectHandle,
+    srcObject: TEE_ObjectHandle,
+) -> TEE_Result {
+    let src = unsafe { object_mut(srcObject, "TEE_CopyObjectAttributes1") }
+        .key()
+        .clone();
+    let dest = unsafe { transient_mut(destObject, "TEE_CopyObjectAttributes1") };
+    if dest.initialized || !src.initialized {
+        tee_panic("TEE_CopyObjectAttributes1", "bad object state");
+    }
+    let attributes = if dest.object_type == src.object_type {
+        src.attributes
+    } else if src.object_type & !0x0100_0000 == dest.object_type
+        && src.object_type != dest.object_type
+    {
+        // A key pair is copied to a public key.
+        let (required, _) = attribute_ids(dest.object_type);
+        src.attributes
+            .into_iter()
+            .filter(|attr| required.contains(&attr.id))
+            .collect()
+    } else {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    };
+    if src.object_size > dest.max_object_size {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    dest.attributes = attributes;
+    dest.object_size = src.object_size;
+    dest.usage &= src.usage;
+    dest.initialized = true;
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CopyObjectAttributes(
+    destObject: TEE_ObjectHandle,
+    srcObject: TEE_ObjectHandle,
+) {
+    if TEE_CopyObjectAttributes1(destObject, srcObject) != raw::TEE_SUCCESS {
+        tee_panic("TEE_CopyObjectAttributes", "incompatible objects");
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GenerateKey(
+    object: TEE_ObjectHandle,
+    keySize: u32,
+    params: *const TEE_Attribute,
+    paramCount: u32,
+) -> TEE_Result {
+    let key = unsafe { transient_mut(object, "TEE_GenerateKey") };
+    if key.initialized {
+        tee_panic("TEE_GenerateKey", "object already initialized");
+    }
+    if keySize > key.max_object_size || is_valid_size(key.object_type, keySize) != Some(true) {
+        return raw::TEE_ERROR_NOT_SUPPORTED;
+    }
+    let params = unsafe { attributes_from_raw(params, paramCount) };
+    match generate_attributes(key.object_type, keySize, &params) {
+        Ok(attributes) => {
+            key.attributes = attributes;
+            key.object_size = keySize;
+            key.initialized = true;
+            raw::TEE_SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetObjectInfo1(
+    object: TEE_ObjectHandle,
+    objectInfo: *mut TEE_ObjectInfo,
+) -> TEE_Result {
+    let object = unsafe { object_mut(object, "TEE_GetObjectInfo1") };
+    unsafe { *objectInfo = object_info(object) };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetObjectInfo(object: TEE_ObjectHandle, objectInfo: *mut TEE_ObjectInfo) {
+    TEE_GetObjectInfo1(object, objectInfo);
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_RestrictObjectUsage1(
+    object: TEE_ObjectHandle,
+    objectUsage: u32,
+) -> TEE_Result {
+    match unsafe { object_mut(object, "TEE_RestrictObjectUsage1") } {
+        Object::Transient(key) => {
+            key.usage &= objectUsage;
+            raw::TEE_SUCCESS
+        }
+        Object::Persistent(handle) => storage::restrict_usage(handle, objectUsage),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_RestrictObjectUsage(object: TEE_ObjectHandle, objectUsage: u32) {
+    TEE_RestrictObjectUsage1(object, objectUsage);
+}
+
+// Checks that an attribute can be read from `key`.
+fn check_readable(key: &KeyObject, attributeID: u32, is_value: bool) -> TEE_Result {
+    if !key.initialized {
+        return raw::TEE_ERROR_ITEM_NOT_FOUND;
+    }
+    if (attributeID & raw::TEE_ATTR_FLAG_VALUE != 0) != is_value {
+        return raw::TEE_ERROR_BAD_PARAMETERS;
+    }
+    if attributeID & raw::TEE_ATTR_FLAG_PUBLIC == 0 && key.usage & raw::TEE_USAGE_EXTRACTABLE == 0 {
+        return raw::TEE_ERROR_ACCESS_DENIED;
+    }
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetObjectBufferAttribute(
+    object: TEE_ObjectHandle,
+    attributeID: u32,
+    buffer: *mut c_void,
+    size: *mut usize,
+) -> TEE_Result {
+    let key = unsafe { object_mut(object, "TEE_GetObjectBufferAttribute") }.key();
+    match check_readable(key, attributeID, false) {
+        raw::TEE_SUCCESS => match key.buffer(attributeID) {
+            Some(value) => unsafe { output(value, buffer, size) },
+            None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+        },
+        code => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetObjectValueAttribute(
+    object: TEE_ObjectHandle,
+    attributeID: u32,
+    a: *mut u32,
+    b: *mut u32,
+) -> TEE_Result {
+    let key = unsafe { object_mut(object, "TEE_GetObjectValueAttribute") }.key();
+    match check_readable(key, attributeID, true) {
+        raw::TEE_SUCCESS => match key.value(attributeID) {
+            Some((value_a, value_b)) => {
+                unsafe {
+                    if !a.is_null() {
+                        *a = value_a;
+                    }
+                    if !b.is_null() {
+                        *b = value_b;
+                    }
+                }
+                raw::TEE_SUCCESS
+            }
+            None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+        },
+        code => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CloseObject(object: TEE_ObjectHandle) {
+    if object.is_null() {
+        return;
+    }
+    let object = unsafe { Box::from_raw(object as *mut Object) };
+    if let Object::Persistent(handle) = *object {
+        storage::close(handle);
+    }
+}
@@ -0,0 +1,500 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// The property sets of the TEE, the client and the TA.
+
+use super::{output, tee_panic, with_state};
+use crate::raw::{self, TEE_PropSetHandle, TEE_Result};
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void, CStr};
+
+/// The property sets which can be configured with [`set_property`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PropertySet {
+    TeeImplementation,
+    CurrentClient,
+    CurrentTa,
+}
+
+impl PropertySet {
+    fn from_raw(handle: TEE_PropSetHandle) -> Option<Self> {
+        if handle == raw::TEE_PROPSET_TEE_IMPLEMENTATION {
+            Some(PropertySet::TeeImplementation)
+        } else if handle == raw::TEE_PROPSET_CURRENT_CLIENT {
+            Some(PropertySet::CurrentClient)
+        } else if handle == raw::TEE_PROPSET_CURRENT_TA {
+            Some(PropertySet::CurrentTa)
+        } else {
+            None
+        }
+    }
+}
+
+/// The value of a property, of one of the types of the specification.
+#[derive(Clone)]
+pub enum PropertyValue {
+    String(String),
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    BinaryBlock(Vec<u8>),
+    Uuid(raw::TEE_UUID),
+    Identity(raw::TEE_Identity),
+}
+
+fn format_uuid(uuid: &raw::TEE_UUID) -> String {
+    let node = &uuid.clockSeqAndNode;
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{}",
+        uuid.timeLow,
+        uuid.timeMid,
+        uuid.timeHiAndVersion,
+        node[0],
+        node[1],
+        super::hex(&node[2..])
+    )
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * index)) as usize & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+impl PropertyValue {
+    // Every property can be read as a string.
+    fn as_string(&self) -> String {
+        match self {
+            PropertyValue::String(value) => value.clone(),
+            PropertyValue::Bool(value) => value.to_string(),
+            PropertyValue::U32(value) => value.to_string(),
+            PropertyValue::U64(value) => value.to_string(),
+            PropertyValue::BinaryBlock(value) => base64(value),
+            PropertyValue::Uuid(value) => format_uuid(value),
+            PropertyValue::Identity(value) => {
+                format!("{}:{}", value.login, format_uuid(&value.uuid))
+            }
+        }
+    }
+}
+
+const NIL_UUID: raw::TEE_UUID = raw::TEE_UUID {
+    timeLow: 0,
+    timeMid: 0,
+    timeHiAndVersion: 0,
+    clockSeqAndNode: [0; 8],
+};
+
+pub(super) struct Properties {
+    tee: BTreeMap<String, PropertyValue>,
+    client: BTreeMap<String, PropertyValue>,
+    ta: BTreeMap<String, PropertyValue>,
+}
+
+impl Properties {
+    fn set(&self, set: PropertySet) -> &BTreeMap<String, PropertyValue> {
+        match set {
+            PropertySet::TeeImplementation => &self.tee,
+            PropertySet::CurrentClient => &self.client,
+            PropertySet::CurrentTa => &self.ta,
+        }
+    }
+
+    fn set_mut(&mut self, set: PropertySet) -> &mut BTreeMap<String, PropertyValue> {
+        match set {
+            PropertySet::TeeImplementation => &mut self.tee,
+            PropertySet::CurrentClient => &mut self.client,
+            PropertySet::CurrentTa => &mut self.ta,
+        }
+    }
+}
+
+impl Default for Properties {
+    fn default() -> Self {
+        use PropertyValue::*;
+        let string = |value: &str| String(value.to_owned());
+        let ta = [
+            ("gpd.ta.appID", Uuid(NIL_UUID)),
+            ("gpd.ta.singleInstance", Bool(true)),
+            ("gpd.ta.multiSession", Bool(false)),
+            ("gpd.ta.instanceKeepAlive", Bool(false)),
+            ("gpd.ta.dataSize", U32(32 * 1024)),
+            ("gpd.ta.stackSize", U32(2 * 1024)),
+            ("gpd.ta.version", string("0.0")),
+            ("gpd.ta.description", string("")),
+            ("gpd.ta.endian", U32(0)),
+            ("gpd.ta.doesNotCloseHandleOnCorruptObject", Bool(false)),
+        ];
+        let client = [
+            (
+                "gpd.client.identity",
+                Identity(raw::TEE_Identity {
+                    login: raw::TEE_LOGIN_PUBLIC,
+                    uuid: NIL_UUID,
+                }),
+            ),
+            ("gpd.client.endian", U32(0)),
+        ];
+        let tee = [
+            ("gpd.tee.apiversion", string("1.3.1")),
+            ("gpd.tee.internalCore.version", U32(0x0103_0100)),
+            ("gpd.tee.description", string("optee-utee-emulator")),
+            ("gpd.tee.deviceID", Uuid(NIL_UUID)),
+            ("gpd.tee.systemTime.protectionLevel", U32(100)),
+            ("gpd.tee.TAPersistentTime.protectionLevel", U32(100)),
+            ("gpd.tee.arith.maxBigIntSize", U32(4096)),
+            ("gpd.tee.cryptography.ecc", Bool(true)),
+            ("gpd.tee.cryptography.nist", Bool(true)),
+            ("gpd.tee.cryptography.bsi-r", Bool(false)),
+            ("gpd.tee.cryptography.bsi-t", Bool(false)),
+            ("gpd.tee.cryptography.ietf", Bool(false)),
+            ("gpd.tee.cryptography.octa", Bool(false)),
+            ("gpd.tee.trustedStorage.private.rollbackProtection", U32(0)),
+            ("gpd.tee.trustedStorage.perso.rollbackProtection", U32(0)),
+            (
+                "gpd.tee.trustedStorage.protected.rollbackProtection",
+                U32(0),
+            ),
+            (
+                "gpd.tee.trustedStorage.antiRollback.protectionLevel",
+                U32(0),
+            ),
+            (
+                "gpd.tee.trustedStorage.rollbackDetection.protectionLevel",
+                U32(0),
+            ),
+            ("gpd.tee.trustedos.implementation.version", string("0")),
+            (
+                "gpd.tee.trustedos.implementation.binaryversion",
+                BinaryBlock(vec![0; 4]),
+            ),
+            ("gpd.tee.trustedos.manufacturer", string("Apache Teaclave")),
+            ("gpd.tee.firmware.implementation.version", string("0")),
+            (
+                "gpd.tee.firmware.implementation.binaryversion",
+                BinaryBlock(vec![0; 4]),
+            ),
+            ("gpd.tee.firmware.manufacturer", string("Apache Teaclave")),
+            ("gpd.tee.event.maxSources", U32(0)),
+        ];
+        let collect = |properties: &[(&str, PropertyValue)]| {
+            properties
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect()
+        };
+        Self {
+            tee: collect(&tee),
+            client: collect(&client),
+            ta: collect(&ta),
+        }
+    }
+}
+
+/// Sets the value of a property for the current thread, e.g. to emulate
+/// another client identity or a TA configuration. New properties can be
+/// added, the default ones (the `gpd.*` properties of the specification)
+/// are restored by [`reset`](super::reset).
+///
+/// # Examples
+///
+/// ``` rust,ignore
+/// use optee_utee_emulator::{set_property, PropertySet, PropertyValue};
+///
+/// set_property(
+///     PropertySet::CurrentTa,
+///     "gpd.ta.description",
+///     PropertyValue::String("Hello World TA".to_string()),
+/// );
+/// ```
+pub fn set_property(set: PropertySet, name: &str, value: PropertyValue) {
+    with_state(|state| {
+        state.properties.set_mut(set).insert(name.to_owned(), value);
+    });
+}
+
+struct Enumerator {
+    names: Vec<String>,
+    set: Option<PropertySet>,
+    next: usize,
+}
+
+// Returns the value of the property `name` of a property set, or of the
+// current property of an enumerator.
+unsafe fn lookup(handle: TEE_PropSetHandle, name: *const c_char) -> Option<PropertyValue> {
+    let (set, name) = match PropertySet::from_raw(handle) {
+        Some(set) => {
+            if name.is_null() {
+                return None;
+            }
+            (set, CStr::from_ptr(name).to_string_lossy().into_owned())
+        }
+        None => {
+            let enumerator = enumerator_mut(handle, "TEE_GetPropertyAs");
+            (
+                enumerator.set?,
+                enumerator.names.get(enumerator.next)?.clone(),
+            )
+        }
+    };
+    with_state(|state| state.properties.set(set).get(&name).cloned())
+}
+
+unsafe fn enumerator_mut<'a>(handle: TEE_PropSetHandle, function: &str) -> &'a mut Enumerator {
+    if handle.is_null() {
+        tee_panic(function, "invalid property enumerator");
+    }
+    &mut *(handle as *mut Enumerator)
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsString(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    valueBuffer: *mut c_char,
+    valueBufferLen: *mut usize,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(value) => {
+            let mut string = value.as_string().into_bytes();
+            string.push(0);
+            unsafe { output(&string, valueBuffer as *mut c_void, valueBufferLen) }
+        }
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsBool(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    value: *mut bool,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(PropertyValue::Bool(property)) => {
+            unsafe { *value = property };
+            raw::TEE_SUCCESS
+        }
+        Some(_) => raw::TEE_ERROR_BAD_FORMAT,
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsU32(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    value: *mut u32,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(PropertyValue::U32(property)) => {
+            unsafe { *value = property };
+            raw::TEE_SUCCESS
+        }
+        Some(_) => raw::TEE_ERROR_BAD_FORMAT,
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsU64(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    value: *mut u64,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(PropertyValue::U64(property)) => {
+            unsafe { *value = property };
+            raw::TEE_SUCCESS
+        }
+        Some(PropertyValue::U32(property)) => {
+            unsafe { *value = property as u64 };
+            raw::TEE_SUCCESS
+        }
+        Some(_) => raw::TEE_ERROR_BAD_FORMAT,
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsBinaryBlock(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    valueBuffer: *mut c_void,
+    valueBufferLen: *mut usize,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(PropertyValue::BinaryBlock(property)) => unsafe {
+            output(&property, valueBuffer, valueBufferLen)
+        },
+        Some(_) => raw::TEE_ERROR_BAD_FORMAT,
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsUUID(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    value: *mut raw::TEE_UUID,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(PropertyValue::Uuid(property)) => {
+            unsafe { *value = property };
+            raw::TEE_SUCCESS
+        }
+        Some(_) => raw::TEE_ERROR_BAD_FORMAT,
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyAsIdentity(
+    propsetOrEnumerator: TEE_PropSetHandle,
+    name: *const c_char,
+    value: *mut raw::TEE_Identity,
+) -> TEE_Result {
+    match unsafe { lookup(propsetOrEnumerator, name) } {
+        Some(PropertyValue::Identity(property)) => {
+            unsafe { *value = property };
+            raw::TEE_SUCCESS
+        }
+        Some(_) => raw::TEE_ERROR_BAD_FORMAT,
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AllocatePropertyEnumerator(enumerator: *mut TEE_PropSetHandle) -> TEE_Result {
+    let new_enumerator = Enumerator {
+        names: Vec::new(),
+        set: None,
+        next: 0,
+    };
+    unsafe { *enumerator = Box::into_raw(Box::new(new_enumerator)) as TEE_PropSetHandle };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_FreePropertyEnumerator(enumerator: TEE_PropSetHandle) {
+    if !enumerator.is_null() {
+        drop(unsafe { Box::from_raw(enumerator as *mut Enumerator) });
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_StartPropertyEnumerator(
+    enumerator: TEE_PropSetHandle,
+    propSet: TEE_PropSetHandle,
+) {
+    let enumerator = unsafe { enumerator_mut(enumerator, "TEE_StartPropertyEnumerator") };
+    let set = PropertySet::from_raw(propSet)
+        .unwrap_or_else(|| tee_panic("TEE_StartPropertyEnumerator", "invalid property set"));
+    enumerator.names = with_state(|state| state.properties.set(set).keys().cloned().collect());
+    enumerator.set = Some(set);
+    enumerator.next = 0;
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_ResetPropertyEnumerator(enumerator: TEE_PropSetHandle) {
+    let enumerator = unsafe { enumerator_mut(enumerator, "TEE_ResetPropertyEnumerator") };
+    enumerator.names.clear();
+    enumerator.set = None;
+    enumerator.next = 0;
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetPropertyName(
+    enumerator: TEE_PropSetHandle,
+    nameBuffer: *mut c_void,
+    nameBufferLen: *mut usize,
+) -> TEE_Result {
+    let enumerator = unsafe { enumerator_mut(enumerator, "TEE_GetPropertyName") };
+    match enumerator.names.get(enumerator.next) {
+        Some(name) => {
+            let mut name = name.clone().into_bytes();
+            name.push(0);
+            unsafe { output(&name, nameBuffer, nameBufferLen) }
+        }
+        None => raw::TEE_ERROR_ITEM_NOT_FOUND,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetNextProperty(enumerator: TEE_PropSetHandle) -> TEE_Result {
+    let enumerator = unsafe { enumerator_mut(enumerator, "TEE_GetNextProperty") };
+    if enumerator.set.is_none() || enumerator.next + 1 >= enumerator.names.len() {
+        enumerator.next = enumerator.names.len();
+        return raw::TEE_ERROR_ITEM_NOT_FOUND;
+    }
+    enumerator.next += 1;
+    raw::TEE_SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use optee_utee::property::{
+        ClientIdentity, PropertyKey, TaDescription, TeeApiVersion, TeeInternalCoreVersion,
+    };
+    use optee_utee::LoginType;
+
+    #[test]
+    fn test_default_properties() {
+        assert_eq!(TeeApiVersion.get().unwrap(), "1.3.1");
+        assert_eq!(TeeInternalCoreVersion.get().unwrap(), 0x0103_0100);
+        assert!(matches!(
+            ClientIdentity.get().unwrap().login_type(),
+            LoginType::Public
+        ));
+    }
+
+    #[test]
+    fn test_set_property() {
+        set_property(
+            PropertySet::CurrentTa,
+            "gpd.ta.description",
+            PropertyValue::String("Hello World TA".to_string()),
+        );
+        assert_eq!(TaDescription.get().unwrap(), "Hello World TA");
+
+        // A property can be read as a string whatever its type.
+        set_property(
+            PropertySet::CurrentTa,
+            "gpd.ta.description",
+            PropertyValue::BinaryBlock(b"hello".to_vec()),
+        );
+        assert_eq!(TaDescription.get().unwrap(), "aGVsbG8=");
+        super::super::reset();
+        assert_eq!(TaDescription.get().unwrap(), "");
+    }
+}
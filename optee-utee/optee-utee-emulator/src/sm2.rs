@@ -0,0 +1,671 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// SM2 keys and the SM2 signature, public key encryption and key exchange of
+// GB/T 32918. There is no RustCrypto crate for SM2 usable here, the curve
+// arithmetic is done with the big integers of the rsa crate: it is slow and
+// not constant time, which is fine for tests only.
+
+use super::object::{find_buffer, find_value, Attribute, KeyObject};
+use crate::raw::{self, TEE_Result};
+use digest::Digest;
+use rand::{rngs::OsRng, RngCore};
+use rsa::BigUint;
+use sm3::Sm3;
+use std::sync::OnceLock;
+
+// The size in bytes of the coordinates, private values and hashes.
+const LEN: usize = 32;
+// The size of the C1 point and the C3 hash of a ciphertext.
+pub(super) const PKE_OVERHEAD: usize = 1 + 2 * LEN + LEN;
+
+type Point = (BigUint, BigUint);
+
+// A point in Jacobian coordinates, the point at infinity has z = 0.
+#[derive(Clone)]
+struct Jacobian {
+    x: BigUint,
+    y: BigUint,
+    z: BigUint,
+}
+
+impl Jacobian {
+    fn infinity() -> Self {
+        Jacobian {
+            x: one(),
+            y: one(),
+            z: zero(),
+        }
+    }
+}
+
+// A curve y^2 = x^3 + ax + b over the prime field of p, with the generator g
+// of order n.
+pub(super) struct Curve {
+    p: BigUint,
+    a: BigUint,
+    b: BigUint,
+    n: BigUint,
+    g: Point,
+}
+
+fn int(hex: &str) -> BigUint {
+    BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+fn zero() -> BigUint {
+    BigUint::from(0u32)
+}
+
+fn one() -> BigUint {
+    BigUint::from(1u32)
+}
+
+// Returns `value` as a big-endian number of LEN bytes.
+fn to_bytes(value: &BigUint) -> [u8; LEN] {
+    let bytes = value.to_bytes_be();
+    let mut out = [0u8; LEN];
+    out[LEN - bytes.len()..].copy_from_slice(&bytes);
+    out
+}
+
+// The key derivation function of GB/T 32918.
+fn kdf(z: &[&[u8]], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + LEN);
+    let mut counter: u32 = 1;
+    while out.len() < len {
+        let mut hash = Sm3::new();
+        for part in z {
+            hash.update(part);
+        }
+        hash.update(counter.to_be_bytes());
+        out.extend_from_slice(&hash.finalize());
+        counter += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+impl Curve {
+    // The curve recommended by GB/T 32918.5, the one of the SM2 keys.
+    pub(super) fn recommended() -> &'static Curve {
+        static CURVE: OnceLock<Curve> = OnceLock::new();
+        CURVE.get_or_init(|| Curve {
+            p: int("FFFFFFFEFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF00000000FFFFFFFFFFFFFFFF"),
+            a: int("FFFFFFFEFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF00000000FFFFFFFFFFFFFFFC"),
+            b: int("28E9FA9E9D9F5E344D5A9E4BCF6509A7F39789F515AB8F92DDBCBD414D940E93"),
+            n: int("FFFFFFFEFFFFFFFFFFFFFFFFFFFFFFFF7203DF6B21C6052B53BBF40939D54123"),
+            g: (
+                int("32C4AE2C1F1981195F9904466A39C9948FE30BBFF2660BE1715A4589334C74C7"),
+                int("BC3736A2F4F6779C59BDCEE36B692153D0A9877CC62A474002DF32E52139F0A0"),
+            ),
+        })
+    }
+
+    fn add_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + b) % &self.p
+    }
+
+    fn sub_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + &self.p - b) % &self.p
+    }
+
+    fn mul_mod(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a * b) % &self.p
+    }
+
+    fn double(&self, point: &Jacobian) -> Jacobian {
+        if point.z == zero() || point.y == zero() {
+            return Jacobian::infinity();
+        }
+        let y2 = self.mul_mod(&point.y, &point.y);
+        let s = self.mul_mod(&BigUint::from(4u32), &self.mul_mod(&point.x, &y2));
+        let z2 = self.mul_mod(&point.z, &point.z);
+        let m = self.add_mod(
+            &self.mul_mod(&BigUint::from(3u32), &self.mul_mod(&point.x, &point.x)),
+            &self.mul_mod(&self.a, &self.mul_mod(&z2, &z2)),
+        );
+        let x = self.sub_mod(&self.mul_mod(&m, &m), &self.add_mod(&s, &s));
+        let y4 = self.mul_mod(&y2, &y2);
+        let y = self.sub_mod(
+            &self.mul_mod(&m, &self.sub_mod(&s, &x)),
+            &self.mul_mod(&BigUint::from(8u32), &y4),
+        );
+        let z = self.mul_mod(&BigUint::from(2u32), &self.mul_mod(&point.y, &point.z));
+        Jacobian { x, y, z }
+    }
+
+    fn add_jacobian(&self, p1: &Jacobian, p2: &Jacobian) -> Jacobian {
+        if p1.z == zero() {
+            return p2.clone();
+        }
+        if p2.z == zero() {
+            return p1.clone();
+        }
+        let z1z1 = self.mul_mod(&p1.z, &p1.z);
+        let z2z2 = self.mul_mod(&p2.z, &p2.z);
+        let u1 = self.mul_mod(&p1.x, &z2z2);
+        let u2 = self.mul_mod(&p2.x, &z1z1);
+        let s1 = self.mul_mod(&p1.y, &self.mul_mod(&p2.z, &z2z2));
+        let s2 = self.mul_mod(&p2.y, &self.mul_mod(&p1.z, &z1z1));
+        if u1 == u2 {
+            if s1 != s2 {
+                return Jacobian::infinity();
+            }
+            return self.double(p1);
+        }
+        let h = self.sub_mod(&u2, &u1);
+        let r = self.sub_mod(&s2, &s1);
+        let h2 = self.mul_mod(&h, &h);
+        let h3 = self.mul_mod(&h2, &h);
+        let u1h2 = self.mul_mod(&u1, &h2);
+        let x = self.sub_mod(
+            &self.sub_mod(&self.mul_mod(&r, &r), &h3),
+            &self.add_mod(&u1h2, &u1h2),
+        );
+        let y = self.sub_mod(
+            &self.mul_mod(&r, &self.sub_mod(&u1h2, &x)),
+            &self.mul_mod(&s1, &h3),
+        );
+        let z = self.mul_mod(&h, &self.mul_mod(&p1.z, &p2.z));
+        Jacobian { x, y, z }
+    }
+
+    fn to_affine(&self, point: &Jacobian) -> Option<Point> {
+        if point.z == zero() {
+            return None;
+        }
+        let z_inv = point.z.modpow(&(&self.p - BigUint::from(2u32)), &self.p);
+        let z_inv2 = self.mul_mod(&z_inv, &z_inv);
+        let x = self.mul_mod(&point.x, &z_inv2);
+        let y = self.mul_mod(&point.y, &self.mul_mod(&z_inv2, &z_inv));
+        Some((x, y))
+    }
+
+    fn from_affine(point: &Point) -> Jacobian {
+        Jacobian {
+            x: point.0.clone(),
+            y: point.1.clone(),
+            z: one(),
+        }
+    }
+
+    // Returns `k * point`, or None for the point at infinity.
+    fn mul(&self, k: &BigUint, point: &Point) -> Option<Point> {
+        let base = Self::from_affine(point);
+        let mut result = Jacobian::infinity();
+        for bit in (0..k.bits()).rev() {
+            result = self.double(&result);
+            if (k >> bit) & one() == one() {
+                result = self.add_jacobian(&result, &base);
+            }
+        }
+        self.to_affine(&result)
+    }
+
+    // Returns `p1 + p2`, or None for the point at infinity.
+    fn add(&self, p1: &Point, p2: &Point) -> Option<Point> {
+        self.to_affine(&self.add_jacobian(&Self::from_affine(p1), &Self::from_affine(p2)))
+    }
+
+    fn is_on_curve(&self, point: &Point) -> bool {
+        let (x, y) = point;
+        if x >= &self.p || y >= &self.p {
+            return false;
+        }
+        let rhs = self.add_mod(
+            &self.add_mod(
+                &self.mul_mod(x, &self.mul_mod(x, x)),
+                &self.mul_mod(&self.a, x),
+            ),
+            &self.b,
+        );
+        self.mul_mod(y, y) == rhs
+    }
+
+    // Returns a random number in [1, n - 1].
+    fn random_scalar(&self) -> BigUint {
+        let mut bytes = [0u8; LEN + 8];
+        OsRng.fill_bytes(&mut bytes);
+        BigUint::from_bytes_be(&bytes) % (&self.n - one()) + one()
+    }
+
+    fn is_valid_scalar(&self, value: &BigUint) -> bool {
+        value > &zero() && value < &self.n
+    }
+
+    pub(super) fn public_point(&self, private: &BigUint) -> Option<Point> {
+        self.mul(private, &self.g)
+    }
+
+    // Returns Z, the hash of the identifier and the public key of a user.
+    pub(super) fn z(&self, id: &[u8], public: &Point) -> [u8; LEN] {
+        let mut hash = Sm3::new();
+        hash.update(((id.len() * 8) as u16).to_be_bytes());
+        hash.update(id);
+        for value in [&self.a, &self.b, &self.g.0, &self.g.1, &public.0, &public.1] {
+            hash.update(to_bytes(value));
+        }
+        hash.finalize().into()
+    }
+
+    // Signs the digest `e` with the random `k`, returns None if another `k`
+    // is needed.
+    fn sign_with(&self, private: &BigUint, e: &BigUint, k: &BigUint) -> Option<Vec<u8>> {
+        let (x1, _) = self.mul(k, &self.g)?;
+        let r = (e + x1) % &self.n;
+        if r == zero() || &r + k == self.n {
+            return None;
+        }
+        let d1_inv = (private + one()).modpow(&(&self.n - BigUint::from(2u32)), &self.n);
+        let rd = (&r * private) % &self.n;
+        let s = (d1_inv * ((k + &self.n - rd) % &self.n)) % &self.n;
+        if s == zero() {
+            return None;
+        }
+        Some([to_bytes(&r), to_bytes(&s)].concat())
+    }
+
+    pub(super) fn sign(&self, private: &BigUint, digest: &[u8]) -> Vec<u8> {
+        let e = BigUint::from_bytes_be(digest);
+        loop {
+            if let Some(signature) = self.sign_with(private, &e, &self.random_scalar()) {
+                return signature;
+            }
+        }
+    }
+
+    pub(super) fn verify(&self, public: &Point, digest: &[u8], signature: &[u8]) -> bool {
+        if signature.len() != 2 * LEN {
+            return false;
+        }
+        let r = BigUint::from_bytes_be(&signature[..LEN]);
+        let s = BigUint::from_bytes_be(&signature[LEN..]);
+        if !self.is_valid_scalar(&r) || !self.is_valid_scalar(&s) {
+            return false;
+        }
+        let t = (&r + &s) % &self.n;
+        if t == zero() {
+            return false;
+        }
+        let point = match (self.mul(&s, &self.g), self.mul(&t, public)) {
+            (Some(sg), Some(tp)) => self.add(&sg, &tp),
+            (point, None) | (None, point) => point,
+        };
+        match point {
+            Some((x1, _)) => (BigUint::from_bytes_be(digest) + x1) % &self.n == r,
+            None => false,
+        }
+    }
+
+    // Encrypts `message` with the random `k`, returns C1 || C3 || C2, or
+    // None if another `k` is needed.
+    fn encrypt_with(&self, public: &Point, message: &[u8], k: &BigUint) -> Option<Vec<u8>> {
+        let (x1, y1) = self.mul(k, &self.g)?;
+        let (x2, y2) = self.mul(k, public)?;
+        let (x2, y2) = (to_bytes(&x2), to_bytes(&y2));
+        let t = kdf(&[&x2, &y2], message.len());
+        if !message.is_empty() && t.iter().all(|byte| *byte == 0) {
+            return None;
+        }
+        let mut ciphertext = Vec::with_capacity(PKE_OVERHEAD + message.len());
+        ciphertext.push(0x04);
+        ciphertext.extend_from_slice(&to_bytes(&x1));
+        ciphertext.extend_from_slice(&to_bytes(&y1));
+        ciphertext.extend_from_slice(
+            &Sm3::new()
+                .chain_update(x2)
+                .chain_update(message)
+                .chain_update(y2)
+                .finalize(),
+        );
+        ciphertext.extend(message.iter().zip(t).map(|(m, t)| m ^ t));
+        Some(ciphertext)
+    }
+
+    pub(super) fn encrypt(&self, public: &Point, message: &[u8]) -> Vec<u8> {
+        loop {
+            if let Some(ciphertext) = self.encrypt_with(public, message, &self.random_scalar()) {
+                return ciphertext;
+            }
+        }
+    }
+
+    pub(super) fn decrypt(&self, private: &BigUint, ciphertext: &[u8]) -> Option<Vec<u8>> {
+        if ciphertext.len() < PKE_OVERHEAD || ciphertext[0] != 0x04 {
+            return None;
+        }
+        let c1 = (
+            BigUint::from_bytes_be(&ciphertext[1..1 + LEN]),
+            BigUint::from_bytes_be(&ciphertext[1 + LEN..1 + 2 * LEN]),
+        );
+        let (c3, c2) = ciphertext[1 + 2 * LEN..].split_at(LEN);
+        if !self.is_on_curve(&c1) {
+            return None;
+        }
+        let (x2, y2) = self.mul(private, &c1)?;
+        let (x2, y2) = (to_bytes(&x2), to_bytes(&y2));
+        let t = kdf(&[&x2, &y2], c2.len());
+        if !c2.is_empty() && t.iter().all(|byte| *byte == 0) {
+            return None;
+        }
+        let message: Vec<u8> = c2.iter().zip(t).map(|(c, t)| c ^ t).collect();
+        let hash = Sm3::new()
+            .chain_update(x2)
+            .chain_update(&message)
+            .chain_update(y2)
+            .finalize();
+        match hash.as_slice() == c3 {
+            true => Some(message),
+            false => None,
+        }
+    }
+
+    // Returns the x̄ of GB/T 32918.3, 2^w + (x & (2^w - 1)).
+    fn x_bar(&self, x: &BigUint) -> BigUint {
+        let w = (self.n.bits() + 1) / 2 - 1;
+        let two_w = one() << w;
+        &two_w + (x & (&two_w - one()))
+    }
+
+    // Derives `len` bytes from the key exchange of the party with the static
+    // key `private`/`public` and the ephemeral key `ephemeral_private`/
+    // `ephemeral`, with the peer of the static key `peer` and the ephemeral
+    // key `peer_ephemeral`. `z_initiator` and `z_responder` are the Z of the
+    // two parties. The optional key confirmation is not done.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn exchange(
+        &self,
+        private: &BigUint,
+        ephemeral_private: &BigUint,
+        ephemeral: &Point,
+        peer: &Point,
+        peer_ephemeral: &Point,
+        z_initiator: &[u8],
+        z_responder: &[u8],
+        len: usize,
+    ) -> Option<Vec<u8>> {
+        if !self.is_on_curve(peer_ephemeral) {
+            return None;
+        }
+        let t = (private + self.x_bar(&ephemeral.0) * ephemeral_private) % &self.n;
+        let peer_point = self.mul(&self.x_bar(&peer_ephemeral.0), peer_ephemeral)?;
+        let (x, y) = self.mul(&t, &self.add(peer, &peer_point)?)?;
+        Some(kdf(
+            &[&to_bytes(&x), &to_bytes(&y), z_initiator, z_responder],
+            len,
+        ))
+    }
+}
+
+fn private_value(key: &KeyObject) -> Result<BigUint, TEE_Result> {
+    key.buffer(raw::TEE_ATTR_ECC_PRIVATE_VALUE)
+        .map(BigUint::from_bytes_be)
+        .ok_or(raw::TEE_ERROR_BAD_PARAMETERS)
+}
+
+// Returns the point of the coordinates `x` and `y` if it is on the curve.
+fn point(x: Option<&[u8]>, y: Option<&[u8]>) -> Result<Point, TEE_Result> {
+    match (x, y) {
+        (Some(x), Some(y)) => {
+            let point = (BigUint::from_bytes_be(x), BigUint::from_bytes_be(y));
+            match Curve::recommended().is_on_curve(&point) {
+                true => Ok(point),
+                false => Err(raw::TEE_ERROR_BAD_PARAMETERS),
+            }
+        }
+        _ => Err(raw::TEE_ERROR_BAD_PARAMETERS),
+    }
+}
+
+fn public_point(key: &KeyObject) -> Result<Point, TEE_Result> {
+    point(
+        key.buffer(raw::TEE_ATTR_ECC_PUBLIC_VALUE_X),
+        key.buffer(raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y),
+    )
+}
+
+pub(super) fn generate() -> Vec<Attribute> {
+    let curve = Curve::recommended();
+    let private = curve.random_scalar();
+    let (x, y) = curve.public_point(&private).unwrap();
+    vec![
+        Attribute::buffer(raw::TEE_ATTR_ECC_PRIVATE_VALUE, &to_bytes(&private)),
+        Attribute::buffer(raw::TEE_ATTR_ECC_PUBLIC_VALUE_X, &to_bytes(&x)),
+        Attribute::buffer(raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y, &to_bytes(&y)),
+    ]
+}
+
+// Signs the SM3 digest of Z and the message, as OP-TEE, the caller computes
+// it.
+pub(super) fn sign(key: &KeyObject, digest: &[u8]) -> Result<Vec<u8>, TEE_Result> {
+    if digest.len() != LEN {
+        return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+    }
+    Ok(Curve::recommended().sign(&private_value(key)?, digest))
+}
+
+pub(super) fn verify(key: &KeyObject, digest: &[u8], signature: &[u8]) -> Result<(), TEE_Result> {
+    if digest.len() != LEN {
+        return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+    }
+    match Curve::recommended().verify(&public_point(key)?, digest, signature) {
+        true => Ok(()),
+        false => Err(raw::TEE_ERROR_SIGNATURE_INVALID),
+    }
+}
+
+pub(super) fn encrypt(key: &KeyObject, data: &[u8]) -> Result<Vec<u8>, TEE_Result> {
+    Ok(Curve::recommended().encrypt(&public_point(key)?, data))
+}
+
+pub(super) fn decrypt(key: &KeyObject, data: &[u8]) -> Result<Vec<u8>, TEE_Result> {
+    Curve::recommended()
+        .decrypt(&private_value(key)?, data)
+        .ok_or(raw::TEE_ERROR_CIPHERTEXT_INVALID)
+}
+
+// Derives `len` bytes from a key exchange, `key` and `ephemeral` being the
+// static and the ephemeral key-pairs of the TA, as set by
+// `TEE_SetOperationKey2`. The key confirmation attributes are not supported.
+pub(super) fn derive(
+    key: &KeyObject,
+    ephemeral: &KeyObject,
+    params: &[Attribute],
+    len: usize,
+) -> Result<Vec<u8>, TEE_Result> {
+    let confirmation = [
+        raw::TEE_ATTR_SM2_KEP_CONFIRMATION_IN,
+        raw::TEE_ATTR_SM2_KEP_CONFIRMATION_OUT,
+    ];
+    if params.iter().any(|attr| confirmation.contains(&attr.id)) {
+        return Err(raw::TEE_ERROR_NOT_SUPPORTED);
+    }
+    let curve = Curve::recommended();
+    let buffer = |id| find_buffer(params, id).ok_or(raw::TEE_ERROR_BAD_PARAMETERS);
+    let peer = point(
+        find_buffer(params, raw::TEE_ATTR_ECC_PUBLIC_VALUE_X),
+        find_buffer(params, raw::TEE_ATTR_ECC_PUBLIC_VALUE_Y),
+    )?;
+    let peer_ephemeral = point(
+        find_buffer(params, raw::TEE_ATTR_ECC_EPHEMERAL_PUBLIC_VALUE_X),
+        find_buffer(params, raw::TEE_ATTR_ECC_EPHEMERAL_PUBLIC_VALUE_Y),
+    )?;
+    let (initiator, responder) = (
+        buffer(raw::TEE_ATTR_SM2_ID_INITIATOR)?,
+        buffer(raw::TEE_ATTR_SM2_ID_RESPONDER)?,
+    );
+    let public = public_point(key)?;
+    let (z_initiator, z_responder) = match find_value(params, raw::TEE_ATTR_SM2_KEP_USER) {
+        Some((0, _)) => (curve.z(initiator, &public), curve.z(responder, &peer)),
+        Some((1, _)) => (curve.z(initiator, &peer), curve.z(responder, &public)),
+        _ => return Err(raw::TEE_ERROR_BAD_PARAMETERS),
+    };
+    curve
+        .exchange(
+            &private_value(key)?,
+            &private_value(ephemeral)?,
+            &public_point(ephemeral)?,
+            &peer,
+            &peer_ephemeral,
+            &z_initiator,
+            &z_responder,
+            len,
+        )
+        .ok_or(raw::TEE_ERROR_BAD_PARAMETERS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::unhex;
+
+    // The curve of the examples of GB/T 32918, over a 256 bits prime field.
+    fn test_curve() -> Curve {
+        Curve {
+            p: int("8542D69E4C044F18E8B92435BF6FF7DE457283915C45517D722EDB8B08F1DFC3"),
+            a: int("787968B4FA32C3FD2417842E73BBFEFF2F3C848B6831D7E0EC65228B3937E498"),
+            b: int("63E4C6D3B23B0C849CF84241484BFE48F61D59A5B16BA06E6E12D1DA27C5249A"),
+            n: int("8542D69E4C044F18E8B92435BF6FF7DD297720630485628D5AE74EE7C32E79B7"),
+            g: (
+                int("421DEBD61B62EAB6746434EBC3CC315E32220B3BADD50BDC4C4E6C147FEDD43D"),
+                int("0680512BCBB42C07D47349D2153B70C4E5D7FDFCBFA36EA1A85841B9E46E09A2"),
+            ),
+        }
+    }
+
+    #[test]
+    fn test_signature() {
+        // GB/T 32918.2, annex A.2
+        let curve = test_curve();
+        let private = int("128B2FA8BD433C6C068C8D803DFF79792A519A55171B1B650C23661D15897263");
+        let public = curve.public_point(&private).unwrap();
+        assert_eq!(
+            public,
+            (
+                int("0AE4C7798AA0F119471BEE11825BE46202BB79E2A5844495E97C04FF4DF2548A"),
+                int("7C0240F88F1CD4E16352A73C17B7F16F07353E53A176D684A9FE0C6BB798E857"),
+            )
+        );
+        let z = curve.z(b"ALICE123@YAHOO.COM", &public);
+        assert_eq!(
+            z.to_vec(),
+            unhex("F4A38489E32B45B6F876E3AC2168CA392362DC8F23459C1D1146FC3DBFB7BC9A")
+        );
+        let digest = Sm3::new()
+            .chain_update(z)
+            .chain_update(b"message digest")
+            .finalize();
+        assert_eq!(
+            digest.to_vec(),
+            unhex("B524F552CD82B8B028476E005C377FB19A87E6FC682D48BB5D42E3D9B9EFFE76")
+        );
+        let k = int("6CB28D99385C175C94F94E934817663FC176D925DD72B727260DBAAE1FB2F96F");
+        let signature = curve
+            .sign_with(&private, &BigUint::from_bytes_be(&digest), &k)
+            .unwrap();
+        assert_eq!(
+            signature,
+            unhex(
+                "40F1EC59F793D9F49E09DCEF49130D4194F79FB1EED2CAA55BACDB49C4E755D1\
+                 6FC6DAC32C5D5CF10C77DFB20F7C2EB667A457872FB09EC56327A67EC7DEEBE7"
+            )
+        );
+        assert!(curve.verify(&public, &digest, &signature));
+        assert!(curve.verify(&public, &digest, &curve.sign(&private, &digest)));
+        let mut tampered = digest;
+        tampered[0] ^= 1;
+        assert!(!curve.verify(&public, &tampered, &signature));
+    }
+
+    #[test]
+    fn test_encryption() {
+        // GB/T 32918.4, annex A.2
+        let curve = test_curve();
+        let private = int("1649AB77A00637BD5E2EFE283FBF353534AA7F7CB89463F208DDBC2920BB0DA0");
+        let public = curve.public_point(&private).unwrap();
+        assert_eq!(
+            public,
+            (
+                int("435B39CCA8F3B508C1488AFC67BE491A0F7BA07E581A0E4849A5CF70628A7E0A"),
+                int("75DDBA78F15FEECB4C7895E2C1CDF5FE01DEBB2CDBADF45399CCF77BBA076A42"),
+            )
+        );
+        let k = int("4C62EEFD6ECFC2B95B92FD6C3D9575148AFA17425546D49018E5388D49DD7B4F");
+        let ciphertext = curve
+            .encrypt_with(&public, b"encryption standard", &k)
+            .unwrap();
+        assert_eq!(
+            ciphertext,
+            unhex(
+                "04245C26FB68B1DDDDB12C4B6BF9F2B6D5FE60A383B0D18D1C4144ABF17F6252E7\
+                 76CB9264C2A7E88E52B19903FDC47378F605E36811F5C07423A24B84400F01B8\
+                 9C3D7360C30156FAB7C80A0276712DA9D8094A634B766D3A285E07480653426D\
+                 650053A89B41C418B0C3AAD00D886C00286467"
+            )
+        );
+        assert_eq!(
+            curve.decrypt(&private, &ciphertext).unwrap(),
+            b"encryption standard"
+        );
+        let mut tampered = ciphertext;
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(curve.decrypt(&private, &tampered).is_none());
+    }
+
+    #[test]
+    fn test_key_exchange() {
+        // GB/T 32918.3, annex A.2
+        let curve = test_curve();
+        let private_a = int("6FCBA2EF9AE0AB902BC3BDE3FF915D44BA4CC78F88E2F8E7F8996D3B8CCEEDEE");
+        let ephemeral_private_a =
+            int("83A2C9C8B96E5AF70BD480B472409A9A327257F1EBB73F5B073354B248668563");
+        let private_b = int("5E35D7D3F3C54DBAC72E61819E730B019A84208CA3A35E4C2E353DFCCB2A3B53");
+        let ephemeral_private_b =
+            int("33FE21940342161C55619C4A0C060293D543C80AF19748CE176D83477DE71C80");
+        let public_a = curve.public_point(&private_a).unwrap();
+        let public_b = curve.public_point(&private_b).unwrap();
+        let ephemeral_a = curve.public_point(&ephemeral_private_a).unwrap();
+        let ephemeral_b = curve.public_point(&ephemeral_private_b).unwrap();
+        let z_a = curve.z(b"ALICE123@YAHOO.COM", &public_a);
+        let z_b = curve.z(b"BILL456@YAHOO.COM", &public_b);
+
+        let key_a = curve.exchange(
+            &private_a,
+            &ephemeral_private_a,
+            &ephemeral_a,
+            &public_b,
+            &ephemeral_b,
+            &z_a,
+            &z_b,
+            16,
+        );
+        let key_b = curve.exchange(
+            &private_b,
+            &ephemeral_private_b,
+            &ephemeral_b,
+            &public_a,
+            &ephemeral_a,
+            &z_a,
+            &z_b,
+            16,
+        );
+        let expected = unhex("55B0AC62A6B927BA23703832C853DED4");
+        assert_eq!(key_a.unwrap(), expected);
+        assert_eq!(key_b.unwrap(), expected);
+    }
+}
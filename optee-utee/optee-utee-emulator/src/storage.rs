@@ -0,0 +1,944 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Persistent objects, kept in memory or in a directory of the host.
+
+use super::object::{self, Attribute, AttributeValue, KeyObject, Object};
+use super::{input, output, tee_panic, with_state};
+use crate::raw::{self, TEE_ObjectEnumHandle, TEE_ObjectHandle, TEE_ObjectInfo, TEE_Result};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::ffi::c_void;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DATA_FLAGS: u32 = raw::TEE_DATA_FLAG_ACCESS_READ
+    | raw::TEE_DATA_FLAG_ACCESS_WRITE
+    | raw::TEE_DATA_FLAG_ACCESS_WRITE_META
+    | raw::TEE_DATA_FLAG_SHARE_READ
+    | raw::TEE_DATA_FLAG_SHARE_WRITE
+    | raw::TEE_DATA_FLAG_OVERWRITE;
+
+// The storage identifier and the identifier of an object.
+type ObjectKey = (u32, Vec<u8>);
+
+#[derive(Clone, Debug, PartialEq)]
+struct StoredObject {
+    key: KeyObject,
+    data: Vec<u8>,
+}
+
+enum Backend {
+    Memory(BTreeMap<ObjectKey, StoredObject>),
+    // One file per object, in a directory per storage.
+    Directory(PathBuf),
+}
+
+pub(super) struct Storage {
+    backend: Backend,
+    // The storage which TEE_STORAGE_PRIVATE refers to.
+    private_storage: u32,
+    // The flags of the handles opened on each object.
+    opened: BTreeMap<ObjectKey, Vec<(u64, u32)>>,
+    next_handle: u64,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Memory(BTreeMap::new()),
+            private_storage: raw::TEE_STORAGE_PRIVATE_REE,
+            opened: BTreeMap::new(),
+            next_handle: 0,
+        }
+    }
+}
+
+/// Keeps the persistent objects of the current thread in `path` instead of in
+/// memory, so that they survive the test process, e.g. to check an upgrade of
+/// the storage format. The directory is created if needed, and the objects of
+/// each storage are stored in a subdirectory named after the storage
+/// identifier.
+///
+/// The objects which were in memory are dropped, and handles opened before
+/// the switch must not be used afterwards.
+pub fn use_storage_dir<P: Into<PathBuf>>(path: P) {
+    with_state(|state| {
+        state.storage = Storage {
+            backend: Backend::Directory(path.into()),
+            private_storage: state.storage.private_storage,
+            ..Storage::default()
+        }
+    });
+}
+
+/// Makes `TEE_STORAGE_PRIVATE` refer to the RPMB storage of the current
+/// thread, as on OP-TEE built without `CFG_REE_FS`. By default it refers to
+/// the REE storage, as on OP-TEE built with `CFG_REE_FS`: in both cases the
+/// private storage is not distinct from the storage it refers to.
+pub fn use_rpmb_as_private_storage() {
+    with_state(|state| state.storage.private_storage = raw::TEE_STORAGE_PRIVATE_RPMB);
+}
+
+// Returns the storage which `storage_id` refers to.
+fn resolve_storage(storage_id: u32) -> Result<u32, TEE_Result> {
+    match storage_id {
+        raw::TEE_STORAGE_PRIVATE => Ok(with_state(|state| state.storage.private_storage)),
+        raw::TEE_STORAGE_PRIVATE_REE | raw::TEE_STORAGE_PRIVATE_RPMB => Ok(storage_id),
+        _ => Err(raw::TEE_ERROR_ITEM_NOT_FOUND),
+    }
+}
+
+// A handle on a persistent object.
+pub(super) struct PersistentHandle {
+    id: u64,
+    storage_id: u32,
+    object_id: Vec<u8>,
+    flags: u32,
+    position: usize,
+    pub key: KeyObject,
+}
+
+impl PersistentHandle {
+    fn object_key(&self) -> ObjectKey {
+        (self.storage_id, self.object_id.clone())
+    }
+}
+
+fn object_path(dir: &Path, (storage_id, object_id): &ObjectKey) -> PathBuf {
+    let name = super::hex(object_id);
+    dir.join(format!("{:08x}", storage_id))
+        .join(format!("{}.obj", name))
+}
+
+fn decode_hex(name: &str) -> Option<Vec<u8>> {
+    if name.len() % 2 != 0 {
+        return None;
+    }
+    (0..name.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(name.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode(object: &StoredObject) -> Vec<u8> {
+    let key = &object.key;
+    let mut buffer = Vec::new();
+    for value in [
+        key.object_type,
+        key.object_size,
+        key.max_object_size,
+        key.usage,
+    ] {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    buffer.push(key.initialized as u8);
+    buffer.extend_from_slice(&(key.attributes.len() as u32).to_le_bytes());
+    for attr in &key.attributes {
+        buffer.extend_from_slice(&attr.id.to_le_bytes());
+        match &attr.value {
+            AttributeValue::Ref(value) => {
+                buffer.push(0);
+                buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                buffer.extend_from_slice(value);
+            }
+            AttributeValue::Value(a, b) => {
+                buffer.push(1);
+                buffer.extend_from_slice(&a.to_le_bytes());
+                buffer.extend_from_slice(&b.to_le_bytes());
+            }
+        }
+    }
+    buffer.extend_from_slice(&(object.data.len() as u64).to_le_bytes());
+    buffer.extend_from_slice(&object.data);
+    buffer
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
+
+fn decode(buffer: &[u8]) -> Option<StoredObject> {
+    let mut decoder = Decoder(buffer);
+    let mut key = KeyObject::new(decoder.u32()?, 0);
+    key.object_size = decoder.u32()?;
+    key.max_object_size = decoder.u32()?;
+    key.usage = decoder.u32()?;
+    key.initialized = decoder.u8()? != 0;
+    for _ in 0..decoder.u32()? {
+        let id = decoder.u32()?;
+        let attr = match decoder.u8()? {
+            0 => {
+                let len = decoder.u32()? as usize;
+                Attribute::buffer(id, decoder.take(len)?)
+            }
+            1 => Attribute::value(id, decoder.u32()?, decoder.u32()?),
+            _ => return None,
+        };
+        key.attributes.push(attr);
+    }
+    let len = decoder.u64()? as usize;
+    let data = decoder.take(len)?.to_vec();
+    if !decoder.0.is_empty() {
+        return None;
+    }
+    Some(StoredObject { key, data })
+}
+
+impl Backend {
+    fn load(&self, key: &ObjectKey) -> Result<Option<StoredObject>, TEE_Result> {
+        match self {
+            Backend::Memory(objects) => Ok(objects.get(key).cloned()),
+            Backend::Directory(dir) => match fs::read(object_path(dir, key)) {
+                Ok(buffer) => decode(&buffer)
+                    .map(Some)
+                    .ok_or(raw::TEE_ERROR_CORRUPT_OBJECT),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(_) => Err(raw::TEE_ERROR_STORAGE_NOT_AVAILABLE),
+            },
+        }
+    }
+
+    fn store(&mut self, key: &ObjectKey, object: &StoredObject) -> Result<(), TEE_Result> {
+        match self {
+            Backend::Memory(objects) => {
+                objects.insert(key.clone(), object.clone());
+                Ok(())
+            }
+            Backend::Directory(dir) => {
+                let path = object_path(dir, key);
+                // Written aside then renamed, so that the object is replaced
+                // atomically.
+                let temporary = path.with_extension("tmp");
+                fs::create_dir_all(path.parent().unwrap())
+                    .and_then(|_| fs::write(&temporary, encode(object)))
+                    .and_then(|_| fs::rename(&temporary, &path))
+                    .map_err(|_| raw::TEE_ERROR_STORAGE_NOT_AVAILABLE)
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &ObjectKey) -> Result<(), TEE_Result> {
+        match self {
+            Backend::Memory(objects) => {
+                objects.remove(key);
+                Ok(())
+            }
+            Backend::Directory(dir) => match fs::remove_file(object_path(dir, key)) {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(_) => Err(raw::TEE_ERROR_STORAGE_NOT_AVAILABLE),
+            },
+        }
+    }
+
+    // Returns the sorted identifiers of the objects of a storage.
+    fn list(&self, storage_id: u32) -> Vec<Vec<u8>> {
+        match self {
+            Backend::Memory(objects) => objects
+                .keys()
+                .filter(|(id, _)| *id == storage_id)
+                .map(|(_, object_id)| object_id.clone())
+                .collect(),
+            Backend::Directory(dir) => {
+                let entries = match fs::read_dir(dir.join(format!("{:08x}", storage_id))) {
+                    Ok(entries) => entries,
+                    Err(_) => return Vec::new(),
+                };
+                let mut ids: Vec<Vec<u8>> = entries
+                    .filter_map(|entry| {
+                        let name = entry.ok()?.file_name().into_string().ok()?;
+                        decode_hex(name.strip_suffix(".obj")?)
+                    })
+                    .collect();
+                ids.sort();
+                ids
+            }
+        }
+    }
+}
+
+impl Storage {
+    // Checks that an object can be opened with `flags` while it is opened by
+    // other handles, following the sharing rules of the specification.
+    fn can_open(&self, key: &ObjectKey, flags: u32) -> bool {
+        let opened = match self.opened.get(key) {
+            Some(opened) => opened,
+            None => return true,
+        };
+        let allows = |granted: u32, requested: u32| {
+            (requested & raw::TEE_DATA_FLAG_ACCESS_READ == 0
+                || granted & raw::TEE_DATA_FLAG_SHARE_READ != 0)
+                && (requested & raw::TEE_DATA_FLAG_ACCESS_WRITE == 0
+                    || granted & raw::TEE_DATA_FLAG_SHARE_WRITE != 0)
+                && requested & raw::TEE_DATA_FLAG_ACCESS_WRITE_META == 0
+        };
+        opened
+            .iter()
+            .all(|(_, other)| allows(*other, flags) && allows(flags, *other))
+    }
+
+    fn open(&mut self, key: ObjectKey, flags: u32, object: StoredObject) -> PersistentHandle {
+        let id = self.next_handle;
+        self.next_handle += 1;
+        self.opened
+            .entry(key.clone())
+            .or_default()
+            .push((id, flags));
+        PersistentHandle {
+            id,
+            storage_id: key.0,
+            object_id: key.1,
+            flags,
+            position: 0,
+            key: object.key,
+        }
+    }
+
+    fn close(&mut self, handle: &PersistentHandle) {
+        let key = handle.object_key();
+        if let Some(opened) = self.opened.get_mut(&key) {
+            opened.retain(|(id, _)| *id != handle.id);
+            if opened.is_empty() {
+                self.opened.remove(&key);
+            }
+        }
+    }
+
+    fn load_opened(&self, handle: &PersistentHandle) -> Result<StoredObject, TEE_Result> {
+        self.backend
+            .load(&handle.object_key())?
+            .ok_or(raw::TEE_ERROR_CORRUPT_OBJECT)
+    }
+}
+
+// Checks the identifier of an object, and returns the storage which
+// `storage_id` refers to.
+fn check_object_id(storage_id: u32, object_id_len: usize) -> Result<u32, TEE_Result> {
+    let storage_id = resolve_storage(storage_id)?;
+    if object_id_len > raw::TEE_OBJECT_ID_MAX_LEN as usize {
+        return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+    }
+    Ok(storage_id)
+}
+
+unsafe fn persistent_mut<'a>(handle: TEE_ObjectHandle, function: &str) -> &'a mut PersistentHandle {
+    match object::object_mut(handle, function) {
+        Object::Persistent(handle) => handle,
+        Object::Transient(_) => tee_panic(function, "not a persistent object"),
+    }
+}
+
+fn require(handle: &PersistentHandle, flag: u32) -> Result<(), TEE_Result> {
+    if handle.flags & flag == 0 {
+        return Err(raw::TEE_ERROR_ACCESS_CONFLICT);
+    }
+    Ok(())
+}
+
+fn to_code(result: Result<(), TEE_Result>) -> TEE_Result {
+    match result {
+        Ok(()) => raw::TEE_SUCCESS,
+        Err(code) => code,
+    }
+}
+
+pub(super) fn fill_info(handle: &PersistentHandle, info: &mut TEE_ObjectInfo) {
+    info.dataSize = with_state(|state| state.storage.load_opened(handle))
+        .map(|object| object.data.len())
+        .unwrap_or(0);
+    info.dataPosition = handle.position;
+    info.handleFlags |= raw::TEE_HANDLE_FLAG_PERSISTENT | handle.flags;
+}
+
+pub(super) fn restrict_usage(handle: &mut PersistentHandle, usage: u32) -> TEE_Result {
+    to_code(with_state(|state| {
+        let mut object = state.storage.load_opened(handle)?;
+        object.key.usage &= usage;
+        state.storage.backend.store(&handle.object_key(), &object)?;
+        handle.key.usage = object.key.usage;
+        Ok(())
+    }))
+}
+
+pub(super) fn close(handle: PersistentHandle) {
+    with_state(|state| state.storage.close(&handle));
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_OpenPersistentObject(
+    storageID: u32,
+    objectID: *const c_void,
+    objectIDLen: usize,
+    flags: u32,
+    object: *mut TEE_ObjectHandle,
+) -> TEE_Result {
+    unsafe { *object = std::ptr::null_mut() };
+    let result = check_object_id(storageID, objectIDLen).and_then(|storage_id| {
+        if flags & !DATA_FLAGS != 0 {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+        let key = (storage_id, unsafe { input(objectID, objectIDLen) }.to_vec());
+        with_state(|state| {
+            let stored = state
+                .storage
+                .backend
+                .load(&key)?
+                .ok_or(raw::TEE_ERROR_ITEM_NOT_FOUND)?;
+            if !state.storage.can_open(&key, flags) {
+                return Err(raw::TEE_ERROR_ACCESS_CONFLICT);
+            }
+            Ok(state.storage.open(key, flags, stored))
+        })
+    });
+    match result {
+        Ok(handle) => {
+            unsafe { *object = object::into_handle(Object::Persistent(handle)) };
+            raw::TEE_SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CreatePersistentObject(
+    storageID: u32,
+    objectID: *const c_void,
+    objectIDLen: usize,
+    flags: u32,
+    attributes: TEE_ObjectHandle,
+    initialData: *const c_void,
+    initialDataLen: usize,
+    object: *mut TEE_ObjectHandle,
+) -> TEE_Result {
+    if !object.is_null() {
+        unsafe { *object = std::ptr::null_mut() };
+    }
+    let key_object = if attributes.is_null() {
+        KeyObject::data()
+    } else {
+        let key = unsafe { object::object_mut(attributes, "TEE_CreatePersistentObject") }.key();
+        if !key.initialized {
+            tee_panic("TEE_CreatePersistentObject", "attributes not initialized");
+        }
+        key.clone()
+    };
+    let result = check_object_id(storageID, objectIDLen).and_then(|storage_id| {
+        if flags & !DATA_FLAGS != 0 {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+        let key = (storage_id, unsafe { input(objectID, objectIDLen) }.to_vec());
+        let stored = StoredObject {
+            key: key_object,
+            data: unsafe { input(initialData, initialDataLen) }.to_vec(),
+        };
+        with_state(|state| {
+            let storage = &mut state.storage;
+            if storage.backend.load(&key)?.is_some()
+                && (flags & raw::TEE_DATA_FLAG_OVERWRITE == 0 || storage.opened.contains_key(&key))
+            {
+                return Err(raw::TEE_ERROR_ACCESS_CONFLICT);
+            }
+            storage.backend.store(&key, &stored)?;
+            if object.is_null() {
+                return Ok(None);
+            }
+            let flags = flags & !raw::TEE_DATA_FLAG_OVERWRITE;
+            Ok(Some(storage.open(key, flags, stored)))
+        })
+    });
+    match result {
+        Ok(Some(handle)) => {
+            unsafe { *object = object::into_handle(Object::Persistent(handle)) };
+            raw::TEE_SUCCESS
+        }
+        Ok(None) => raw::TEE_SUCCESS,
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CloseAndDeletePersistentObject1(object: TEE_ObjectHandle) -> TEE_Result {
+    if object.is_null() {
+        return raw::TEE_SUCCESS;
+    }
+    let handle = unsafe { persistent_mut(object, "TEE_CloseAndDeletePersistentObject1") };
+    if let Err(code) = require(handle, raw::TEE_DATA_FLAG_ACCESS_WRITE_META) {
+        return code;
+    }
+    let result = with_state(|state| {
+        state.storage.close(handle);
+        state.storage.backend.remove(&handle.object_key())
+    });
+    drop(unsafe { Box::from_raw(object as *mut Object) });
+    to_code(result)
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_CloseAndDeletePersistentObject(object: TEE_ObjectHandle) {
+    TEE_CloseAndDeletePersistentObject1(object);
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_RenamePersistentObject(
+    object: TEE_ObjectHandle,
+    newObjectID: *const c_void,
+    newObjectIDLen: usize,
+) -> TEE_Result {
+    let handle = unsafe { persistent_mut(object, "TEE_RenamePersistentObject") };
+    to_code(
+        require(handle, raw::TEE_DATA_FLAG_ACCESS_WRITE_META)
+            .and_then(|_| check_object_id(handle.storage_id, newObjectIDLen))
+            .and_then(|_| {
+                let old_key = handle.object_key();
+                let new_key = (
+                    handle.storage_id,
+                    unsafe { input(newObjectID, newObjectIDLen) }.to_vec(),
+                );
+                if new_key == old_key {
+                    return Ok(());
+                }
+                with_state(|state| {
+                    let storage = &mut state.storage;
+                    if storage.backend.load(&new_key)?.is_some() {
+                        return Err(raw::TEE_ERROR_ACCESS_CONFLICT);
+                    }
+                    let stored = storage.load_opened(handle)?;
+                    storage.backend.store(&new_key, &stored)?;
+                    storage.backend.remove(&old_key)?;
+                    if let Some(opened) = storage.opened.remove(&old_key) {
+                        storage.opened.insert(new_key.clone(), opened);
+                    }
+                    handle.object_id = new_key.1;
+                    Ok(())
+                })
+            }),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_ReadObjectData(
+    object: TEE_ObjectHandle,
+    buffer: *mut c_void,
+    size: usize,
+    count: *mut usize,
+) -> TEE_Result {
+    let handle = unsafe { persistent_mut(object, "TEE_ReadObjectData") };
+    unsafe { *count = 0 };
+    to_code(
+        require(handle, raw::TEE_DATA_FLAG_ACCESS_READ).and_then(|_| {
+            let stored = with_state(|state| state.storage.load_opened(handle))?;
+            let start = handle.position.min(stored.data.len());
+            let read = size.min(stored.data.len() - start);
+            if read > 0 {
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        stored.data[start..].as_ptr(),
+                        buffer as *mut u8,
+                        read,
+                    )
+                };
+            }
+            handle.position += read;
+            unsafe { *count = read };
+            Ok(())
+        }),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_WriteObjectData(
+    object: TEE_ObjectHandle,
+    buffer: *const c_void,
+    size: usize,
+) -> TEE_Result {
+    let handle = unsafe { persistent_mut(object, "TEE_WriteObjectData") };
+    to_code(
+        require(handle, raw::TEE_DATA_FLAG_ACCESS_WRITE).and_then(|_| {
+            let end = handle.position + size;
+            if end > raw::TEE_DATA_MAX_POSITION as usize {
+                return Err(raw::TEE_ERROR_OVERFLOW);
+            }
+            with_state(|state| {
+                let mut stored = state.storage.load_opened(handle)?;
+                if stored.data.len() < end {
+                    stored.data.resize(end, 0);
+                }
+                stored.data[handle.position..end].copy_from_slice(unsafe { input(buffer, size) });
+                state.storage.backend.store(&handle.object_key(), &stored)
+            })?;
+            handle.position = end;
+            Ok(())
+        }),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_TruncateObjectData(object: TEE_ObjectHandle, size: usize) -> TEE_Result {
+    let handle = unsafe { persistent_mut(object, "TEE_TruncateObjectData") };
+    to_code(
+        require(handle, raw::TEE_DATA_FLAG_ACCESS_WRITE).and_then(|_| {
+            with_state(|state| {
+                let mut stored = state.storage.load_opened(handle)?;
+                stored.data.resize(size, 0);
+                state.storage.backend.store(&handle.object_key(), &stored)
+            })
+        }),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_SeekObjectData(
+    object: TEE_ObjectHandle,
+    offset: i64,
+    whence: raw::TEE_Whence,
+) -> TEE_Result {
+    let handle = unsafe { persistent_mut(object, "TEE_SeekObjectData") };
+    let base = match whence {
+        raw::TEE_Whence::TEE_DATA_SEEK_SET => 0,
+        raw::TEE_Whence::TEE_DATA_SEEK_CUR => handle.position as i64,
+        raw::TEE_Whence::TEE_DATA_SEEK_END => {
+            match with_state(|state| state.storage.load_opened(handle)) {
+                Ok(stored) => stored.data.len() as i64,
+                Err(code) => return code,
+            }
+        }
+    };
+    // A position before the beginning of the stream is clamped to it.
+    let position = base.saturating_add(offset).max(0);
+    if position > raw::TEE_DATA_MAX_POSITION as i64 {
+        return raw::TEE_ERROR_OVERFLOW;
+    }
+    handle.position = position as usize;
+    raw::TEE_SUCCESS
+}
+
+struct Enumerator {
+    storage_id: u32,
+    object_ids: Vec<Vec<u8>>,
+    next: usize,
+    started: bool,
+}
+
+unsafe fn enumerator_mut<'a>(handle: TEE_ObjectEnumHandle, function: &str) -> &'a mut Enumerator {
+    if handle.is_null() {
+        tee_panic(function, "invalid enumerator handle");
+    }
+    &mut *(handle as *mut Enumerator)
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_AllocatePersistentObjectEnumerator(
+    objectEnumerator: *mut TEE_ObjectEnumHandle,
+) -> TEE_Result {
+    let enumerator = Enumerator {
+        storage_id: 0,
+        object_ids: Vec::new(),
+        next: 0,
+        started: false,
+    };
+    unsafe { *objectEnumerator = Box::into_raw(Box::new(enumerator)) as TEE_ObjectEnumHandle };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_FreePersistentObjectEnumerator(objectEnumerator: TEE_ObjectEnumHandle) {
+    if !objectEnumerator.is_null() {
+        drop(unsafe { Box::from_raw(objectEnumerator as *mut Enumerator) });
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_ResetPersistentObjectEnumerator(objectEnumerator: TEE_ObjectEnumHandle) {
+    let enumerator =
+        unsafe { enumerator_mut(objectEnumerator, "TEE_ResetPersistentObjectEnumerator") };
+    enumerator.object_ids.clear();
+    enumerator.next = 0;
+    enumerator.started = false;
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_StartPersistentObjectEnumerator(
+    objectEnumerator: TEE_ObjectEnumHandle,
+    storageID: u32,
+) -> TEE_Result {
+    let enumerator =
+        unsafe { enumerator_mut(objectEnumerator, "TEE_StartPersistentObjectEnumerator") };
+    let storage_id = match resolve_storage(storageID) {
+        Ok(storage_id) => storage_id,
+        Err(code) => return code,
+    };
+    // The identifiers are listed now, objects created afterwards are not
+    // enumerated.
+    let object_ids = with_state(|state| state.storage.backend.list(storage_id));
+    if object_ids.is_empty() {
+        return raw::TEE_ERROR_ITEM_NOT_FOUND;
+    }
+    *enumerator = Enumerator {
+        storage_id,
+        object_ids,
+        next: 0,
+        started: true,
+    };
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetNextPersistentObject(
+    objectEnumerator: TEE_ObjectEnumHandle,
+    objectInfo: *mut TEE_ObjectInfo,
+    objectID: *mut c_void,
+    objectIDLen: *mut usize,
+) -> TEE_Result {
+    let enumerator = unsafe { enumerator_mut(objectEnumerator, "TEE_GetNextPersistentObject") };
+    if !enumerator.started {
+        return raw::TEE_ERROR_ITEM_NOT_FOUND;
+    }
+    while let Some(object_id) = enumerator.object_ids.get(enumerator.next) {
+        enumerator.next += 1;
+        let key = (enumerator.storage_id, object_id.clone());
+        let stored = match with_state(|state| state.storage.backend.load(&key)) {
+            Ok(Some(stored)) => stored,
+            // Deleted since the enumeration was started.
+            Ok(None) => continue,
+            Err(code) => return code,
+        };
+        if !objectInfo.is_null() {
+            unsafe {
+                *objectInfo = TEE_ObjectInfo {
+                    objectType: stored.key.object_type,
+                    objectSize: stored.key.object_size,
+                    maxObjectSize: stored.key.max_object_size,
+                    objectUsage: stored.key.usage,
+                    dataSize: stored.data.len(),
+                    dataPosition: 0,
+                    handleFlags: raw::TEE_HANDLE_FLAG_PERSISTENT | raw::TEE_HANDLE_FLAG_INITIALIZED,
+                }
+            };
+        }
+        // The buffer is at least TEE_OBJECT_ID_MAX_LEN bytes long, the length
+        // is only an output.
+        let mut len = raw::TEE_OBJECT_ID_MAX_LEN as usize;
+        let code = unsafe { output(object_id, objectID, &mut len) };
+        unsafe { *objectIDLen = len };
+        return code;
+    }
+    raw::TEE_ERROR_ITEM_NOT_FOUND
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use optee_utee::{DataFlag, ErrorKind, ObjectStorageConstants, PersistentObject, Whence};
+
+    const READ_WRITE: u32 = raw::TEE_DATA_FLAG_ACCESS_READ | raw::TEE_DATA_FLAG_ACCESS_WRITE;
+
+    fn create(object_id: &[u8], data: &[u8]) -> optee_utee::Result<PersistentObject> {
+        PersistentObject::create(
+            ObjectStorageConstants::Private,
+            object_id,
+            DataFlag::from_bits_truncate(READ_WRITE | raw::TEE_DATA_FLAG_ACCESS_WRITE_META),
+            None,
+            data,
+        )
+    }
+
+    fn read_all(object: &PersistentObject) -> Vec<u8> {
+        object.seek(0, Whence::DataSeekSet).unwrap();
+        let mut buffer = [0u8; 64];
+        let read = object.read(&mut buffer).unwrap() as usize;
+        buffer[..read].to_vec()
+    }
+
+    #[test]
+    fn test_persistent_object_data() {
+        let mut object = create(b"data", b"hello").unwrap();
+        object.seek(0, Whence::DataSeekEnd).unwrap();
+        object.write(b" world").unwrap();
+        assert_eq!(read_all(&object), b"hello world");
+
+        // Writing past the end fills the gap with zeros.
+        object.seek(2, Whence::DataSeekEnd).unwrap();
+        object.write(b"!").unwrap();
+        assert_eq!(read_all(&object), b"hello world\0\0!");
+
+        object.truncate(5).unwrap();
+        assert_eq!(read_all(&object), b"hello");
+
+        object.rename(b"renamed").unwrap();
+        drop(object);
+        let flags = DataFlag::ACCESS_READ | DataFlag::ACCESS_WRITE_META;
+        let err = PersistentObject::open(ObjectStorageConstants::Private, b"data", flags)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ItemNotFound);
+        let object =
+            PersistentObject::open(ObjectStorageConstants::Private, b"renamed", flags).unwrap();
+        assert_eq!(read_all(&object), b"hello");
+        object.close_and_delete().unwrap();
+    }
+
+    #[test]
+    fn test_sharing_rules() {
+        let object = create(b"shared", b"").unwrap();
+        let err = PersistentObject::open(
+            ObjectStorageConstants::Private,
+            b"shared",
+            DataFlag::ACCESS_READ,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), ErrorKind::AccessConflict);
+        let err = create(b"shared", b"").err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::AccessConflict);
+        drop(object);
+
+        let flags = DataFlag::ACCESS_READ | DataFlag::SHARE_READ;
+        let first = PersistentObject::open(ObjectStorageConstants::Private, b"shared", flags);
+        let second = PersistentObject::open(ObjectStorageConstants::Private, b"shared", flags);
+        assert!(first.is_ok() && second.is_ok());
+    }
+
+    #[test]
+    fn test_list() {
+        for object_id in [&b"user/alice"[..], b"user/bob", b"config"] {
+            drop(create(object_id, object_id).unwrap());
+        }
+        let mut list =
+            PersistentObject::list_with_prefix(ObjectStorageConstants::Private, b"user/").unwrap();
+        let mut listed = list
+            .by_ref()
+            .map(|item| {
+                let (object_id, info) = item.unwrap();
+                assert_eq!(info.data_size(), object_id.len());
+                object_id
+            })
+            .collect::<Vec<_>>();
+        listed.sort();
+        assert_eq!(listed, [&b"user/alice"[..], b"user/bob"]);
+        assert!(list.next().is_none());
+
+        PersistentObject::open(
+            ObjectStorageConstants::Private,
+            b"user/bob",
+            DataFlag::ACCESS_WRITE_META,
+        )
+        .unwrap()
+        .close_and_delete()
+        .unwrap();
+        list.restart().unwrap();
+        assert_eq!(list.count(), 1);
+        let list = PersistentObject::list(ObjectStorageConstants::Private).unwrap();
+        assert_eq!(list.count(), 2);
+    }
+
+    #[test]
+    fn test_std_compat() {
+        use optee_utee::{SeekFrom, StdCompatRead, StdCompatSeek, StdCompatWrite};
+
+        let mut object = create(b"std compat", b"").unwrap();
+        StdCompatWrite::write_all(&mut object, b"0123456789").unwrap();
+        assert_eq!(object.stream_position().unwrap(), 10);
+        assert_eq!(
+            StdCompatSeek::seek(&mut object, SeekFrom::End(-4)).unwrap(),
+            6
+        );
+        StdCompatWrite::write_all(&mut object, b"ab").unwrap();
+        assert_eq!(
+            StdCompatSeek::seek(&mut object, SeekFrom::Current(-3)).unwrap(),
+            5
+        );
+
+        let mut buffer = [0u8; 5];
+        object.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"5ab89");
+        assert_eq!(object.stream_position().unwrap(), 10);
+
+        // Reading past the end of the data fails, after reading what is left.
+        object.rewind().unwrap();
+        StdCompatSeek::seek(&mut object, SeekFrom::Start(8)).unwrap();
+        let mut buffer = [0u8; 4];
+        assert_eq!(
+            object.read_exact(&mut buffer).unwrap_err().kind(),
+            ErrorKind::NoData
+        );
+        assert_eq!(&buffer[..2], b"89");
+        assert_eq!(
+            StdCompatSeek::seek(&mut object, SeekFrom::Start(u64::MAX))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::Overflow
+        );
+    }
+
+    #[test]
+    fn test_storage_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        use_storage_dir(dir.path());
+        drop(create(b"persisted", b"42").unwrap());
+
+        // Another storage on the same directory sees the object.
+        super::super::reset();
+        use_storage_dir(dir.path());
+        let object = PersistentObject::open(
+            ObjectStorageConstants::Private,
+            b"persisted",
+            DataFlag::ACCESS_READ,
+        )
+        .unwrap();
+        assert_eq!(read_all(&object), b"42");
+        let ree_dir = format!("{:08x}", raw::TEE_STORAGE_PRIVATE_REE);
+        assert_eq!(fs::read_dir(dir.path().join(ree_dir)).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_private_storage_alias() {
+        let open = |storage_id| {
+            PersistentObject::open(storage_id, b"object", DataFlag::ACCESS_READ).is_ok()
+        };
+        drop(create(b"object", b"42").unwrap());
+        assert!(open(ObjectStorageConstants::PrivateRee));
+        assert!(!open(ObjectStorageConstants::PrivateRpmb));
+
+        super::super::reset();
+        use_rpmb_as_private_storage();
+        drop(create(b"object", b"42").unwrap());
+        assert!(open(ObjectStorageConstants::PrivateRpmb));
+        assert!(!open(ObjectStorageConstants::PrivateRee));
+    }
+}
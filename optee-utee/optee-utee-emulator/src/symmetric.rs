@@ -0,0 +1,701 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Block ciphers and the chaining, MAC and authenticated encryption modes
+// built on them, implemented by hand so that they can be fed incrementally.
+
+use super::crypto::{new_digest, Digest};
+use crate::raw::{self, TEE_Result};
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+use std::convert::TryInto;
+
+#[derive(Clone)]
+pub(super) enum BlockCipher {
+    Aes128(aes::Aes128),
+    Aes192(aes::Aes192),
+    Aes256(aes::Aes256),
+    Des(des::Des),
+    TdesEde2(des::TdesEde2),
+    TdesEde3(des::TdesEde3),
+    Sm4(sm4::Sm4),
+}
+
+impl BlockCipher {
+    pub fn new(object_type: u32, key: &[u8]) -> Option<Self> {
+        let cipher = match (object_type, key.len()) {
+            (raw::TEE_TYPE_AES, 16) => Self::Aes128(KeyInit::new_from_slice(key).ok()?),
+            (raw::TEE_TYPE_AES, 24) => Self::Aes192(KeyInit::new_from_slice(key).ok()?),
+            (raw::TEE_TYPE_AES, 32) => Self::Aes256(KeyInit::new_from_slice(key).ok()?),
+            (raw::TEE_TYPE_DES, 8) => Self::Des(KeyInit::new_from_slice(key).ok()?),
+            (raw::TEE_TYPE_DES3, 16) => Self::TdesEde2(KeyInit::new_from_slice(key).ok()?),
+            (raw::TEE_TYPE_DES3, 24) => Self::TdesEde3(KeyInit::new_from_slice(key).ok()?),
+            (raw::TEE_TYPE_SM4, 16) => Self::Sm4(KeyInit::new_from_slice(key).ok()?),
+            _ => return None,
+        };
+        Some(cipher)
+    }
+
+    pub fn block_size(&self) -> usize {
+        match self {
+            Self::Aes128(_) | Self::Aes192(_) | Self::Aes256(_) | Self::Sm4(_) => 16,
+            Self::Des(_) | Self::TdesEde2(_) | Self::TdesEde3(_) => 8,
+        }
+    }
+
+    pub fn encrypt(&self, block: &mut [u8]) {
+        match self {
+            Self::Aes128(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Aes192(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Aes256(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Des(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Self::TdesEde2(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Self::TdesEde3(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Sm4(c) => c.encrypt_block(GenericArray::from_mut_slice(block)),
+        }
+    }
+
+    pub fn decrypt(&self, block: &mut [u8]) {
+        match self {
+            Self::Aes128(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Aes192(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Aes256(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Des(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Self::TdesEde2(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Self::TdesEde3(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+            Self::Sm4(c) => c.decrypt_block(GenericArray::from_mut_slice(block)),
+        }
+    }
+}
+
+fn xor(dest: &mut [u8], src: &[u8]) {
+    dest.iter_mut().zip(src).for_each(|(d, s)| *d ^= s);
+}
+
+// The key stream of a counter mode, the counter being the last
+// `counter_len` bytes of the block.
+#[derive(Clone)]
+struct Keystream {
+    counter: Vec<u8>,
+    counter_len: usize,
+    unused: Vec<u8>,
+}
+
+impl Keystream {
+    fn new(counter: Vec<u8>, counter_len: usize) -> Self {
+        Self {
+            counter,
+            counter_len,
+            unused: Vec::new(),
+        }
+    }
+
+    fn increment(&mut self) {
+        let start = self.counter.len() - self.counter_len;
+        for byte in self.counter[start..].iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+
+    fn apply(&mut self, cipher: &BlockCipher, data: &mut [u8]) {
+        for byte in data {
+            if self.unused.is_empty() {
+                let mut block = self.counter.clone();
+                cipher.encrypt(&mut block);
+                self.increment();
+                block.reverse();
+                self.unused = block;
+            }
+            *byte ^= self.unused.pop().unwrap();
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum ChainMode {
+    Ecb,
+    Cbc,
+    Ctr,
+}
+
+// A symmetric cipher operation, between TEE_CipherInit and
+// TEE_CipherDoFinal.
+#[derive(Clone)]
+pub(super) struct Cipher {
+    cipher: BlockCipher,
+    mode: ChainMode,
+    encrypt: bool,
+    chain: Vec<u8>,
+    pending: Vec<u8>,
+    keystream: Option<Keystream>,
+}
+
+impl Cipher {
+    pub fn new(
+        cipher: BlockCipher,
+        mode: ChainMode,
+        encrypt: bool,
+        iv: &[u8],
+    ) -> Result<Self, TEE_Result> {
+        let block_size = cipher.block_size();
+        if mode != ChainMode::Ecb && iv.len() != block_size {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+        let keystream = match mode {
+            ChainMode::Ctr => Some(Keystream::new(iv.to_vec(), block_size)),
+            _ => None,
+        };
+        Ok(Self {
+            cipher,
+            mode,
+            encrypt,
+            chain: iv.to_vec(),
+            pending: Vec::new(),
+            keystream,
+        })
+    }
+
+    // Returns the size of the output of an update with `len` bytes.
+    pub fn update_len(&self, len: usize) -> usize {
+        match self.mode {
+            ChainMode::Ctr => len,
+            _ => {
+                let block_size = self.cipher.block_size();
+                (self.pending.len() + len) / block_size * block_size
+            }
+        }
+    }
+
+    // Returns the size of the output of the final call with `len` bytes.
+    pub fn final_len(&self, len: usize) -> usize {
+        self.pending.len() + len
+    }
+
+    fn process_block(&mut self, block: &mut [u8]) {
+        match (self.mode, self.encrypt) {
+            (ChainMode::Cbc, true) => {
+                xor(block, &self.chain);
+                self.cipher.encrypt(block);
+                self.chain.copy_from_slice(block);
+            }
+            (ChainMode::Cbc, false) => {
+                let input = block.to_vec();
+                self.cipher.decrypt(block);
+                xor(block, &self.chain);
+                self.chain = input;
+            }
+            (_, true) => self.cipher.encrypt(block),
+            (_, false) => self.cipher.decrypt(block),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        if let Some(keystream) = &mut self.keystream {
+            let mut output = data.to_vec();
+            keystream.apply(&self.cipher, &mut output);
+            return output;
+        }
+        self.pending.extend_from_slice(data);
+        let block_size = self.cipher.block_size();
+        let complete = self.pending.len() / block_size * block_size;
+        let mut output: Vec<u8> = self.pending.drain(..complete).collect();
+        for block in output.chunks_mut(block_size) {
+            self.process_block(block);
+        }
+        output
+    }
+
+    pub fn finish(&mut self, data: &[u8]) -> Result<Vec<u8>, TEE_Result> {
+        if self.mode != ChainMode::Ctr
+            && (self.pending.len() + data.len()) % self.cipher.block_size() != 0
+        {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+        Ok(self.update(data))
+    }
+}
+
+// CBC-MAC state: the last cipher block and the bytes of an incomplete block.
+#[derive(Clone)]
+pub(super) struct CbcMac {
+    state: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl CbcMac {
+    fn new(iv: Vec<u8>) -> Self {
+        Self {
+            state: iv,
+            pending: Vec::new(),
+        }
+    }
+
+    fn absorb(&mut self, cipher: &BlockCipher, data: &[u8]) {
+        let block_size = cipher.block_size();
+        self.pending.extend_from_slice(data);
+        let complete = self.pending.len() / block_size * block_size;
+        for block in self.pending[..complete].chunks(block_size) {
+            xor(&mut self.state, block);
+            cipher.encrypt(&mut self.state);
+        }
+        self.pending.drain(..complete);
+    }
+
+    // Pads the pending bytes with zeros up to a block.
+    fn pad(&mut self, cipher: &BlockCipher) {
+        if !self.pending.is_empty() {
+            let padding = cipher.block_size() - self.pending.len();
+            self.absorb(cipher, &vec![0; padding]);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) enum Mac {
+    Hmac {
+        inner: Digest,
+        outer: Digest,
+    },
+    Cbc {
+        cipher: BlockCipher,
+        mac: CbcMac,
+        pkcs5: bool,
+    },
+    Cmac {
+        cipher: BlockCipher,
+        state: Vec<u8>,
+        pending: Vec<u8>,
+    },
+}
+
+impl Mac {
+    pub fn hmac(hash_id: u32, key: &[u8]) -> Option<Self> {
+        let block_size = if matches!(hash_id, 5 | 6) { 128 } else { 64 };
+        let mut inner = new_digest(hash_id)?;
+        let mut outer = new_digest(hash_id)?;
+        let mut key = if key.len() > block_size {
+            let mut digest = new_digest(hash_id)?;
+            digest.update(key);
+            digest.finalize().to_vec()
+        } else {
+            key.to_vec()
+        };
+        key.resize(block_size, 0);
+        inner.update(&key.iter().map(|byte| byte ^ 0x36).collect::<Vec<_>>());
+        outer.update(&key.iter().map(|byte| byte ^ 0x5c).collect::<Vec<_>>());
+        Some(Self::Hmac { inner, outer })
+    }
+
+    pub fn cbc_mac(cipher: BlockCipher, iv: &[u8], pkcs5: bool) -> Result<Self, TEE_Result> {
+        let iv = match iv.len() {
+            0 => vec![0; cipher.block_size()],
+            len if len == cipher.block_size() => iv.to_vec(),
+            _ => return Err(raw::TEE_ERROR_BAD_PARAMETERS),
+        };
+        Ok(Self::Cbc {
+            cipher,
+            mac: CbcMac::new(iv),
+            pkcs5,
+        })
+    }
+
+    pub fn cmac(cipher: BlockCipher) -> Self {
+        Self::Cmac {
+            state: vec![0; cipher.block_size()],
+            cipher,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn output_len(&self) -> usize {
+        match self {
+            Self::Hmac { outer, .. } => outer.output_size(),
+            Self::Cbc { cipher, .. } | Self::Cmac { cipher, .. } => cipher.block_size(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Hmac { inner, .. } => inner.update(data),
+            Self::Cbc { cipher, mac, .. } => mac.absorb(cipher, data),
+            Self::Cmac {
+                cipher,
+                state,
+                pending,
+            } => {
+                // The last block is kept until the final call, to be combined
+                // with a subkey.
+                let block_size = cipher.block_size();
+                pending.extend_from_slice(data);
+                while pending.len() > block_size {
+                    xor(state, &pending[..block_size]);
+                    cipher.encrypt(state);
+                    pending.drain(..block_size);
+                }
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<Vec<u8>, TEE_Result> {
+        match self {
+            Self::Hmac { inner, outer } => {
+                let mut outer = outer.clone();
+                outer.update(&inner.finalize_reset());
+                Ok(outer.finalize().to_vec())
+            }
+            Self::Cbc { cipher, mac, pkcs5 } => {
+                if *pkcs5 {
+                    let padding = cipher.block_size() - mac.pending.len();
+                    mac.absorb(cipher, &vec![padding as u8; padding]);
+                } else if !mac.pending.is_empty() {
+                    return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+                }
+                Ok(mac.state.clone())
+            }
+            Self::Cmac {
+                cipher,
+                state,
+                pending,
+            } => {
+                let block_size = cipher.block_size();
+                let mut subkey = vec![0; block_size];
+                cipher.encrypt(&mut subkey);
+                double(&mut subkey);
+                let mut last = pending.clone();
+                if last.len() < block_size {
+                    double(&mut subkey);
+                    last.push(0x80);
+                    last.resize(block_size, 0);
+                }
+                xor(&mut last, &subkey);
+                xor(state, &last);
+                cipher.encrypt(state);
+                Ok(state.clone())
+            }
+        }
+    }
+}
+
+// Multiplication by x in GF(2^n), for the CMAC subkeys.
+fn double(block: &mut [u8]) {
+    let carry = block[0] >> 7;
+    for index in 0..block.len() {
+        let next = block.get(index + 1).map_or(0, |byte| byte >> 7);
+        block[index] = (block[index] << 1) | next;
+    }
+    if carry == 1 {
+        let last = block.len() - 1;
+        block[last] ^= if block.len() == 16 { 0x87 } else { 0x1b };
+    }
+}
+
+// Multiplication in GF(2^128) as defined for GHASH.
+fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0;
+    let mut v = y;
+    for i in 0..128 {
+        if (x >> (127 - i)) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 {
+            (v >> 1) ^ (0xE1 << 120)
+        } else {
+            v >> 1
+        };
+    }
+    z
+}
+
+#[derive(Clone)]
+struct Ghash {
+    h: u128,
+    state: u128,
+    pending: Vec<u8>,
+}
+
+impl Ghash {
+    fn update(&mut self, data: &[u8]) {
+        self.pending.extend_from_slice(data);
+        while self.pending.len() >= 16 {
+            let block: Vec<u8> = self.pending.drain(..16).collect();
+            self.absorb(&block);
+        }
+    }
+
+    fn absorb(&mut self, block: &[u8]) {
+        let mut padded = [0u8; 16];
+        padded[..block.len()].copy_from_slice(block);
+        self.state = gf_mul(self.state ^ u128::from_be_bytes(padded), self.h);
+    }
+
+    fn pad(&mut self) {
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.absorb(&block);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Gcm {
+    cipher: BlockCipher,
+    encrypt: bool,
+    tag_len: usize,
+    j0: Vec<u8>,
+    keystream: Keystream,
+    ghash: Ghash,
+    aad_len: u64,
+    text_len: u64,
+    in_text: bool,
+}
+
+#[derive(Clone)]
+pub(super) struct Ccm {
+    cipher: BlockCipher,
+    encrypt: bool,
+    tag_len: usize,
+    s0: Vec<u8>,
+    keystream: Keystream,
+    mac: CbcMac,
+    aad_len: usize,
+    payload_len: usize,
+    aad_done: usize,
+    text_done: usize,
+}
+
+#[derive(Clone)]
+pub(super) enum Ae {
+    Gcm(Gcm),
+    Ccm(Ccm),
+}
+
+impl Ae {
+    pub fn gcm(
+        cipher: BlockCipher,
+        encrypt: bool,
+        nonce: &[u8],
+        tag_bits: u32,
+    ) -> Result<Self, TEE_Result> {
+        if !matches!(tag_bits, 96 | 104 | 112 | 120 | 128) || nonce.is_empty() {
+            return Err(raw::TEE_ERROR_NOT_SUPPORTED);
+        }
+        let mut h = vec![0u8; 16];
+        cipher.encrypt(&mut h);
+        let h = u128::from_be_bytes(h.try_into().unwrap());
+        let j0 = if nonce.len() == 12 {
+            let mut j0 = nonce.to_vec();
+            j0.extend_from_slice(&[0, 0, 0, 1]);
+            j0
+        } else {
+            let mut ghash = Ghash {
+                h,
+                state: 0,
+                pending: Vec::new(),
+            };
+            ghash.update(nonce);
+            ghash.pad();
+            ghash.absorb(&((nonce.len() as u128) * 8).to_be_bytes());
+            ghash.state.to_be_bytes().to_vec()
+        };
+        let mut keystream = Keystream::new(j0.clone(), 4);
+        keystream.increment();
+        Ok(Self::Gcm(Gcm {
+            cipher,
+            encrypt,
+            tag_len: tag_bits as usize / 8,
+            j0,
+            keystream,
+            ghash: Ghash {
+                h,
+                state: 0,
+                pending: Vec::new(),
+            },
+            aad_len: 0,
+            text_len: 0,
+            in_text: false,
+        }))
+    }
+
+    pub fn ccm(
+        cipher: BlockCipher,
+        encrypt: bool,
+        nonce: &[u8],
+        tag_bits: u32,
+        aad_len: usize,
+        payload_len: usize,
+    ) -> Result<Self, TEE_Result> {
+        let tag_len = tag_bits as usize / 8;
+        if !matches!(tag_bits, 32 | 48 | 64 | 80 | 96 | 112 | 128) {
+            return Err(raw::TEE_ERROR_NOT_SUPPORTED);
+        }
+        if !(7..=13).contains(&nonce.len()) {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+        let q = 15 - nonce.len();
+        if q < 8 && payload_len >> (q * 8) != 0 {
+            return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+        }
+        let mut b0 =
+            vec![((aad_len > 0) as u8) << 6 | ((tag_len as u8 - 2) / 2) << 3 | (q as u8 - 1)];
+        b0.extend_from_slice(nonce);
+        b0.extend_from_slice(&(payload_len as u64).to_be_bytes()[8 - q..]);
+        let mut mac = CbcMac::new(vec![0; 16]);
+        mac.absorb(&cipher, &b0);
+        if aad_len > 0 {
+            if aad_len < 0xFF00 {
+                mac.absorb(&cipher, &(aad_len as u16).to_be_bytes());
+            } else if aad_len <= u32::MAX as usize {
+                mac.absorb(&cipher, &[0xFF, 0xFE]);
+                mac.absorb(&cipher, &(aad_len as u32).to_be_bytes());
+            } else {
+                mac.absorb(&cipher, &[0xFF, 0xFF]);
+                mac.absorb(&cipher, &(aad_len as u64).to_be_bytes());
+            }
+        }
+        let mut a0 = vec![q as u8 - 1];
+        a0.extend_from_slice(nonce);
+        a0.resize(16, 0);
+        let mut s0 = a0.clone();
+        cipher.encrypt(&mut s0);
+        let mut keystream = Keystream::new(a0, q);
+        keystream.increment();
+        Ok(Self::Ccm(Ccm {
+            cipher,
+            encrypt,
+            tag_len,
+            s0,
+            keystream,
+            mac,
+            aad_len,
+            payload_len,
+            aad_done: 0,
+            text_done: 0,
+        }))
+    }
+
+    pub fn tag_len(&self) -> usize {
+        match self {
+            Self::Gcm(gcm) => gcm.tag_len,
+            Self::Ccm(ccm) => ccm.tag_len,
+        }
+    }
+
+    pub fn update_aad(&mut self, data: &[u8]) -> Result<(), TEE_Result> {
+        match self {
+            Self::Gcm(gcm) => {
+                if gcm.in_text {
+                    return Err(raw::TEE_ERROR_BAD_STATE);
+                }
+                gcm.ghash.update(data);
+                gcm.aad_len += data.len() as u64;
+            }
+            Self::Ccm(ccm) => {
+                if ccm.text_done > 0 || ccm.aad_done + data.len() > ccm.aad_len {
+                    return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+                }
+                ccm.mac.absorb(&ccm.cipher, data);
+                ccm.aad_done += data.len();
+                if ccm.aad_done == ccm.aad_len {
+                    ccm.mac.pad(&ccm.cipher);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, TEE_Result> {
+        let mut output = data.to_vec();
+        match self {
+            Self::Gcm(gcm) => {
+                if !gcm.in_text {
+                    gcm.ghash.pad();
+                    gcm.in_text = true;
+                }
+                if !gcm.encrypt {
+                    gcm.ghash.update(data);
+                }
+                gcm.keystream.apply(&gcm.cipher, &mut output);
+                if gcm.encrypt {
+                    gcm.ghash.update(&output);
+                }
+                gcm.text_len += data.len() as u64;
+            }
+            Self::Ccm(ccm) => {
+                if ccm.aad_done != ccm.aad_len || ccm.text_done + data.len() > ccm.payload_len {
+                    return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+                }
+                if ccm.encrypt {
+                    ccm.mac.absorb(&ccm.cipher, data);
+                }
+                ccm.keystream.apply(&ccm.cipher, &mut output);
+                if !ccm.encrypt {
+                    ccm.mac.absorb(&ccm.cipher, &output);
+                }
+                ccm.text_done += data.len();
+            }
+        }
+        Ok(output)
+    }
+
+    // Returns the full length tag of the processed data.
+    fn tag(&mut self) -> Result<Vec<u8>, TEE_Result> {
+        let mut tag = match self {
+            Self::Gcm(gcm) => {
+                gcm.ghash.pad();
+                let lengths = ((gcm.aad_len as u128 * 8) << 64) | (gcm.text_len as u128 * 8);
+                gcm.ghash.absorb(&lengths.to_be_bytes());
+                let mut tag = gcm.j0.clone();
+                gcm.cipher.encrypt(&mut tag);
+                xor(&mut tag, &gcm.ghash.state.to_be_bytes());
+                tag
+            }
+            Self::Ccm(ccm) => {
+                if ccm.text_done != ccm.payload_len || ccm.aad_done != ccm.aad_len {
+                    return Err(raw::TEE_ERROR_BAD_PARAMETERS);
+                }
+                ccm.mac.pad(&ccm.cipher);
+                let mut tag = ccm.mac.state.clone();
+                xor(&mut tag, &ccm.s0);
+                tag
+            }
+        };
+        tag.truncate(self.tag_len());
+        Ok(tag)
+    }
+
+    pub fn encrypt_final(&mut self, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), TEE_Result> {
+        let output = self.update(data)?;
+        Ok((output, self.tag()?))
+    }
+
+    pub fn decrypt_final(&mut self, data: &[u8], tag: &[u8]) -> Result<Vec<u8>, TEE_Result> {
+        let output = self.update(data)?;
+        let expected = self.tag()?;
+        if expected.len() != tag.len()
+            || expected
+                .iter()
+                .zip(tag)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                != 0
+        {
+            return Err(raw::TEE_ERROR_MAC_INVALID);
+        }
+        Ok(output)
+    }
+}
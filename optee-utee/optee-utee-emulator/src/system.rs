@@ -0,0 +1,265 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+// Random numbers, time, cancellation and panics.
+
+use super::{input, tee_panic, with_state};
+use crate::raw::{self, TEE_Result, TEE_Time};
+use rand::RngCore;
+use std::ffi::{c_int, c_void};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub(super) struct TimeState {
+    boot: Instant,
+    // The TA persistent time as an offset to the system time, None until it
+    // is set.
+    ta_offset: Option<i128>,
+    // Set by roll_back_ta_time until the time is set again.
+    ta_needs_reset: bool,
+}
+
+impl Default for TimeState {
+    fn default() -> Self {
+        Self {
+            boot: Instant::now(),
+            ta_offset: None,
+            ta_needs_reset: false,
+        }
+    }
+}
+
+pub(super) struct CancellationState {
+    requested: bool,
+    masked: bool,
+}
+
+// Cancellation is masked when an entry point of a TA is called.
+impl Default for CancellationState {
+    fn default() -> Self {
+        Self {
+            requested: false,
+            masked: true,
+        }
+    }
+}
+
+/// Requests the cancellation of the current operation, as a client
+/// application would do with `TEEC_RequestCancellation`. It is seen by
+/// `TEE_GetCancellationFlag` and interrupts `TEE_Wait` unless cancellation is
+/// masked, and stays requested until [`reset`](super::reset). As in a TA,
+/// cancellation is masked until `TEE_UnmaskCancellation` is called.
+pub fn request_cancellation() {
+    with_state(|state| state.cancellation.requested = true);
+}
+
+/// Simulates a rollback of the TA persistent time detected by the TEE, e.g.
+/// after its storage was restored from an older copy:
+/// `TEE_GetTAPersistentTime` fails with `TEE_ERROR_TIME_NEEDS_RESET` until the
+/// time is set again with `TEE_SetTAPersistentTime`.
+pub fn roll_back_ta_time() {
+    with_state(|state| state.time.ta_needs_reset = true);
+}
+
+fn to_time(duration: Duration) -> TEE_Time {
+    TEE_Time {
+        seconds: duration.as_secs() as u32,
+        millis: duration.subsec_millis(),
+    }
+}
+
+fn system_millis() -> i128 {
+    with_state(|state| state.time.boot.elapsed().as_millis() as i128)
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GenerateRandom(randomBuffer: *mut c_void, randomBufferLen: usize) {
+    if randomBufferLen == 0 {
+        return;
+    }
+    let buffer =
+        unsafe { std::slice::from_raw_parts_mut(randomBuffer as *mut u8, randomBufferLen) };
+    rand::rngs::OsRng.fill_bytes(buffer);
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetSystemTime(time: *mut TEE_Time) {
+    let elapsed = with_state(|state| state.time.boot.elapsed());
+    unsafe { *time = to_time(elapsed) };
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetREETime(time: *mut TEE_Time) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    unsafe { *time = to_time(now) };
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_Wait(timeout: u32) -> TEE_Result {
+    // The state is per thread, so the cancellation can only have been
+    // requested before the wait.
+    if TEE_GetCancellationFlag() {
+        return raw::TEE_ERROR_CANCEL;
+    }
+    match timeout {
+        raw::TEE_TIMEOUT_INFINITE => loop {
+            std::thread::park();
+        },
+        timeout => std::thread::sleep(Duration::from_millis(timeout as u64)),
+    }
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetTAPersistentTime(time: *mut TEE_Time) -> TEE_Result {
+    let (offset, needs_reset) =
+        with_state(|state| (state.time.ta_offset, state.time.ta_needs_reset));
+    let offset = match offset {
+        Some(offset) if !needs_reset => offset,
+        _ => {
+            unsafe {
+                *time = TEE_Time {
+                    seconds: 0,
+                    millis: 0,
+                }
+            };
+            if needs_reset {
+                return raw::TEE_ERROR_TIME_NEEDS_RESET;
+            }
+            return raw::TEE_ERROR_TIME_NOT_SET;
+        }
+    };
+    let millis = (system_millis() + offset).max(0);
+    let seconds = millis / 1000;
+    unsafe {
+        *time = TEE_Time {
+            seconds: seconds as u32,
+            millis: (millis % 1000) as u32,
+        }
+    };
+    if seconds > u32::MAX as i128 {
+        return raw::TEE_ERROR_OVERFLOW;
+    }
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_SetTAPersistentTime(time: *const TEE_Time) -> TEE_Result {
+    let time = unsafe { &*time };
+    let millis = time.seconds as i128 * 1000 + time.millis as i128;
+    let offset = millis - system_millis();
+    with_state(|state| {
+        state.time.ta_offset = Some(offset);
+        state.time.ta_needs_reset = false;
+    });
+    raw::TEE_SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_GetCancellationFlag() -> bool {
+    with_state(|state| state.cancellation.requested && !state.cancellation.masked)
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_MaskCancellation() -> bool {
+    with_state(|state| std::mem::replace(&mut state.cancellation.masked, true))
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_UnmaskCancellation() -> bool {
+    with_state(|state| std::mem::replace(&mut state.cancellation.masked, false))
+}
+
+#[no_mangle]
+pub extern "C" fn TEE_Panic(panicCode: TEE_Result) {
+    tee_panic("TEE_Panic", &format!("panic code 0x{:08x}", panicCode));
+}
+
+#[no_mangle]
+pub extern "C" fn _utee_log(buf: *const c_void, len: usize) {
+    // Printed with `print!` so that the output is captured by the test harness.
+    print!("{}", String::from_utf8_lossy(unsafe { input(buf, len) }));
+}
+
+#[no_mangle]
+pub extern "C" fn trace_set_level(level: c_int) {
+    with_state(|state| state.trace_level = level);
+}
+
+#[no_mangle]
+pub extern "C" fn trace_get_level() -> c_int {
+    with_state(|state| state.trace_level)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use optee_utee::{Cancellation, ErrorKind, Time};
+
+    #[test]
+    fn test_ta_time() {
+        let mut time = Time::new();
+        assert_eq!(time.ta_time().err().unwrap().kind(), ErrorKind::TimeNotSet);
+        time.seconds = 1000;
+        time.set_ta_time().unwrap();
+        let mut now = Time::new();
+        now.ta_time().unwrap();
+        assert!(now.seconds >= 1000 && now.seconds <= 1001);
+
+        roll_back_ta_time();
+        assert_eq!(
+            now.ta_time().err().unwrap().kind(),
+            ErrorKind::TimeNeedsReset
+        );
+        time.set_ta_time().unwrap();
+        now.ta_time().unwrap();
+    }
+
+    #[test]
+    fn test_cancellation() {
+        request_cancellation();
+        // Cancellation is masked at the start.
+        assert!(!Cancellation::is_requested());
+        assert!(Cancellation::unmask());
+        assert!(Cancellation::is_requested());
+        assert_eq!(
+            Time::wait(raw::TEE_TIMEOUT_INFINITE).err().unwrap().kind(),
+            ErrorKind::Cancel
+        );
+        assert!(!Cancellation::mask());
+        assert!(!Cancellation::is_requested());
+        Time::wait(1).unwrap();
+        assert!(Cancellation::unmask());
+        assert!(Cancellation::is_requested());
+    }
+
+    #[test]
+    fn test_cancellation_nested_mask() {
+        // The values returned by mask and unmask let nested sections restore
+        // the state of the outer one.
+        assert!(Cancellation::unmask());
+        assert!(!Cancellation::unmask());
+        assert!(!Cancellation::mask());
+        assert!(Cancellation::mask());
+        request_cancellation();
+        assert!(!Cancellation::is_requested());
+        assert!(Cancellation::unmask());
+        assert!(!Cancellation::unmask());
+        assert!(Cancellation::is_requested());
+    }
+}
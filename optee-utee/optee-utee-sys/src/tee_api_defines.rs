@@ -118,6 +118,9 @@ pub const TEE_MODE_ILLEGAL_VALUE: u32 = 0x7FFFFFFF;
 
 // Other constants
 pub const TEE_STORAGE_PRIVATE: u32 = 0x00000001;
+// OP-TEE extensions selecting the backend of the private storage
+pub const TEE_STORAGE_PRIVATE_REE: u32 = 0x80000000;
+pub const TEE_STORAGE_PRIVATE_RPMB: u32 = 0x80000100;
 
 pub const TEE_DATA_FLAG_ACCESS_READ: u32 = 0x00000001;
 pub const TEE_DATA_FLAG_ACCESS_WRITE: u32 = 0x00000002;
@@ -304,7 +307,35 @@ pub const TEE_ECC_CURVE_NIST_P224: u32 = 0x00000002;
 pub const TEE_ECC_CURVE_NIST_P256: u32 = 0x00000003;
 pub const TEE_ECC_CURVE_NIST_P384: u32 = 0x00000004;
 pub const TEE_ECC_CURVE_NIST_P521: u32 = 0x00000005;
-pub const TEE_ECC_CURVE_SM2: u32 = 0x00000300;
+pub const TEE_ECC_CURVE_SM2: u32 = 0x00000400;
+
+// OP-TEE extensions of the key derivation functions
+pub const TEE_ALG_HKDF_MD5_DERIVE_KEY: u32 = 0x800010C0;
+pub const TEE_ALG_HKDF_SHA1_DERIVE_KEY: u32 = 0x800020C0;
+pub const TEE_ALG_HKDF_SHA224_DERIVE_KEY: u32 = 0x800030C0;
+pub const TEE_ALG_HKDF_SHA256_DERIVE_KEY: u32 = 0x800040C0;
+pub const TEE_ALG_HKDF_SHA384_DERIVE_KEY: u32 = 0x800050C0;
+pub const TEE_ALG_HKDF_SHA512_DERIVE_KEY: u32 = 0x800060C0;
+pub const TEE_TYPE_HKDF_IKM: u32 = 0xA10000C0;
+pub const TEE_ATTR_HKDF_IKM: u32 = 0xC00001C0;
+pub const TEE_ATTR_HKDF_SALT: u32 = 0xD00002C0;
+pub const TEE_ATTR_HKDF_INFO: u32 = 0xD00003C0;
+pub const TEE_ATTR_HKDF_OKM_LENGTH: u32 = 0xF00004C0;
+pub const TEE_ALG_CONCAT_KDF_SHA1_DERIVE_KEY: u32 = 0x800020C1;
+pub const TEE_ALG_CONCAT_KDF_SHA224_DERIVE_KEY: u32 = 0x800030C1;
+pub const TEE_ALG_CONCAT_KDF_SHA256_DERIVE_KEY: u32 = 0x800040C1;
+pub const TEE_ALG_CONCAT_KDF_SHA384_DERIVE_KEY: u32 = 0x800050C1;
+pub const TEE_ALG_CONCAT_KDF_SHA512_DERIVE_KEY: u32 = 0x800060C1;
+pub const TEE_TYPE_CONCAT_KDF_Z: u32 = 0xA10000C1;
+pub const TEE_ATTR_CONCAT_KDF_Z: u32 = 0xC00001C1;
+pub const TEE_ATTR_CONCAT_KDF_OTHER_INFO: u32 = 0xD00002C1;
+pub const TEE_ATTR_CONCAT_KDF_DKM_LENGTH: u32 = 0xF00003C1;
+pub const TEE_ALG_PBKDF2_HMAC_SHA1_DERIVE_KEY: u32 = 0x800020C2;
+pub const TEE_TYPE_PBKDF2_PASSWORD: u32 = 0xA10000C2;
+pub const TEE_ATTR_PBKDF2_PASSWORD: u32 = 0xC00001C2;
+pub const TEE_ATTR_PBKDF2_SALT: u32 = 0xD00002C2;
+pub const TEE_ATTR_PBKDF2_DKM_LENGTH: u32 = 0xF00003C2;
+pub const TEE_ATTR_PBKDF2_ITERATION_COUNT: u32 = 0xF00004C2;
 
 // Panicked Functions Identification
 // TA Interface
@@ -0,0 +1,76 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use optee_utee_sys as raw;
+
+/// Access to the cancellation state of the current operation.
+///
+/// A client application may request the cancellation of a pending operation,
+/// the TA is then expected to poll [`Cancellation::is_requested`] in its
+/// long-running loops and return early, usually with `ErrorKind::Cancel`.
+/// Cancellation is masked when an entry point is called, so a TA which polls
+/// for cancellation must first unmask it with [`Cancellation::unmask`].
+pub struct Cancellation;
+
+impl Cancellation {
+    /// Returns true if cancellation of the current operation has been requested
+    /// and cancellation is not masked.
+    ///
+    /// # Example
+    ///
+    /// ``` rust,no_run
+    /// # use optee_utee::{Cancellation, Error, ErrorKind, Result};
+    /// # fn train_one_epoch() {}
+    /// # fn main() -> Result<()> {
+    /// Cancellation::unmask();
+    /// for _ in 0..100 {
+    ///     if Cancellation::is_requested() {
+    ///         return Err(Error::new(ErrorKind::Cancel));
+    ///     }
+    ///     train_one_epoch();
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_requested() -> bool {
+        unsafe { raw::TEE_GetCancellationFlag() }
+    }
+
+    /// Masks the effects of cancellation, [`Cancellation::is_requested`] then
+    /// returns false until cancellation is unmasked. Returns true if
+    /// cancellation was already masked.
+    ///
+    /// # Example
+    ///
+    /// ``` rust,no_run
+    /// # use optee_utee::Cancellation;
+    /// let was_masked = Cancellation::mask();
+    /// // A section which must not be interrupted.
+    /// if !was_masked {
+    ///     Cancellation::unmask();
+    /// }
+    /// ```
+    pub fn mask() -> bool {
+        unsafe { raw::TEE_MaskCancellation() }
+    }
+
+    /// Unmasks the effects of cancellation. Returns true if cancellation was
+    /// masked before the call.
+    pub fn unmask() -> bool {
+        unsafe { raw::TEE_UnmaskCancellation() }
+    }
+}
@@ -0,0 +1,394 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::stream::{pkcs7_pad, pkcs7_unpad};
+use crate::key::{
+    AesAeMode, AesCipherMode, AesKey, Curve, EcdsaHash, EcdsaKeypair, EcdsaPublicKey,
+    Ed25519Keypair, Ed25519PublicKey, HmacHash, HmacKey, RsaEncryptionScheme, RsaKeypair,
+    RsaPublicKey, Sm2DsaKeypair, Sm2DsaPublicKey, Sm2PkeKeypair, Sm2PkePublicKey,
+};
+use crate::{
+    AlgorithmId, AttributeId, Digest, Error, ErrorKind, GenericObject, OperationMode, Result,
+    TransientObject,
+};
+use alloc::vec::Vec;
+
+/// The length in bytes of the tags appended by [aes_gcm_seal].
+pub const AES_GCM_TAG_LEN: usize = 16;
+
+const AES_BLOCK_LEN: usize = 16;
+
+const SM2_LEN: usize = 32;
+
+// The parameters `a`, `b`, `Gx` and `Gy` of the SM2 curve, hashed with the
+// identifier and the public key of a signer.
+const SM2_CURVE_PARAMETERS: [u8; 4 * SM2_LEN] = [
+    0xFF, 0xFF, 0xFF, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFC,
+    0x28, 0xE9, 0xFA, 0x9E, 0x9D, 0x9F, 0x5E, 0x34, 0x4D, 0x5A, 0x9E, 0x4B, 0xCF, 0x65, 0x09, 0xA7,
+    0xF3, 0x97, 0x89, 0xF5, 0x15, 0xAB, 0x8F, 0x92, 0xDD, 0xBC, 0xBD, 0x41, 0x4D, 0x94, 0x0E, 0x93,
+    0x32, 0xC4, 0xAE, 0x2C, 0x1F, 0x19, 0x81, 0x19, 0x5F, 0x99, 0x04, 0x46, 0x6A, 0x39, 0xC9, 0x94,
+    0x8F, 0xE3, 0x0B, 0xBF, 0xF2, 0x66, 0x0B, 0xE1, 0x71, 0x5A, 0x45, 0x89, 0x33, 0x4C, 0x74, 0xC7,
+    0xBC, 0x37, 0x36, 0xA2, 0xF4, 0xF6, 0x77, 0x9C, 0x59, 0xBD, 0xCE, 0xE3, 0x6B, 0x69, 0x21, 0x53,
+    0xD0, 0xA9, 0x87, 0x7C, 0xC6, 0x2A, 0x47, 0x40, 0x02, 0xDF, 0x32, 0xE5, 0x21, 0x39, 0xF0, 0xA0,
+];
+
+/// Returns the SHA-256 hash of `data`.
+pub fn sha256(data: &[u8]) -> Result<Vec<u8>> {
+    digest(AlgorithmId::Sha256, 32, data)
+}
+
+/// Returns the SHA-384 hash of `data`.
+pub fn sha384(data: &[u8]) -> Result<Vec<u8>> {
+    digest(AlgorithmId::Sha384, 48, data)
+}
+
+/// Returns the SHA-512 hash of `data`.
+pub fn sha512(data: &[u8]) -> Result<Vec<u8>> {
+    digest(AlgorithmId::Sha512, 64, data)
+}
+
+/// Returns the SM3 hash of `data`.
+pub fn sm3(data: &[u8]) -> Result<Vec<u8>> {
+    digest(AlgorithmId::Sm3, 32, data)
+}
+
+/// Returns the HMAC of `message` with the hash function `H` and the key `key`.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::easy;
+/// # use optee_utee::key::Sha256;
+/// # fn main() -> optee_utee::Result<()> {
+/// # let key = [0u8; 32];
+/// let tag = easy::hmac::<Sha256>(&key, b"message")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If the length of `key` is not a valid key size of the
+///    HMAC algorithm.
+pub fn hmac<H: HmacHash>(key: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let mac = HmacKey::<H>::from_bytes(key)?.mac()?;
+    mac.init(&[]);
+    // Large enough for the tags of all the hash functions.
+    let mut tag = vec![0u8; 64];
+    let len = mac.compute_final(message, &mut tag)?;
+    tag.truncate(len);
+    Ok(tag)
+}
+
+/// Encrypts and authenticates `plaintext` and `aad` with AES-GCM, returns the
+/// ciphertext followed by the tag of [AES_GCM_TAG_LEN] bytes.
+///
+/// The `nonce` must never be reused with the same key, a random nonce of 12
+/// bytes is the usual choice.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::easy;
+/// # use optee_utee::Random;
+/// # fn main() -> optee_utee::Result<()> {
+/// # let key = [0u8; 32];
+/// let mut nonce = [0u8; 12];
+/// Random::generate(&mut nonce);
+/// let sealed = easy::aes_gcm_seal(&key, &nonce, b"header", b"secret")?;
+/// let opened = easy::aes_gcm_open(&key, &nonce, b"header", &sealed)?;
+/// assert_eq!(opened, b"secret");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `key` is not of 16, 24 or 32 bytes.
+/// 2) `NotSupported`: If the length of `nonce` is not supported.
+pub fn aes_gcm_seal(key: &[u8], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let operation = AesKey::from_bytes(key)?.ae(AesAeMode::Gcm, OperationMode::Encrypt)?;
+    operation.init(nonce, AES_GCM_TAG_LEN * 8, aad.len(), plaintext.len())?;
+    operation.update_aad(aad);
+    let mut sealed = vec![0u8; plaintext.len() + AES_GCM_TAG_LEN];
+    let (ciphertext, tag) = sealed.split_at_mut(plaintext.len());
+    let (len, tag_len) = operation.encrypt_final(plaintext, ciphertext, tag)?;
+    if len != plaintext.len() || tag_len != AES_GCM_TAG_LEN {
+        return Err(Error::new(ErrorKind::Generic));
+    }
+    Ok(sealed)
+}
+
+/// Checks and decrypts `sealed`, the output of [aes_gcm_seal] with the same
+/// `key`, `nonce` and `aad`, returns the plaintext.
+///
+/// # Errors
+///
+/// 1) `MacInvalid`: If `sealed` or `aad` is not authentic.
+/// 2) `BadParameters`: If `key` is not of 16, 24 or 32 bytes.
+/// 3) `NotSupported`: If the length of `nonce` is not supported.
+pub fn aes_gcm_open(key: &[u8], nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    if sealed.len() < AES_GCM_TAG_LEN {
+        return Err(Error::new(ErrorKind::MacInvalid));
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - AES_GCM_TAG_LEN);
+    let operation = AesKey::from_bytes(key)?.ae(AesAeMode::Gcm, OperationMode::Decrypt)?;
+    operation.init(nonce, AES_GCM_TAG_LEN * 8, aad.len(), ciphertext.len())?;
+    operation.update_aad(aad);
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let len = operation.decrypt_final(ciphertext, &mut plaintext, tag)?;
+    plaintext.truncate(len);
+    Ok(plaintext)
+}
+
+/// Encrypts `plaintext` with AES-CBC, padded with PKCS#7, returns the
+/// ciphertext.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `key` is not of 16, 24 or 32 bytes, or `iv` is not
+///    of 16 bytes.
+pub fn aes_cbc_pkcs7_encrypt(key: &[u8], iv: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    if iv.len() != AES_BLOCK_LEN {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let mut padded = Vec::with_capacity(plaintext.len() + AES_BLOCK_LEN);
+    padded.extend_from_slice(plaintext);
+    pkcs7_pad(plaintext.len(), AES_BLOCK_LEN, &mut padded);
+    let operation =
+        AesKey::from_bytes(key)?.cipher(AesCipherMode::CbcNopad, OperationMode::Encrypt)?;
+    operation.init(iv);
+    let mut ciphertext = vec![0u8; padded.len()];
+    let len = operation.do_final(&padded, &mut ciphertext)?;
+    ciphertext.truncate(len);
+    Ok(ciphertext)
+}
+
+/// Decrypts `ciphertext` with AES-CBC and removes its PKCS#7 padding, returns
+/// the plaintext.
+///
+/// The padding is checked without branching on its content, but CBC is not
+/// authenticated, the ciphertext should be checked with a MAC before, or AES-GCM
+/// be used instead.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `key` is not of 16, 24 or 32 bytes, `iv` is not of
+///    16 bytes, or the length of `ciphertext` is not a non-zero multiple of 16.
+/// 2) `BadFormat`: If the padding is invalid.
+pub fn aes_cbc_pkcs7_decrypt(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    if iv.len() != AES_BLOCK_LEN || ciphertext.is_empty() || ciphertext.len() % AES_BLOCK_LEN != 0 {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let operation =
+        AesKey::from_bytes(key)?.cipher(AesCipherMode::CbcNopad, OperationMode::Decrypt)?;
+    operation.init(iv);
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let len = operation.do_final(ciphertext, &mut plaintext)?;
+    plaintext.truncate(len);
+    pkcs7_unpad(&mut plaintext, AES_BLOCK_LEN)?;
+    Ok(plaintext)
+}
+
+/// Hashes `message` with `hash` and signs the digest with the ECDSA key `key`,
+/// returns the signature as the concatenation of `r` and `s`.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::easy;
+/// # use optee_utee::key::{EcdsaHash, EcdsaKeypair, NistP256};
+/// # fn main() -> optee_utee::Result<()> {
+/// let key = EcdsaKeypair::<NistP256>::generate()?;
+/// let signature = easy::ecdsa_sign_message(&key, EcdsaHash::Sha256, b"message")?;
+/// easy::ecdsa_verify_message(&key.public_key()?, EcdsaHash::Sha256, b"message", &signature)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn ecdsa_sign_message<C: Curve>(
+    key: &EcdsaKeypair<C>,
+    hash: EcdsaHash,
+    message: &[u8],
+) -> Result<Vec<u8>> {
+    let digest = hash_message(hash, message)?;
+    let mut signature = vec![0u8; 2 * ((C::KEY_SIZE + 7) / 8)];
+    let len = key
+        .signer(hash)?
+        .sign_digest(&[], &digest, &mut signature)?;
+    signature.truncate(len);
+    Ok(signature)
+}
+
+/// Hashes `message` with `hash` and verifies `signature`, the concatenation of
+/// `r` and `s`, with the ECDSA key `key`.
+///
+/// # Errors
+///
+/// 1) `SignatureInvalid`: If the signature is invalid.
+pub fn ecdsa_verify_message<C: Curve>(
+    key: &EcdsaPublicKey<C>,
+    hash: EcdsaHash,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    let digest = hash_message(hash, message)?;
+    key.verifier(hash)?.verify_digest(&[], &digest, signature)
+}
+
+/// Signs `message` with the Ed25519 key `key`, returns the signature of 64
+/// bytes.
+pub fn ed25519_sign(key: &Ed25519Keypair, message: &[u8]) -> Result<Vec<u8>> {
+    let mut signature = vec![0u8; 64];
+    let len = key.signer()?.sign_digest(&[], message, &mut signature)?;
+    signature.truncate(len);
+    Ok(signature)
+}
+
+/// Verifies the Ed25519 `signature` of `message` with the key `key`.
+///
+/// # Errors
+///
+/// 1) `SignatureInvalid`: If the signature is invalid.
+pub fn ed25519_verify(key: &Ed25519PublicKey, message: &[u8], signature: &[u8]) -> Result<()> {
+    key.verifier()?.verify_digest(&[], message, signature)
+}
+
+/// Encrypts `plaintext` with RSAES-OAEP, SHA-256 and MGF1 with SHA-256, and the
+/// key `key`, returns the ciphertext of the size of the modulus.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `plaintext` is longer than the size of the modulus
+///    minus 66 bytes.
+pub fn rsa_oaep_encrypt(key: &RsaPublicKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    key.encrypter(RsaEncryptionScheme::OaepMgf1Sha256)?
+        .encrypt(&[], plaintext)
+}
+
+/// Decrypts `ciphertext`, the output of [rsa_oaep_encrypt] with the public key
+/// of `key`, returns the plaintext.
+pub fn rsa_oaep_decrypt(key: &RsaKeypair, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    key.decrypter(RsaEncryptionScheme::OaepMgf1Sha256)?
+        .decrypt(&[], ciphertext)
+}
+
+/// Signs `message` with the SM2 key `key` of the signer with the identifier
+/// `id`, returns the signature of 64 bytes, the concatenation of `r` and `s`.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `id` is longer than 8191 bytes.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::easy;
+/// # use optee_utee::key::Sm2DsaKeypair;
+/// # fn main() -> optee_utee::Result<()> {
+/// let key = Sm2DsaKeypair::generate()?;
+/// let signature = easy::sm2_sign(&key, b"1234567812345678", b"message")?;
+/// easy::sm2_verify(&key.public_key()?, b"1234567812345678", b"message", &signature)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn sm2_sign(key: &Sm2DsaKeypair, id: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    let digest = sm2_hash_message(key.object(), id, message)?;
+    let mut signature = vec![0u8; 2 * SM2_LEN];
+    let len = key.signer()?.sign_digest(&[], &digest, &mut signature)?;
+    signature.truncate(len);
+    Ok(signature)
+}
+
+/// Verifies the SM2 `signature` of `message` with the key `key` of the signer
+/// with the identifier `id`.
+///
+/// # Errors
+///
+/// 1) `BadParameters`: If `id` is longer than 8191 bytes.
+/// 2) `SignatureInvalid`: If the signature is invalid.
+pub fn sm2_verify(
+    key: &Sm2DsaPublicKey,
+    id: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    let digest = sm2_hash_message(key.object(), id, message)?;
+    key.verifier()?.verify_digest(&[], &digest, signature)
+}
+
+/// Encrypts `plaintext` with the SM2 key `key`, returns the ciphertext, the
+/// concatenation of `C1`, `C3` and `C2`.
+pub fn sm2_encrypt(key: &Sm2PkePublicKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    key.encrypter()?.encrypt(&[], plaintext)
+}
+
+/// Decrypts `ciphertext`, the output of [sm2_encrypt] with the public key of
+/// `key`, returns the plaintext.
+pub fn sm2_decrypt(key: &Sm2PkeKeypair, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    key.decrypter()?.decrypt(&[], ciphertext)
+}
+
+fn digest(algorithm: AlgorithmId, len: usize, data: &[u8]) -> Result<Vec<u8>> {
+    let mut hash = vec![0u8; len];
+    let len = Digest::allocate(algorithm)?.do_final(data, &mut hash)?;
+    hash.truncate(len);
+    Ok(hash)
+}
+
+fn hash_message(hash: EcdsaHash, message: &[u8]) -> Result<Vec<u8>> {
+    match hash {
+        EcdsaHash::Sha1 => digest(AlgorithmId::Sha1, 20, message),
+        EcdsaHash::Sha224 => digest(AlgorithmId::Sha224, 28, message),
+        EcdsaHash::Sha256 => sha256(message),
+        EcdsaHash::Sha384 => sha384(message),
+        EcdsaHash::Sha512 => sha512(message),
+    }
+}
+
+// Returns `SM3(Z || message)`, where `Z` is the hash of the identifier and
+// the public key of the signer.
+fn sm2_hash_message(key: &TransientObject, id: &[u8], message: &[u8]) -> Result<Vec<u8>> {
+    // The length of the identifier is hashed as a 16-bit number of bits.
+    if id.len() > 0x1FFF {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let mut public_key = [0u8; 2 * SM2_LEN];
+    let (x, y) = public_key.split_at_mut(SM2_LEN);
+    sm2_coordinate(key, AttributeId::EccPublicValueX, x)?;
+    sm2_coordinate(key, AttributeId::EccPublicValueY, y)?;
+
+    let operation = Digest::allocate(AlgorithmId::Sm3)?;
+    operation.update(&((id.len() * 8) as u16).to_be_bytes());
+    operation.update(id);
+    operation.update(&SM2_CURVE_PARAMETERS);
+    let mut z = [0u8; 32];
+    operation.do_final(&public_key, &mut z)?;
+    operation.update(&z);
+    let mut hash = vec![0u8; 32];
+    operation.do_final(message, &mut hash)?;
+    Ok(hash)
+}
+
+// Writes the coordinate `id` of the public point of `key` into `out`,
+// left-padded with zeros.
+fn sm2_coordinate(key: &TransientObject, id: AttributeId, out: &mut [u8]) -> Result<()> {
+    let mut buffer = [0u8; SM2_LEN];
+    let len = key.ref_attribute(id, &mut buffer)?;
+    out[SM2_LEN - len..].copy_from_slice(&buffer[..len]);
+    Ok(())
+}
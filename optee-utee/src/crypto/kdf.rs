@@ -0,0 +1,306 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::{
+    AlgorithmId, Attribute, AttributeId, AttributeMemref, AttributeValue, DeriveKey, Error,
+    ErrorKind, Result, TransientObject, TransientObjectType,
+};
+use alloc::vec::Vec;
+
+/// The maximum length in bytes of the input keys and the derived keys, the
+/// 4096 bits of a [GenericSecret](crate::TransientObjectType::GenericSecret).
+pub const MAX_KEY_LEN: usize = 512;
+
+/// The hash functions of [Hkdf].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HkdfHash {
+    Md5,
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HkdfHash {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::Md5 => AlgorithmId::HkdfMd5DeriveKey,
+            Self::Sha1 => AlgorithmId::HkdfSha1DeriveKey,
+            Self::Sha224 => AlgorithmId::HkdfSha224DeriveKey,
+            Self::Sha256 => AlgorithmId::HkdfSha256DeriveKey,
+            Self::Sha384 => AlgorithmId::HkdfSha384DeriveKey,
+            Self::Sha512 => AlgorithmId::HkdfSha512DeriveKey,
+        }
+    }
+}
+
+/// The hash functions of [ConcatKdf].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConcatKdfHash {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl ConcatKdfHash {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::Sha1 => AlgorithmId::ConcatKdfSha1DeriveKey,
+            Self::Sha224 => AlgorithmId::ConcatKdfSha224DeriveKey,
+            Self::Sha256 => AlgorithmId::ConcatKdfSha256DeriveKey,
+            Self::Sha384 => AlgorithmId::ConcatKdfSha384DeriveKey,
+            Self::Sha512 => AlgorithmId::ConcatKdfSha512DeriveKey,
+        }
+    }
+}
+
+/// A builder of HKDF (RFC 5869) derivations, producing
+/// [GenericSecret](crate::TransientObjectType::GenericSecret) objects.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::kdf::{Hkdf, HkdfHash};
+/// # use optee_utee::{AttributeId, GenericObject};
+/// # fn main() -> optee_utee::Result<()> {
+/// # let (shared_secret, salt) = ([0u8; 32], [0u8; 16]);
+/// let key = Hkdf::new(HkdfHash::Sha256)
+///     .with_salt(&salt)
+///     .with_info(b"session key")
+///     .derive(&shared_secret, 32)?;
+/// let mut session_key = [0u8; 32];
+/// key.ref_attribute(AttributeId::SecretValue, &mut session_key)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Hkdf<'a> {
+    hash: HkdfHash,
+    salt: Option<&'a [u8]>,
+    info: Option<&'a [u8]>,
+}
+
+impl<'a> Hkdf<'a> {
+    /// Creates a new builder with the hash function `hash`, without salt and
+    /// info.
+    pub fn new(hash: HkdfHash) -> Self {
+        Self {
+            hash,
+            salt: None,
+            info: None,
+        }
+    }
+
+    /// Sets the salt of the extract step.
+    pub fn with_salt(mut self, salt: &'a [u8]) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Sets the context and application specific info of the expand step.
+    pub fn with_info(mut self, info: &'a [u8]) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Derives a secret of `okm_len` bytes from the input keying material
+    /// `ikm`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `ikm` is empty or longer than [MAX_KEY_LEN]
+    ///    bytes, or if `okm_len` is zero or larger than [MAX_KEY_LEN].
+    /// 2) `NotSupported`: If the algorithm is not supported.
+    pub fn derive(&self, ikm: &[u8], okm_len: usize) -> Result<TransientObject> {
+        let mut params: Vec<Attribute> =
+            vec![AttributeValue::from_value(AttributeId::HkdfOkmLength, okm_len as u32, 0).into()];
+        if let Some(salt) = self.salt {
+            params.push(AttributeMemref::from_ref(AttributeId::HkdfSalt, salt).into());
+        }
+        if let Some(info) = self.info {
+            params.push(AttributeMemref::from_ref(AttributeId::HkdfInfo, info).into());
+        }
+        derive(
+            self.hash.algorithm(),
+            TransientObjectType::HkdfIkm,
+            AttributeId::HkdfIkm,
+            ikm,
+            &params,
+            okm_len,
+        )
+    }
+}
+
+/// A builder of the Concat KDF derivations, the single-step key derivation of
+/// NIST SP 800-56A with a hash function, producing
+/// [GenericSecret](crate::TransientObjectType::GenericSecret) objects.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::kdf::{ConcatKdf, ConcatKdfHash};
+/// # fn main() -> optee_utee::Result<()> {
+/// # let (shared_secret, other_info) = ([0u8; 32], [0u8; 16]);
+/// let key = ConcatKdf::new(ConcatKdfHash::Sha256)
+///     .with_other_info(&other_info)
+///     .derive(&shared_secret, 16)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ConcatKdf<'a> {
+    hash: ConcatKdfHash,
+    other_info: Option<&'a [u8]>,
+}
+
+impl<'a> ConcatKdf<'a> {
+    /// Creates a new builder with the hash function `hash`, without other
+    /// info.
+    pub fn new(hash: ConcatKdfHash) -> Self {
+        Self {
+            hash,
+            other_info: None,
+        }
+    }
+
+    /// Sets the other info, the context of the derivation.
+    pub fn with_other_info(mut self, other_info: &'a [u8]) -> Self {
+        self.other_info = Some(other_info);
+        self
+    }
+
+    /// Derives a secret of `dkm_len` bytes from the shared secret
+    /// `shared_secret`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `shared_secret` is empty or longer than
+    ///    [MAX_KEY_LEN] bytes, or if `dkm_len` is zero or larger than
+    ///    [MAX_KEY_LEN].
+    /// 2) `NotSupported`: If the algorithm is not supported.
+    pub fn derive(&self, shared_secret: &[u8], dkm_len: usize) -> Result<TransientObject> {
+        let mut params: Vec<Attribute> =
+            vec![
+                AttributeValue::from_value(AttributeId::ConcatKdfDkmLength, dkm_len as u32, 0)
+                    .into(),
+            ];
+        if let Some(other_info) = self.other_info {
+            params.push(
+                AttributeMemref::from_ref(AttributeId::ConcatKdfOtherInfo, other_info).into(),
+            );
+        }
+        derive(
+            self.hash.algorithm(),
+            TransientObjectType::ConcatKdfZ,
+            AttributeId::ConcatKdfZ,
+            shared_secret,
+            &params,
+            dkm_len,
+        )
+    }
+}
+
+/// A builder of PBKDF2 (RFC 8018) derivations with HMAC-SHA1, producing
+/// [GenericSecret](crate::TransientObjectType::GenericSecret) objects.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::kdf::Pbkdf2;
+/// # fn main() -> optee_utee::Result<()> {
+/// # let salt = [0u8; 16];
+/// let key = Pbkdf2::new(100_000)
+///     .with_salt(&salt)
+///     .derive(b"password", 32)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Pbkdf2<'a> {
+    iteration_count: u32,
+    salt: Option<&'a [u8]>,
+}
+
+impl<'a> Pbkdf2<'a> {
+    /// Creates a new builder with `iteration_count` iterations, without salt.
+    pub fn new(iteration_count: u32) -> Self {
+        Self {
+            iteration_count,
+            salt: None,
+        }
+    }
+
+    /// Sets the salt.
+    pub fn with_salt(mut self, salt: &'a [u8]) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// Derives a secret of `dkm_len` bytes from the password `password`.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If the iteration count is zero, if `password` is
+    ///    empty or longer than [MAX_KEY_LEN] bytes, or if `dkm_len` is zero or
+    ///    larger than [MAX_KEY_LEN].
+    /// 2) `NotSupported`: If the algorithm is not supported.
+    pub fn derive(&self, password: &[u8], dkm_len: usize) -> Result<TransientObject> {
+        if self.iteration_count == 0 {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let mut params: Vec<Attribute> = vec![
+            AttributeValue::from_value(AttributeId::Pbkdf2DkmLength, dkm_len as u32, 0).into(),
+            AttributeValue::from_value(AttributeId::Pbkdf2IterationCount, self.iteration_count, 0)
+                .into(),
+        ];
+        if let Some(salt) = self.salt {
+            params.push(AttributeMemref::from_ref(AttributeId::Pbkdf2Salt, salt).into());
+        }
+        derive(
+            AlgorithmId::Pbkdf2HmacSha1DeriveKey,
+            TransientObjectType::Pbkdf2Password,
+            AttributeId::Pbkdf2Password,
+            password,
+            &params,
+            dkm_len,
+        )
+    }
+}
+
+// Derives a secret of `len` bytes from `key`, held by an object of
+// `key_type` as the attribute `key_id`. `TEE_DeriveKey` panics on invalid
+// parameters, so the lengths are checked beforehand.
+fn derive(
+    algorithm: AlgorithmId,
+    key_type: TransientObjectType,
+    key_id: AttributeId,
+    key: &[u8],
+    params: &[Attribute],
+    len: usize,
+) -> Result<TransientObject> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN || len == 0 || len > MAX_KEY_LEN {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    let key_size = key.len() * 8;
+    let mut object = TransientObject::allocate(key_type, key_size)?;
+    object.populate(&[AttributeMemref::from_ref(key_id, key).into()])?;
+    let operation = DeriveKey::allocate(algorithm, key_size)?;
+    operation.set_key(&object)?;
+    let mut secret = TransientObject::allocate(TransientObjectType::GenericSecret, len * 8)?;
+    operation.derive(params, &mut secret);
+    Ok(secret)
+}
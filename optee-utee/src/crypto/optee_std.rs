@@ -0,0 +1,67 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::{AeReader, AeWriter, CipherReader, CipherWriter};
+use crate::{Digest, Mac, StdCompatRead, StdCompatWrite};
+
+impl std::io::Write for Digest {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        StdCompatWrite::write(self, buf).map_err(Into::into)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::io::Write for Mac {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        StdCompatWrite::write(self, buf).map_err(Into::into)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, W: StdCompatWrite> std::io::Write for CipherWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        StdCompatWrite::write(self, buf).map_err(Into::into)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        StdCompatWrite::flush(self).map_err(Into::into)
+    }
+}
+
+impl<'a, W: StdCompatWrite> std::io::Write for AeWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        StdCompatWrite::write(self, buf).map_err(Into::into)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        StdCompatWrite::flush(self).map_err(Into::into)
+    }
+}
+
+impl<'a, R: StdCompatRead> std::io::Read for CipherReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        StdCompatRead::read(self, buf).map_err(Into::into)
+    }
+}
+
+impl<'a, R: StdCompatRead> std::io::Read for AeReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        StdCompatRead::read(self, buf).map_err(Into::into)
+    }
+}
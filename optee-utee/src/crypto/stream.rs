@@ -0,0 +1,559 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use crate::{
+    Cipher, Digest, Error, ErrorKind, Mac, OperationMode, Result, StdCompatRead, StdCompatWrite, AE,
+};
+use alloc::vec::Vec;
+
+// The size of the chunks read from the inner reader of a `CipherReader` or
+// `AeReader`.
+const CHUNK_LEN: usize = 1024;
+
+// The largest block size of the algorithms, the bound of the data the TEE
+// keeps between two updates.
+const MAX_BLOCK_LEN: usize = 16;
+
+/// The padding of the plaintext of a [CipherWriter] or [CipherReader].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Padding {
+    /// No padding, the length of the data must be a multiple of the block size
+    /// for the ECB and CBC algorithms.
+    None,
+    /// PKCS#7 padding, added when encrypting and checked and removed when
+    /// decrypting, for the ECB and CBC algorithms.
+    Pkcs7,
+}
+
+/// Digests the data written, as [update](Digest::update) does.
+impl StdCompatWrite for Digest {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+}
+
+/// MACs the data written, as [update](Mac::update) does.
+impl StdCompatWrite for Mac {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+}
+
+/// Collects the data written, e.g. the output of a [CipherWriter].
+impl StdCompatWrite for Vec<u8> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+/// Reads the data of a buffer, e.g. the input of a [CipherReader].
+impl StdCompatRead for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len().min(self.len());
+        let (data, rest) = self.split_at(len);
+        buf[..len].copy_from_slice(data);
+        *self = rest;
+        Ok(len)
+    }
+}
+
+// Appends the PKCS#7 padding of data of `len` bytes to `out`.
+pub(super) fn pkcs7_pad(len: usize, block_len: usize, out: &mut Vec<u8>) {
+    let pad = block_len - len % block_len;
+    out.resize(out.len() + pad, pad as u8);
+}
+
+// Checks and removes the PKCS#7 padding of `data`, without branching on the
+// content of the padding.
+pub(super) fn pkcs7_unpad(data: &mut Vec<u8>, block_len: usize) -> Result<()> {
+    if data.is_empty() || data.len() % block_len != 0 {
+        return Err(Error::new(ErrorKind::BadFormat));
+    }
+    let pad = data[data.len() - 1];
+    let last_block = &data[data.len() - block_len..];
+    let mut invalid = (pad == 0) as u8 | (pad as usize > block_len) as u8;
+    for (index, byte) in last_block.iter().enumerate() {
+        let in_padding = (block_len - index <= pad as usize) as u8;
+        invalid |= in_padding & (*byte != pad) as u8;
+    }
+    if invalid != 0 {
+        return Err(Error::new(ErrorKind::BadFormat));
+    }
+    data.truncate(data.len() - pad as usize);
+    Ok(())
+}
+
+// The block size of a cipher algorithm, from its main algorithm identifier.
+fn block_len(algorithm: u32) -> usize {
+    match algorithm & 0xFF {
+        // DES and Triple DES
+        0x11 | 0x13 => 8,
+        _ => 16,
+    }
+}
+
+// A transformation of a stream of data, a cipher or AE operation.
+trait Transform {
+    fn update(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<()>;
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()>;
+}
+
+struct CipherTransform<'a> {
+    cipher: &'a Cipher,
+    padding: Padding,
+    decrypt: bool,
+    block_len: usize,
+    // The number of bytes given to update, for the padding of the last block.
+    len: usize,
+    // The plaintext held back when decrypting with padding, as the last block
+    // holds the padding.
+    held: Vec<u8>,
+}
+
+impl<'a> CipherTransform<'a> {
+    fn new(cipher: &'a Cipher, padding: Padding) -> Self {
+        let info = cipher.info();
+        Self {
+            cipher,
+            padding,
+            decrypt: info.mode() == OperationMode::Decrypt as u32,
+            block_len: block_len(info.algorithm()),
+            len: 0,
+            held: Vec::new(),
+        }
+    }
+
+    fn unpadding(&self) -> bool {
+        self.decrypt && self.padding == Padding::Pkcs7
+    }
+}
+
+impl<'a> Transform for CipherTransform<'a> {
+    fn update(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let mut output = vec![0u8; input.len() + MAX_BLOCK_LEN];
+        let len = self.cipher.update(input, &mut output)?;
+        self.len += input.len();
+        if self.unpadding() {
+            self.held.extend_from_slice(&output[..len]);
+            if self.held.len() > self.block_len {
+                let released = self.held.len() - self.block_len;
+                out.extend(self.held.drain(..released));
+            }
+        } else {
+            out.extend_from_slice(&output[..len]);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let mut input = Vec::new();
+        if !self.decrypt && self.padding == Padding::Pkcs7 {
+            pkcs7_pad(self.len, self.block_len, &mut input);
+        }
+        let mut output = vec![0u8; input.len() + 2 * MAX_BLOCK_LEN];
+        let len = self.cipher.do_final(&input, &mut output)?;
+        if self.unpadding() {
+            self.held.extend_from_slice(&output[..len]);
+            pkcs7_unpad(&mut self.held, self.block_len)?;
+            out.append(&mut self.held);
+        } else {
+            out.extend_from_slice(&output[..len]);
+        }
+        Ok(())
+    }
+}
+
+// The tag of an AE operation, computed when encrypting or checked when
+// decrypting.
+enum Tag<'a> {
+    Compute(Vec<u8>),
+    Check(&'a [u8]),
+}
+
+struct AeTransform<'a> {
+    ae: &'a AE,
+    tag: Tag<'a>,
+}
+
+impl<'a> Transform for AeTransform<'a> {
+    fn update(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        ae_update(self.ae, input, out)
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        match &mut self.tag {
+            Tag::Compute(tag) => {
+                let len = ae_encrypt_final(self.ae, tag, out)?;
+                tag.truncate(len);
+                Ok(())
+            }
+            Tag::Check(tag) => ae_decrypt_final(self.ae, tag, out),
+        }
+    }
+}
+
+fn ae_update(ae: &AE, input: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    let mut output = vec![0u8; input.len() + MAX_BLOCK_LEN];
+    let len = ae.update(input, &mut output)?;
+    out.extend_from_slice(&output[..len]);
+    Ok(())
+}
+
+// Returns the length of the tag.
+fn ae_encrypt_final(ae: &AE, tag: &mut [u8], out: &mut Vec<u8>) -> Result<usize> {
+    let mut output = [0u8; 2 * MAX_BLOCK_LEN];
+    let (len, tag_len) = ae.encrypt_final(&[], &mut output, tag)?;
+    out.extend_from_slice(&output[..len]);
+    Ok(tag_len)
+}
+
+fn ae_decrypt_final(ae: &AE, tag: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    let mut output = [0u8; 2 * MAX_BLOCK_LEN];
+    let len = ae.decrypt_final(&[], &mut output, tag)?;
+    out.extend_from_slice(&output[..len]);
+    Ok(())
+}
+
+// The output of a transformation not yet returned by a reader.
+struct Pending<T> {
+    transform: T,
+    output: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<T: Transform> Pending<T> {
+    fn new(transform: T) -> Self {
+        Self {
+            transform,
+            output: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    fn read<R: StdCompatRead>(&mut self, inner: &mut R, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut chunk = [0u8; CHUNK_LEN];
+        while self.position == self.output.len() && !self.finished {
+            self.output.clear();
+            self.position = 0;
+            match inner.read(&mut chunk)? {
+                0 => {
+                    self.finished = true;
+                    self.transform.finish(&mut self.output)?;
+                }
+                len => self.transform.update(&chunk[..len], &mut self.output)?,
+            }
+        }
+        let len = buf.len().min(self.output.len() - self.position);
+        buf[..len].copy_from_slice(&self.output[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// A writer which encrypts or decrypts the data written with a
+/// [Cipher](crate::Cipher) operation and writes the result to an inner writer.
+///
+/// Data of any length can be written, the TEE keeps the partial blocks until
+/// more data or [finish](CipherWriter::finish) completes them. The operation
+/// must have its key set and be initialized.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::{CipherWriter, Padding};
+/// # use optee_utee::{AlgorithmId, Cipher, OperationMode, StdCompatWrite};
+/// # use optee_utee::{DataFlag, ObjectStorageConstants, PersistentObject, TransientObject};
+/// # fn main() -> optee_utee::Result<()> {
+/// # let key = TransientObject::null_object();
+/// # let iv = [0u8; 16];
+/// let cipher = Cipher::allocate(AlgorithmId::AesCbcNopad, OperationMode::Encrypt, 256)?;
+/// cipher.set_key(&key)?;
+/// cipher.init(&iv);
+/// let object = PersistentObject::create(
+///     ObjectStorageConstants::Private,
+///     b"encrypted",
+///     DataFlag::ACCESS_WRITE,
+///     None,
+///     &[],
+/// )?;
+/// let mut writer = CipherWriter::new(&cipher, Padding::Pkcs7, object);
+/// writer.write_all(b"a large payload")?;
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CipherWriter<'a, W> {
+    transform: CipherTransform<'a>,
+    inner: W,
+    output: Vec<u8>,
+}
+
+impl<'a, W: StdCompatWrite> CipherWriter<'a, W> {
+    /// Creates a writer of the output of `cipher` to `inner`, which encrypts
+    /// or decrypts according to the mode of `cipher`.
+    pub fn new(cipher: &'a Cipher, padding: Padding, inner: W) -> Self {
+        Self {
+            transform: CipherTransform::new(cipher, padding),
+            inner,
+            output: Vec::new(),
+        }
+    }
+
+    /// Finalizes the operation, adding or removing the padding, writes the
+    /// rest of the output and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If the padding of the decrypted data is invalid.
+    ///
+    /// # Panics
+    ///
+    /// 1) If the data is not a multiple of the block size of an ECB or CBC
+    ///    algorithm, without padding.
+    pub fn finish(mut self) -> Result<W> {
+        self.output.clear();
+        self.transform.finish(&mut self.output)?;
+        self.inner.write_all(&self.output)?;
+        Ok(self.inner)
+    }
+}
+
+impl<'a, W: StdCompatWrite> StdCompatWrite for CipherWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.output.clear();
+        self.transform.update(buf, &mut self.output)?;
+        self.inner.write_all(&self.output)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader which encrypts or decrypts the data of an inner reader with a
+/// [Cipher](crate::Cipher) operation.
+///
+/// The operation is finalized when the inner reader reaches its end. The
+/// operation must have its key set and be initialized.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::crypto::{CipherReader, Padding};
+/// # use optee_utee::{AlgorithmId, Cipher, OperationMode, StdCompatRead};
+/// # use optee_utee::{DataFlag, ObjectStorageConstants, PersistentObject, TransientObject};
+/// # fn main() -> optee_utee::Result<()> {
+/// # let key = TransientObject::null_object();
+/// # let iv = [0u8; 16];
+/// let cipher = Cipher::allocate(AlgorithmId::AesCbcNopad, OperationMode::Decrypt, 256)?;
+/// cipher.set_key(&key)?;
+/// cipher.init(&iv);
+/// let object = PersistentObject::open(
+///     ObjectStorageConstants::Private,
+///     b"encrypted",
+///     DataFlag::ACCESS_READ,
+/// )?;
+/// let mut reader = CipherReader::new(&cipher, Padding::Pkcs7, object);
+/// let mut buffer = [0u8; 256];
+/// while reader.read(&mut buffer)? != 0 {
+///     // Process the plaintext.
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct CipherReader<'a, R> {
+    pending: Pending<CipherTransform<'a>>,
+    inner: R,
+}
+
+impl<'a, R: StdCompatRead> CipherReader<'a, R> {
+    /// Creates a reader of the output of `cipher` for the data of `inner`,
+    /// which encrypts or decrypts according to the mode of `cipher`.
+    pub fn new(cipher: &'a Cipher, padding: Padding, inner: R) -> Self {
+        Self {
+            pending: Pending::new(CipherTransform::new(cipher, padding)),
+            inner,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Fails with `BadFormat` if the padding of the decrypted data is invalid.
+impl<'a, R: StdCompatRead> StdCompatRead for CipherReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.pending.read(&mut self.inner, buf)
+    }
+}
+
+/// A writer which encrypts or decrypts the data written with an
+/// [AE](crate::AE) operation and writes the result to an inner writer.
+///
+/// The operation must have its key set and be initialized, with its AAD if
+/// any. When decrypting, the plaintext is written before the tag is checked
+/// by [finish_decrypt](AeWriter::finish_decrypt), it must be discarded if the
+/// check fails.
+pub struct AeWriter<'a, W> {
+    ae: &'a AE,
+    inner: W,
+    output: Vec<u8>,
+}
+
+impl<'a, W: StdCompatWrite> AeWriter<'a, W> {
+    /// Creates a writer of the output of `ae` to `inner`.
+    pub fn new(ae: &'a AE, inner: W) -> Self {
+        Self {
+            ae,
+            inner,
+            output: Vec::new(),
+        }
+    }
+
+    /// Finalizes an encryption, writes the rest of the ciphertext, stores the
+    /// tag in `tag` and returns the inner writer with the length of the tag.
+    ///
+    /// # Errors
+    ///
+    /// 1) `ShortBuffer`: If `tag` is shorter than the tag length of the
+    ///    operation.
+    ///
+    /// # Panics
+    ///
+    /// 1) If the mode of the operation is not
+    ///    [Encrypt](crate::OperationMode::Encrypt).
+    pub fn finish_encrypt(mut self, tag: &mut [u8]) -> Result<(W, usize)> {
+        self.output.clear();
+        let tag_len = ae_encrypt_final(self.ae, tag, &mut self.output)?;
+        self.inner.write_all(&self.output)?;
+        Ok((self.inner, tag_len))
+    }
+
+    /// Finalizes a decryption, checks `tag`, writes the rest of the plaintext
+    /// and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// 1) `MacInvalid`: If the data is not authentic, the plaintext written so
+    ///    far must be discarded.
+    ///
+    /// # Panics
+    ///
+    /// 1) If the mode of the operation is not
+    ///    [Decrypt](crate::OperationMode::Decrypt).
+    pub fn finish_decrypt(mut self, tag: &[u8]) -> Result<W> {
+        self.output.clear();
+        ae_decrypt_final(self.ae, tag, &mut self.output)?;
+        self.inner.write_all(&self.output)?;
+        Ok(self.inner)
+    }
+}
+
+impl<'a, W: StdCompatWrite> StdCompatWrite for AeWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.output.clear();
+        ae_update(self.ae, buf, &mut self.output)?;
+        self.inner.write_all(&self.output)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader which encrypts or decrypts the data of an inner reader with an
+/// [AE](crate::AE) operation.
+///
+/// The operation is finalized when the inner reader reaches its end. The
+/// operation must have its key set and be initialized, with its AAD if any.
+/// When decrypting, the plaintext is returned before the tag is checked at the
+/// end, it must be discarded if a read fails with `MacInvalid`.
+pub struct AeReader<'a, R> {
+    pending: Pending<AeTransform<'a>>,
+    inner: R,
+}
+
+impl<'a, R: StdCompatRead> AeReader<'a, R> {
+    /// Creates a reader of the ciphertext of the data of `inner`, the tag of
+    /// `tag_len` bytes is available from [tag](AeReader::tag) at the end.
+    ///
+    /// # Panics
+    ///
+    /// 1) If the mode of `ae` is not [Encrypt](crate::OperationMode::Encrypt),
+    ///    when the end is reached.
+    pub fn encrypt(ae: &'a AE, tag_len: usize, inner: R) -> Self {
+        let transform = AeTransform {
+            ae,
+            tag: Tag::Compute(vec![0u8; tag_len]),
+        };
+        Self {
+            pending: Pending::new(transform),
+            inner,
+        }
+    }
+
+    /// Creates a reader of the plaintext of the data of `inner`, which is
+    /// checked against `tag` at the end.
+    ///
+    /// # Panics
+    ///
+    /// 1) If the mode of `ae` is not [Decrypt](crate::OperationMode::Decrypt),
+    ///    when the end is reached.
+    pub fn decrypt(ae: &'a AE, tag: &'a [u8], inner: R) -> Self {
+        let transform = AeTransform {
+            ae,
+            tag: Tag::Check(tag),
+        };
+        Self {
+            pending: Pending::new(transform),
+            inner,
+        }
+    }
+
+    /// Returns the tag of an encryption once the end of the data is reached,
+    /// None before that or when decrypting.
+    pub fn tag(&self) -> Option<&[u8]> {
+        match &self.pending.transform.tag {
+            Tag::Compute(tag) if self.pending.finished => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Fails with `MacInvalid` if the decrypted data is not authentic.
+impl<'a, R: StdCompatRead> StdCompatRead for AeReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.pending.read(&mut self.inner, buf)
+    }
+}
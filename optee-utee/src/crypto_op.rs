@@ -85,6 +85,16 @@ impl OperationInfo {
     pub fn max_key_size(&self) -> u32 {
         self.raw.maxKeySize
     }
+
+    /// Return the `algorithm` field of the raw structure `TEE_OperationInfo`.
+    pub fn algorithm(&self) -> u32 {
+        self.raw.algorithm
+    }
+
+    /// Return the `mode` field of the raw structure `TEE_OperationInfo`.
+    pub fn mode(&self) -> u32 {
+        self.raw.mode
+    }
 }
 
 /// Every operation of [AE](AE), [Asymmetric](Asymmetric), [Cipher](Cipher),
@@ -1315,22 +1325,29 @@ impl Asymmetric {
         let p: Vec<raw::TEE_Attribute> = params.iter().map(|p| p.raw()).collect();
         let mut res_size: usize = self.info().key_size() as usize;
         let mut res_vec: Vec<u8> = vec![0u8; res_size as usize];
-        match unsafe {
-            raw::TEE_AsymmetricEncrypt(
-                self.handle(),
-                p.as_ptr() as _,
-                params.len() as u32,
-                src.as_ptr() as _,
-                src.len(),
-                res_vec.as_mut_ptr() as _,
-                &mut res_size,
-            )
-        } {
-            raw::TEE_SUCCESS => {
-                res_vec.truncate(res_size);
-                return Ok(res_vec);
+        loop {
+            match unsafe {
+                raw::TEE_AsymmetricEncrypt(
+                    self.handle(),
+                    p.as_ptr() as _,
+                    params.len() as u32,
+                    src.as_ptr() as _,
+                    src.len(),
+                    res_vec.as_mut_ptr() as _,
+                    &mut res_size,
+                )
+            } {
+                raw::TEE_SUCCESS => {
+                    res_vec.truncate(res_size);
+                    return Ok(res_vec);
+                }
+                // The output of SM2 is larger than the key size, the required
+                // size is set by the implementation.
+                raw::TEE_ERROR_SHORT_BUFFER if res_size > res_vec.len() => {
+                    res_vec.resize(res_size, 0)
+                }
+                code => return Err(Error::from_raw_error(code)),
             }
-            code => Err(Error::from_raw_error(code)),
         }
     }
 
@@ -1358,22 +1375,29 @@ impl Asymmetric {
         let p: Vec<raw::TEE_Attribute> = params.iter().map(|p| p.raw()).collect();
         let mut res_size: usize = self.info().key_size() as usize;
         let mut res_vec: Vec<u8> = vec![0u8; res_size as usize];
-        match unsafe {
-            raw::TEE_AsymmetricDecrypt(
-                self.handle(),
-                p.as_ptr() as _,
-                params.len() as u32,
-                src.as_ptr() as _,
-                src.len(),
-                res_vec.as_mut_ptr() as _,
-                &mut res_size,
-            )
-        } {
-            raw::TEE_SUCCESS => {
-                res_vec.truncate(res_size as usize);
-                return Ok(res_vec);
+        loop {
+            match unsafe {
+                raw::TEE_AsymmetricDecrypt(
+                    self.handle(),
+                    p.as_ptr() as _,
+                    params.len() as u32,
+                    src.as_ptr() as _,
+                    src.len(),
+                    res_vec.as_mut_ptr() as _,
+                    &mut res_size,
+                )
+            } {
+                raw::TEE_SUCCESS => {
+                    res_vec.truncate(res_size);
+                    return Ok(res_vec);
+                }
+                // The output of SM2 is larger than the key size, the required
+                // size is set by the implementation.
+                raw::TEE_ERROR_SHORT_BUFFER if res_size > res_vec.len() => {
+                    res_vec.resize(res_size, 0)
+                }
+                code => return Err(Error::from_raw_error(code)),
             }
-            code => Err(Error::from_raw_error(code)),
         }
     }
 
@@ -1519,7 +1543,20 @@ impl DeriveKey {
     ///
     /// 1) `params`: For algorithm [DhDeriveSharedSecret][AlgorithmId::DhDeriveSharedSecret],
     ///    [DhPublicValue](../object/enum.AttributeId.html#variant.DhPublicValue) is required as
-    ///    the passed in attribute.
+    ///    the passed in attribute. For the HKDF algorithms,
+    ///    [HkdfOkmLength](../object/enum.AttributeId.html#variant.HkdfOkmLength) is required,
+    ///    [HkdfSalt](../object/enum.AttributeId.html#variant.HkdfSalt) and
+    ///    [HkdfInfo](../object/enum.AttributeId.html#variant.HkdfInfo) are optional. For the
+    ///    Concat KDF algorithms,
+    ///    [ConcatKdfDkmLength](../object/enum.AttributeId.html#variant.ConcatKdfDkmLength) is
+    ///    required and
+    ///    [ConcatKdfOtherInfo](../object/enum.AttributeId.html#variant.ConcatKdfOtherInfo) is
+    ///    optional. For [Pbkdf2HmacSha1DeriveKey][AlgorithmId::Pbkdf2HmacSha1DeriveKey],
+    ///    [Pbkdf2DkmLength](../object/enum.AttributeId.html#variant.Pbkdf2DkmLength) and
+    ///    [Pbkdf2IterationCount](../object/enum.AttributeId.html#variant.Pbkdf2IterationCount)
+    ///    are required and [Pbkdf2Salt](../object/enum.AttributeId.html#variant.Pbkdf2Salt) is
+    ///    optional. The [kdf](crate::crypto::kdf) module derives keys with these algorithms
+    ///    without dealing with the attributes.
     /// 2) `object`: An uninitialized transient object to be filled with the derived key.
     ///
     /// # Example
@@ -1597,8 +1634,9 @@ impl DeriveKey {
     }
 
     /// Function usage is similar to [Digest::allocate](Digest::allocate).
-    /// Currently only supports [DhDeriveSharedSecret][AlgorithmId::DhDeriveSharedSecret] as
-    /// `algo`.
+    /// Currently supports [DhDeriveSharedSecret][AlgorithmId::DhDeriveSharedSecret],
+    /// [EcDhDeriveSharedSecret][AlgorithmId::EcDhDeriveSharedSecret], [Sm2Kep][AlgorithmId::Sm2Kep]
+    /// and the HKDF, Concat KDF and PBKDF2 OP-TEE extensions as `algo`.
     pub fn allocate(algo: AlgorithmId, max_key_size: usize) -> Result<Self> {
         match OperationHandle::allocate(algo, OperationMode::Derive, max_key_size) {
             Ok(handle) => Ok(Self(handle)),
@@ -1621,6 +1659,22 @@ impl DeriveKey {
         self.0.set_key(object)
     }
 
+    /// Function usage is similar to [Cipher::set_key_2](Cipher::set_key_2), for
+    /// [Sm2Kep](AlgorithmId::Sm2Kep) which takes the static and the ephemeral
+    /// key-pairs.
+    pub fn set_key_2<T: GenericObject, D: GenericObject>(
+        &self,
+        object1: &T,
+        object2: &D,
+    ) -> Result<()> {
+        match unsafe {
+            raw::TEE_SetOperationKey2(self.handle(), object1.handle(), object2.handle())
+        } {
+            raw::TEE_SUCCESS => Ok(()),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+
     /// Function usage is similar to [Digest::copy](Digest::copy).
     pub fn copy<T: OpHandle>(&mut self, src: &T) {
         self.0.copy(src)
@@ -1701,6 +1755,12 @@ pub enum AlgorithmId {
     Des3CbcMacNopad = 0x30000113,
     /// [Mac](Mac) supported algorithm.
     Des3CbcMacPkcs5 = 0x30000513,
+    /// [Cipher](Cipher) supported algorithm.
+    Sm4EcbNopad = 0x10000014,
+    /// [Cipher](Cipher) supported algorithm.
+    Sm4CbcNopad = 0x10000114,
+    /// [Cipher](Cipher) supported algorithm.
+    Sm4Ctr = 0x10000214,
     /// [Asymmetric](Asymmetric) supported algorithm, can be applied with
     /// [Sign](OperationMode::Sign) or [Verify](OperationMode::Verify) mode.
     RsassaPkcs1V15 = 0xF0000830,
@@ -1798,8 +1858,41 @@ pub enum AlgorithmId {
     /// [Asymmetric](Asymmetric) supported algorithm, can be applied with
     /// [Sign](OperationMode::Sign) or [Verify](OperationMode::Verify) mode.
     Ed25519 = 0x70006043,
+    /// [Asymmetric](Asymmetric) supported algorithm, can be applied with
+    /// [Sign](OperationMode::Sign) or [Verify](OperationMode::Verify) mode.
+    Sm2DsaSm3 = 0x70006045,
+    /// [Asymmetric](Asymmetric) supported algorithm, can be applied with
+    /// [Encrypt](OperationMode::Encrypt) or [Decrypt](OperationMode::Decrypt) mode.
+    Sm2Pke = 0x80000045,
+    /// [DeriveKey](DeriveKey) supported algorithm, taking two keys set with
+    /// [set_key_2](DeriveKey::set_key_2).
+    Sm2Kep = 0x60000045,
     /// [DeriveKey](DeriveKey) supported algorithm.
     X25519 = 0x80000044,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    HkdfMd5DeriveKey = 0x800010C0,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    HkdfSha1DeriveKey = 0x800020C0,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    HkdfSha224DeriveKey = 0x800030C0,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    HkdfSha256DeriveKey = 0x800040C0,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    HkdfSha384DeriveKey = 0x800050C0,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    HkdfSha512DeriveKey = 0x800060C0,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    ConcatKdfSha1DeriveKey = 0x800020C1,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    ConcatKdfSha224DeriveKey = 0x800030C1,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    ConcatKdfSha256DeriveKey = 0x800040C1,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    ConcatKdfSha384DeriveKey = 0x800050C1,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    ConcatKdfSha512DeriveKey = 0x800060C1,
+    /// [DeriveKey](DeriveKey) supported algorithm, an OP-TEE extension.
+    Pbkdf2HmacSha1DeriveKey = 0x800020C2,
     /// [Digest](Digest) supported algorithm.
     Md5 = 0x50000001,
     /// [Digest](Digest) supported algorithm.
@@ -1812,6 +1905,8 @@ pub enum AlgorithmId {
     Sha384 = 0x50000005,
     /// [Digest](Digest) supported algorithm.
     Sha512 = 0x50000006,
+    /// [Digest](Digest) supported algorithm.
+    Sm3 = 0x50000007,
     /// [Mac](Mac) supported algorithm.
     Md5Sha1 = 0x5000000F,
     /// [Mac](Mac) supported algorithm.
@@ -1826,6 +1921,8 @@ pub enum AlgorithmId {
     HmacSha384 = 0x30000005,
     /// [Mac](Mac) supported algorithm.
     HmacSha512 = 0x30000006,
+    /// [Mac](Mac) supported algorithm.
+    HmacSm3 = 0x30000007,
     /// Reserved for GlobalPlatform compliance test applications.
     IllegalValue = 0xefffffff,
 }
@@ -1848,4 +1945,6 @@ pub enum ElementId {
     EccCurveNistP521 = 0x00000005,
     /// Source: `IETF`, Generic: `N`, Size: 256 bits
     EccCurve25519 = 0x00000300,
+    /// Source: `OSCCA`, Generic: `N`, Size: 256 bits
+    EccCurveSm2 = 0x00000400,
 }
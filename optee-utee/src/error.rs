@@ -285,6 +285,22 @@ impl From<ErrorKind> for Error {
     }
 }
 
+#[cfg(target_os = "optee")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        use std::io::ErrorKind as IoErrorKind;
+        let kind = match err.kind() {
+            ErrorKind::AccessDenied => IoErrorKind::PermissionDenied,
+            ErrorKind::BadParameters | ErrorKind::Overflow => IoErrorKind::InvalidInput,
+            ErrorKind::ItemNotFound => IoErrorKind::NotFound,
+            ErrorKind::NoData => IoErrorKind::UnexpectedEof,
+            ErrorKind::OutOfMemory => IoErrorKind::OutOfMemory,
+            _ => IoErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 #[repr(u32)]
 pub enum ErrorOrigin {
@@ -0,0 +1,1243 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use crate::{
+    AlgorithmId, Asymmetric, Attribute, AttributeId, AttributeMemref, AttributeValue, Cipher,
+    DeriveKey, ElementId, Error, ErrorKind, GenericObject, Mac, OperationMode, Result,
+    TransientObject, TransientObjectType, AE,
+};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A named elliptic curve, the parameter of the ECDSA and ECDH keys.
+pub trait Curve: sealed::Sealed {
+    /// The value of the [EccCurve](crate::AttributeId::EccCurve) attribute.
+    const CURVE: u32;
+    /// The size of the keys in bits.
+    const KEY_SIZE: usize;
+}
+
+/// A hash function, the parameter of the HMAC keys.
+pub trait HmacHash: sealed::Sealed {
+    /// The type of the HMAC keys.
+    const OBJECT_TYPE: TransientObjectType;
+    /// The HMAC algorithm.
+    const ALGORITHM: AlgorithmId;
+}
+
+macro_rules! curves {
+    ($($(#[$meta:meta])* $name:ident = ($element:ident, $size:expr);)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+            pub struct $name;
+
+            impl sealed::Sealed for $name {}
+
+            impl Curve for $name {
+                const CURVE: u32 = ElementId::$element as u32;
+                const KEY_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+curves! {
+    /// The NIST P-192 curve.
+    NistP192 = (EccCurveNistP192, 192);
+    /// The NIST P-224 curve.
+    NistP224 = (EccCurveNistP224, 224);
+    /// The NIST P-256 curve.
+    NistP256 = (EccCurveNistP256, 256);
+    /// The NIST P-384 curve.
+    NistP384 = (EccCurveNistP384, 384);
+    /// The NIST P-521 curve.
+    NistP521 = (EccCurveNistP521, 521);
+}
+
+// The size of the SM2 keys. Their types imply the curve, unlike the other ECC
+// keys they have no curve attribute.
+const SM2_KEY_SIZE: usize = 256;
+
+macro_rules! hmac_hashes {
+    ($($(#[$meta:meta])* $name:ident = ($object_type:ident, $algorithm:ident);)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+            pub struct $name;
+
+            impl sealed::Sealed for $name {}
+
+            impl HmacHash for $name {
+                const OBJECT_TYPE: TransientObjectType = TransientObjectType::$object_type;
+                const ALGORITHM: AlgorithmId = AlgorithmId::$algorithm;
+            }
+        )*
+    };
+}
+
+hmac_hashes! {
+    /// MD5, for HMAC-MD5 keys.
+    Md5 = (HmacMd5, HmacMd5);
+    /// SHA-1, for HMAC-SHA1 keys.
+    Sha1 = (HmacSha1, HmacSha1);
+    /// SHA-224, for HMAC-SHA224 keys.
+    Sha224 = (HmacSha224, HmacSha224);
+    /// SHA-256, for HMAC-SHA256 keys.
+    Sha256 = (HmacSha256, HmacSha256);
+    /// SHA-384, for HMAC-SHA384 keys.
+    Sha384 = (HmacSha384, HmacSha384);
+    /// SHA-512, for HMAC-SHA512 keys.
+    Sha512 = (HmacSha512, HmacSha512);
+    /// SM3, for HMAC-SM3 keys.
+    Sm3 = (HmacSm3, HmacSm3);
+}
+
+/// The [Cipher](crate::Cipher) algorithms of an [AesKey](AesKey).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AesCipherMode {
+    EcbNopad,
+    CbcNopad,
+    Ctr,
+    Cts,
+}
+
+/// The [Mac](crate::Mac) algorithms of an [AesKey](AesKey).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AesMacMode {
+    CbcMacNopad,
+    CbcMacPkcs5,
+    Cmac,
+}
+
+/// The [AE](crate::AE) algorithms of an [AesKey](AesKey).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AesAeMode {
+    Ccm,
+    Gcm,
+}
+
+/// The [Cipher](crate::Cipher) algorithms of an [Sm4Key](Sm4Key).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sm4CipherMode {
+    EcbNopad,
+    CbcNopad,
+    Ctr,
+}
+
+/// The signature schemes of the RSA keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RsaSignatureScheme {
+    Pkcs1V15Sha1,
+    Pkcs1V15Sha224,
+    Pkcs1V15Sha256,
+    Pkcs1V15Sha384,
+    Pkcs1V15Sha512,
+    PssMgf1Sha1,
+    PssMgf1Sha224,
+    PssMgf1Sha256,
+    PssMgf1Sha384,
+    PssMgf1Sha512,
+}
+
+/// The encryption schemes of the RSA keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RsaEncryptionScheme {
+    Pkcs1V15,
+    OaepMgf1Sha1,
+    OaepMgf1Sha224,
+    OaepMgf1Sha256,
+    OaepMgf1Sha384,
+    OaepMgf1Sha512,
+}
+
+/// The hash functions of the digests signed with the ECDSA keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EcdsaHash {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl AesCipherMode {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::EcbNopad => AlgorithmId::AesEcbNopad,
+            Self::CbcNopad => AlgorithmId::AesCbcNopad,
+            Self::Ctr => AlgorithmId::AesCtr,
+            Self::Cts => AlgorithmId::AesCts,
+        }
+    }
+}
+
+impl AesMacMode {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::CbcMacNopad => AlgorithmId::AesCbcMacNopad,
+            Self::CbcMacPkcs5 => AlgorithmId::AesCbcMacPkcs5,
+            Self::Cmac => AlgorithmId::AesCmac,
+        }
+    }
+}
+
+impl AesAeMode {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::Ccm => AlgorithmId::AesCcm,
+            Self::Gcm => AlgorithmId::AesGcm,
+        }
+    }
+}
+
+impl Sm4CipherMode {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::EcbNopad => AlgorithmId::Sm4EcbNopad,
+            Self::CbcNopad => AlgorithmId::Sm4CbcNopad,
+            Self::Ctr => AlgorithmId::Sm4Ctr,
+        }
+    }
+}
+
+impl RsaSignatureScheme {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::Pkcs1V15Sha1 => AlgorithmId::RsassaPkcs1V15Sha1,
+            Self::Pkcs1V15Sha224 => AlgorithmId::RsassaPkcs1V15Sha224,
+            Self::Pkcs1V15Sha256 => AlgorithmId::RsassaPkcs1V15Sha256,
+            Self::Pkcs1V15Sha384 => AlgorithmId::RsassaPkcs1V15Sha384,
+            Self::Pkcs1V15Sha512 => AlgorithmId::RsassaPkcs1V15Sha512,
+            Self::PssMgf1Sha1 => AlgorithmId::RsassaPkcs1PssMgf1Sha1,
+            Self::PssMgf1Sha224 => AlgorithmId::RsassaPkcs1PssMgf1Sha224,
+            Self::PssMgf1Sha256 => AlgorithmId::RsassaPkcs1PssMgf1Sha256,
+            Self::PssMgf1Sha384 => AlgorithmId::RsassaPkcs1PssMgf1Sha384,
+            Self::PssMgf1Sha512 => AlgorithmId::RsassaPkcs1PssMgf1Sha512,
+        }
+    }
+}
+
+impl RsaEncryptionScheme {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::Pkcs1V15 => AlgorithmId::RsaesPkcs1V15,
+            Self::OaepMgf1Sha1 => AlgorithmId::RsaesPkcs1OAepMgf1Sha1,
+            Self::OaepMgf1Sha224 => AlgorithmId::RsaesPkcs1OAepMgf1Sha224,
+            Self::OaepMgf1Sha256 => AlgorithmId::RsaesPkcs1OAepMgf1Sha256,
+            Self::OaepMgf1Sha384 => AlgorithmId::RsaesPkcs1OAepMgf1Sha384,
+            Self::OaepMgf1Sha512 => AlgorithmId::RsaesPkcs1OAepMgf1Sha512,
+        }
+    }
+}
+
+impl EcdsaHash {
+    fn algorithm(self) -> AlgorithmId {
+        match self {
+            Self::Sha1 => AlgorithmId::EcDsaSha1,
+            Self::Sha224 => AlgorithmId::EcDsaSha224,
+            Self::Sha256 => AlgorithmId::EcDsaSha256,
+            Self::Sha384 => AlgorithmId::EcDsaSha384,
+            Self::Sha512 => AlgorithmId::EcDsaSha512,
+        }
+    }
+}
+
+/// The CRT components of an RSA private key, as big-endian unsigned integers.
+pub struct RsaCrtComponents<'a> {
+    /// The prime `p`.
+    pub prime1: &'a [u8],
+    /// The prime `q`.
+    pub prime2: &'a [u8],
+    /// `d mod (p - 1)`.
+    pub exponent1: &'a [u8],
+    /// `d mod (q - 1)`.
+    pub exponent2: &'a [u8],
+    /// `q^-1 mod p`.
+    pub coefficient: &'a [u8],
+}
+
+/// An AES key.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::key::{AesAeMode, AesKey};
+/// # use optee_utee::OperationMode;
+/// # fn main() -> optee_utee::Result<()> {
+/// let key = AesKey::generate(256)?;
+/// let operation = key.ae(AesAeMode::Gcm, OperationMode::Encrypt)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AesKey {
+    object: TransientObject,
+}
+
+/// An HMAC key, for the hash function `H`.
+pub struct HmacKey<H: HmacHash> {
+    object: TransientObject,
+    hash: PhantomData<H>,
+}
+
+/// An RSA key-pair.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::key::{RsaKeypair, RsaSignatureScheme};
+/// # fn main() -> optee_utee::Result<()> {
+/// # let digest = [0u8; 32];
+/// let key = RsaKeypair::generate(2048)?;
+/// let mut signature = [0u8; 256];
+/// key.signer(RsaSignatureScheme::PssMgf1Sha256)?
+///     .sign_digest(&[], &digest, &mut signature)?;
+/// key.public_key()?
+///     .verifier(RsaSignatureScheme::PssMgf1Sha256)?
+///     .verify_digest(&[], &digest, &signature)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RsaKeypair {
+    object: TransientObject,
+}
+
+/// An RSA public key.
+pub struct RsaPublicKey {
+    object: TransientObject,
+}
+
+/// An ECDSA key-pair on the curve `C`.
+pub struct EcdsaKeypair<C: Curve> {
+    object: TransientObject,
+    curve: PhantomData<C>,
+}
+
+/// An ECDSA public key on the curve `C`.
+pub struct EcdsaPublicKey<C: Curve> {
+    object: TransientObject,
+    curve: PhantomData<C>,
+}
+
+/// An ECDH key-pair on the curve `C`.
+pub struct EcdhKeypair<C: Curve> {
+    object: TransientObject,
+    curve: PhantomData<C>,
+}
+
+/// An ECDH public key on the curve `C`.
+pub struct EcdhPublicKey<C: Curve> {
+    object: TransientObject,
+    curve: PhantomData<C>,
+}
+
+/// An Ed25519 key-pair.
+pub struct Ed25519Keypair {
+    object: TransientObject,
+}
+
+/// An Ed25519 public key.
+pub struct Ed25519PublicKey {
+    object: TransientObject,
+}
+
+/// An SM4 key.
+pub struct Sm4Key {
+    object: TransientObject,
+}
+
+/// An SM2 key-pair for digital signatures.
+///
+/// The digest to sign is the SM3 hash of the identifier of the signer and its
+/// public key followed by the message, [sm2_sign](crate::crypto::easy::sm2_sign)
+/// computes it.
+pub struct Sm2DsaKeypair {
+    object: TransientObject,
+}
+
+/// An SM2 public key for digital signatures.
+pub struct Sm2DsaPublicKey {
+    object: TransientObject,
+}
+
+/// An SM2 key-pair for public key encryption.
+pub struct Sm2PkeKeypair {
+    object: TransientObject,
+}
+
+/// An SM2 public key for public key encryption.
+pub struct Sm2PkePublicKey {
+    object: TransientObject,
+}
+
+/// An SM2 key-pair for key exchange, used both as the static key of a party
+/// and as the ephemeral key of an exchange.
+///
+/// # Example
+///
+/// ``` rust,no_run
+/// # use optee_utee::key::{Sm2KepKeypair, Sm2KepPeer, Sm2KepPublicKey, Sm2KepRole};
+/// # fn main() -> optee_utee::Result<()> {
+/// # let key = Sm2KepKeypair::generate()?;
+/// # let (peer_key, peer_ephemeral_key) = (key.public_key()?, key.public_key()?);
+/// let ephemeral = Sm2KepKeypair::generate()?;
+/// // The public key of `ephemeral` is sent to the peer, which replies with
+/// // its own ephemeral public key.
+/// let peer = Sm2KepPeer {
+///     public_key: &peer_key,
+///     ephemeral_public_key: &peer_ephemeral_key,
+///     id: b"responder",
+/// };
+/// let secret = key.shared_secret(&ephemeral, Sm2KepRole::Initiator, b"initiator", &peer, 16)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Sm2KepKeypair {
+    object: TransientObject,
+}
+
+/// An SM2 public key for key exchange.
+pub struct Sm2KepPublicKey {
+    object: TransientObject,
+}
+
+/// The role of a party in an SM2 key exchange.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sm2KepRole {
+    Initiator,
+    Responder,
+}
+
+/// The peer of an SM2 key exchange.
+pub struct Sm2KepPeer<'a> {
+    /// The static public key of the peer.
+    pub public_key: &'a Sm2KepPublicKey,
+    /// The ephemeral public key of the peer for this exchange.
+    pub ephemeral_public_key: &'a Sm2KepPublicKey,
+    /// The identifier of the peer.
+    pub id: &'a [u8],
+}
+
+// Implements the conversions between a typed key and its object. The
+// conversion from an object fails with `BadParameters` if `$check` does, it
+// checks the type of the object and the curve of the ECC keys.
+macro_rules! key_object {
+    ($name:ident $(<$param:ident: $bound:ident>)?, |$object:ident| $check:expr) => {
+        impl$(<$param: $bound>)? $name$(<$param>)? {
+            /// Returns the object of the key, for the APIs which take any
+            /// [GenericObject](crate::GenericObject).
+            pub fn object(&self) -> &TransientObject {
+                &self.object
+            }
+
+            /// Converts the key into its object.
+            pub fn into_object(self) -> TransientObject {
+                self.object
+            }
+        }
+
+        impl$(<$param: $bound>)? TryFrom<TransientObject> for $name$(<$param>)? {
+            type Error = Error;
+
+            fn try_from($object: TransientObject) -> Result<Self> {
+                $check?;
+                Ok(Self::new($object))
+            }
+        }
+    };
+}
+
+key_object!(AesKey, |object| check_type(
+    &object,
+    TransientObjectType::Aes
+));
+key_object!(HmacKey<H: HmacHash>, |object| check_type(&object, H::OBJECT_TYPE));
+key_object!(RsaKeypair, |object| check_type(
+    &object,
+    TransientObjectType::RsaKeypair
+));
+key_object!(RsaPublicKey, |object| check_type(
+    &object,
+    TransientObjectType::RsaPublicKey
+));
+key_object!(EcdsaKeypair<C: Curve>, |object| check_curve::<C>(
+    &object,
+    TransientObjectType::EcdsaKeypair
+));
+key_object!(EcdsaPublicKey<C: Curve>, |object| check_curve::<C>(
+    &object,
+    TransientObjectType::EcdsaPublicKey
+));
+key_object!(EcdhKeypair<C: Curve>, |object| check_curve::<C>(
+    &object,
+    TransientObjectType::EcdhKeypair
+));
+key_object!(EcdhPublicKey<C: Curve>, |object| check_curve::<C>(
+    &object,
+    TransientObjectType::EcdhPublicKey
+));
+key_object!(Ed25519Keypair, |object| check_type(
+    &object,
+    TransientObjectType::Ed25519Keypair
+));
+key_object!(Ed25519PublicKey, |object| check_type(
+    &object,
+    TransientObjectType::Ed25519PublicKey
+));
+key_object!(Sm4Key, |object| check_type(
+    &object,
+    TransientObjectType::Sm4
+));
+key_object!(Sm2DsaKeypair, |object| check_type(
+    &object,
+    TransientObjectType::Sm2DsaKeypair
+));
+key_object!(Sm2DsaPublicKey, |object| check_type(
+    &object,
+    TransientObjectType::Sm2DsaPublicKey
+));
+key_object!(Sm2PkeKeypair, |object| check_type(
+    &object,
+    TransientObjectType::Sm2PkeKeypair
+));
+key_object!(Sm2PkePublicKey, |object| check_type(
+    &object,
+    TransientObjectType::Sm2PkePublicKey
+));
+key_object!(Sm2KepKeypair, |object| check_type(
+    &object,
+    TransientObjectType::Sm2KepKeypair
+));
+key_object!(Sm2KepPublicKey, |object| check_type(
+    &object,
+    TransientObjectType::Sm2KepPublicKey
+));
+
+impl AesKey {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random key of `key_size` bits, 128, 192 or 256.
+    pub fn generate(key_size: usize) -> Result<Self> {
+        generate(TransientObjectType::Aes, key_size, &[]).map(Self::new)
+    }
+
+    /// Import the key `key`, of 16, 24 or 32 bytes.
+    pub fn from_bytes(key: &[u8]) -> Result<Self> {
+        from_secret(TransientObjectType::Aes, key).map(Self::new)
+    }
+
+    /// Allocate a [Cipher](crate::Cipher) operation of this key.
+    pub fn cipher(&self, algorithm: AesCipherMode, mode: OperationMode) -> Result<Cipher> {
+        let operation = Cipher::allocate(algorithm.algorithm(), mode, key_size(&self.object)?)?;
+        operation.set_key(&self.object)?;
+        Ok(operation)
+    }
+
+    /// Allocate a [Mac](crate::Mac) operation of this key.
+    pub fn mac(&self, algorithm: AesMacMode) -> Result<Mac> {
+        let operation = Mac::allocate(algorithm.algorithm(), key_size(&self.object)?)?;
+        operation.set_key(&self.object)?;
+        Ok(operation)
+    }
+
+    /// Allocate an [AE](crate::AE) operation of this key.
+    pub fn ae(&self, algorithm: AesAeMode, mode: OperationMode) -> Result<AE> {
+        let operation = AE::allocate(algorithm.algorithm(), mode, key_size(&self.object)?)?;
+        operation.set_key(&self.object)?;
+        Ok(operation)
+    }
+}
+
+impl<H: HmacHash> HmacKey<H> {
+    fn new(object: TransientObject) -> Self {
+        Self {
+            object,
+            hash: PhantomData,
+        }
+    }
+
+    /// Generate a random key of `key_size` bits, a multiple of 8 in the range
+    /// of the hash function, see [TransientObjectType](crate::TransientObjectType).
+    pub fn generate(key_size: usize) -> Result<Self> {
+        generate(H::OBJECT_TYPE, key_size, &[]).map(Self::new)
+    }
+
+    /// Import the key `key`.
+    pub fn from_bytes(key: &[u8]) -> Result<Self> {
+        from_secret(H::OBJECT_TYPE, key).map(Self::new)
+    }
+
+    /// Allocate the HMAC [Mac](crate::Mac) operation of this key.
+    pub fn mac(&self) -> Result<Mac> {
+        let operation = Mac::allocate(H::ALGORITHM, key_size(&self.object)?)?;
+        operation.set_key(&self.object)?;
+        Ok(operation)
+    }
+}
+
+impl RsaKeypair {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random key-pair with a modulus of `key_size` bits and the
+    /// public exponent 65537.
+    pub fn generate(key_size: usize) -> Result<Self> {
+        generate(TransientObjectType::RsaKeypair, key_size, &[]).map(Self::new)
+    }
+
+    /// Import a key-pair from its components, big-endian unsigned integers.
+    pub fn from_components(
+        modulus: &[u8],
+        public_exponent: &[u8],
+        private_exponent: &[u8],
+        crt: Option<RsaCrtComponents>,
+    ) -> Result<Self> {
+        let mut attributes: Vec<Attribute> = vec![
+            AttributeMemref::from_ref(AttributeId::RsaModulus, modulus).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPublicExponent, public_exponent).into(),
+            AttributeMemref::from_ref(AttributeId::RsaPrivateExponent, private_exponent).into(),
+        ];
+        if let Some(crt) = crt.as_ref() {
+            for (id, value) in [
+                (AttributeId::RsaPrime1, crt.prime1),
+                (AttributeId::RsaPrime2, crt.prime2),
+                (AttributeId::RsaExponent1, crt.exponent1),
+                (AttributeId::RsaExponent2, crt.exponent2),
+                (AttributeId::RsaCoefficient, crt.coefficient),
+            ]
+            .iter()
+            {
+                attributes.push(AttributeMemref::from_ref(*id, value).into());
+            }
+        }
+        populate(
+            TransientObjectType::RsaKeypair,
+            bit_length(modulus),
+            &attributes,
+        )
+        .map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<RsaPublicKey> {
+        public_key(&self.object, TransientObjectType::RsaPublicKey).map(RsaPublicKey::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) signing operation of this
+    /// key.
+    pub fn signer(&self, scheme: RsaSignatureScheme) -> Result<Asymmetric> {
+        asymmetric(&self.object, scheme.algorithm(), OperationMode::Sign)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) verifying operation of this
+    /// key.
+    pub fn verifier(&self, scheme: RsaSignatureScheme) -> Result<Asymmetric> {
+        asymmetric(&self.object, scheme.algorithm(), OperationMode::Verify)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) encryption operation of
+    /// this key.
+    pub fn encrypter(&self, scheme: RsaEncryptionScheme) -> Result<Asymmetric> {
+        asymmetric(&self.object, scheme.algorithm(), OperationMode::Encrypt)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) decryption operation of
+    /// this key.
+    pub fn decrypter(&self, scheme: RsaEncryptionScheme) -> Result<Asymmetric> {
+        asymmetric(&self.object, scheme.algorithm(), OperationMode::Decrypt)
+    }
+}
+
+impl RsaPublicKey {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Import a public key from its components, big-endian unsigned integers.
+    pub fn from_components(modulus: &[u8], public_exponent: &[u8]) -> Result<Self> {
+        populate(
+            TransientObjectType::RsaPublicKey,
+            bit_length(modulus),
+            &[
+                AttributeMemref::from_ref(AttributeId::RsaModulus, modulus).into(),
+                AttributeMemref::from_ref(AttributeId::RsaPublicExponent, public_exponent).into(),
+            ],
+        )
+        .map(Self::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) verifying operation of this
+    /// key.
+    pub fn verifier(&self, scheme: RsaSignatureScheme) -> Result<Asymmetric> {
+        asymmetric(&self.object, scheme.algorithm(), OperationMode::Verify)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) encryption operation of
+    /// this key.
+    pub fn encrypter(&self, scheme: RsaEncryptionScheme) -> Result<Asymmetric> {
+        asymmetric(&self.object, scheme.algorithm(), OperationMode::Encrypt)
+    }
+}
+
+impl<C: Curve> EcdsaKeypair<C> {
+    fn new(object: TransientObject) -> Self {
+        Self {
+            object,
+            curve: PhantomData,
+        }
+    }
+
+    /// Generate a random key-pair.
+    pub fn generate() -> Result<Self> {
+        generate_ecc::<C>(TransientObjectType::EcdsaKeypair).map(Self::new)
+    }
+
+    /// Import a key-pair from its private value and the coordinates of its
+    /// public point, big-endian unsigned integers.
+    pub fn from_components(private_value: &[u8], x: &[u8], y: &[u8]) -> Result<Self> {
+        ecc_keypair::<C>(TransientObjectType::EcdsaKeypair, private_value, x, y).map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<EcdsaPublicKey<C>> {
+        public_key(&self.object, TransientObjectType::EcdsaPublicKey).map(EcdsaPublicKey::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) operation of this key,
+    /// signing digests of the hash function `hash`.
+    pub fn signer(&self, hash: EcdsaHash) -> Result<Asymmetric> {
+        asymmetric(&self.object, hash.algorithm(), OperationMode::Sign)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) operation of this key,
+    /// verifying signatures of digests of the hash function `hash`.
+    pub fn verifier(&self, hash: EcdsaHash) -> Result<Asymmetric> {
+        asymmetric(&self.object, hash.algorithm(), OperationMode::Verify)
+    }
+}
+
+impl<C: Curve> EcdsaPublicKey<C> {
+    fn new(object: TransientObject) -> Self {
+        Self {
+            object,
+            curve: PhantomData,
+        }
+    }
+
+    /// Import a public key from the coordinates of its point, big-endian
+    /// unsigned integers.
+    pub fn from_components(x: &[u8], y: &[u8]) -> Result<Self> {
+        ecc_public_key::<C>(TransientObjectType::EcdsaPublicKey, x, y).map(Self::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) operation of this key,
+    /// verifying signatures of digests of the hash function `hash`.
+    pub fn verifier(&self, hash: EcdsaHash) -> Result<Asymmetric> {
+        asymmetric(&self.object, hash.algorithm(), OperationMode::Verify)
+    }
+}
+
+impl<C: Curve> EcdhKeypair<C> {
+    fn new(object: TransientObject) -> Self {
+        Self {
+            object,
+            curve: PhantomData,
+        }
+    }
+
+    /// Generate a random key-pair.
+    pub fn generate() -> Result<Self> {
+        generate_ecc::<C>(TransientObjectType::EcdhKeypair).map(Self::new)
+    }
+
+    /// Import a key-pair from its private value and the coordinates of its
+    /// public point, big-endian unsigned integers.
+    pub fn from_components(private_value: &[u8], x: &[u8], y: &[u8]) -> Result<Self> {
+        ecc_keypair::<C>(TransientObjectType::EcdhKeypair, private_value, x, y).map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<EcdhPublicKey<C>> {
+        public_key(&self.object, TransientObjectType::EcdhPublicKey).map(EcdhPublicKey::new)
+    }
+
+    /// Derive the secret shared with the owner of `peer`, as a
+    /// [GenericSecret](crate::TransientObjectType::GenericSecret) object.
+    pub fn shared_secret(&self, peer: &EcdhPublicKey<C>) -> Result<TransientObject> {
+        let (x, y) = ecc_coordinates(&peer.object, C::KEY_SIZE)?;
+        let operation = DeriveKey::allocate(AlgorithmId::EcDhDeriveSharedSecret, C::KEY_SIZE)?;
+        operation.set_key(&self.object)?;
+        let len = (C::KEY_SIZE + 7) / 8;
+        let mut secret = TransientObject::allocate(TransientObjectType::GenericSecret, len * 8)?;
+        operation.derive(
+            &[
+                AttributeMemref::from_ref(AttributeId::EccPublicValueX, &x).into(),
+                AttributeMemref::from_ref(AttributeId::EccPublicValueY, &y).into(),
+            ],
+            &mut secret,
+        );
+        Ok(secret)
+    }
+}
+
+impl<C: Curve> EcdhPublicKey<C> {
+    fn new(object: TransientObject) -> Self {
+        Self {
+            object,
+            curve: PhantomData,
+        }
+    }
+
+    /// Import a public key from the coordinates of its point, big-endian
+    /// unsigned integers.
+    pub fn from_components(x: &[u8], y: &[u8]) -> Result<Self> {
+        ecc_public_key::<C>(TransientObjectType::EcdhPublicKey, x, y).map(Self::new)
+    }
+}
+
+impl Ed25519Keypair {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random key-pair.
+    pub fn generate() -> Result<Self> {
+        generate(TransientObjectType::Ed25519Keypair, 256, &[]).map(Self::new)
+    }
+
+    /// Import a key-pair from its 32-byte private and public values.
+    pub fn from_components(private_value: &[u8], public_value: &[u8]) -> Result<Self> {
+        populate(
+            TransientObjectType::Ed25519Keypair,
+            256,
+            &[
+                AttributeMemref::from_ref(AttributeId::Ed25519PrivateValue, private_value).into(),
+                AttributeMemref::from_ref(AttributeId::Ed25519PublicValue, public_value).into(),
+            ],
+        )
+        .map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<Ed25519PublicKey> {
+        public_key(&self.object, TransientObjectType::Ed25519PublicKey).map(Ed25519PublicKey::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) signing operation of this
+    /// key, the message is given in place of the digest.
+    pub fn signer(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Ed25519, OperationMode::Sign)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) verifying operation of this
+    /// key, the message is given in place of the digest.
+    pub fn verifier(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Ed25519, OperationMode::Verify)
+    }
+}
+
+impl Ed25519PublicKey {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Import a public key from its 32-byte public value.
+    pub fn from_bytes(public_value: &[u8]) -> Result<Self> {
+        populate(
+            TransientObjectType::Ed25519PublicKey,
+            256,
+            &[AttributeMemref::from_ref(AttributeId::Ed25519PublicValue, public_value).into()],
+        )
+        .map(Self::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) verifying operation of this
+    /// key, the message is given in place of the digest.
+    pub fn verifier(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Ed25519, OperationMode::Verify)
+    }
+}
+
+impl Sm4Key {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random 128-bit key.
+    pub fn generate() -> Result<Self> {
+        generate(TransientObjectType::Sm4, 128, &[]).map(Self::new)
+    }
+
+    /// Import the key `key`, of 16 bytes.
+    pub fn from_bytes(key: &[u8]) -> Result<Self> {
+        from_secret(TransientObjectType::Sm4, key).map(Self::new)
+    }
+
+    /// Allocate a [Cipher](crate::Cipher) operation of this key.
+    pub fn cipher(&self, algorithm: Sm4CipherMode, mode: OperationMode) -> Result<Cipher> {
+        let operation = Cipher::allocate(algorithm.algorithm(), mode, key_size(&self.object)?)?;
+        operation.set_key(&self.object)?;
+        Ok(operation)
+    }
+}
+
+impl Sm2DsaKeypair {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random key-pair.
+    pub fn generate() -> Result<Self> {
+        generate(TransientObjectType::Sm2DsaKeypair, SM2_KEY_SIZE, &[]).map(Self::new)
+    }
+
+    /// Import a key-pair from its private value and the coordinates of its
+    /// public point, big-endian unsigned integers.
+    pub fn from_components(private_value: &[u8], x: &[u8], y: &[u8]) -> Result<Self> {
+        sm2_keypair(TransientObjectType::Sm2DsaKeypair, private_value, x, y).map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<Sm2DsaPublicKey> {
+        public_key(&self.object, TransientObjectType::Sm2DsaPublicKey).map(Sm2DsaPublicKey::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) signing operation of this
+    /// key.
+    pub fn signer(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Sm2DsaSm3, OperationMode::Sign)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) verifying operation of this
+    /// key.
+    pub fn verifier(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Sm2DsaSm3, OperationMode::Verify)
+    }
+}
+
+impl Sm2DsaPublicKey {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Import a public key from the coordinates of its point, big-endian
+    /// unsigned integers.
+    pub fn from_components(x: &[u8], y: &[u8]) -> Result<Self> {
+        sm2_public_key(TransientObjectType::Sm2DsaPublicKey, x, y).map(Self::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) verifying operation of this
+    /// key.
+    pub fn verifier(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Sm2DsaSm3, OperationMode::Verify)
+    }
+}
+
+impl Sm2PkeKeypair {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random key-pair.
+    pub fn generate() -> Result<Self> {
+        generate(TransientObjectType::Sm2PkeKeypair, SM2_KEY_SIZE, &[]).map(Self::new)
+    }
+
+    /// Import a key-pair from its private value and the coordinates of its
+    /// public point, big-endian unsigned integers.
+    pub fn from_components(private_value: &[u8], x: &[u8], y: &[u8]) -> Result<Self> {
+        sm2_keypair(TransientObjectType::Sm2PkeKeypair, private_value, x, y).map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<Sm2PkePublicKey> {
+        public_key(&self.object, TransientObjectType::Sm2PkePublicKey).map(Sm2PkePublicKey::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) encryption operation of
+    /// this key.
+    pub fn encrypter(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Sm2Pke, OperationMode::Encrypt)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) decryption operation of
+    /// this key.
+    pub fn decrypter(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Sm2Pke, OperationMode::Decrypt)
+    }
+}
+
+impl Sm2PkePublicKey {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Import a public key from the coordinates of its point, big-endian
+    /// unsigned integers.
+    pub fn from_components(x: &[u8], y: &[u8]) -> Result<Self> {
+        sm2_public_key(TransientObjectType::Sm2PkePublicKey, x, y).map(Self::new)
+    }
+
+    /// Allocate an [Asymmetric](crate::Asymmetric) encryption operation of
+    /// this key.
+    pub fn encrypter(&self) -> Result<Asymmetric> {
+        asymmetric(&self.object, AlgorithmId::Sm2Pke, OperationMode::Encrypt)
+    }
+}
+
+impl Sm2KepKeypair {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Generate a random key-pair.
+    pub fn generate() -> Result<Self> {
+        generate(TransientObjectType::Sm2KepKeypair, SM2_KEY_SIZE, &[]).map(Self::new)
+    }
+
+    /// Import a key-pair from its private value and the coordinates of its
+    /// public point, big-endian unsigned integers.
+    pub fn from_components(private_value: &[u8], x: &[u8], y: &[u8]) -> Result<Self> {
+        sm2_keypair(TransientObjectType::Sm2KepKeypair, private_value, x, y).map(Self::new)
+    }
+
+    /// Returns the public key of the key-pair.
+    pub fn public_key(&self) -> Result<Sm2KepPublicKey> {
+        public_key(&self.object, TransientObjectType::Sm2KepPublicKey).map(Sm2KepPublicKey::new)
+    }
+
+    /// Derive the key of `len` bytes agreed with `peer`, as a
+    /// [GenericSecret](crate::TransientObjectType::GenericSecret) object.
+    ///
+    /// This key-pair is the static key of the party with the identifier `id`
+    /// and `ephemeral` is its ephemeral key-pair of the exchange. The optional
+    /// key confirmation is not done.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadParameters`: If `len` is zero or larger than 512.
+    pub fn shared_secret(
+        &self,
+        ephemeral: &Sm2KepKeypair,
+        role: Sm2KepRole,
+        id: &[u8],
+        peer: &Sm2KepPeer,
+        len: usize,
+    ) -> Result<TransientObject> {
+        // The maximum size of a GenericSecret, `TEE_DeriveKey` panics
+        // instead of failing with a larger one.
+        if len == 0 || len > 512 {
+            return Err(Error::new(ErrorKind::BadParameters));
+        }
+        let (x, y) = ecc_coordinates(&peer.public_key.object, SM2_KEY_SIZE)?;
+        let (ephemeral_x, ephemeral_y) =
+            ecc_coordinates(&peer.ephemeral_public_key.object, SM2_KEY_SIZE)?;
+        let (initiator, responder, user) = match role {
+            Sm2KepRole::Initiator => (id, peer.id, 0),
+            Sm2KepRole::Responder => (peer.id, id, 1),
+        };
+        let operation = DeriveKey::allocate(AlgorithmId::Sm2Kep, SM2_KEY_SIZE)?;
+        operation.set_key_2(&self.object, &ephemeral.object)?;
+        let mut secret = TransientObject::allocate(TransientObjectType::GenericSecret, len * 8)?;
+        operation.derive(
+            &[
+                AttributeMemref::from_ref(AttributeId::EccPublicValueX, &x).into(),
+                AttributeMemref::from_ref(AttributeId::EccPublicValueY, &y).into(),
+                AttributeMemref::from_ref(AttributeId::EccEphemeralPublicValueX, &ephemeral_x)
+                    .into(),
+                AttributeMemref::from_ref(AttributeId::EccEphemeralPublicValueY, &ephemeral_y)
+                    .into(),
+                AttributeMemref::from_ref(AttributeId::Sm2IdInitiator, initiator).into(),
+                AttributeMemref::from_ref(AttributeId::Sm2IdResponder, responder).into(),
+                AttributeValue::from_value(AttributeId::Sm2KepUser, user, 0).into(),
+            ],
+            &mut secret,
+        );
+        Ok(secret)
+    }
+}
+
+impl Sm2KepPublicKey {
+    fn new(object: TransientObject) -> Self {
+        Self { object }
+    }
+
+    /// Import a public key from the coordinates of its point, big-endian
+    /// unsigned integers.
+    pub fn from_components(x: &[u8], y: &[u8]) -> Result<Self> {
+        sm2_public_key(TransientObjectType::Sm2KepPublicKey, x, y).map(Self::new)
+    }
+}
+
+// Returns the number of bits of a big-endian unsigned integer.
+fn bit_length(value: &[u8]) -> usize {
+    match value.iter().position(|byte| *byte != 0) {
+        Some(first) => (value.len() - first) * 8 - value[first].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn key_size(object: &TransientObject) -> Result<usize> {
+    Ok(object.info()?.object_size())
+}
+
+fn check_type(object: &TransientObject, object_type: TransientObjectType) -> Result<()> {
+    if object.info()?.object_type() != object_type as u32 {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    Ok(())
+}
+
+fn check_curve<C: Curve>(object: &TransientObject, object_type: TransientObjectType) -> Result<()> {
+    check_type(object, object_type)?;
+    let (curve, _) = object.value_attribute(AttributeId::EccCurve as u32)?;
+    if curve != C::CURVE {
+        return Err(Error::new(ErrorKind::BadParameters));
+    }
+    Ok(())
+}
+
+fn generate(
+    object_type: TransientObjectType,
+    key_size: usize,
+    params: &[Attribute],
+) -> Result<TransientObject> {
+    let object = TransientObject::allocate(object_type, key_size)?;
+    object.generate_key(key_size, params)?;
+    Ok(object)
+}
+
+fn populate(
+    object_type: TransientObjectType,
+    key_size: usize,
+    attributes: &[Attribute],
+) -> Result<TransientObject> {
+    let mut object = TransientObject::allocate(object_type, key_size)?;
+    object.populate(attributes)?;
+    Ok(object)
+}
+
+fn from_secret(object_type: TransientObjectType, key: &[u8]) -> Result<TransientObject> {
+    let attribute = AttributeMemref::from_ref(AttributeId::SecretValue, key);
+    populate(object_type, key.len() * 8, &[attribute.into()])
+}
+
+fn public_key(
+    keypair: &TransientObject,
+    object_type: TransientObjectType,
+) -> Result<TransientObject> {
+    let mut object = TransientObject::allocate(object_type, key_size(keypair)?)?;
+    object.copy_attribute_from(keypair)?;
+    Ok(object)
+}
+
+fn generate_ecc<C: Curve>(object_type: TransientObjectType) -> Result<TransientObject> {
+    let curve = AttributeValue::from_value(AttributeId::EccCurve, C::CURVE, 0);
+    generate(object_type, C::KEY_SIZE, &[curve.into()])
+}
+
+fn ecc_keypair<C: Curve>(
+    object_type: TransientObjectType,
+    private_value: &[u8],
+    x: &[u8],
+    y: &[u8],
+) -> Result<TransientObject> {
+    populate(
+        object_type,
+        C::KEY_SIZE,
+        &[
+            AttributeMemref::from_ref(AttributeId::EccPrivateValue, private_value).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+            AttributeValue::from_value(AttributeId::EccCurve, C::CURVE, 0).into(),
+        ],
+    )
+}
+
+fn ecc_public_key<C: Curve>(
+    object_type: TransientObjectType,
+    x: &[u8],
+    y: &[u8],
+) -> Result<TransientObject> {
+    populate(
+        object_type,
+        C::KEY_SIZE,
+        &[
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+            AttributeValue::from_value(AttributeId::EccCurve, C::CURVE, 0).into(),
+        ],
+    )
+}
+
+// Returns the coordinates of the public point of an ECC key of `key_size`
+// bits.
+fn ecc_coordinates(object: &TransientObject, key_size: usize) -> Result<(Vec<u8>, Vec<u8>)> {
+    let len = (key_size + 7) / 8;
+    let mut x = vec![0u8; len];
+    let x_len = object.ref_attribute(AttributeId::EccPublicValueX, &mut x)?;
+    x.truncate(x_len);
+    let mut y = vec![0u8; len];
+    let y_len = object.ref_attribute(AttributeId::EccPublicValueY, &mut y)?;
+    y.truncate(y_len);
+    Ok((x, y))
+}
+
+fn sm2_keypair(
+    object_type: TransientObjectType,
+    private_value: &[u8],
+    x: &[u8],
+    y: &[u8],
+) -> Result<TransientObject> {
+    populate(
+        object_type,
+        SM2_KEY_SIZE,
+        &[
+            AttributeMemref::from_ref(AttributeId::EccPrivateValue, private_value).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+        ],
+    )
+}
+
+fn sm2_public_key(object_type: TransientObjectType, x: &[u8], y: &[u8]) -> Result<TransientObject> {
+    populate(
+        object_type,
+        SM2_KEY_SIZE,
+        &[
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+        ],
+    )
+}
+
+fn asymmetric(
+    object: &TransientObject,
+    algorithm: AlgorithmId,
+    mode: OperationMode,
+) -> Result<Asymmetric> {
+    let operation = Asymmetric::allocate(algorithm, mode, key_size(object)?)?;
+    operation.set_key(object)?;
+    Ok(operation)
+}
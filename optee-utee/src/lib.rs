@@ -44,6 +44,7 @@ fn panic(_info: &PanicInfo) -> ! {
 }
 
 pub use self::arithmetical::*;
+pub use self::cancellation::Cancellation;
 pub use self::crypto_op::*;
 pub use self::error::{Error, ErrorKind, Result};
 pub use self::extension::*;
@@ -62,10 +63,13 @@ pub mod trace;
 #[macro_use]
 mod macros;
 pub mod arithmetical;
+pub mod cancellation;
+pub mod crypto;
 pub mod crypto_op;
 mod error;
 pub mod extension;
 pub mod identity;
+pub mod key;
 pub mod net;
 pub mod object;
 mod parameter;
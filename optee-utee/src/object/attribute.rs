@@ -149,6 +149,7 @@ impl AttributeValue {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u32)]
 pub enum AttributeId {
     /// Used for all secret keys for symmetric ciphers, MACs, and HMACs
@@ -210,6 +211,42 @@ pub enum AttributeId {
     X25519PrivateValue = 0xC0000A44,
     /// ECC Curve algorithm
     EccCurve = 0xF0000441,
+    /// SM2 identifier of the initiator of a key exchange
+    Sm2IdInitiator = 0xD0000446,
+    /// SM2 identifier of the responder of a key exchange
+    Sm2IdResponder = 0xD0000546,
+    /// SM2 key exchange role: 0 for the initiator, 1 for the responder
+    Sm2KepUser = 0xF0000646,
+    /// SM2 key exchange confirmation received from the peer
+    Sm2KepConfirmationIn = 0xD0000746,
+    /// SM2 key exchange confirmation sent to the peer
+    Sm2KepConfirmationOut = 0xD0000846,
+    /// ECC ephemeral public value: `x`
+    EccEphemeralPublicValueX = 0xD0000946,
+    /// ECC ephemeral public value: `y`
+    EccEphemeralPublicValueY = 0xD0000A46,
+    /// HKDF input keying material
+    HkdfIkm = 0xC00001C0,
+    /// HKDF salt
+    HkdfSalt = 0xD00002C0,
+    /// HKDF info
+    HkdfInfo = 0xD00003C0,
+    /// HKDF output keying material length in bytes
+    HkdfOkmLength = 0xF00004C0,
+    /// Concat KDF shared secret: `Z`
+    ConcatKdfZ = 0xC00001C1,
+    /// Concat KDF other info
+    ConcatKdfOtherInfo = 0xD00002C1,
+    /// Concat KDF derived keying material length in bytes
+    ConcatKdfDkmLength = 0xF00003C1,
+    /// PBKDF2 password
+    Pbkdf2Password = 0xC00001C2,
+    /// PBKDF2 salt
+    Pbkdf2Salt = 0xD00002C2,
+    /// PBKDF2 derived keying material length in bytes
+    Pbkdf2DkmLength = 0xF00003C2,
+    /// PBKDF2 iteration count
+    Pbkdf2IterationCount = 0xF00004C2,
     BitProtected = (1 << 28),
     BitValue = (1 << 29),
 }
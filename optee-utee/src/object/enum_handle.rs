@@ -15,12 +15,12 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec::Vec};
 
 use optee_utee_sys as raw;
 
-use super::ObjectInfo;
-use crate::{Error, Result};
+use super::{MiscellaneousConstants, ObjectInfo, ObjectStorageConstants};
+use crate::{Error, ErrorKind, Result};
 
 // TODO: The examples and detailed function explanation will be added after we
 // test this struct and its functions.
@@ -103,3 +103,74 @@ impl Drop for ObjectEnumHandle {
         }
     }
 }
+
+/// An iterator over the [PersistentObject](crate::PersistentObject)s of a
+/// Trusted Storage, returned by
+/// [PersistentObject::list](crate::PersistentObject::list) and
+/// [PersistentObject::list_with_prefix](crate::PersistentObject::list_with_prefix).
+///
+/// Each item is the identifier of an object and its information. The iterator
+/// ends when all objects have been enumerated or after the first error.
+pub struct ObjectList {
+    enumerator: ObjectEnumHandle,
+    storage_id: u32,
+    prefix: Vec<u8>,
+    done: bool,
+}
+
+impl ObjectList {
+    pub(crate) fn new(storage_id: ObjectStorageConstants, prefix: &[u8]) -> Result<Self> {
+        let mut list = Self {
+            enumerator: ObjectEnumHandle::allocate()?,
+            storage_id: storage_id as u32,
+            prefix: prefix.to_vec(),
+            done: false,
+        };
+        list.restart()?;
+        Ok(list)
+    }
+
+    /// Restart the enumeration from the beginning, objects created or deleted
+    /// since the previous start are taken into account.
+    pub fn restart(&mut self) -> Result<()> {
+        self.enumerator.reset();
+        match self.enumerator.start(self.storage_id) {
+            Ok(()) => self.done = false,
+            // An empty storage.
+            Err(err) if err.kind() == ErrorKind::ItemNotFound => self.done = true,
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for ObjectList {
+    type Item = Result<(Vec<u8>, ObjectInfo)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The identifier is an output only, the buffer must be able to hold the
+        // longest one.
+        let mut object_id = [0u8; MiscellaneousConstants::TeeObjectIdMaxLen as usize];
+        while !self.done {
+            let mut object_info = ObjectInfo::from_raw(unsafe { core::mem::zeroed() });
+            match self.enumerator.get_next::<()>(&mut object_info, &mut object_id) {
+                Ok(len) => {
+                    let object_id = &object_id[..len as usize];
+                    if object_id.starts_with(&self.prefix) {
+                        return Some(Ok((object_id.to_vec(), object_info)));
+                    }
+                }
+                Err(err) => {
+                    self.done = true;
+                    self.enumerator.reset();
+                    if err.kind() != ErrorKind::ItemNotFound {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl core::iter::FusedIterator for ObjectList {}
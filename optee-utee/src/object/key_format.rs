@@ -0,0 +1,448 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use super::key_wrap::buffer_attribute;
+use super::{
+    Attribute, AttributeId, AttributeMemref, AttributeValue, GenericObject, TransientObject,
+    TransientObjectType,
+};
+use crate::{ElementId, Error, ErrorKind, Result};
+use alloc::{string::String, vec::Vec};
+use der::asn1::{AnyRef, BitStringRef, ObjectIdentifier, OctetStringRef, UintRef};
+use der::pem::{self, LineEnding};
+use der::zeroize::Zeroizing;
+use der::{Decode, Encode};
+use pkcs1::{RsaPrivateKey, RsaPublicKey};
+use pkcs8::PrivateKeyInfo;
+use sec1::EcPrivateKey;
+use spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoRef};
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const X25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.110");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+// The named curves of EC keys, with their `EccCurve` attribute value and size
+// in bits.
+const CURVES: &[(ObjectIdentifier, u32, usize)] = &[
+    (
+        ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.1"),
+        ElementId::EccCurveNistP192 as u32,
+        192,
+    ),
+    (
+        ObjectIdentifier::new_unwrap("1.3.132.0.33"),
+        ElementId::EccCurveNistP224 as u32,
+        224,
+    ),
+    (
+        ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7"),
+        ElementId::EccCurveNistP256 as u32,
+        256,
+    ),
+    (
+        ObjectIdentifier::new_unwrap("1.3.132.0.34"),
+        ElementId::EccCurveNistP384 as u32,
+        384,
+    ),
+    (
+        ObjectIdentifier::new_unwrap("1.3.132.0.35"),
+        ElementId::EccCurveNistP521 as u32,
+        521,
+    ),
+];
+
+impl TransientObject {
+    /// Decode a private key into a key-pair object, the encoding is one of:
+    ///
+    /// 1) PKCS#8 `PrivateKeyInfo` of an RSA, EC, Ed25519 or X25519 key.
+    /// 2) PKCS#1 `RSAPrivateKey`.
+    /// 3) SEC1 `ECPrivateKey`.
+    ///
+    /// RSA keys are decoded as [RsaKeypair](TransientObjectType::RsaKeypair)
+    /// and EC keys as [EcdsaKeypair](TransientObjectType::EcdsaKeypair).
+    /// The public key is required for EC, Ed25519 and X25519 keys since the
+    /// TEE can't compute it, it is part of keys encoded by OpenSSL but it is
+    /// optional in PKCS#8.
+    ///
+    /// Requires the `key_formats` feature.
+    ///
+    /// # Example
+    ///
+    /// ``` rust,no_run
+    /// # use optee_utee::TransientObject;
+    /// # fn main() -> optee_utee::Result<()> {
+    /// # let der = [0u8; 0];
+    /// let key = TransientObject::from_private_key_der(&der)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `der` is not a private key in one of these encodings.
+    /// 2) `NotSupported`: If the key algorithm or curve is not supported, if
+    ///    the public key is missing or if the object type or size is not
+    ///    supported by the implementation.
+    pub fn from_private_key_der(der: &[u8]) -> Result<Self> {
+        if let Ok(info) = PrivateKeyInfo::from_der(der) {
+            return from_private_key_info(&info);
+        }
+        if let Ok(key) = RsaPrivateKey::from_der(der) {
+            return from_rsa_private_key(&key);
+        }
+        match EcPrivateKey::from_der(der) {
+            Ok(key) => from_ec_private_key(&key, None),
+            Err(_) => Err(Error::new(ErrorKind::BadFormat)),
+        }
+    }
+
+    /// Decode a PEM encoded private key, labeled `PRIVATE KEY` (PKCS#8),
+    /// `RSA PRIVATE KEY` (PKCS#1) or `EC PRIVATE KEY` (SEC1). See
+    /// [from_private_key_der](TransientObject::from_private_key_der).
+    ///
+    /// Requires the `key_formats` feature.
+    pub fn from_private_key_pem(pem: &str) -> Result<Self> {
+        let (label, der) = pem::decode_vec(pem.as_bytes()).map_err(bad_format)?;
+        let der = Zeroizing::new(der);
+        match label {
+            "PRIVATE KEY" => from_private_key_info(&decode(&der)?),
+            "RSA PRIVATE KEY" => from_rsa_private_key(&decode(&der)?),
+            "EC PRIVATE KEY" => from_ec_private_key(&decode(&der)?, None),
+            _ => Err(Error::new(ErrorKind::BadFormat)),
+        }
+    }
+
+    /// Decode a public key into a public key object, the encoding is either
+    /// a `SubjectPublicKeyInfo` of an RSA, EC, Ed25519 or X25519 key or a
+    /// PKCS#1 `RSAPublicKey`.
+    ///
+    /// RSA keys are decoded as
+    /// [RsaPublicKey](TransientObjectType::RsaPublicKey) and EC keys as
+    /// [EcdsaPublicKey](TransientObjectType::EcdsaPublicKey).
+    ///
+    /// Requires the `key_formats` feature.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `der` is not a public key in one of these encodings.
+    /// 2) `NotSupported`: If the key algorithm or curve is not supported, if
+    ///    an EC point is compressed or if the object type or size is not
+    ///    supported by the implementation.
+    pub fn from_public_key_der(der: &[u8]) -> Result<Self> {
+        if let Ok(info) = SubjectPublicKeyInfoRef::from_der(der) {
+            return from_public_key_info(&info);
+        }
+        from_rsa_public_key(&decode(der)?)
+    }
+
+    /// Decode a PEM encoded public key, labeled `PUBLIC KEY`
+    /// (`SubjectPublicKeyInfo`) or `RSA PUBLIC KEY` (PKCS#1). See
+    /// [from_public_key_der](TransientObject::from_public_key_der).
+    ///
+    /// Requires the `key_formats` feature.
+    pub fn from_public_key_pem(pem: &str) -> Result<Self> {
+        let (label, der) = pem::decode_vec(pem.as_bytes()).map_err(bad_format)?;
+        match label {
+            "PUBLIC KEY" => from_public_key_info(&decode(&der)?),
+            "RSA PUBLIC KEY" => from_rsa_public_key(&decode(&der)?),
+            _ => Err(Error::new(ErrorKind::BadFormat)),
+        }
+    }
+
+    /// Encode the public key of an RSA, ECDSA, ECDH, Ed25519 or X25519 object,
+    /// either a key-pair or a public key, as a `SubjectPublicKeyInfo`.
+    ///
+    /// Requires the `key_formats` feature.
+    ///
+    /// # Example
+    ///
+    /// ``` rust,no_run
+    /// # use optee_utee::{TransientObject, TransientObjectType};
+    /// # fn main() -> optee_utee::Result<()> {
+    /// let key = TransientObject::allocate(TransientObjectType::RsaKeypair, 2048)?;
+    /// key.generate_key(2048, &[])?;
+    /// let public_key = key.to_public_key_der()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 1) `NotSupported`: If the object is not one of these types.
+    /// 2) `ItemNotFound`: If the object is not initialized.
+    pub fn to_public_key_der(&self) -> Result<Vec<u8>> {
+        let object_type = self.info()?.object_type();
+        let is =
+            |types: &[TransientObjectType]| types.iter().any(|known| *known as u32 == object_type);
+        if is(&[
+            TransientObjectType::RsaPublicKey,
+            TransientObjectType::RsaKeypair,
+        ]) {
+            let modulus = buffer_attribute(self, AttributeId::RsaModulus)?;
+            let exponent = buffer_attribute(self, AttributeId::RsaPublicExponent)?;
+            let key = RsaPublicKey {
+                modulus: UintRef::new(&modulus.0).map_err(bad_format)?,
+                public_exponent: UintRef::new(&exponent.0).map_err(bad_format)?,
+            };
+            let key = key.to_der().map_err(bad_format)?;
+            encode_public_key_info(RSA_ENCRYPTION, Some(AnyRef::NULL), &key)
+        } else if is(&[
+            TransientObjectType::EcdsaPublicKey,
+            TransientObjectType::EcdsaKeypair,
+            TransientObjectType::EcdhPublicKey,
+            TransientObjectType::EcdhKeypair,
+        ]) {
+            let (curve, _) = self.value_attribute(AttributeId::EccCurve as u32)?;
+            let (oid, _, size) = CURVES
+                .iter()
+                .find(|(_, known, _)| *known == curve)
+                .ok_or(Error::new(ErrorKind::NotSupported))?;
+            let len = (size + 7) / 8;
+            let mut point = vec![0x04];
+            for id in [AttributeId::EccPublicValueX, AttributeId::EccPublicValueY] {
+                let coordinate = buffer_attribute(self, id)?;
+                if coordinate.0.len() > len {
+                    return Err(Error::new(ErrorKind::BadFormat));
+                }
+                point.resize(point.len() + len - coordinate.0.len(), 0);
+                point.extend_from_slice(&coordinate.0);
+            }
+            encode_public_key_info(EC_PUBLIC_KEY, Some(AnyRef::from(oid)), &point)
+        } else if is(&[
+            TransientObjectType::Ed25519PublicKey,
+            TransientObjectType::Ed25519Keypair,
+        ]) {
+            let key = buffer_attribute(self, AttributeId::Ed25519PublicValue)?;
+            encode_public_key_info(ED25519, None, &key.0)
+        } else if is(&[
+            TransientObjectType::X25519PublicKey,
+            TransientObjectType::X25519Keypair,
+        ]) {
+            let key = buffer_attribute(self, AttributeId::X25519PublicValue)?;
+            encode_public_key_info(X25519, None, &key.0)
+        } else {
+            Err(Error::new(ErrorKind::NotSupported))
+        }
+    }
+
+    /// Encode the public key of the object as a `SubjectPublicKeyInfo`,
+    /// labeled `PUBLIC KEY` in PEM. See
+    /// [to_public_key_der](TransientObject::to_public_key_der).
+    ///
+    /// Requires the `key_formats` feature.
+    pub fn to_public_key_pem(&self) -> Result<String> {
+        let der = self.to_public_key_der()?;
+        pem::encode_string("PUBLIC KEY", LineEnding::LF, &der).map_err(bad_format)
+    }
+}
+
+fn bad_format<E>(_err: E) -> Error {
+    Error::new(ErrorKind::BadFormat)
+}
+
+fn decode<'a, T: Decode<'a>>(der: &'a [u8]) -> Result<T> {
+    T::from_der(der).map_err(bad_format)
+}
+
+fn populate(
+    object_type: TransientObjectType,
+    size: usize,
+    attributes: &[Attribute],
+) -> Result<TransientObject> {
+    let mut object = TransientObject::allocate(object_type, size)?;
+    object.populate(attributes)?;
+    Ok(object)
+}
+
+// Returns the `EccCurve` attribute value and the size of a named curve.
+fn curve(oid: ObjectIdentifier) -> Result<(u32, usize)> {
+    CURVES
+        .iter()
+        .find(|(known, _, _)| *known == oid)
+        .map(|(_, curve, size)| (*curve, *size))
+        .ok_or(Error::new(ErrorKind::NotSupported))
+}
+
+// Splits an uncompressed EC point into its coordinates.
+fn ec_point(point: &[u8], size: usize) -> Result<(&[u8], &[u8])> {
+    let len = (size + 7) / 8;
+    match point.first() {
+        Some(0x04) if point.len() == 1 + 2 * len => Ok(point[1..].split_at(len)),
+        Some(0x02) | Some(0x03) => Err(Error::new(ErrorKind::NotSupported)),
+        _ => Err(Error::new(ErrorKind::BadFormat)),
+    }
+}
+
+fn bit_length(value: &[u8]) -> usize {
+    match value.first() {
+        Some(first) => value.len() * 8 - first.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn from_private_key_info(info: &PrivateKeyInfo) -> Result<TransientObject> {
+    let oid = info.algorithm.oid;
+    if oid == RSA_ENCRYPTION {
+        from_rsa_private_key(&decode(info.private_key)?)
+    } else if oid == EC_PUBLIC_KEY {
+        let curve = info.algorithm.parameters_oid().map_err(bad_format)?;
+        from_ec_private_key(&decode(info.private_key)?, Some(curve))
+    } else if oid == ED25519 || oid == X25519 {
+        // RFC 8410, the private key is wrapped in another OCTET STRING.
+        let private_key = decode::<OctetStringRef>(info.private_key)?;
+        let public_key = info.public_key.ok_or(Error::new(ErrorKind::NotSupported))?;
+        let (object_type, public_id, private_id) = if oid == ED25519 {
+            (
+                TransientObjectType::Ed25519Keypair,
+                AttributeId::Ed25519PublicValue,
+                AttributeId::Ed25519PrivateValue,
+            )
+        } else {
+            (
+                TransientObjectType::X25519Keypair,
+                AttributeId::X25519PublicValue,
+                AttributeId::X25519PrivateValue,
+            )
+        };
+        populate(
+            object_type,
+            256,
+            &[
+                AttributeMemref::from_ref(public_id, public_key).into(),
+                AttributeMemref::from_ref(private_id, private_key.as_bytes()).into(),
+            ],
+        )
+    } else {
+        Err(Error::new(ErrorKind::NotSupported))
+    }
+}
+
+fn from_rsa_private_key(key: &RsaPrivateKey) -> Result<TransientObject> {
+    if key.other_prime_infos.is_some() {
+        return Err(Error::new(ErrorKind::NotSupported));
+    }
+    let attributes: Vec<Attribute> = [
+        (AttributeId::RsaModulus, key.modulus),
+        (AttributeId::RsaPublicExponent, key.public_exponent),
+        (AttributeId::RsaPrivateExponent, key.private_exponent),
+        (AttributeId::RsaPrime1, key.prime1),
+        (AttributeId::RsaPrime2, key.prime2),
+        (AttributeId::RsaExponent1, key.exponent1),
+        (AttributeId::RsaExponent2, key.exponent2),
+        (AttributeId::RsaCoefficient, key.coefficient),
+    ]
+    .iter()
+    .map(|(id, value)| AttributeMemref::from_ref(*id, value.as_bytes()).into())
+    .collect();
+    let size = bit_length(key.modulus.as_bytes());
+    populate(TransientObjectType::RsaKeypair, size, &attributes)
+}
+
+// The curve is given by the algorithm of a PKCS#8 key, it may then be omitted
+// from the SEC1 key.
+fn from_ec_private_key(
+    key: &EcPrivateKey,
+    curve_oid: Option<ObjectIdentifier>,
+) -> Result<TransientObject> {
+    let named_curve = key
+        .parameters
+        .and_then(|parameters| parameters.named_curve());
+    let oid = match (curve_oid, named_curve) {
+        (Some(oid), Some(named)) if oid != named => {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        (Some(oid), _) | (None, Some(oid)) => oid,
+        (None, None) => return Err(Error::new(ErrorKind::BadFormat)),
+    };
+    let (curve, size) = curve(oid)?;
+    let public_key = key.public_key.ok_or(Error::new(ErrorKind::NotSupported))?;
+    let (x, y) = ec_point(public_key, size)?;
+    populate(
+        TransientObjectType::EcdsaKeypair,
+        size,
+        &[
+            AttributeMemref::from_ref(AttributeId::EccPrivateValue, key.private_key).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+            AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+            AttributeValue::from_value(AttributeId::EccCurve, curve, 0).into(),
+        ],
+    )
+}
+
+fn from_public_key_info(info: &SubjectPublicKeyInfoRef) -> Result<TransientObject> {
+    let oid = info.algorithm.oid;
+    let key = info
+        .subject_public_key
+        .as_bytes()
+        .ok_or(Error::new(ErrorKind::BadFormat))?;
+    if oid == RSA_ENCRYPTION {
+        from_rsa_public_key(&decode(key)?)
+    } else if oid == EC_PUBLIC_KEY {
+        let (curve, size) = curve(info.algorithm.parameters_oid().map_err(bad_format)?)?;
+        let (x, y) = ec_point(key, size)?;
+        populate(
+            TransientObjectType::EcdsaPublicKey,
+            size,
+            &[
+                AttributeMemref::from_ref(AttributeId::EccPublicValueX, x).into(),
+                AttributeMemref::from_ref(AttributeId::EccPublicValueY, y).into(),
+                AttributeValue::from_value(AttributeId::EccCurve, curve, 0).into(),
+            ],
+        )
+    } else if oid == ED25519 {
+        populate(
+            TransientObjectType::Ed25519PublicKey,
+            256,
+            &[AttributeMemref::from_ref(AttributeId::Ed25519PublicValue, key).into()],
+        )
+    } else if oid == X25519 {
+        populate(
+            TransientObjectType::X25519PublicKey,
+            256,
+            &[AttributeMemref::from_ref(AttributeId::X25519PublicValue, key).into()],
+        )
+    } else {
+        Err(Error::new(ErrorKind::NotSupported))
+    }
+}
+
+fn from_rsa_public_key(key: &RsaPublicKey) -> Result<TransientObject> {
+    populate(
+        TransientObjectType::RsaPublicKey,
+        bit_length(key.modulus.as_bytes()),
+        &[
+            AttributeMemref::from_ref(AttributeId::RsaModulus, key.modulus.as_bytes()).into(),
+            AttributeMemref::from_ref(
+                AttributeId::RsaPublicExponent,
+                key.public_exponent.as_bytes(),
+            )
+            .into(),
+        ],
+    )
+}
+
+fn encode_public_key_info(
+    oid: ObjectIdentifier,
+    parameters: Option<AnyRef>,
+    key: &[u8],
+) -> Result<Vec<u8>> {
+    let info = SubjectPublicKeyInfoRef {
+        algorithm: AlgorithmIdentifierRef { oid, parameters },
+        subject_public_key: BitStringRef::from_bytes(key).map_err(bad_format)?,
+    };
+    info.to_der().map_err(bad_format)
+}
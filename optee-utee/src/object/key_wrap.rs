@@ -0,0 +1,463 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use super::{
+    Attribute, AttributeId, AttributeMemref, AttributeValue, GenericObject, TransientObject,
+    TransientObjectType,
+};
+use crate::{AlgorithmId, Cipher, Error, ErrorKind, OperationMode, Random, Result, AE};
+use alloc::vec::Vec;
+use optee_utee_sys as raw;
+
+// A wrapped key is the header followed by the protected payload:
+//
+//   magic (4) | version (1) | algorithm (1) | reserved (2) | wrapped payload
+//
+// The payload is the object type and size, each followed by the attributes of
+// the object, all integers in big-endian:
+//
+//   object type (4) | object size (4) | attribute*
+//   attribute = id (4) | (a (4) | b (4))     for value attributes
+//             | id (4) | length (4) | data   for buffer attributes
+const MAGIC: [u8; 4] = *b"TAKW";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 8;
+const GCM_NONCE_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+// The alternative initial value of RFC 5649, followed by the message length.
+const KWP_AIV: [u8; 4] = [0xA6, 0x59, 0x59, 0xA6];
+
+const SECRET: &[AttributeId] = &[AttributeId::SecretValue];
+const RSA_PUBLIC: &[AttributeId] = &[AttributeId::RsaModulus, AttributeId::RsaPublicExponent];
+const RSA_KEYPAIR: &[AttributeId] = &[
+    AttributeId::RsaModulus,
+    AttributeId::RsaPublicExponent,
+    AttributeId::RsaPrivateExponent,
+    AttributeId::RsaPrime1,
+    AttributeId::RsaPrime2,
+    AttributeId::RsaExponent1,
+    AttributeId::RsaExponent2,
+    AttributeId::RsaCoefficient,
+];
+const ECC_PUBLIC: &[AttributeId] = &[
+    AttributeId::EccPublicValueX,
+    AttributeId::EccPublicValueY,
+    AttributeId::EccCurve,
+];
+const ECC_KEYPAIR: &[AttributeId] = &[
+    AttributeId::EccPublicValueX,
+    AttributeId::EccPublicValueY,
+    AttributeId::EccPrivateValue,
+    AttributeId::EccCurve,
+];
+const ED25519_PUBLIC: &[AttributeId] = &[AttributeId::Ed25519PublicValue];
+const ED25519_KEYPAIR: &[AttributeId] = &[
+    AttributeId::Ed25519PublicValue,
+    AttributeId::Ed25519PrivateValue,
+];
+const X25519_PUBLIC: &[AttributeId] = &[AttributeId::X25519PublicValue];
+const X25519_KEYPAIR: &[AttributeId] = &[
+    AttributeId::X25519PublicValue,
+    AttributeId::X25519PrivateValue,
+];
+
+// The object types which can be wrapped and the attributes serialized for
+// them. Attributes which the object doesn't carry, like the CRT components of
+// an RSA key, are skipped.
+const LAYOUTS: &[(TransientObjectType, &[AttributeId])] = &[
+    (TransientObjectType::Aes, SECRET),
+    (TransientObjectType::Des, SECRET),
+    (TransientObjectType::Des3, SECRET),
+    (TransientObjectType::HmacMd5, SECRET),
+    (TransientObjectType::HmacSha1, SECRET),
+    (TransientObjectType::HmacSha224, SECRET),
+    (TransientObjectType::HmacSha256, SECRET),
+    (TransientObjectType::HmacSha384, SECRET),
+    (TransientObjectType::HmacSha512, SECRET),
+    (TransientObjectType::GenericSecret, SECRET),
+    (TransientObjectType::RsaPublicKey, RSA_PUBLIC),
+    (TransientObjectType::RsaKeypair, RSA_KEYPAIR),
+    (TransientObjectType::EcdsaPublicKey, ECC_PUBLIC),
+    (TransientObjectType::EcdsaKeypair, ECC_KEYPAIR),
+    (TransientObjectType::EcdhPublicKey, ECC_PUBLIC),
+    (TransientObjectType::EcdhKeypair, ECC_KEYPAIR),
+    (TransientObjectType::Ed25519PublicKey, ED25519_PUBLIC),
+    (TransientObjectType::Ed25519Keypair, ED25519_KEYPAIR),
+    (TransientObjectType::X25519PublicKey, X25519_PUBLIC),
+    (TransientObjectType::X25519Keypair, X25519_KEYPAIR),
+];
+
+/// Algorithms protecting the key material exported by
+/// [export_wrapped](crate::TransientObject::export_wrapped).
+///
+/// Both use the AES key given as wrapping key, the algorithm is recorded in
+/// the wrapped key so that
+/// [import_wrapped](crate::TransientObject::import_wrapped) doesn't need it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum KeyWrapAlgorithm {
+    /// AES key wrap with padding as specified by RFC 5649, computed with an
+    /// [AesEcbNopad](crate::AlgorithmId::AesEcbNopad) [Cipher](crate::Cipher).
+    /// Wrapping is deterministic.
+    AesKw = 1,
+    /// AES-GCM with a random 96-bit nonce and a 128-bit tag, computed with an
+    /// [AE](crate::AE) operation. The header of the wrapped key is
+    /// authenticated too.
+    AesGcm = 2,
+}
+
+impl KeyWrapAlgorithm {
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::AesKw),
+            2 => Some(Self::AesGcm),
+            _ => None,
+        }
+    }
+}
+
+impl TransientObject {
+    /// Export all the attributes of the object as a versioned blob, encrypted
+    /// and integrity protected with `wrapping_key`, so that the key can be
+    /// backed up or moved to another TA, which rebuilds it with
+    /// [import_wrapped](TransientObject::import_wrapped).
+    ///
+    /// # Parameters
+    ///
+    /// 1) `wrapping_key`: An [Aes](TransientObjectType::Aes) key.
+    /// 2) `algorithm`: The [KeyWrapAlgorithm](crate::KeyWrapAlgorithm) to use.
+    ///
+    /// # Example
+    ///
+    /// ``` rust,no_run
+    /// # use optee_utee::{AttributeId, AttributeValue, KeyWrapAlgorithm};
+    /// # use optee_utee::{TransientObject, TransientObjectType};
+    /// # fn main() -> optee_utee::Result<()> {
+    /// let wrapping_key = TransientObject::allocate(TransientObjectType::Aes, 256)?;
+    /// wrapping_key.generate_key(256, &[])?;
+    /// let key = TransientObject::allocate(TransientObjectType::EcdsaKeypair, 256)?;
+    /// let curve = AttributeValue::from_value(AttributeId::EccCurve, 3, 0);
+    /// key.generate_key(256, &[curve.into()])?;
+    /// let wrapped = key.export_wrapped(&wrapping_key, KeyWrapAlgorithm::AesGcm)?;
+    /// let restored = TransientObject::import_wrapped(&wrapping_key, &wrapped)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 1) `NotSupported`: If the type of the object can't be wrapped or if
+    ///    `wrapping_key` is not a supported AES key.
+    /// 2) `AccessDenied`: If the object usage doesn't contain
+    ///    [Extractable](crate::UsageFlag::EXTRACTABLE).
+    /// 3) `ItemNotFound`: If the object is not initialized.
+    ///
+    /// # Panics
+    ///
+    /// 1) If `wrapping_key` is not initialized.
+    /// 2) If the Implementation detects any other error associated with this
+    ///    function which is not explicitly associated with a defined return
+    ///    code for this function.
+    pub fn export_wrapped<T: GenericObject>(
+        &self,
+        wrapping_key: &T,
+        algorithm: KeyWrapAlgorithm,
+    ) -> Result<Vec<u8>> {
+        let payload = serialize(self)?;
+        let mut wrapped = Vec::from(MAGIC);
+        wrapped.extend_from_slice(&[VERSION, algorithm as u8, 0, 0]);
+        match algorithm {
+            KeyWrapAlgorithm::AesKw => {
+                let body = aes_kw_wrap(wrapping_key, &payload.0)?;
+                wrapped.extend_from_slice(&body);
+            }
+            KeyWrapAlgorithm::AesGcm => {
+                let body = aes_gcm_seal(wrapping_key, &wrapped, &payload.0)?;
+                wrapped.extend_from_slice(&body);
+            }
+        }
+        Ok(wrapped)
+    }
+
+    /// Rebuild an object exported by
+    /// [export_wrapped](TransientObject::export_wrapped) with the same
+    /// `wrapping_key`. The returned object is allocated with the type and size
+    /// of the exported one and populated with its attributes.
+    ///
+    /// # Errors
+    ///
+    /// 1) `BadFormat`: If `wrapped` is not a wrapped key.
+    /// 2) `NotSupported`: If `wrapped` uses an unknown version or algorithm, or
+    ///    if the object type or size is not supported by the implementation.
+    /// 3) `MacInvalid`: If `wrapped` was modified or wrapped with another key.
+    ///
+    /// # Panics
+    ///
+    /// 1) If `wrapping_key` is not initialized.
+    /// 2) If the Implementation detects any other error associated with this
+    ///    function which is not explicitly associated with a defined return
+    ///    code for this function.
+    pub fn import_wrapped<T: GenericObject>(wrapping_key: &T, wrapped: &[u8]) -> Result<Self> {
+        if wrapped.len() < HEADER_LEN || wrapped[..4] != MAGIC {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let (header, body) = wrapped.split_at(HEADER_LEN);
+        if header[4] != VERSION {
+            return Err(Error::new(ErrorKind::NotSupported));
+        }
+        let payload = match KeyWrapAlgorithm::from_id(header[5]) {
+            Some(KeyWrapAlgorithm::AesKw) => aes_kw_unwrap(wrapping_key, body)?,
+            Some(KeyWrapAlgorithm::AesGcm) => aes_gcm_open(wrapping_key, header, body)?,
+            None => return Err(Error::new(ErrorKind::NotSupported)),
+        };
+
+        let mut reader = Reader(&payload.0);
+        let (object_type, layout) = layout(reader.u32()?)?;
+        let object_size = reader.u32()?;
+        let mut attributes: Vec<Attribute> = Vec::new();
+        while !reader.0.is_empty() {
+            let id = reader.u32()?;
+            let id = *layout
+                .iter()
+                .find(|known| **known as u32 == id)
+                .ok_or(Error::new(ErrorKind::BadFormat))?;
+            if id as u32 & raw::TEE_ATTR_FLAG_VALUE != 0 {
+                let (a, b) = (reader.u32()?, reader.u32()?);
+                attributes.push(AttributeValue::from_value(id, a, b).into());
+            } else {
+                let len = reader.u32()? as usize;
+                attributes.push(AttributeMemref::from_ref(id, reader.take(len)?).into());
+            }
+        }
+        let mut object = TransientObject::allocate(object_type, object_size as usize)?;
+        object.populate(&attributes)?;
+        Ok(object)
+    }
+}
+
+// Key material, wiped when dropped.
+pub(super) struct Secret(pub(super) Vec<u8>);
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        for byte in self.0.iter_mut() {
+            // SAFETY: byte is a valid reference, the volatile write keeps the
+            // compiler from eliding the wipe of a buffer which is then freed.
+            unsafe { core::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(Error::new(ErrorKind::BadFormat));
+        }
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(data)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let data = self.take(4)?;
+        Ok(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+}
+
+fn layout(object_type: u32) -> Result<(TransientObjectType, &'static [AttributeId])> {
+    LAYOUTS
+        .iter()
+        .find(|(known, _)| *known as u32 == object_type)
+        .copied()
+        .ok_or(Error::new(ErrorKind::NotSupported))
+}
+
+fn serialize<T: GenericObject>(object: &T) -> Result<Secret> {
+    let info = object.info()?;
+    let (_, layout) = layout(info.object_type())?;
+    let mut payload = Secret(Vec::new());
+    let data = &mut payload.0;
+    data.extend_from_slice(&info.object_type().to_be_bytes());
+    data.extend_from_slice(&(info.object_size() as u32).to_be_bytes());
+    for &id in layout {
+        if id as u32 & raw::TEE_ATTR_FLAG_VALUE != 0 {
+            let (a, b) = match object.value_attribute(id as u32) {
+                Ok(value) => value,
+                Err(err) if err.kind() == ErrorKind::ItemNotFound => continue,
+                Err(err) => return Err(err),
+            };
+            data.extend_from_slice(&(id as u32).to_be_bytes());
+            data.extend_from_slice(&a.to_be_bytes());
+            data.extend_from_slice(&b.to_be_bytes());
+        } else {
+            let value = match buffer_attribute(object, id) {
+                Ok(value) => value,
+                Err(err) if err.kind() == ErrorKind::ItemNotFound => continue,
+                Err(err) => return Err(err),
+            };
+            data.extend_from_slice(&(id as u32).to_be_bytes());
+            data.extend_from_slice(&(value.0.len() as u32).to_be_bytes());
+            data.extend_from_slice(&value.0);
+        }
+    }
+    Ok(payload)
+}
+
+// Reads a buffer attribute whatever its size, which is queried first.
+pub(super) fn buffer_attribute<T: GenericObject>(object: &T, id: AttributeId) -> Result<Secret> {
+    let mut size = 0;
+    match unsafe {
+        raw::TEE_GetObjectBufferAttribute(
+            object.handle(),
+            id as u32,
+            core::ptr::null_mut(),
+            &mut size,
+        )
+    } {
+        raw::TEE_SUCCESS | raw::TEE_ERROR_SHORT_BUFFER => {}
+        code => return Err(Error::from_raw_error(code)),
+    }
+    let mut value = Secret(vec![0u8; size]);
+    let len = object.ref_attribute(id, &mut value.0)?;
+    value.0.truncate(len);
+    Ok(value)
+}
+
+fn aes_ecb<T: GenericObject>(key: &T, mode: OperationMode) -> Result<Cipher> {
+    let cipher = Cipher::allocate(AlgorithmId::AesEcbNopad, mode, key.info()?.object_size())?;
+    cipher.set_key(key)?;
+    cipher.init(&[]);
+    Ok(cipher)
+}
+
+// Encrypts or decrypts the block `a | r` in place with an ECB operation.
+fn aes_block(cipher: &Cipher, a: &mut [u8], r: &mut [u8]) -> Result<()> {
+    let mut block = [0u8; 16];
+    block[..8].copy_from_slice(a);
+    block[8..].copy_from_slice(r);
+    let mut output = [0u8; 16];
+    cipher.update(&block, &mut output)?;
+    a.copy_from_slice(&output[..8]);
+    r.copy_from_slice(&output[8..]);
+    block.fill(0);
+    output.fill(0);
+    Ok(())
+}
+
+// RFC 5649 section 4.1, the output starts with the integrity check register.
+fn aes_kw_wrap<T: GenericObject>(key: &T, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = aes_ecb(key, OperationMode::Encrypt)?;
+    let blocks = (plaintext.len() + 7) / 8;
+    let mut wrapped = Vec::with_capacity(8 * (blocks + 1));
+    wrapped.extend_from_slice(&KWP_AIV);
+    wrapped.extend_from_slice(&(plaintext.len() as u32).to_be_bytes());
+    wrapped.extend_from_slice(plaintext);
+    wrapped.resize(8 * (blocks + 1), 0);
+
+    let (a, r) = wrapped.split_at_mut(8);
+    if blocks == 1 {
+        aes_block(&cipher, a, r)?;
+    } else {
+        for j in 0..6 {
+            for i in 0..blocks {
+                aes_block(&cipher, a, &mut r[8 * i..8 * i + 8])?;
+                let t = (blocks * j + i + 1) as u64;
+                xor(a, &t.to_be_bytes());
+            }
+        }
+    }
+    Ok(wrapped)
+}
+
+// RFC 5649 section 4.2, fails with `MacInvalid` if the integrity check does.
+fn aes_kw_unwrap<T: GenericObject>(key: &T, wrapped: &[u8]) -> Result<Secret> {
+    if wrapped.len() < 16 || wrapped.len() % 8 != 0 {
+        return Err(Error::new(ErrorKind::BadFormat));
+    }
+    let cipher = aes_ecb(key, OperationMode::Decrypt)?;
+    let blocks = wrapped.len() / 8 - 1;
+    let mut unwrapped = Secret(wrapped.to_vec());
+
+    let (a, r) = unwrapped.0.split_at_mut(8);
+    if blocks == 1 {
+        aes_block(&cipher, a, r)?;
+    } else {
+        for j in (0..6).rev() {
+            for i in (0..blocks).rev() {
+                let t = (blocks * j + i + 1) as u64;
+                xor(a, &t.to_be_bytes());
+                aes_block(&cipher, a, &mut r[8 * i..8 * i + 8])?;
+            }
+        }
+    }
+    let len = u32::from_be_bytes([a[4], a[5], a[6], a[7]]) as usize;
+    let valid = a[..4] == KWP_AIV
+        && len > 8 * (blocks - 1)
+        && len <= 8 * blocks
+        && r[len..].iter().all(|byte| *byte == 0);
+    if !valid {
+        return Err(Error::new(ErrorKind::MacInvalid));
+    }
+    unwrapped.0.copy_within(8..8 + len, 0);
+    unwrapped.0.truncate(len);
+    Ok(unwrapped)
+}
+
+fn xor(data: &mut [u8], mask: &[u8]) {
+    for (byte, mask) in data.iter_mut().zip(mask) {
+        *byte ^= mask;
+    }
+}
+
+// Returns the nonce, the ciphertext and the tag.
+fn aes_gcm_seal<T: GenericObject>(key: &T, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let operation = AE::allocate(
+        AlgorithmId::AesGcm,
+        OperationMode::Encrypt,
+        key.info()?.object_size(),
+    )?;
+    operation.set_key(key)?;
+    let mut sealed = vec![0u8; GCM_NONCE_LEN + plaintext.len() + GCM_TAG_LEN];
+    let (nonce, rest) = sealed.split_at_mut(GCM_NONCE_LEN);
+    Random::generate(nonce);
+    operation.init(nonce, GCM_TAG_LEN * 8, aad.len(), plaintext.len())?;
+    operation.update_aad(aad);
+    let (ciphertext, tag) = rest.split_at_mut(plaintext.len());
+    operation.encrypt_final(plaintext, ciphertext, tag)?;
+    Ok(sealed)
+}
+
+fn aes_gcm_open<T: GenericObject>(key: &T, aad: &[u8], sealed: &[u8]) -> Result<Secret> {
+    if sealed.len() < GCM_NONCE_LEN + GCM_TAG_LEN {
+        return Err(Error::new(ErrorKind::BadFormat));
+    }
+    let (nonce, rest) = sealed.split_at(GCM_NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - GCM_TAG_LEN);
+    let operation = AE::allocate(
+        AlgorithmId::AesGcm,
+        OperationMode::Decrypt,
+        key.info()?.object_size(),
+    )?;
+    operation.set_key(key)?;
+    operation.init(nonce, GCM_TAG_LEN * 8, aad.len(), ciphertext.len())?;
+    operation.update_aad(aad);
+    let mut plaintext = Secret(vec![0u8; ciphertext.len()]);
+    let len = operation.decrypt_final(ciphertext, &mut plaintext.0, tag)?;
+    plaintext.0.truncate(len);
+    Ok(plaintext)
+}
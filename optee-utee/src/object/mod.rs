@@ -18,17 +18,27 @@
 mod attribute;
 mod enum_handle;
 mod generic_object;
+#[cfg(feature = "key_formats")]
+mod key_format;
+mod key_wrap;
 mod object_define;
 mod object_handle;
 mod object_info;
 mod persistent_object;
 mod transient_object;
 
+#[cfg(target_os = "optee")]
+mod optee_std;
+
+mod optee_no_std;
+
 pub use attribute::*;
-pub use enum_handle::ObjectEnumHandle;
+pub use enum_handle::{ObjectEnumHandle, ObjectList};
 pub use generic_object::GenericObject;
+pub use key_wrap::KeyWrapAlgorithm;
 pub use object_define::*;
 pub use object_handle::ObjectHandle;
 pub use object_info::ObjectInfo;
+pub use optee_no_std::{SeekFrom, StdCompatRead, StdCompatSeek, StdCompatWrite};
 pub use persistent_object::PersistentObject;
 pub use transient_object::{TransientObject, TransientObjectType};
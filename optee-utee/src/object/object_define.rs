@@ -39,9 +39,19 @@ impl Into<raw::TEE_Whence> for Whence {
     }
 }
 
+/// The Trusted Storage spaces in which persistent objects are kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum ObjectStorageConstants {
+    /// The private storage of the TA, backed by the default storage of the
+    /// implementation.
     Private = 0x00000001,
+    /// The private storage of the TA in the REE file system (OP-TEE
+    /// extension).
+    PrivateRee = 0x80000000,
+    /// The private storage of the TA in the RPMB partition of the eMMC, which
+    /// protects against rollback (OP-TEE extension).
+    PrivateRpmb = 0x80000100,
     IllegalValue = 0x7FFFFFFF,
 }
 
@@ -75,4 +75,9 @@ impl ObjectInfo {
     pub fn object_type(&self) -> u32 {
         self.raw.objectType
     }
+
+    /// Return the `dataPosition` field of the raw structure `TEE_ObjectInfo`.
+    pub fn data_position(&self) -> usize {
+        self.raw.dataPosition
+    }
 }
@@ -0,0 +1,191 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use core::convert::TryFrom;
+
+use optee_utee_sys as raw;
+
+use super::{GenericObject, PersistentObject, Whence};
+use crate::{Error, ErrorKind, Result};
+
+/// The position to move the data position indicator of a
+/// [PersistentObject](crate::PersistentObject) to, the equivalent of
+/// `std::io::SeekFrom` for TAs built without std.
+///
+/// Unlike [seek](crate::PersistentObject::seek), offsets are 64-bit, an offset
+/// which cannot be passed to the TEE is rejected with `Overflow`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeekFrom {
+    /// Sets the position to the provided number of bytes.
+    Start(u64),
+    /// Sets the position to the size of the object data plus the offset.
+    End(i64),
+    /// Sets the position to the current position plus the offset.
+    Current(i64),
+}
+
+impl SeekFrom {
+    fn into_raw(self) -> Result<(i64, raw::TEE_Whence)> {
+        let (offset, whence) = match self {
+            SeekFrom::Start(offset) => (
+                i64::try_from(offset).map_err(|_| Error::new(ErrorKind::Overflow))?,
+                Whence::DataSeekSet,
+            ),
+            SeekFrom::End(offset) => (offset, Whence::DataSeekEnd),
+            SeekFrom::Current(offset) => (offset, Whence::DataSeekCur),
+        };
+        Ok((offset, whence.into()))
+    }
+}
+
+/// A trait used for convenience; import it so that the code remains consistent
+/// with the std version (with the only difference being the return error type).
+///
+/// As the inherent [read](crate::PersistentObject::read) takes precedence, call
+/// it as `StdCompatRead::read(&mut object, buf)` when needed.
+///
+/// ``` rust,no_run
+/// # use optee_utee::{PersistentObject, ObjectStorageConstants, DataFlag};
+/// # fn main() -> optee_utee::Result<()> {
+/// use optee_utee::StdCompatRead;
+///
+/// let mut object = PersistentObject::open(
+///     ObjectStorageConstants::Private,
+///     b"counter",
+///     DataFlag::ACCESS_READ,
+/// )?;
+/// let mut counter = [0u8; 8];
+/// object.read_exact(&mut counter)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait StdCompatRead {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => break,
+                n => buf = &mut buf[n..],
+            }
+        }
+        if !buf.is_empty() {
+            return Err(Error::new(ErrorKind::NoData));
+        }
+        Ok(())
+    }
+}
+
+/// A trait used for convenience; import it so that the code remains consistent
+/// with the std version (with the only difference being the return error type).
+///
+/// As the inherent [write](crate::PersistentObject::write) takes precedence,
+/// call it as `StdCompatWrite::write(&mut object, buf)` when needed.
+pub trait StdCompatWrite {
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(Error::new(ErrorKind::Generic)),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A trait used for convenience; import it so that the code remains consistent
+/// with the std version (with the only difference being the return error type).
+///
+/// As the inherent [seek](crate::PersistentObject::seek) takes precedence, call
+/// it as `StdCompatSeek::seek(&mut object, pos)`.
+///
+/// ``` rust,no_run
+/// # use optee_utee::{PersistentObject, ObjectStorageConstants, DataFlag};
+/// # fn main() -> optee_utee::Result<()> {
+/// use optee_utee::{SeekFrom, StdCompatSeek};
+///
+/// let mut object = PersistentObject::open(
+///     ObjectStorageConstants::Private,
+///     b"log",
+///     DataFlag::ACCESS_WRITE,
+/// )?;
+/// let size = StdCompatSeek::seek(&mut object, SeekFrom::End(0))?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait StdCompatSeek {
+    /// Moves the data position indicator and returns the new position from
+    /// the start of the stream.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+    fn rewind(&mut self) -> Result<()> {
+        self.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+impl StdCompatRead for PersistentObject {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        PersistentObject::read(self, buf).map(|count| count as usize)
+    }
+}
+
+impl StdCompatWrite for PersistentObject {
+    // TEE_WriteObjectData either writes the whole buffer or fails.
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        PersistentObject::write(self, buf)?;
+        Ok(buf.len())
+    }
+}
+
+impl StdCompatSeek for PersistentObject {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (offset, whence) = pos.into_raw()?;
+        match unsafe { raw::TEE_SeekObjectData(self.handle(), offset, whence) } {
+            raw::TEE_SUCCESS => Ok(self.info()?.data_position() as u64),
+            code => Err(Error::from_raw_error(code)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seek_from_into_raw() {
+        let (offset, whence) = SeekFrom::Start(u32::MAX as u64).into_raw().unwrap();
+        assert_eq!(offset, u32::MAX as i64);
+        assert!(matches!(whence, raw::TEE_Whence::TEE_DATA_SEEK_SET));
+
+        let (offset, whence) = SeekFrom::Current(-16).into_raw().unwrap();
+        assert_eq!(offset, -16);
+        assert!(matches!(whence, raw::TEE_Whence::TEE_DATA_SEEK_CUR));
+
+        let (offset, whence) = SeekFrom::End(i64::MIN).into_raw().unwrap();
+        assert_eq!(offset, i64::MIN);
+        assert!(matches!(whence, raw::TEE_Whence::TEE_DATA_SEEK_END));
+
+        let err = SeekFrom::Start(u64::MAX).into_raw().map(|_| ()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Overflow);
+    }
+}
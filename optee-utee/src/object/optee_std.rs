@@ -0,0 +1,49 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use super::{PersistentObject, SeekFrom, StdCompatRead, StdCompatSeek, StdCompatWrite};
+
+impl std::io::Read for PersistentObject {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        StdCompatRead::read(self, buf).map_err(Into::into)
+    }
+}
+
+impl std::io::Write for PersistentObject {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        StdCompatWrite::write(self, buf).map_err(Into::into)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::io::Seek for PersistentObject {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        StdCompatSeek::seek(self, pos.into()).map_err(Into::into)
+    }
+}
+
+impl From<std::io::SeekFrom> for SeekFrom {
+    fn from(pos: std::io::SeekFrom) -> Self {
+        match pos {
+            std::io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+            std::io::SeekFrom::End(offset) => SeekFrom::End(offset),
+            std::io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
+        }
+    }
+}
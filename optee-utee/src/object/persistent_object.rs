@@ -17,7 +17,7 @@
 
 use optee_utee_sys as raw;
 
-use super::{DataFlag, GenericObject, ObjectHandle, ObjectStorageConstants, Whence};
+use super::{DataFlag, GenericObject, ObjectHandle, ObjectList, ObjectStorageConstants, Whence};
 use crate::{Error, Result};
 
 /// An object identified by an Object Identifier and including a Data Stream.
@@ -197,6 +197,47 @@ impl PersistentObject {
         }
     }
 
+    /// List the persistent objects of a Trusted Storage.
+    ///
+    /// # Parameters
+    ///
+    /// 1) `storage_id`: The storage to enumerate which is defined in
+    ///    [ObjectStorageConstants](crate::ObjectStorageConstants).
+    ///
+    /// # Example
+    ///
+    /// ``` rust,no_run
+    /// # use optee_utee::{PersistentObject, ObjectStorageConstants};
+    /// # fn main() -> optee_utee::Result<()> {
+    /// for item in PersistentObject::list(ObjectStorageConstants::Private)? {
+    ///     let (object_id, info) = item?;
+    ///     // ...
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// 1) `OutOfMemory`: If there is not enough memory to allocate the
+    ///    enumerator.
+    /// 2) `CorruptObject`: If the storage is corrupt.
+    /// 3) `StorageNotAvailable`: If the storage is currently inaccessible.
+    ///
+    /// An empty storage is not an error, the iterator is just empty.
+    pub fn list(storage_id: ObjectStorageConstants) -> Result<ObjectList> {
+        ObjectList::new(storage_id, &[])
+    }
+
+    /// List the persistent objects of a Trusted Storage whose identifier
+    /// starts with `prefix`, see [list](Self::list).
+    pub fn list_with_prefix(
+        storage_id: ObjectStorageConstants,
+        prefix: &[u8],
+    ) -> Result<ObjectList> {
+        ObjectList::new(storage_id, prefix)
+    }
+
     /// Marks an object for deletion and closes the object.
     ///
     /// # Example
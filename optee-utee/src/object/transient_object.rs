@@ -24,6 +24,7 @@ use crate::{Error, Result};
 
 /// Define types of [TransientObject](crate::TransientObject) with
 /// predefined maximum sizes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u32)]
 pub enum TransientObjectType {
     /// 128, 192, or 256 bits
@@ -34,6 +35,8 @@ pub enum TransientObjectType {
     /// 128 or 192 bits including the parity bits. This gives effective key
     /// sizes of 112 or 168 bits
     Des3 = 0xA0000013,
+    /// 128 bits
+    Sm4 = 0xA0000014,
     /// Between 64 and 512 bits, multiple of 8 bits
     HmacMd5 = 0xA0000001,
     /// Between 80 and 512 bits, multiple of 8 bits
@@ -46,6 +49,8 @@ pub enum TransientObjectType {
     HmacSha384 = 0xA0000005,
     /// Between 256 and 1024 bits, multiple of 8 bits
     HmacSha512 = 0xA0000006,
+    /// Between 80 and 1024 bits, multiple of 8 bits
+    HmacSm3 = 0xA0000007,
     /// The number of bits in the modulus. 256, 512, 768, 1024, 1536 and
     /// 2048-bit keys SHALL be supported.
     /// Support for other key sizes including bigger key sizes is
@@ -93,9 +98,36 @@ pub enum TransientObjectType {
     /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_25519
     /// defined in Table 6-14 is supported.
     X25519Keypair = 0xA1000044,
+    /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_SM2 defined in
+    /// Table 6-14 is supported.
+    Sm2DsaPublicKey = 0xA0000045,
+    /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_SM2 defined in
+    /// Table 6-14 is supported.
+    Sm2DsaKeypair = 0xA1000045,
+    /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_SM2 defined in
+    /// Table 6-14 is supported.
+    Sm2KepPublicKey = 0xA0000046,
+    /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_SM2 defined in
+    /// Table 6-14 is supported.
+    Sm2KepKeypair = 0xA1000046,
+    /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_SM2 defined in
+    /// Table 6-14 is supported.
+    Sm2PkePublicKey = 0xA0000047,
+    /// 256 bits. Conditional: Available only if TEE_ECC_CURVE_SM2 defined in
+    /// Table 6-14 is supported.
+    Sm2PkeKeypair = 0xA1000047,
     /// Multiple of 8 bits, up to 4096 bits. This type is intended for secret
     /// data that has been derived from a key derivation scheme.
     GenericSecret = 0xA0000000,
+    /// Multiple of 8 bits, up to 4096 bits. The input keying material of
+    /// HKDF, an OP-TEE extension.
+    HkdfIkm = 0xA10000C0,
+    /// Multiple of 8 bits, up to 4096 bits. The shared secret of the Concat
+    /// KDF, an OP-TEE extension.
+    ConcatKdfZ = 0xA10000C1,
+    /// Multiple of 8 bits, up to 4096 bits. The password of PBKDF2, an OP-TEE
+    /// extension.
+    Pbkdf2Password = 0xA10000C2,
     /// Object is corrupted.
     CorruptedObject = 0xA00000BE,
     /// 0 – All data is in the associated data stream.